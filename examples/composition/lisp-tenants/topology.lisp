@@ -0,0 +1,11 @@
+; route -> function -> event, once per tenant
+(defun tenant (name)
+  (-> (route (+ "/api/" name) [method "POST"])
+      (function (+ name "-loader") [uri "./loader"])
+      (event "Loaded")))
+
+; Loaded fans out to both functions
+(topology "x-lisp-tenants"
+  (map tenant '("acme" "globex"))
+  (-> (event "Loaded" [producer "default"]) (function "indexer" [uri "./loader"]))
+  (-> (event "Loaded") (function "notifier" [uri "./loader"])))
@@ -1,5 +1,6 @@
 (ns handler
-  (:gen-class))
+  (:gen-class
+   :implements [com.amazonaws.services.lambda.runtime.RequestHandler]))
 
-(defn -main [& args]
-  (println "this is a lambda"))
+(defn -handleRequest [_ event _context]
+  (str "this is a lambda: " event))
@@ -0,0 +1,271 @@
+use crate::types::BuildStatus;
+use compiler::{
+    Lang,
+    spec::function::BuildKind,
+};
+use composer::Function;
+use kit as u;
+use provider::{
+    Auth,
+    aws::s3,
+};
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+use walkdir::WalkDir;
+
+// build outputs and scratch dirs that must not affect the key
+const IGNORED: [&str; 12] = [
+    ".git",
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "vendor",
+    "slab",
+    "__pycache__",
+    ".venv",
+    "Dockerfile",
+    ".dockerignore",
+    "build.json",
+];
+
+/// What a cached build restores: the artifact path relative to the
+/// function dir, or for images the uri and local image digest
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Manifest {
+    pub key: String,
+    pub kind: BuildKind,
+    pub artifact: String,
+    pub digest: Option<String>,
+}
+
+fn cache_dir() -> String {
+    match std::env::var("TC_BUILD_CACHE_DIR") {
+        Ok(d) => d,
+        Err(_) => String::from("/tmp/tc-build-cache"),
+    }
+}
+
+fn bucket() -> Option<String> {
+    std::env::var("TC_BUILD_CACHE_BUCKET").ok()
+}
+
+/// Builds are cached unless TC_NO_BUILD_CACHE is set. MicroVM images
+/// live in the microvm registry and are always rebuilt
+pub fn is_enabled(kind: &BuildKind) -> bool {
+    match std::env::var("TC_NO_BUILD_CACHE") {
+        Ok(_) => false,
+        Err(_) => *kind != BuildKind::MicroVmImage,
+    }
+}
+
+fn is_ignored(path: &Path) -> bool {
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => IGNORED.contains(&name) || name.ends_with(".zip"),
+        None => false,
+    }
+}
+
+fn closure_files(dir: &str) -> Vec<PathBuf> {
+    let root = PathBuf::from(u::root());
+    let closure = differ::compute_closure(Path::new(dir), &root);
+    let mut roots = closure.roots;
+    if roots.is_empty() {
+        roots.insert(PathBuf::from(dir));
+    }
+    let mut xs: Vec<PathBuf> = closure.files.into_iter().collect();
+    for r in roots {
+        let walker = WalkDir::new(r)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|e| !is_ignored(e.path()));
+        for entry in walker.filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() {
+                xs.push(entry.into_path());
+            }
+        }
+    }
+    xs.sort();
+    xs.dedup();
+    xs
+}
+
+/// Key of a build: the content of every file in the function's dependency
+/// closure, its build spec, runtime, arch, the layers and extensions it
+/// runs with and the name layers and extensions are packaged under
+pub fn make_key(function: &Function, name: &str, kind: &BuildKind, code_only: bool) -> String {
+    let Function {
+        dir,
+        build,
+        runtime,
+        ..
+    } = function;
+    let root = u::root();
+    let mut lines: Vec<String> = vec![];
+    lines.push(serde_json::to_string(kind).unwrap());
+    lines.push(serde_json::to_string(build).unwrap());
+    lines.push(runtime.lang.to_str());
+    lines.push(serde_json::to_string(&runtime.arch).unwrap());
+    if *kind == BuildKind::Image {
+        lines.push(format!("{}:{}", runtime.uri, code_only));
+    }
+    if matches!(kind, BuildKind::Layer | BuildKind::Extension) {
+        lines.push(name.to_string());
+    }
+    let mut layers = runtime.layers.clone();
+    layers.sort();
+    lines.push(layers.join(","));
+    // compiled runtimes leave their bootstrap in the dir
+    let compiled = matches!(runtime.lang.to_lang(), Lang::Rust | Lang::Go);
+    for path in closure_files(dir) {
+        if compiled && path.file_name().is_some_and(|n| n == "bootstrap") {
+            continue;
+        }
+        let p = path.to_string_lossy();
+        let rel = p.strip_prefix(&root).unwrap_or(&p);
+        lines.push(format!("{} {}", rel, u::checksum_file(&p)));
+    }
+    u::checksum_str(&lines.join("\n"))
+}
+
+fn image_digest(uri: &str) -> Option<String> {
+    let cmd = format!("docker image inspect --format '{{{{.Id}}}}' {}", uri);
+    let (status, out, _) = u::runc(&cmd, &u::pwd());
+    match status {
+        true if !out.trim().is_empty() => Some(out.trim().to_string()),
+        _ => None,
+    }
+}
+
+fn file_name(path: &str) -> String {
+    match Path::new(path).file_name() {
+        Some(n) => n.to_string_lossy().to_string(),
+        None => path.to_string(),
+    }
+}
+
+fn read_manifest(key: &str) -> Option<Manifest> {
+    let path = format!("{}/{}/manifest.json", cache_dir(), key);
+    match u::file_exists(&path) {
+        true => serde_json::from_str(&u::slurp(&path)).ok(),
+        false => None,
+    }
+}
+
+async fn fetch(auth: &Auth, key: &str) -> Option<Manifest> {
+    let bucket = bucket()?;
+    let dir = format!("{}/{}", cache_dir(), key);
+    u::mkdir(&dir);
+    let client = s3::make_client(auth).await;
+    let prefix = format!("build-cache/{}", key);
+    let manifest = format!("{}/manifest.json", &dir);
+    let found = s3::download_file(
+        &client,
+        &bucket,
+        &format!("{}/manifest.json", &prefix),
+        &manifest,
+    )
+    .await;
+    if !found {
+        return None;
+    }
+    let m: Manifest = serde_json::from_str(&u::slurp(&manifest)).ok()?;
+    let name = file_name(&m.artifact);
+    let file = format!("{}/{}", &dir, &name);
+    let key = format!("{}/{}", &prefix, &name);
+    if m.digest.is_some() || !s3::download_file(&client, &bucket, &key, &file).await {
+        u::sh(&format!("rm -rf {}", &dir), &u::pwd());
+        return None;
+    }
+    tracing::debug!("Fetched build cache {} from s3://{}", &m.key, bucket);
+    Some(m)
+}
+
+/// Restores a cached artifact for the key, from the local cache or the
+/// cache bucket. Images are cached locally only: they are reused while
+/// the recorded digest is still in the local docker daemon
+pub async fn restore(auth: &Auth, key: &str, dir: &str) -> Option<BuildStatus> {
+    let m = match read_manifest(key) {
+        Some(m) => m,
+        None => fetch(auth, key).await?,
+    };
+    let path = match m.digest {
+        Some(ref digest) => {
+            if image_digest(&m.artifact).as_ref() != Some(digest) {
+                return None;
+            }
+            m.artifact.clone()
+        }
+        None => {
+            let cached = format!("{}/{}/{}", cache_dir(), key, file_name(&m.artifact));
+            if !u::file_exists(&cached) {
+                return None;
+            }
+            // artifacts are recorded relative to the function dir
+            let path = format!("{}/{}", dir, &m.artifact);
+            u::mkdir(&u::parent_dir(&path));
+            u::sh(&format!("cp -f {} {}", &cached, &path), dir);
+            path
+        }
+    };
+    tracing::debug!("Build cache hit {}: {}", key, &path);
+    Some(BuildStatus {
+        path: path,
+        status: true,
+        out: format!("cache hit {}", key),
+        err: String::from(""),
+    })
+}
+
+/// Stores the artifact of a successful build under the key, and uploads
+/// it to the cache bucket when TC_BUILD_CACHE_BUCKET is set. Images stay
+/// in the local docker daemon and are not uploaded
+pub async fn store(auth: &Auth, key: &str, kind: &BuildKind, dir: &str, status: &BuildStatus) {
+    let (artifact, digest) = match kind {
+        BuildKind::Image => match image_digest(&status.path) {
+            Some(d) => (status.path.clone(), Some(d)),
+            None => return,
+        },
+        _ => match status.path.strip_prefix(&format!("{}/", dir)) {
+            Some(rel) if u::file_exists(&status.path) => (rel.to_string(), None),
+            _ => return,
+        },
+    };
+    let cdir = format!("{}/{}", cache_dir(), key);
+    u::mkdir(&cdir);
+    let m = Manifest {
+        key: key.to_string(),
+        kind: kind.clone(),
+        artifact: artifact,
+        digest: digest,
+    };
+    let manifest = format!("{}/manifest.json", &cdir);
+    u::write_str(&manifest, &serde_json::to_string(&m).unwrap());
+    let name = file_name(&m.artifact);
+    let file = format!("{}/{}", &cdir, &name);
+    if m.digest.is_none() {
+        u::sh(&format!("cp -f {} {}", &status.path, &file), dir);
+    }
+
+    if m.digest.is_some() {
+        return;
+    }
+    if let Some(bucket) = bucket() {
+        let client = s3::make_client(auth).await;
+        let prefix = format!("build-cache/{}", key);
+        let manifest_key = format!("{}/manifest.json", &prefix);
+        let file_key = format!("{}/{}", &prefix, &name);
+        s3::upload_file(&client, &bucket, &file, &file_key).await;
+        s3::upload_file(&client, &bucket, &manifest, &manifest_key).await;
+    }
+}
+
+pub fn clear() {
+    u::sh(&format!("rm -rf {}", cache_dir()), &u::pwd());
+}
@@ -35,7 +35,7 @@ fn deps_str(deps: Vec<String>) -> String {
     }
 }
 
-fn gen_dockerignore(dir: &str) -> bool {
+fn gen_dockerignore(dir: &str) -> crate::DockerIgnore {
     crate::gen_dockerignore(dir)
 }
 
@@ -74,7 +74,10 @@ async fn build_with_docker(auth: &Auth, name: &str, dir: &str) -> (bool, String,
 
     let cmd_str = format!(
         "docker buildx build --platform=linux/amd64 --provenance=false -t {} --secret id=aws-key,src={} --secret id=aws-secret,src={} --secret id=aws-session,src={} --build-context shared={root} .",
-        name, &key_file, &secret_file, &session_file
+        crate::image_tag(dir),
+        &key_file,
+        &secret_file,
+        &session_file
     );
 
     tracing::debug!("Building with docker {}", &cmd_str);
@@ -89,17 +92,17 @@ async fn build_with_docker(auth: &Auth, name: &str, dir: &str) -> (bool, String,
         sh("rm -f Dockerfile wrapper", dir);
         tracing::debug!("Build Fail {} {} {}", status, out, err);
         println!("Failed to build {}", name);
-        std::process::exit(1);
     }
 
     (status, out, err)
 }
 
-fn copy_from_docker(dir: &str, name: &str) {
-    let temp_cont = &format!("tmp-{}", name);
+fn copy_from_docker(dir: &str) {
+    let tag = crate::image_tag(dir);
+    let temp_cont = &format!("tmp-{}", &tag);
     let clean = &format!("docker rm -f {}", &temp_cont);
 
-    let run = format!("docker run -d --name {} {}", &temp_cont, name);
+    let run = format!("docker run -d --name {} {}", &temp_cont, &tag);
     sh(&clean, dir);
     sh(&run, dir);
     let id = sh(&format!("docker ps -aqf \"name={}\"", temp_cont), dir);
@@ -121,28 +124,33 @@ pub async fn build(
     spec: &Build,
 ) -> BuildStatus {
     match langr.to_lang() {
-        Lang::Rust | Lang::Go => super::inline::build(auth, dir, name, langr, arch, "", spec).await,
+        Lang::Rust | Lang::Go | Lang::Clojure => {
+            super::inline::build(auth, dir, name, langr, arch, "", spec).await
+        }
         _ => {
             let Build { command, pre, .. } = spec;
 
             if !pre.is_empty() {
-                let created_root_dockerignore = gen_dockerignore(dir);
+                let ignore = gen_dockerignore(dir);
                 gen_dockerfile(dir, langr, pre);
 
                 let (status, out, err) = build_with_docker(&auth, name, dir).await;
                 sh("rm -f Dockerfile wrapper .dockerignore", dir);
-                if created_root_dockerignore {
-                    crate::cleanup_root_dockerignore(dir);
-                }
+                drop(ignore);
                 if status {
-                    copy_from_docker(dir, name);
+                    copy_from_docker(dir);
                     let cmd = "zip -q -9 -r ../lambda.zip .";
                     let build_dir = format!("{}/build", dir);
                     sh(&cmd, &build_dir);
                     sh("rm -rf build build.json", dir);
                 } else {
-                    println!("Failed to run pre commands: {} {}", out, err);
-                    std::process::exit(1)
+                    println!("Failed to run pre commands");
+                    return BuildStatus {
+                        path: format!("{}/lambda.zip", dir),
+                        status: false,
+                        out: out,
+                        err: err,
+                    };
                 }
             }
 
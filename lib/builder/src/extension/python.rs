@@ -40,10 +40,14 @@ RUN pip install -vvv -r requirements.txt --target=/build/python --upgrade
 }
 
 pub fn copy_from_docker(dir: &str) {
-    let temp_cont = &format!("tmp-{}", u::basedir(dir));
+    let temp_cont = &format!("tmp-{}", crate::image_tag(dir));
     let clean = &format!("docker rm -f {}", &temp_cont);
 
-    let run = format!("docker run -d --name {} {}", &temp_cont, u::basedir(dir));
+    let run = format!(
+        "docker run -d --name {} {}",
+        &temp_cont,
+        crate::image_tag(dir)
+    );
     u::runcmd_quiet(&clean, dir);
     sh(&run, dir);
     let id = u::sh(&format!("docker ps -aqf \"name={}\"", temp_cont), dir);
@@ -62,7 +66,7 @@ pub fn copy_from_docker(dir: &str) {
 pub fn build_with_docker(dir: &str) -> (bool, String, String) {
     let cmd_str = format!(
         "docker build --no-cache  --platform=linux/amd64 . -t {}",
-        u::basedir(dir)
+        crate::image_tag(dir)
     );
     let (status, out, err) = u::runc(&cmd_str, dir);
     if !status {
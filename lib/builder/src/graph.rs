@@ -0,0 +1,133 @@
+use compiler::spec::function::BuildKind;
+use composer::Function;
+use std::{
+    collections::HashSet,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+// kinds other builds consume, so they are built first
+fn is_shared(kind: &BuildKind) -> bool {
+    match kind {
+        BuildKind::Layer
+        | BuildKind::Library
+        | BuildKind::Slab
+        | BuildKind::Runtime
+        | BuildKind::Extension => true,
+        _ => false,
+    }
+}
+
+// layers publish as `{name}-dev` or, when split, `{name}-0-dev`
+fn is_layer_of(layer: &str, name: &str) -> bool {
+    layer == name || layer.starts_with(&format!("{}-", name))
+}
+
+fn uses_layer(f: &Function, name: &str) -> bool {
+    f.runtime.layers.iter().any(|l| is_layer_of(l, name))
+}
+
+fn canonical(dir: &str) -> PathBuf {
+    Path::new(dir).canonicalize().unwrap_or(PathBuf::from(dir))
+}
+
+/// Builds of a topology and its nodes, each function dir built once,
+/// with the shared builds each one waits for: layers it names in its
+/// runtime and libraries in its dependency closure
+pub struct Graph {
+    pub functions: Vec<Function>,
+    pub deps: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    pub fn new(functions: Vec<Function>) -> Graph {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut fns: Vec<Function> = functions
+            .into_iter()
+            .filter(|f| seen.insert(f.dir.clone()))
+            .collect();
+        fns.sort_by(|a, b| a.dir.cmp(&b.dir));
+
+        let root = PathBuf::from(kit::root());
+        let shared: Vec<(usize, PathBuf)> = fns
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| is_shared(&f.build.kind))
+            .map(|(i, f)| (i, canonical(&f.dir)))
+            .collect();
+
+        let mut deps: Vec<Vec<usize>> = vec![];
+        for (i, f) in fns.iter().enumerate() {
+            if shared.is_empty() || is_shared(&f.build.kind) {
+                deps.push(vec![]);
+                continue;
+            }
+            let closure = differ::compute_closure(Path::new(&f.dir), &root);
+            let xs = shared
+                .iter()
+                .filter(|(j, dir)| {
+                    *j != i && (uses_layer(f, &fns[*j].name) || closure.roots.contains(dir))
+                })
+                .map(|(j, _)| *j)
+                .collect();
+            deps.push(xs);
+        }
+        Graph {
+            functions: fns,
+            deps: deps,
+        }
+    }
+
+    /// Builds not yet started whose dependencies are all done
+    pub fn ready(&self, done: &HashSet<usize>, started: &HashSet<usize>) -> Vec<usize> {
+        (0..self.deps.len())
+            .filter(|i| !started.contains(i) && !done.contains(i))
+            .filter(|i| self.deps[*i].iter().all(|d| done.contains(d)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(xs: &[usize]) -> HashSet<usize> {
+        xs.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_ready() {
+        // 0 and 1 are shared, 2 uses both and 3 uses none
+        let graph = Graph {
+            functions: vec![],
+            deps: vec![vec![], vec![], vec![0, 1], vec![]],
+        };
+        assert_eq!(graph.ready(&set(&[]), &set(&[])), vec![0, 1, 3]);
+        assert_eq!(graph.ready(&set(&[]), &set(&[0, 3])), vec![1]);
+        assert_eq!(graph.ready(&set(&[0]), &set(&[1, 3])), Vec::<usize>::new());
+        assert_eq!(graph.ready(&set(&[0, 1]), &set(&[3])), vec![2]);
+        assert_eq!(
+            graph.ready(&set(&[0, 1, 2, 3]), &set(&[])),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn test_is_layer_of() {
+        assert!(is_layer_of("deps", "deps"));
+        assert!(is_layer_of("deps-dev", "deps"));
+        assert!(is_layer_of("deps-0-dev", "deps"));
+        assert!(!is_layer_of("depsx-dev", "deps"));
+        assert!(!is_layer_of("dep", "deps"));
+    }
+
+    #[test]
+    fn test_is_shared() {
+        assert!(is_shared(&BuildKind::Layer));
+        assert!(is_shared(&BuildKind::Slab));
+        assert!(!is_shared(&BuildKind::Inline));
+        assert!(!is_shared(&BuildKind::Code));
+    }
+}
@@ -24,7 +24,7 @@ use provider::{
 };
 use std::collections::HashMap;
 
-fn gen_dockerignore(dir: &str) -> bool {
+fn gen_dockerignore(dir: &str) -> crate::DockerIgnore {
     crate::gen_dockerignore(dir)
 }
 
@@ -156,7 +156,7 @@ pub async fn build(
         gen_base_dockerfile(dir, langr, bspec);
     }
 
-    let created_root_dockerignore = gen_dockerignore(dir);
+    let ignore = gen_dockerignore(dir);
 
     let uri = if code_only {
         code_image_uri
@@ -170,9 +170,7 @@ pub async fn build(
 
     bar.inc(3);
     sh("rm -rf Dockerfile build build.json .dockerignore", dir);
-    if created_root_dockerignore {
-        crate::cleanup_root_dockerignore(dir);
-    }
+    drop(ignore);
     bar.finish();
     BuildStatus {
         path: uri.to_string(),
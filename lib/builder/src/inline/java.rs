@@ -0,0 +1,59 @@
+use kit as u;
+
+fn deps_str(deps: Vec<String>) -> String {
+    if deps.len() >= 2 {
+        deps.join(" && ")
+    } else if deps.len() == 1 {
+        deps.first().unwrap().to_string()
+    } else {
+        String::from("echo 0")
+    }
+}
+
+// installs the build tool if the image lacks it and builds the uberjar
+fn build_steps(package_manager: &str) -> (&'static str, &'static str) {
+    match package_manager {
+        "clojure" => (
+            "curl -sL -O https://github.com/clojure/brew-install/releases/latest/download/linux-install.sh && bash linux-install.sh",
+            "clojure -T:build uber",
+        ),
+        "lein" => (
+            "curl -sL -o /usr/local/bin/lein https://raw.githubusercontent.com/technomancy/leiningen/stable/bin/lein && chmod +x /usr/local/bin/lein",
+            "lein uberjar",
+        ),
+        "gradle" => (
+            "echo 0",
+            "if [ -f gradlew ]; then sh gradlew -q shadowJar -x test; else gradle -q shadowJar -x test; fi",
+        ),
+        _ => ("echo 0", "mvn -q -B -DskipTests package"),
+    }
+}
+
+pub fn gen_dockerfile(dir: &str, package_manager: &str, pre: &Vec<String>) {
+    let pre = deps_str(pre.to_vec());
+    let (install, build) = build_steps(package_manager);
+
+    // the uberjar is the largest jar the build produces; Lambda puts the
+    // jars under lib/ on the classpath
+    let f = format!(
+        r#"
+FROM public.ecr.aws/sam/build-java21:latest AS builder
+
+ENV GIT_SSH_COMMAND="ssh -o BatchMode=yes -o StrictHostKeyChecking=accept-new"
+
+RUN {install}
+
+WORKDIR /src
+COPY . .
+
+RUN --mount=type=ssh \
+    {pre} && \
+    {build}
+
+RUN mkdir -p /build/lib && \
+    cp "$(find target build/libs -maxdepth 2 -name '*.jar' ! -name 'original-*' 2>/dev/null | xargs ls -S | head -1)" /build/lib/
+"#
+    );
+    let dockerfile = format!("{}/Dockerfile", dir);
+    u::write_str(&dockerfile, &f);
+}
@@ -1,4 +1,5 @@
 mod go;
+mod java;
 mod node;
 mod python;
 mod ruby;
@@ -23,7 +24,12 @@ use provider::{
 };
 
 fn gen_dockerfile(dir: &str, arch: &Arch, langr: &LangRuntime, skip_dev_deps: bool, bs: &Build) {
-    let Build { pre, post, .. } = bs;
+    let Build {
+        pre,
+        post,
+        package_manager,
+        ..
+    } = bs;
     match langr.to_lang() {
         Lang::Python => python::gen_dockerfile(dir, langr, bs),
         Lang::Ruby => {
@@ -36,12 +42,17 @@ fn gen_dockerfile(dir: &str, arch: &Arch, langr: &LangRuntime, skip_dev_deps: bo
         Lang::Rust => rust::gen_dockerfile(dir),
         Lang::Go => go::gen_dockerfile(dir, arch, pre),
         Lang::Node => node::gen_dockerfile(dir),
-        _ => todo!(),
+        Lang::Clojure => java::gen_dockerfile(dir, package_manager, pre),
     }
 }
 
 fn gen_dockerfile_unshared(dir: &str, arch: &Arch, langr: &LangRuntime, _wrap: bool, bs: &Build) {
-    let Build { pre, post, .. } = bs;
+    let Build {
+        pre,
+        post,
+        package_manager,
+        ..
+    } = bs;
 
     match langr.to_lang() {
         Lang::Python => python::gen_dockerfile_unshared(dir, langr, bs),
@@ -49,11 +60,11 @@ fn gen_dockerfile_unshared(dir: &str, arch: &Arch, langr: &LangRuntime, _wrap: b
         Lang::Rust => rust::gen_dockerfile(dir),
         Lang::Go => go::gen_dockerfile(dir, arch, pre),
         Lang::Node => node::gen_dockerfile(dir),
-        _ => todo!(),
+        Lang::Clojure => java::gen_dockerfile(dir, package_manager, pre),
     }
 }
 
-fn gen_dockerignore(dir: &str) -> bool {
+fn gen_dockerignore(dir: &str) -> crate::DockerIgnore {
     crate::gen_dockerignore(dir)
 }
 
@@ -104,7 +115,7 @@ async fn build_with_docker(
 
         let out = format!(
             "docker buildx build --platform=linux/amd64 --ssh default --provenance=false --load -t {} --secret id=aws-key,src={} --secret id=aws-secret,src={} --secret id=aws-session,src={} --build-arg AUTH_TOKEN={} --build-context shared={root} .",
-            u::basedir(dir),
+            crate::image_tag(dir),
             &key_file,
             &secret_file,
             &session_file,
@@ -114,7 +125,7 @@ async fn build_with_docker(
     } else {
         format!(
             "docker buildx build --platform=linux/amd64 --load -t {} .",
-            u::basedir(dir)
+            crate::image_tag(dir)
         )
     };
 
@@ -125,18 +136,20 @@ async fn build_with_docker(
     sh(&format!("rm -f {}", &session_file), dir);
 
     if !status {
-        println!("Build failed: {} {}", out, err);
         sh("rm -f Dockerfile wrapper", dir);
-        panic!("Build failed");
     }
     (status, out, err)
 }
 
 fn copy_from_docker(dir: &str, langr: &LangRuntime) {
-    let temp_cont = &format!("tmp-{}-{}", u::basedir(dir), u::randstr_n(4));
+    let temp_cont = &format!("tmp-{}-{}", crate::image_tag(dir), u::randstr_n(4));
     let clean = &format!("docker rm -f {}", &temp_cont);
 
-    let run = format!("docker run -d --name {} {}", &temp_cont, u::basedir(dir));
+    let run = format!(
+        "docker run -d --name {} {}",
+        &temp_cont,
+        crate::image_tag(dir)
+    );
     sh(&clean, dir);
     sh(&run, dir);
     let id = sh(&format!("docker ps -aqf \"name={}\"", temp_cont), dir);
@@ -186,7 +199,10 @@ fn zip(dir: &str, langr: &LangRuntime) {
             let command = "zip -q -r lambda.zip bootstrap";
             sh(command, dir);
         }
-        _ => todo!(),
+        Lang::Clojure => {
+            let cmd = "cd build && zip -q -9 -r ../lambda.zip lib && cd -";
+            sh(&cmd, dir);
+        }
     }
 }
 
@@ -242,24 +258,29 @@ pub async fn build(
             gen_dockerfile_unshared(dir, arch, langr, *skip_dev_deps, bs);
         }
         bar.inc(1);
-        let created_root_dockerignore = gen_dockerignore(dir);
+        let ignore = gen_dockerignore(dir);
         bar.inc(2);
 
         let (status, out, err) = build_with_docker(auth, dir, langr, name, *shared_context).await;
         bar.inc(3);
 
         if !status {
+            sh("rm -f .dockerignore", dir);
+            drop(ignore);
+            bar.finish();
             println!("Inline build failed {}", name);
-            println!("Err {}, {}", out, err);
-            std::process::exit(1);
+            return BuildStatus {
+                path: format!("{}/lambda.zip", dir),
+                status: false,
+                out: out,
+                err: err,
+            };
         }
 
         copy_from_docker(dir, langr);
         bar.inc(4);
         sh("rm -f Dockerfile wrapper .dockerignore", dir);
-        if created_root_dockerignore {
-            crate::cleanup_root_dockerignore(dir);
-        }
+        drop(ignore);
         bar.inc(5);
 
         sh(command, dir);
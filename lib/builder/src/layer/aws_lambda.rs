@@ -67,6 +67,7 @@ pub fn make_runtimes(lang: &str) -> Vec<Runtime> {
         "python3.12" => vec![Runtime::Python310, Runtime::Python311, Runtime::Python312],
         "provided" => vec![Runtime::Provided],
         "providedal2" => vec![Runtime::Providedal2],
+        "provided.al2023" => vec!["provided.al2023".into()],
         "go" => vec!["provided.al2023".into()],
         "janet" => vec!["provided.al2023".into()],
         "rust" => vec!["provided.al2023".into()],
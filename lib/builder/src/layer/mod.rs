@@ -10,6 +10,7 @@ use crate::{
 };
 use colored::Colorize;
 use compiler::{
+    BuildKind,
     Lang,
     LangRuntime,
 };
@@ -18,8 +19,7 @@ use kit as u;
 use kit::sh;
 use std::collections::HashMap;
 
-fn should_split(dir: &str) -> bool {
-    let zipfile = "deps.zip";
+fn should_split(dir: &str, zipfile: &str) -> bool {
     let size;
     if u::path_exists(dir, zipfile) {
         size = u::path_size(dir, zipfile);
@@ -29,8 +29,8 @@ fn should_split(dir: &str) -> bool {
     size >= 60000000.0
 }
 
-fn split(dir: &str) {
-    let zipfile = format!("{}/deps.zip", dir);
+fn split(dir: &str, zipfile: &str) {
+    let zipfile = format!("{}/{}", dir, zipfile);
     let size;
     if u::file_exists(&zipfile) {
         size = u::file_size(&zipfile);
@@ -74,21 +74,28 @@ pub async fn publish(auth: &Auth, build: &BuildOutput) {
         runtime,
         name,
         artifact,
+        kind,
         ..
     } = build;
 
-    let lang = runtime.to_str();
-    if should_split(&dir) {
+    let lang = match kind {
+        BuildKind::Runtime => String::from("provided.al2023"),
+        _ => runtime.to_str(),
+    };
+    // zipsplit names the parts deps1.zip, deps2.zip ...
+    let stem = u::basename(artifact);
+    let zipfile = format!("{}.zip", &stem);
+    if should_split(&dir, &zipfile) {
         println!("Split layer ... {}", &name);
-        split(&dir);
-        if u::path_exists(dir, "deps1.zip") {
-            do_publish(auth, &lang, &format!("{}-0-dev", &name), "deps1.zip").await;
-        }
-        if u::path_exists(dir, "deps2.zip") {
-            do_publish(auth, &lang, &format!("{}-1-dev", &name), "deps2.zip").await;
-        }
-        if u::path_exists(dir, "deps3.zip") {
-            do_publish(auth, &lang, &format!("{}-2-dev", &name), "deps3.zip").await;
+        split(&dir, &zipfile);
+        let mut n = 1;
+        loop {
+            let part = format!("{}/{}{}.zip", dir, &stem, n);
+            if !u::file_exists(&part) {
+                break;
+            }
+            do_publish(auth, &lang, &format!("{}-{}-dev", &name, n - 1), &part).await;
+            n += 1;
         }
     } else {
         let layer_name = format!("{}-dev", &name);
@@ -137,10 +144,14 @@ pub fn gen_dockerfile(dir: &str, langr: &LangRuntime, package_manager: &str) {
 }
 
 pub fn copy_from_docker(dir: &str) {
-    let temp_cont = &format!("tmp-{}", u::basedir(dir));
+    let temp_cont = &format!("tmp-{}", crate::image_tag(dir));
     let clean = &format!("docker rm -f {}", &temp_cont);
 
-    let run = format!("docker run -d --name {} {}", &temp_cont, u::basedir(dir));
+    let run = format!(
+        "docker run -d --name {} {}",
+        &temp_cont,
+        crate::image_tag(dir)
+    );
     u::runcmd_quiet(&clean, dir);
     sh(&run, dir);
     let id = u::sh(&format!("docker ps -aqf \"name={}\"", temp_cont), dir);
@@ -162,11 +173,11 @@ pub fn build_with_docker(dir: &str) -> (bool, String, String) {
         Ok(e) => format!(
             "docker build --no-cache  --platform=linux/amd64 --ssh default={} --secret id=aws,src=$HOME/.aws/credentials --build-context shared={root} . -t {}",
             &e,
-            u::basedir(dir)
+            crate::image_tag(dir)
         ),
         Err(_) => format!(
             "docker build --no-cache  --platform=linux/amd64 --ssh default --secret id=aws,src=$HOME/.aws/credentials --build-context shared={root} . -t {}",
-            u::basedir(dir)
+            crate::image_tag(dir)
         ),
     };
     let (status, out, err) = u::runc(&cmd_str, dir);
@@ -212,11 +223,9 @@ fn clean(dir: &str) {
 pub fn build(dir: &str, name: &str, langr: &LangRuntime, bspec: &Build) -> BuildStatus {
     sh("rm -f deps.zip", dir);
     gen_dockerfile(dir, langr, &bspec.package_manager);
-    let created_root_dockerignore = crate::gen_dockerignore(dir);
+    let ignore = crate::gen_dockerignore(dir);
     let (status, out, err) = build_with_docker(dir);
-    if created_root_dockerignore {
-        crate::cleanup_root_dockerignore(dir);
-    }
+    drop(ignore);
     copy_from_docker(dir);
     if !u::path_exists(dir, "function.yml") && !u::path_exists(dir, "function.json") {
         copy(dir, langr);
@@ -1,18 +1,32 @@
+pub mod cache;
 mod code;
 mod extension;
+mod graph;
 mod image;
 mod inline;
 mod layer;
 mod library;
 mod microvm;
 pub mod page;
+mod runtime;
+mod slab;
 mod types;
 
-use crate::types::BuildOutput;
+use crate::{
+    graph::Graph,
+    types::{
+        BuildOutput,
+        BuildStatus,
+    },
+};
 use colored::Colorize;
 use compiler::spec::function::BuildKind;
 use composer::Function;
 use configurator::Config;
+use futures::stream::{
+    FuturesUnordered,
+    StreamExt,
+};
 use kit as u;
 use kit::sh;
 use provider::{
@@ -20,15 +34,47 @@ use provider::{
     aws::ecr,
 };
 use std::{
+    collections::HashSet,
     panic,
     str::FromStr,
+    sync::Mutex,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use tabled::{
+    Style,
+    Table,
+    Tabled,
 };
 
+// builds holding the root `.dockerignore`, and whether tc wrote it
+static ROOT_DOCKERIGNORE: Mutex<(usize, bool)> = Mutex::new((0, false));
+
+/// Holds the root `.dockerignore` while a build uses it. Builds run side
+/// by side, so the last one to drop it removes the file, and only if tc
+/// wrote it
+pub(crate) struct DockerIgnore;
+
+impl Drop for DockerIgnore {
+    fn drop(&mut self) {
+        let mut shared = ROOT_DOCKERIGNORE.lock().unwrap_or_else(|e| e.into_inner());
+        shared.0 = shared.0.saturating_sub(1);
+        if shared.0 == 0 && shared.1 {
+            let root = u::root();
+            sh(&format!("rm -f {}/.dockerignore", &root), &root);
+            shared.1 = false;
+        }
+    }
+}
+
 /// Writes a `.dockerignore` to `dir` and, if no user-managed `.dockerignore`
 /// exists at the repo root, also writes one there so that named build contexts
-/// (`--build-context shared={root}`) are filtered. Returns `true` if a root
-/// `.dockerignore` was created (caller is responsible for cleaning it up).
-pub(crate) fn gen_dockerignore(dir: &str) -> bool {
+/// (`--build-context shared={root}`) are filtered. The root file is kept
+/// until the returned guard, and those of builds running alongside, are
+/// dropped
+pub(crate) fn gen_dockerignore(dir: &str) -> DockerIgnore {
     let f = format!(
         r#"
 **/node_modules/
@@ -52,18 +98,21 @@ npm-debug.log
     let file = format!("{}/.dockerignore", dir);
     u::write_str(&file, &f);
 
-    let root = u::root();
-    let root_file = format!("{}/.dockerignore", root);
-    if !u::file_exists(&root_file) {
+    let root_file = format!("{}/.dockerignore", u::root());
+    let mut shared = ROOT_DOCKERIGNORE.lock().unwrap_or_else(|e| e.into_inner());
+    if shared.0 == 0 && !u::file_exists(&root_file) {
         u::write_str(&root_file, &f);
-        return true;
+        shared.1 = true;
     }
-    false
+    shared.0 += 1;
+    DockerIgnore
 }
 
-pub(crate) fn cleanup_root_dockerignore(dir: &str) {
-    let root = u::root();
-    sh(&format!("rm -f {}/.dockerignore", root), dir);
+/// Local docker tag of a build, unique per function dir so that builds
+/// running side by side do not overwrite each other's image
+pub(crate) fn image_tag(dir: &str) -> String {
+    let base = u::basedir(dir).to_lowercase();
+    format!("{}-{}", base, &u::checksum_str(dir)[..8])
 }
 
 pub fn just_images(recursive: bool) -> Vec<BuildOutput> {
@@ -96,13 +145,13 @@ pub fn just_images(recursive: bool) -> Vec<BuildOutput> {
     outs
 }
 
-pub async fn build(
+async fn build_kind(
     auth: &Auth,
     function: &Function,
-    name: Option<String>,
-    kind: Option<String>,
+    name: &str,
+    kind: &BuildKind,
     code_only: bool,
-) -> Vec<BuildOutput> {
+) -> BuildStatus {
     let Function {
         dir,
         build,
@@ -112,23 +161,15 @@ pub async fn build(
 
     let langr = &runtime.lang;
 
-    let kind = match kind {
-        Some(k) => BuildKind::from_str(&k).unwrap(),
-        None => build.kind.clone(),
-    };
-
-    let name = u::maybe_string(name, &function.name);
-    let auth = provider::init_centralized_auth(auth).await;
-
-    let build_status = match kind {
+    match kind {
         BuildKind::Image => {
-            image::build(&auth, dir, &name, langr, &runtime.uri, &build, code_only).await
+            image::build(auth, dir, name, langr, &runtime.uri, &build, code_only).await
         }
         BuildKind::Inline => {
             inline::build(
-                &auth,
+                auth,
                 dir,
-                &name,
+                name,
                 langr,
                 &runtime.arch,
                 &runtime.uri,
@@ -136,15 +177,78 @@ pub async fn build(
             )
             .await
         }
-        BuildKind::Layer => layer::build(dir, &name, langr, &build),
+        BuildKind::Layer => layer::build(dir, name, langr, &build),
         BuildKind::Library => library::build(dir, langr, &build),
-        BuildKind::Slab => todo!(),
-        BuildKind::Code => code::build(&auth, dir, &name, langr, &runtime.arch, &build).await,
-        BuildKind::Extension => extension::build(dir, &name, langr),
-        BuildKind::MicroVmImage => microvm::build(&auth, dir, &runtime, &build).await,
-        BuildKind::Runtime => todo!(),
+        BuildKind::Slab => slab::build(dir, name, langr, &build),
+        BuildKind::Code => code::build(auth, dir, name, langr, &runtime.arch, &build).await,
+        BuildKind::Extension => extension::build(dir, name, langr),
+        BuildKind::MicroVmImage => microvm::build(auth, dir, &runtime, &build).await,
+        BuildKind::Runtime => runtime::build(auth, dir, name, langr, &runtime.arch, &build).await,
+    }
+}
+
+// builds the function, reusing a cached artifact when its key matches.
+// Returns the kind built, its status and whether it came from the cache
+async fn try_build(
+    auth: &Auth,
+    function: &Function,
+    name: &str,
+    kind: Option<String>,
+    code_only: bool,
+) -> (BuildKind, BuildStatus, bool) {
+    let kind = match kind {
+        Some(k) => BuildKind::from_str(&k).unwrap(),
+        None => function.build.kind.clone(),
     };
 
+    let auth = provider::init_centralized_auth(auth).await;
+
+    let key = match cache::is_enabled(&kind) {
+        true => Some(cache::make_key(function, name, &kind, code_only)),
+        false => None,
+    };
+    let cached = match &key {
+        Some(k) => cache::restore(&auth, k, &function.dir).await,
+        None => None,
+    };
+
+    match cached {
+        Some(c) => {
+            println!("Cached: {} ({})", name, key.as_deref().unwrap_or_default().green());
+            (kind, c, true)
+        }
+        None => {
+            let status = build_kind(&auth, function, name, &kind, code_only).await;
+            if let (Some(k), true) = (&key, status.status) {
+                cache::store(&auth, k, &kind, &function.dir, &status).await;
+            }
+            (kind, status, false)
+        }
+    }
+}
+
+fn output_of(function: &Function, name: &str, kind: BuildKind, status: BuildStatus) -> BuildOutput {
+    BuildOutput {
+        name: name.to_string(),
+        dir: function.dir.clone(),
+        artifact: status.path,
+        kind: kind,
+        uri: function.runtime.uri.clone(),
+        runtime: function.runtime.lang.clone(),
+        version: function.build.version.clone(),
+    }
+}
+
+pub async fn build(
+    auth: &Auth,
+    function: &Function,
+    name: Option<String>,
+    kind: Option<String>,
+    code_only: bool,
+) -> Vec<BuildOutput> {
+    let name = u::maybe_string(name, &function.name);
+    let (kind, build_status, _) = try_build(auth, function, &name, kind, code_only).await;
+
     if !build_status.status {
         println!("{}", build_status.out.red());
         println!("{}", build_status.err);
@@ -154,28 +258,163 @@ pub async fn build(
         panic!("Build failed")
     }
 
-    let out = BuildOutput {
-        name: String::from(name),
-        dir: dir.to_string(),
-        artifact: build_status.path,
-        kind: kind.clone(),
-        uri: runtime.uri.clone(),
-        runtime: langr.clone(),
-        version: build.version.clone(),
+    vec![output_of(function, &name, kind, build_status)]
+}
+
+const DEFAULT_PARALLELISM: usize = 4;
+
+// builds run at a time with --parallel
+fn parallelism() -> usize {
+    match std::env::var("TC_BUILD_PARALLELISM") {
+        Ok(n) => match n.parse::<usize>() {
+            Ok(p) if p > 0 => p,
+            _ => {
+                eprintln!(
+                    "{} TC_BUILD_PARALLELISM={} is not a positive number, using {}",
+                    "Warning:".yellow(),
+                    &n,
+                    DEFAULT_PARALLELISM
+                );
+                DEFAULT_PARALLELISM
+            }
+        },
+        Err(_) => DEFAULT_PARALLELISM,
+    }
+}
+
+fn log_dir() -> String {
+    String::from("/tmp/tc/build-logs")
+}
+
+struct Outcome {
+    index: usize,
+    output: Option<BuildOutput>,
+    cached: bool,
+    error: Option<String>,
+    elapsed: Duration,
+}
+
+// runs the build on a blocking thread, since the language builders shell
+// out synchronously, and writes its output to the function's log
+async fn run_build(auth: &Auth, function: &Function, index: usize) -> Outcome {
+    let auth = auth.clone();
+    let f = function.clone();
+    let log = format!("{}/{}_{}.log", log_dir(), &f.namespace, &f.name);
+    let start = Instant::now();
+    let handle = tokio::runtime::Handle::current();
+    let res = tokio::task::spawn_blocking(move || {
+        handle.block_on(async {
+            let (kind, status, cached) = try_build(&auth, &f, &f.name, None, false).await;
+            u::write_str(&log, &format!("{}\n{}", &status.out, &status.err));
+            match status.status {
+                true => Ok((output_of(&f, &f.name, kind, status), cached)),
+                false => Err(log),
+            }
+        })
+    })
+    .await;
+    let (output, cached, error) = match res {
+        Ok(Ok((out, cached))) => (Some(out), cached, None),
+        Ok(Err(log)) => (None, false, Some(format!("see {}", log))),
+        Err(e) => (None, false, Some(e.to_string())),
     };
-    vec![out]
+    Outcome {
+        index: index,
+        output: output,
+        cached: cached,
+        error: error,
+        elapsed: start.elapsed(),
+    }
 }
 
-pub async fn build_recursive(auth: &Auth, dir: &str, _parallel: bool) -> Vec<BuildOutput> {
-    let mut outs: Vec<BuildOutput> = vec![];
+#[derive(Tabled, Clone, Debug)]
+struct Summary {
+    name: String,
+    kind: String,
+    duration: String,
+    size: String,
+    cached: bool,
+}
 
-    //TODO  parallelize
+fn summary_of(function: &Function, outcome: &Outcome) -> Summary {
+    let size = match &outcome.output {
+        Some(o) if u::file_exists(&o.artifact) => u::file_size_human(u::file_size(&o.artifact)),
+        _ => String::from("-"),
+    };
+    Summary {
+        name: function.name.clone(),
+        kind: function.build.kind.to_str(),
+        duration: format!("{:#}", u::time_format(outcome.elapsed)),
+        size: size,
+        cached: outcome.cached,
+    }
+}
 
+/// Builds every function of the topology and its nodes. Shared layers and
+/// libraries are built before the functions that use them; with
+/// `parallel`, up to TC_BUILD_PARALLELISM builds run at a time
+pub async fn build_recursive(auth: &Auth, dir: &str, parallel: bool) -> Vec<BuildOutput> {
     let topology = composer::compose(dir, true);
+    let graph = Graph::new(topology.functions().into_values().collect());
+    let total = graph.functions.len();
+    let limit = match parallel {
+        true => parallelism(),
+        false => 1,
+    };
+    u::mkdir(&log_dir());
 
-    for (_, function) in topology.functions {
-        let mut out = build(auth, &function, None, None, false).await;
-        outs.append(&mut out);
+    let start = Instant::now();
+    let mut done: HashSet<usize> = HashSet::new();
+    let mut started: HashSet<usize> = HashSet::new();
+    let mut outs: Vec<BuildOutput> = vec![];
+    let mut summaries: Vec<Summary> = vec![];
+    let mut failed = false;
+    let mut running = FuturesUnordered::new();
+    loop {
+        if !failed {
+            for i in graph.ready(&done, &started) {
+                if running.len() >= limit {
+                    break;
+                }
+                started.insert(i);
+                running.push(run_build(auth, &graph.functions[i], i));
+            }
+        }
+        let outcome = match running.next().await {
+            Some(o) => o,
+            None => break,
+        };
+        let f = &graph.functions[outcome.index];
+        summaries.push(summary_of(f, &outcome));
+        if let Some(e) = &outcome.error {
+            failed = true;
+            println!("{} {} ({})", "Failed".red(), &f.name, e);
+            continue;
+        }
+        done.insert(outcome.index);
+        println!(
+            "[{}/{}] {} {} ({:#}, {} running)",
+            done.len(),
+            total,
+            &f.name,
+            "built".green(),
+            u::time_format(outcome.elapsed),
+            running.len()
+        );
+        if let Some(out) = outcome.output {
+            outs.push(out);
+        }
+    }
+
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    let table = Table::new(summaries).with(Style::psql()).to_string();
+    println!("{}", table);
+    println!("Built in {:#}", u::time_format(start.elapsed()));
+    if failed {
+        panic::set_hook(Box::new(|_| {
+            println!("Build Failed");
+        }));
+        panic!("Build failed")
     }
     outs
 }
@@ -189,6 +428,10 @@ pub fn clean(recursive: bool) {
     for b in buildables {
         if b.kind == BuildKind::Inline {
             kit::sh("rm -rf build && rm -f bootstrap", &b.dir);
+        } else if b.kind == BuildKind::Slab {
+            kit::sh("rm -rf slab slab.zip", &b.dir);
+        } else if b.kind == BuildKind::Runtime {
+            kit::sh("rm -f runtime.zip", &b.dir);
         } else {
             kit::sh(
                 "rm -rf lambda.zip deps.zip build && rm -f bootstrap",
@@ -202,9 +445,11 @@ pub async fn publish(auth: &Auth, builds: Vec<BuildOutput>) {
     let auth = provider::init_centralized_auth(auth).await;
     for build in builds {
         match build.kind {
-            BuildKind::Layer | BuildKind::Library | BuildKind::Extension => {
-                layer::publish(&auth, &build).await
-            }
+            BuildKind::Layer
+            | BuildKind::Library
+            | BuildKind::Extension
+            | BuildKind::Slab
+            | BuildKind::Runtime => layer::publish(&auth, &build).await,
             BuildKind::Inline => match std::env::var("TC_USE_ASSET_STORE") {
                 Ok(_) => inline::publish(&auth, &build).await,
                 Err(_) => (),
@@ -18,7 +18,7 @@ async fn get_token(auth: &Auth) -> String {
     }
 }
 
-fn gen_dockerignore(dir: &str) -> bool {
+fn gen_dockerignore(dir: &str) -> crate::DockerIgnore {
     crate::gen_dockerignore(dir)
 }
 
@@ -32,12 +32,12 @@ async fn build_with_docker(auth: &Auth, dir: &str) -> (bool, String, String) {
             "docker buildx build --platform=linux/amd64 --ssh default={} --build-arg AUTH_TOKEN={} -t {} --build-context shared={root} .",
             &e,
             &token,
-            u::basedir(dir)
+            crate::image_tag(dir)
         ),
         Err(_) => format!(
             "docker buildx build --platform=linux/amd64 --ssh default --build-arg AUTH_TOKEN={} --load -t {} --build-context shared={root} .",
             &token,
-            u::basedir(dir)
+            crate::image_tag(dir)
         ),
     };
     let (status, out, err) = u::runc(&cmd_str, dir);
@@ -48,10 +48,14 @@ async fn build_with_docker(auth: &Auth, dir: &str) -> (bool, String, String) {
 }
 
 fn copy_from_docker(dir: &str) {
-    let temp_cont = &format!("tmp-{}", u::basedir(dir));
+    let temp_cont = &format!("tmp-{}", crate::image_tag(dir));
     let clean = &format!("docker rm -f {}", &temp_cont);
 
-    let run = format!("docker run -d --name {} {}", &temp_cont, u::basedir(dir));
+    let run = format!(
+        "docker run -d --name {} {}",
+        &temp_cont,
+        crate::image_tag(dir)
+    );
     sh(&clean, dir);
     sh(&run, dir);
     let id = sh(&format!("docker ps -aqf \"name={}\"", temp_cont), dir);
@@ -85,13 +89,14 @@ pub async fn build(
     node::gen_dockerfile(dir, command, config_template);
 
     bar.inc(1);
-    let created_root_dockerignore = gen_dockerignore(dir);
+    let ignore = gen_dockerignore(dir);
     bar.inc(2);
 
     let (status, out, err) = build_with_docker(auth, dir).await;
     if !status {
         println!("{}", &out);
         println!("{}", &err);
+        drop(ignore);
         std::process::exit(1);
     }
 
@@ -100,9 +105,7 @@ pub async fn build(
     copy_from_docker(dir);
     bar.inc(4);
     sh("rm -f Dockerfile wrapper .dockerignore", dir);
-    if created_root_dockerignore {
-        crate::cleanup_root_dockerignore(dir);
-    }
+    drop(ignore);
     clean_tmp(dir, config_template);
     bar.inc(5);
 }
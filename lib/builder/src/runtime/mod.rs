@@ -0,0 +1,60 @@
+use crate::{
+    Auth,
+    types::BuildStatus,
+};
+use colored::Colorize;
+use compiler::{
+    Arch,
+    Lang,
+    LangRuntime,
+};
+use composer::Build;
+use kit as u;
+use kit::sh;
+
+/// Builds a custom `provided.al2023` runtime published as a layer, with
+/// the runtime's `bootstrap` at the root of `runtime.zip`. Rust and Go
+/// runtimes compile their bootstrap, others ship it as a script
+pub async fn build(
+    auth: &Auth,
+    dir: &str,
+    name: &str,
+    langr: &LangRuntime,
+    arch: &Arch,
+    bspec: &Build,
+) -> BuildStatus {
+    sh("rm -f runtime.zip", dir);
+    match langr.to_lang() {
+        Lang::Rust | Lang::Go => {
+            // only the compiled bootstrap goes into the layer
+            let spec = Build {
+                command: String::from("echo 0"),
+                ..bspec.clone()
+            };
+            let status = crate::inline::build(auth, dir, name, langr, arch, "", &spec).await;
+            sh("mv -f lambda.zip runtime.zip && rm -f bootstrap", dir);
+            BuildStatus {
+                path: format!("{}/runtime.zip", dir),
+                ..status
+            }
+        }
+        _ => {
+            if !u::path_exists(dir, "bootstrap") {
+                panic!("No bootstrap found")
+            }
+            for cmd in &bspec.pre {
+                u::runcmd_stream(cmd, dir);
+            }
+            let cmd = "chmod +x bootstrap && zip -q -9 -r runtime.zip . -x '*.zip' -x '.*'";
+            let (status, out, err) = u::runc(cmd, dir);
+            let size = u::file_size_human(u::path_size(dir, "runtime.zip"));
+            println!("Size: {} ({})", name, size.green());
+            BuildStatus {
+                path: format!("{}/runtime.zip", dir),
+                status: status,
+                out: out,
+                err: err,
+            }
+        }
+    }
+}
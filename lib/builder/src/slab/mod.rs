@@ -1,18 +1,36 @@
-use super::LangRuntime;
+use crate::types::BuildStatus;
+use colored::Colorize;
+use compiler::{
+    Lang,
+    LangRuntime,
+};
+use composer::Build;
 use kit as u;
 use kit::sh;
 
-use crate::types::BuildStatus;
-
 fn find_image(runtime: &LangRuntime) -> String {
-    match runtime {
-        LangRuntime::Python312 => String::from("public.ecr.aws/sam/build-python3.12:latest"),
-        _ => todo!(),
+    let tag = match runtime.to_lang() {
+        Lang::Python | Lang::Ruby => runtime.to_str(),
+        Lang::Node => format!("nodejs{}.x", runtime.to_str().replace("node", "")),
+        Lang::Rust | Lang::Go => String::from("provided.al2023"),
+        _ => runtime.to_str(),
+    };
+    format!("public.ecr.aws/sam/build-{}:latest", tag)
+}
+
+fn deps_str(deps: Vec<String>) -> String {
+    if deps.len() >= 2 {
+        deps.join(" && ")
+    } else if deps.len() == 1 {
+        deps.first().unwrap().to_string()
+    } else {
+        String::from("echo 0")
     }
 }
 
-fn gen_dockerfile(dir: &str, runtime: &LangRuntime) {
+fn gen_dockerfile(dir: &str, runtime: &LangRuntime, pre: &Vec<String>) {
     let image = find_image(&runtime);
+    let pre_commands = deps_str(pre.to_vec());
 
     let f = format!(
         r#"
@@ -24,11 +42,9 @@ ENV PATH $HOME/.cargo/bin:$PATH
 
 COPY slab.sh /slab.sh
 
-RUN mkdir -p /build/lib
+RUN chmod +x /slab.sh && mkdir -p /build/lib
 
-RUN dnf update -yy
-
-RUN dnf -y install libXext libSM libXrender
+RUN {pre_commands}
 
 RUN --mount=type=ssh /slab.sh
 
@@ -38,33 +54,38 @@ RUN --mount=type=ssh /slab.sh
     u::write_str(&dockerfile, &f);
 }
 
-pub fn build_with_docker(dir: &str) {
+pub fn build_with_docker(dir: &str) -> (bool, String, String) {
     let cmd_str = match std::env::var("DOCKER_SSH") {
         Ok(e) => format!(
             "docker build --no-cache  --ssh default={} --secret id=aws,src=$HOME/.aws/credentials . -t {}",
             &e,
-            u::basedir(dir)
+            crate::image_tag(dir)
         ),
         Err(_) => format!(
             "docker build --no-cache  --ssh default --secret id=aws,src=$HOME/.aws/credentials . -t {}",
-            u::basedir(dir)
+            crate::image_tag(dir)
         ),
     };
-    let ret = u::runp(&cmd_str, dir);
-    if !ret {
-        sh("rm -rf Dockerfile build", dir);
+    let (status, out, err) = u::runc(&cmd_str, dir);
+    if !status {
+        sh("rm -rf Dockerfile slab", dir);
         std::panic::set_hook(Box::new(|_| {
             println!("Build failed");
         }));
         panic!("Build failed")
     }
+    (status, out, err)
 }
 
 fn copy_from_docker(dir: &str) {
-    let temp_cont = &format!("tmp-{}", u::basedir(dir));
+    let temp_cont = &format!("tmp-{}", crate::image_tag(dir));
     let clean = &format!("docker rm -f {}", &temp_cont);
 
-    let run = format!("docker run -d --name {} {}", &temp_cont, u::basedir(dir));
+    let run = format!(
+        "docker run -d --name {} {}",
+        &temp_cont,
+        crate::image_tag(dir)
+    );
     u::runcmd_quiet(&clean, dir);
     sh(&run, dir);
     let id = u::sh(&format!("docker ps -aqf \"name={}\"", temp_cont), dir);
@@ -80,22 +101,33 @@ fn copy_from_docker(dir: &str) {
     sh(&clean, dir);
 }
 
-pub fn build(
-    dir: &str,
-    runtime: &LangRuntime,
-) -> BuildStatus {
-
+/// Builds a slab: a dependency bundle shared by many functions, produced
+/// by the dir's `slab.sh` into /build and zipped into `slab.zip`. Slabs
+/// are published as layers, split into parts when large
+pub fn build(dir: &str, name: &str, runtime: &LangRuntime, bspec: &Build) -> BuildStatus {
     if !u::path_exists(dir, "slab.sh") {
         panic!("No slab.sh found")
     }
 
-    gen_dockerfile(dir, runtime);
-    build_with_docker(dir);
+    sh("rm -rf slab slab.zip", dir);
+    gen_dockerfile(dir, runtime, &bspec.pre);
+    let (status, out, err) = build_with_docker(dir);
     copy_from_docker(dir);
     sh("rm -f Dockerfile", dir);
 
     if u::path_exists(dir, "pyproject.toml") {
         sh("rm -f requirements.txt", dir);
     }
-    format!("{}/slab", dir)
+    sh("zip -q -9 -r ../slab.zip .", &format!("{}/slab", dir));
+    sh("rm -rf slab", dir);
+
+    let size = u::file_size_human(u::path_size(dir, "slab.zip"));
+    println!("Size: {} ({})", name, size.green());
+
+    BuildStatus {
+        path: format!("{}/slab.zip", dir),
+        status: status,
+        out: out,
+        err: err,
+    }
 }
@@ -30,6 +30,8 @@ pub enum Entity {
     Schedule,
     #[serde(alias = "page")]
     Page,
+    #[serde(alias = "store")]
+    Store,
 }
 
 impl FromStr for Entity {
@@ -47,6 +49,7 @@ impl FromStr for Entity {
             "trigger" | "triggers" => Ok(Entity::Trigger),
             "schedule" | "schedules" => Ok(Entity::Schedule),
             "page" | "pages" => Ok(Entity::Page),
+            "store" | "stores" => Ok(Entity::Store),
             _ => Err(ParseError),
         }
     }
@@ -65,6 +68,7 @@ impl Entity {
             Entity::Trigger => s!("trigger"),
             Entity::Schedule => s!("schedule"),
             Entity::Page => s!("page"),
+            Entity::Store => s!("store"),
         }
     }
 
@@ -88,6 +92,7 @@ impl Entity {
             "channels",
             "pools",
             "pages",
+            "stores",
         ];
         for x in v {
             println!("{x}");
@@ -108,6 +113,7 @@ impl Entity {
             "lambda" => Some(Entity::Function),
             "states" => Some(Entity::State),
             "appsync" => Some(Entity::Mutation),
+            "dynamodb" | "s3" => Some(Entity::Store),
             _ => None,
         }
     }
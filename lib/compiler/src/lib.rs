@@ -27,27 +27,25 @@ use std::{
 
 use validator::Validate;
 
+/// Prints why a spec is invalid and aborts compilation
+pub fn validation_error(e: impl std::fmt::Display) -> ! {
+    println!("{}", e);
+    std::panic::set_hook(Box::new(|_| {
+        println!("Validation Error");
+    }));
+    panic!("validation error");
+}
+
 pub fn compile(dir: &str) -> TopologySpec {
     let yaml_file = format!("{}/topology.yml", dir);
     let function_file = format!("{}/function.yml", dir);
     let lisp_file = format!("{}/topology.lisp", dir);
-    if u::file_exists(&yaml_file) {
+    if u::file_exists(&yaml_file) || u::file_exists(&lisp_file) {
         let spec = TopologySpec::new(&yaml_file);
         match spec.validate() {
             Ok(_) => spec,
-            Err(e) => {
-                println!("{}", e);
-                std::panic::set_hook(Box::new(|_| {
-                    println!("Validation Error");
-                }));
-                panic!("validation error");
-
-            }
+            Err(e) => validation_error(e),
         }
-    } else if u::file_exists(&lisp_file) {
-        let data = u::slurp(&lisp_file);
-        lisp::load(data);
-        TopologySpec::new(&yaml_file)
     } else if u::file_exists(&function_file) {
         TopologySpec::standalone(dir)
     } else {
@@ -105,7 +103,8 @@ pub fn guess_runtime(dir: &str) -> LangRuntime {
 
 pub fn is_topology_dir(dir: &str) -> bool {
     let topology_file = format!("{}/topology.yml", dir);
-    Path::new(&topology_file).exists()
+    let lisp_file = format!("{}/topology.lisp", dir);
+    Path::new(&topology_file).exists() || Path::new(&lisp_file).exists()
 }
 
 pub fn root_namespaces(dir: &str) -> HashMap<String, String> {
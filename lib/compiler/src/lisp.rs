@@ -3,6 +3,7 @@ mod env;
 mod expr;
 mod parser;
 mod symbol;
+mod topology;
 
 use colored::Colorize;
 use env::Env;
@@ -17,6 +18,7 @@ use symbol::Symbol;
 
 fn make_env() -> Env {
     let mut env = Env::new();
+    topology::bind(&mut env);
 
     env.bind_builtin("env", |env, args| {
         // Get the env as a map
@@ -935,6 +937,21 @@ pub fn load(mut program: String) {
     }
 }
 
+/// Evaluates a program of one or more forms and returns the value of the
+/// last one as JSON
+pub fn eval(program: &str) -> std::result::Result<serde_json::Value, String> {
+    let mut env = make_env();
+    let program = format!("{{\n{}\n}}", program);
+    let e = Expr::parse(&program)?;
+    match topology::strip(env.eval(e)) {
+        Expr::Err(e) => match *e {
+            Expr::String(s) => Err(s),
+            e => Err(e.to_string()),
+        },
+        e => Ok(e.into()),
+    }
+}
+
 async fn process_cmd(env: &mut Env, mut line: &str) {
     match Expr::parse(&mut line) {
         Ok(e) => {
@@ -211,6 +211,7 @@ impl From<Expr> for serde_json::Value {
             Expr::Float(f) => Number(serde_json::Number::from_f64(f).unwrap()),
             Expr::Int(i) => Number(serde_json::Number::from(i)),
             Expr::String(s) => String(s),
+            Expr::Symbol(s) => String(s.name().to_string()),
             Expr::List(l) => Array(l.into_iter().map(|e| e.into()).collect()),
             Expr::Tree(m) => Object(
                 m.into_iter()
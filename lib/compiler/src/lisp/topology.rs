@@ -0,0 +1,344 @@
+use super::{
+    env::Env,
+    expr::Expr,
+};
+use std::collections::BTreeMap;
+
+// key marking a tree as an entity built by one of the builtins below
+const ENTITY: &str = "tc:entity";
+
+fn key(s: &str) -> Expr {
+    Expr::String(s.to_string())
+}
+
+fn name_of(e: &Expr) -> Option<String> {
+    match e {
+        Expr::String(s) => Some(s.clone()),
+        Expr::Symbol(s) => Some(s.name().to_string()),
+        _ => None,
+    }
+}
+
+// spec fields given as a tree or map, with symbol keys read as strings
+fn props(e: Expr) -> Result<BTreeMap<Expr, Expr>, Expr> {
+    let pairs: Vec<(Expr, Expr)> = match e {
+        Expr::None => vec![],
+        Expr::Tree(t) => t.into_iter().collect(),
+        Expr::Map(m) => m.into_iter().collect(),
+        e => return Err(Expr::error(format!("Invalid spec {}", e))),
+    };
+    let mut h: BTreeMap<Expr, Expr> = BTreeMap::new();
+    for (k, v) in pairs {
+        match name_of(&k) {
+            Some(k) => h.insert(key(&k), v),
+            None => return Err(Expr::error(format!("Invalid key {}", k))),
+        };
+    }
+    Ok(h)
+}
+
+fn entity(kind: &str, name: &str, spec: BTreeMap<Expr, Expr>) -> Expr {
+    let mut t = BTreeMap::new();
+    t.insert(key(ENTITY), key(kind));
+    t.insert(key("name"), key(name));
+    t.insert(key("spec"), Expr::Tree(spec));
+    Expr::Tree(t)
+}
+
+fn as_entity(e: &Expr) -> Option<(String, String, BTreeMap<Expr, Expr>)> {
+    if let Expr::Tree(t) = e {
+        let kind = t.get(&key(ENTITY)).and_then(name_of)?;
+        let name = t.get(&key("name")).and_then(name_of)?;
+        let spec = match t.get(&key("spec")) {
+            Some(Expr::Tree(s)) => s.clone(),
+            _ => BTreeMap::new(),
+        };
+        return Some((kind, name, spec));
+    }
+    None
+}
+
+// (function "name" [uri "./loader" runtime [lang "python3.12"]])
+fn make(env: &mut Env, args: Vec<Expr>, kind: &str) -> Expr {
+    let name = match args.get(0).map(|a| env.eval(a.clone())) {
+        Some(e) => match name_of(&e) {
+            Some(n) => n,
+            None => return Expr::error(format!("Invalid {} name {}", kind, e)),
+        },
+        None => return Expr::error(format!("{} needs a name", kind)),
+    };
+    let spec = match args.get(1) {
+        Some(a) => env.eval(a.clone()),
+        None => Expr::None,
+    };
+    match props(spec) {
+        Ok(spec) => entity(kind, &name, spec),
+        Err(e) => e,
+    }
+}
+
+// the key under which an entity of this kind is referenced as a target
+fn target_key(kind: &str) -> Option<&'static str> {
+    match kind {
+        "functions" => Some("function"),
+        "events" => Some("event"),
+        "queues" => Some("queue"),
+        "channels" => Some("channel"),
+        _ => None,
+    }
+}
+
+// names in order, once each
+fn dedup(xs: Vec<String>) -> Vec<String> {
+    let mut seen: Vec<String> = vec![];
+    for x in xs {
+        if !seen.contains(&x) {
+            seen.push(x);
+        }
+    }
+    seen
+}
+
+// names given as `function` and `functions`
+fn functions_of(spec: &BTreeMap<Expr, Expr>) -> Vec<String> {
+    let mut xs: Vec<String> = vec![];
+    if let Some(f) = spec.get(&key("function")).and_then(name_of) {
+        xs.push(f);
+    }
+    if let Some(Expr::List(fs)) = spec.get(&key("functions")) {
+        xs.extend(fs.iter().filter_map(name_of));
+    }
+    xs
+}
+
+// a single function is given as `function`, several as `functions`
+fn set_functions(spec: &mut BTreeMap<Expr, Expr>, fns: Vec<String>) {
+    spec.remove(&key("function"));
+    spec.remove(&key("functions"));
+    match fns.len() {
+        0 => (),
+        1 => {
+            spec.insert(key("function"), key(&fns[0]));
+        }
+        _ => {
+            let xs = fns.iter().map(|f| key(f)).collect();
+            spec.insert(key("functions"), Expr::List(xs));
+        }
+    }
+}
+
+// makes `from` target `to`. Events fan out to several functions
+fn link(from: &Expr, to: &Expr) -> Result<Expr, Expr> {
+    let (kind, name, mut spec) = match as_entity(from) {
+        Some(x) => x,
+        None => return Err(Expr::error(format!("-> expects entities, got {}", from))),
+    };
+    let (to_kind, to_name, _) = match as_entity(to) {
+        Some(x) => x,
+        None => return Err(Expr::error(format!("-> expects entities, got {}", to))),
+    };
+    let k = match target_key(&to_kind) {
+        Some(k) => k,
+        None => return Err(Expr::error(format!("{} cannot be a target", to_kind))),
+    };
+    if kind == "events" && k == "function" {
+        let mut fns = functions_of(&spec);
+        fns.push(to_name);
+        set_functions(&mut spec, dedup(fns));
+    } else {
+        spec.insert(key(k), key(&to_name));
+    }
+    Ok(entity(&kind, &name, spec))
+}
+
+// (-> (route "/api/etl" [method "POST"]) (function "enhancer") (event "Notify"))
+fn chain(env: &mut Env, args: Vec<Expr>) -> Expr {
+    let xs: Vec<Expr> = args.into_iter().map(|a| env.eval(a)).collect();
+    let mut out: Vec<Expr> = vec![];
+    for (i, x) in xs.iter().enumerate() {
+        match xs.get(i + 1) {
+            Some(next) => match link(x, next) {
+                Ok(e) => out.push(e),
+                Err(e) => return e,
+            },
+            None => out.push(x.clone()),
+        }
+    }
+    Expr::List(out)
+}
+
+fn merge(into: &mut BTreeMap<Expr, Expr>, from: BTreeMap<Expr, Expr>) {
+    for (k, v) in from {
+        into.insert(k, v);
+    }
+}
+
+// merges the spec of an entity met again. Events keep every function
+// they fan out to; other entities target one entity of each kind
+fn merge_entity(
+    kind: &str,
+    name: &str,
+    into: &mut BTreeMap<Expr, Expr>,
+    mut from: BTreeMap<Expr, Expr>,
+) -> Result<(), Expr> {
+    if kind == "events" {
+        let mut fns = functions_of(into);
+        fns.extend(functions_of(&from));
+        from.remove(&key("function"));
+        from.remove(&key("functions"));
+        set_functions(into, dedup(fns));
+    }
+    for k in ["function", "event", "queue", "channel"] {
+        let a = into.get(&key(k)).and_then(name_of);
+        let b = from.get(&key(k)).and_then(name_of);
+        if let (Some(a), Some(b)) = (a, b) {
+            if a != b {
+                return Err(Expr::error(format!(
+                    "{} {} targets both {} {} and {}",
+                    kind, name, k, a, b
+                )));
+            }
+        }
+    }
+    merge(into, from);
+    Ok(())
+}
+
+// entities of the same kind and name are merged, so that an entity can
+// appear in several chains
+fn collect(spec: &mut BTreeMap<Expr, Expr>, e: Expr) -> Result<(), Expr> {
+    match e {
+        Expr::None => Ok(()),
+        Expr::Err(_) => Err(e),
+        Expr::List(xs) => {
+            for x in xs {
+                collect(spec, x)?;
+            }
+            Ok(())
+        }
+        e => match as_entity(&e) {
+            Some((kind, name, props)) => {
+                let mut section = match spec.remove(&key(&kind)) {
+                    Some(Expr::Tree(t)) => t,
+                    _ => BTreeMap::new(),
+                };
+                let mut entry = match section.remove(&key(&name)) {
+                    Some(Expr::Tree(t)) => t,
+                    _ => BTreeMap::new(),
+                };
+                merge_entity(&kind, &name, &mut entry, props)?;
+                section.insert(key(&name), Expr::Tree(entry));
+                spec.insert(key(&kind), Expr::Tree(section));
+                Ok(())
+            }
+            None => {
+                merge(spec, props(e)?);
+                Ok(())
+            }
+        },
+    }
+}
+
+// (topology "etl" [hyphenated_names true] entities...)
+fn topology(env: &mut Env, args: Vec<Expr>) -> Expr {
+    let mut xs = args.into_iter().map(|a| env.eval(a));
+    let name = match xs.next().as_ref().and_then(name_of) {
+        Some(n) => n,
+        None => return Expr::error("topology needs a name"),
+    };
+    let mut spec: BTreeMap<Expr, Expr> = BTreeMap::new();
+    spec.insert(key("name"), key(&name));
+    for x in xs {
+        if let Err(e) = collect(&mut spec, x) {
+            return e;
+        }
+    }
+    Expr::Tree(spec)
+}
+
+/// Binds the builtins that build a topology spec
+pub fn bind(env: &mut Env) {
+    env.bind_builtin("topology", topology);
+    env.bind_builtin("->", chain);
+    env.bind_builtin("function", |env, args| make(env, args, "functions"));
+    env.bind_builtin("route", |env, args| make(env, args, "routes"));
+    env.bind_builtin("event", |env, args| make(env, args, "events"));
+    env.bind_builtin("queue", |env, args| make(env, args, "queues"));
+    env.bind_builtin("channel", |env, args| make(env, args, "channels"));
+    env.bind_builtin("store", |env, args| make(env, args, "stores"));
+    env.bind_builtin("page", |env, args| make(env, args, "pages"));
+    env.bind_builtin("trigger", |env, args| make(env, args, "triggers"));
+}
+
+/// Entities evaluated outside a topology read as their spec
+pub fn strip(e: Expr) -> Expr {
+    match as_entity(&e) {
+        Some((_, _, spec)) => Expr::Tree(spec),
+        None => match e {
+            Expr::List(xs) => Expr::List(xs.into_iter().map(strip).collect()),
+            e => e,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lisp::eval;
+    use serde_json::json;
+
+    #[test]
+    fn test_event_fans_out_across_chains() {
+        let v = eval(
+            r#"(topology "t"
+                 (-> (event "Loaded" [producer "default"]) (function "indexer"))
+                 (-> (event "Loaded") (function "notifier"))
+                 (-> (event "Loaded") (function "indexer")))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            v["events"]["Loaded"],
+            json!({"producer": "default", "functions": ["indexer", "notifier"]})
+        );
+    }
+
+    #[test]
+    fn test_event_keeps_given_functions() {
+        let v = eval(
+            r#"(topology "t"
+                 (event "Loaded" [functions '("a" "b")])
+                 (-> (event "Loaded") (function "c")))"#,
+        )
+        .unwrap();
+        assert_eq!(v["events"]["Loaded"]["functions"], json!(["a", "b", "c"]));
+        assert_eq!(v["events"]["Loaded"]["function"], json!(null));
+    }
+
+    #[test]
+    fn test_merges_props_of_entities() {
+        let v = eval(
+            r#"(topology "t"
+                 (-> (route "/api/a" [method "POST"]) (function "a"))
+                 (function "a" [uri "./a"])
+                 (-> (function "a") (event "Done")))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            v["routes"]["/api/a"],
+            json!({"method": "POST", "function": "a"})
+        );
+        assert_eq!(v["functions"]["a"], json!({"uri": "./a", "event": "Done"}));
+    }
+
+    #[test]
+    fn test_rejects_conflicting_targets() {
+        let r = eval(
+            r#"(topology "t"
+                 (-> (route "/api/a") (function "a"))
+                 (-> (route "/api/a") (function "b")))"#,
+        );
+        assert!(
+            r.unwrap_err()
+                .contains("routes /api/a targets both function a and b")
+        );
+    }
+}
@@ -18,11 +18,16 @@ pub mod mutation;
 pub mod page;
 pub mod queue;
 pub mod route;
+pub mod store;
 
-use crate::yaml;
+use crate::{
+    lisp,
+    yaml,
+};
 pub use channel::ChannelSpec;
 pub use event::EventSpec;
 pub use function::{
+    EffectSpec,
     InlineFunctionSpec,
     Lang,
     LangRuntime,
@@ -36,6 +41,10 @@ pub use mutation::MutationSpec;
 pub use page::PageSpec;
 pub use queue::QueueSpec;
 pub use route::RouteSpec;
+pub use store::{
+    StoreKind,
+    StoreSpec,
+};
 use yaml::Transformer;
 
 use validator::Validate;
@@ -151,6 +160,7 @@ pub struct TopologySpec {
     pub channels: Option<HashMap<String, ChannelSpec>>,
     pub triggers: Option<HashMap<String, TriggerSpec>>,
     pub pages: Option<HashMap<String, PageSpec>>,
+    pub stores: Option<HashMap<String, StoreSpec>>,
     pub tests: Option<HashMap<String, TestSpec>>,
     pub states: Option<Value>,
     pub flow: Option<Value>,
@@ -185,11 +195,17 @@ impl Default for TopologySpec {
             channels: None,
             triggers: None,
             pages: None,
+            stores: None,
             tests: None,
         }
     }
 }
 
+// topology.lisp next to the given topology.yml
+fn lisp_file_of(topology_spec_file: &str) -> String {
+    format!("{}/topology.lisp", u::parent_dir(topology_spec_file))
+}
+
 impl TopologySpec {
     pub fn new(topology_spec_file: &str) -> TopologySpec {
         if u::file_exists(topology_spec_file) {
@@ -215,6 +231,11 @@ impl TopologySpec {
                     spec
                 }
             }
+        } else if u::file_exists(&lisp_file_of(topology_spec_file)) {
+            match TopologySpec::from_lisp(&lisp_file_of(topology_spec_file)) {
+                Ok(spec) => spec,
+                Err(e) => panic!("{:?}", e),
+            }
         } else {
             TopologySpec {
                 name: s!("tc"),
@@ -223,6 +244,31 @@ impl TopologySpec {
         }
     }
 
+    /// Evaluates a `topology.lisp`, whose last form builds the spec with
+    /// `(topology ...)`. Its value goes through the same include macros
+    /// as a `topology.yml`
+    pub fn from_lisp(lisp_file: &str) -> Result<TopologySpec, String> {
+        tracing::debug!("Evaluating topology {}", lisp_file);
+        let v = match lisp::eval(&u::slurp(lisp_file)) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("{}: {}", lisp_file, e)),
+        };
+        let dir = u::parent_dir(lisp_file);
+        let v = match Transformer::new(PathBuf::from(lisp_file), false, &dir) {
+            Ok(transformer) => match serde_yaml::to_value(v) {
+                Ok(v) => transformer.transform(v),
+                Err(e) => return Err(format!("{}: {}", lisp_file, e)),
+            },
+            Err(e) => return Err(format!("{}: {}", lisp_file, e)),
+        };
+        let mut spec: TopologySpec = match serde_yaml::from_value(v) {
+            Ok(spec) => spec,
+            Err(e) => return Err(format!("{}: {}", lisp_file, e)),
+        };
+        spec.dir = Some(dir);
+        Ok(spec)
+    }
+
     pub fn standalone(dir: &str) -> TopologySpec {
         if let Some(fspec) = function::load_fspec_file(dir) {
             TopologySpec {
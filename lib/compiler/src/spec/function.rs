@@ -451,6 +451,76 @@ pub struct TestSpec {
 
     #[serde(default)]
     pub entity: Option<String>,
+
+    /// Responses of downstream entities (`function/name`, `state/name`
+    /// or a resource arn) used when testing locally
+    #[serde(default)]
+    pub mocks: HashMap<String, serde_json::Value>,
+
+    /// Compare the response with the one recorded on the first run
+    #[serde(default)]
+    pub snapshot: Option<bool>,
+
+    /// JSON schema the response must conform to, inline or a json file
+    #[serde(default)]
+    pub schema: Option<serde_json::Value>,
+
+    /// Paths of volatile fields left out of comparisons
+    #[serde(default)]
+    pub ignore: Vec<String>,
+
+    /// Downstream effects to wait for after invoking the entity
+    #[serde(default)]
+    pub effects: Vec<EffectSpec>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EffectSpec {
+    /// `state`, `state/<namespace>`, `event/<name>`, `queue/<name>` or
+    /// `channel/<name>`
+    pub entity: String,
+
+    #[serde(default)]
+    pub expect: Option<String>,
+
+    #[serde(default)]
+    pub condition: Option<String>,
+
+    /// Seconds to wait for the effect
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RolloutSpec {
+    /// `canary` shifts traffic to the new version in steps, `blue-green`
+    /// all at once
+    #[serde(default)]
+    pub strategy: Option<String>,
+
+    /// Percent of traffic on the new version at each step
+    #[serde(default)]
+    pub steps: Vec<u8>,
+
+    /// Time to watch each step before the next one, e.g. `5m`
+    #[serde(default)]
+    pub interval: Option<String>,
+
+    /// Alias that shifts traffic, the function name by default
+    #[serde(default)]
+    pub alias: Option<String>,
+
+    /// Error and throttle rates, in percent of invocations, that revert
+    /// the alias to the previous version
+    #[serde(default)]
+    pub max_error_rate: Option<f64>,
+    #[serde(default)]
+    pub max_throttle_rate: Option<f64>,
+
+    /// Invocations of the new version a step needs to be judged. Steps
+    /// with fewer are inconclusive and revert the alias
+    #[serde(default)]
+    pub min_invocations: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -496,6 +566,7 @@ pub struct FunctionSpec {
     pub targets: Option<Vec<TargetSpec>>,
     pub shared: Option<bool>,
     pub aux_files: Option<Vec<String>>,
+    pub rollout: Option<RolloutSpec>,
 }
 
 fn find_revision(dir: &str) -> String {
@@ -622,6 +693,7 @@ pub fn load_fspec_file(dir: &str) -> Option<FunctionSpec> {
             targets: None,
             shared: None,
             aux_files: None,
+            rollout: None,
         })
     }
 }
@@ -650,6 +722,7 @@ impl FunctionSpec {
                 targets: None,
                 shared: None,
                 aux_files: None,
+                rollout: None,
             },
         }
     }
@@ -666,6 +739,7 @@ pub struct InlineFunctionSpec {
     pub queue: Option<String>,
     pub mutation: Option<String>,
     pub channel: Option<String>,
+    pub store: Option<String>,
     pub fqn: Option<String>,
     pub runtime: Option<RuntimeSpec>,
     pub build: Option<BuildSpec>,
@@ -695,6 +769,7 @@ impl InlineFunctionSpec {
             targets: targets,
             shared: None,
             aux_files: None,
+            rollout: None,
         }
     }
 
@@ -729,10 +804,23 @@ impl InlineFunctionSpec {
             };
             xs.push(t);
         }
+        if let Some(s) = &self.store {
+            let t = TargetSpec {
+                entity: Entity::Store,
+                name: s.to_string(),
+            };
+            xs.push(t);
+        }
         Some(xs)
     }
 }
 
+fn is_jvm_dir(dir: &str) -> bool {
+    ["deps.edn", "project.clj", "pom.xml", "build.gradle", "build.gradle.kts"]
+        .iter()
+        .any(|f| u::path_exists(dir, f))
+}
+
 pub fn infer_lang(dir: &str) -> LangRuntime {
     if u::path_exists(dir, "handler.py") || u::path_exists(dir, "pyproject.toml") {
         LangRuntime::Python310
@@ -742,7 +830,7 @@ pub fn infer_lang(dir: &str) -> LangRuntime {
         LangRuntime::Node22
     } else if u::path_exists(dir, "Gemfile") || u::path_exists(dir, "handler.rb") {
         LangRuntime::Ruby32
-    } else if u::path_exists(dir, "deps.edn") {
+    } else if is_jvm_dir(dir) {
         LangRuntime::Java21
     } else {
         LangRuntime::Python310
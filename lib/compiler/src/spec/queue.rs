@@ -3,6 +3,13 @@ use serde_derive::{
     Serialize,
 };
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeadLetterSpec {
+    pub name: Option<String>,
+    #[serde(alias = "maxReceiveCount")]
+    pub max_receive_count: Option<i32>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct QueueSpec {
     #[serde(default)]
@@ -11,4 +18,20 @@ pub struct QueueSpec {
     pub name: Option<String>,
 
     pub function: Option<String>,
+
+    #[serde(default)]
+    pub fifo: bool,
+    #[serde(default, alias = "content_based_dedup")]
+    pub content_dedup: bool,
+    pub visibility_timeout: Option<i32>,
+    #[serde(alias = "message_retention")]
+    pub retention: Option<i32>,
+    #[serde(alias = "dead_letter")]
+    pub dlq: Option<DeadLetterSpec>,
+
+    pub batch_size: Option<i32>,
+    #[serde(alias = "maximum_batching_window")]
+    pub batch_window: Option<i32>,
+    #[serde(default, alias = "report_batch_item_failures")]
+    pub partial_batch_failures: bool,
 }
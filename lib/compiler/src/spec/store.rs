@@ -0,0 +1,46 @@
+use kit::*;
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum StoreKind {
+    #[serde(alias = "dynamodb", alias = "ddb", alias = "table")]
+    Dynamodb,
+    #[serde(alias = "s3", alias = "bucket")]
+    S3,
+}
+
+impl StoreKind {
+    pub fn to_str(&self) -> String {
+        match self {
+            StoreKind::Dynamodb => s!("dynamodb"),
+            StoreKind::S3 => s!("s3"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StoreSpec {
+    pub kind: StoreKind,
+
+    /// Name of an existing table or bucket. Stores with a given name
+    /// are referenced, not created or deleted
+    pub name: Option<String>,
+
+    // dynamodb
+    pub table: Option<String>,
+    pub hash_key: Option<String>,
+    pub range_key: Option<String>,
+
+    // s3
+    pub bucket: Option<String>,
+
+    /// Functions that are granted access to this store
+    #[serde(default)]
+    pub functions: Vec<String>,
+
+    #[serde(default)]
+    pub readonly: bool,
+}
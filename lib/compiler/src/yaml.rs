@@ -32,6 +32,28 @@ fn load_yaml(file_path: PathBuf) -> Result<Value> {
     Ok(data)
 }
 
+const MACROS: [&str; 3] = ["!include", "!read", "!mutations"];
+
+fn tag_macros(input: Value) -> Value {
+    match input {
+        Value::String(s) => match s.split_once(' ') {
+            Some((tag, arg)) if MACROS.contains(&tag) => Value::Tagged(
+                TaggedValue {
+                    tag: Tag::new(tag),
+                    value: Value::String(arg.to_string()),
+                }
+                .into(),
+            ),
+            _ => Value::String(s),
+        },
+        Value::Sequence(seq) => Value::Sequence(seq.into_iter().map(tag_macros).collect()),
+        Value::Mapping(map) => Value::Mapping(Mapping::from_iter(
+            map.into_iter().map(|(k, v)| (k, tag_macros(v))),
+        )),
+        v => v,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Transformer {
     error_on_circular: bool,
@@ -45,6 +67,12 @@ impl Transformer {
         Self::new_node(root_path, strict, dir, None)
     }
 
+    /// Expands the include macros of a value that was not read from YAML,
+    /// where a macro is a string such as `"!include ./file.yml"`
+    pub fn transform(&self, input: Value) -> Value {
+        self.clone().recursive_process(tag_macros(input))
+    }
+
     pub fn parse(&self) -> Value {
         let file_path = self.root_path.clone();
         let input = load_yaml(file_path).unwrap();
@@ -0,0 +1,281 @@
+use crate::{
+    Entity,
+    Function,
+    Policy,
+    Topology,
+    aws::{
+        role::Kind,
+        template,
+    },
+};
+use serde_derive::Serialize;
+
+/// A statement a function needs, derived from its topology edges
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Need {
+    pub sid: String,
+    pub actions: Vec<String>,
+    pub resources: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Missing {
+    pub action: String,
+    pub resource: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Wildcard {
+    pub sid: String,
+    pub actions: Vec<String>,
+    pub resources: Vec<String>,
+}
+
+/// What a function needs compared with the role it is deployed with
+#[derive(Serialize, Clone, Debug)]
+pub struct Audit {
+    pub namespace: String,
+    pub function: String,
+    pub role: String,
+    pub kind: Kind,
+    pub required: Vec<Need>,
+    pub missing: Vec<Missing>,
+    pub wildcards: Vec<Wildcard>,
+}
+
+impl Audit {
+    /// Least-privilege policy granting only what the function needs
+    pub fn policy(&self) -> Policy {
+        let xs = self
+            .required
+            .iter()
+            .map(|n| (n.sid.clone(), n.actions.clone(), n.resources.clone()))
+            .collect();
+        Policy::from_statements(xs)
+    }
+}
+
+fn sid_of(prefix: &str, name: &str) -> String {
+    let name: String = name.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    format!("{}{}", prefix, name)
+}
+
+fn need(sid: String, actions: Vec<&str>, resources: Vec<String>) -> Need {
+    Need {
+        sid: sid,
+        actions: actions.into_iter().map(|a| a.to_string()).collect(),
+        resources: resources,
+    }
+}
+
+fn appsync_arn(path: &str) -> String {
+    format!("arn:aws:appsync:{{{{region}}}}:{{{{account}}}}:apis/*/{}", path)
+}
+
+/// Statements the function needs: its own logs, the entities it targets,
+/// the stores it references and the queues it consumes
+pub fn required(topology: &Topology, f: &Function) -> Vec<Need> {
+    let log_group = format!("/aws/lambda/{}", &f.fqn);
+    let mut xs: Vec<Need> = vec![need(
+        String::from("Logs"),
+        vec![
+            "logs:CreateLogGroup",
+            "logs:CreateLogStream",
+            "logs:PutLogEvents",
+        ],
+        vec![template::log_group_arn(&log_group)],
+    )];
+
+    for t in &f.targets {
+        let name = &t.name;
+        let n = match t.entity {
+            Entity::Function => {
+                let arn = match topology.functions.get(name) {
+                    Some(g) => g.arn.clone(),
+                    None => template::lambda_arn(name),
+                };
+                need(sid_of("Invoke", name), vec!["lambda:InvokeFunction"], vec![arn])
+            }
+            Entity::Event => {
+                let arn = match topology.events.get(name) {
+                    Some(e) => e.bus_arn.clone(),
+                    None => template::event_bus_arn(&topology.config.aws.eventbridge.bus),
+                };
+                need(sid_of("Event", name), vec!["events:PutEvents"], vec![arn])
+            }
+            Entity::Queue => {
+                let arn = match topology.queues.get(name) {
+                    Some(q) => q.arn.clone(),
+                    None => template::sqs_arn(name),
+                };
+                need(sid_of("Queue", name), vec!["sqs:SendMessage"], vec![arn])
+            }
+            Entity::Mutation => {
+                let path = format!("types/Mutation/fields/{}", name);
+                need(
+                    sid_of("Mutation", name),
+                    vec!["appsync:GraphQL"],
+                    vec![appsync_arn(&path)],
+                )
+            }
+            Entity::Channel => need(
+                sid_of("Channel", name),
+                vec!["appsync:EventPublish"],
+                vec![appsync_arn("channelNamespace/*")],
+            ),
+            Entity::State => {
+                let arn = match &topology.flow {
+                    Some(flow) => flow.arn.clone(),
+                    None => template::sfn_arn(name),
+                };
+                need(sid_of("State", name), vec!["states:StartExecution"], vec![arn])
+            }
+            _ => continue,
+        };
+        xs.push(n);
+    }
+
+    for (name, store) in &topology.stores {
+        let targeted = f
+            .targets
+            .iter()
+            .any(|t| t.entity == Entity::Store && &t.name == name);
+        if targeted || store.functions.contains(&f.name) {
+            xs.push(Need {
+                sid: sid_of("Store", name),
+                actions: store.actions(),
+                resources: store.resources(),
+            });
+        }
+    }
+
+    for (name, queue) in &topology.queues {
+        let consumes = queue
+            .targets
+            .iter()
+            .any(|t| t.entity == Entity::Function && t.name == f.name);
+        if consumes {
+            xs.push(need(
+                sid_of("Consume", name),
+                vec![
+                    "sqs:ReceiveMessage",
+                    "sqs:DeleteMessage",
+                    "sqs:GetQueueAttributes",
+                    "sqs:ChangeMessageVisibility",
+                ],
+                vec![queue.arn.clone()],
+            ));
+        }
+    }
+    xs.sort_by(|a, b| a.sid.cmp(&b.sid));
+    xs
+}
+
+/// Needed actions the policy does not allow
+pub fn missing(policy: &Policy, needs: &[Need]) -> Vec<Missing> {
+    let mut xs: Vec<Missing> = vec![];
+    for n in needs {
+        for action in &n.actions {
+            for resource in &n.resources {
+                if !policy.allows(action, resource) {
+                    xs.push(Missing {
+                        action: action.clone(),
+                        resource: resource.clone(),
+                    });
+                }
+            }
+        }
+    }
+    xs
+}
+
+fn audit_function(topology: &Topology, f: &Function) -> Audit {
+    let role = &f.runtime.role;
+    let required = required(topology, f);
+    // provided roles are managed outside tc, so their policy is unknown
+    let (missing, wildcards) = match role.kind {
+        Kind::Provided => (vec![], vec![]),
+        _ => {
+            let wildcards = role
+                .policy
+                .wildcards()
+                .into_iter()
+                .map(|(sid, actions, resources)| Wildcard {
+                    sid: sid,
+                    actions: actions,
+                    resources: resources,
+                })
+                .collect();
+            (missing(&role.policy, &required), wildcards)
+        }
+    };
+    Audit {
+        namespace: topology.namespace.clone(),
+        function: f.name.clone(),
+        role: role.name.clone(),
+        kind: role.kind.clone(),
+        required: required,
+        missing: missing,
+        wildcards: wildcards,
+    }
+}
+
+/// Audits the roles of all functions in the topology and its nodes
+pub fn audit(topology: &Topology) -> Vec<Audit> {
+    let mut xs: Vec<Audit> = topology
+        .functions
+        .values()
+        .map(|f| audit_function(topology, f))
+        .collect();
+    for node in topology.nodes.values() {
+        xs.extend(audit(node));
+    }
+    xs.sort_by(|a, b| (&a.namespace, &a.function).cmp(&(&b.namespace, &b.function)));
+    xs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn needs() -> Vec<Need> {
+        vec![
+            need(
+                String::from("InvokeB"),
+                vec!["lambda:InvokeFunction"],
+                vec![template::lambda_arn("ns_b_{{sandbox}}")],
+            ),
+            need(
+                String::from("QueueJobs"),
+                vec!["sqs:SendMessage"],
+                vec![template::sqs_arn("jobs")],
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_missing() {
+        let policy = Policy::new(Entity::Function);
+        let xs = missing(&policy, &needs());
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].action, "sqs:SendMessage");
+
+        let tight = Policy::from_statements(vec![(
+            String::from("Jobs"),
+            vec![String::from("SQS:Send*")],
+            vec![String::from("arn:aws:sqs:*:*:jobs")],
+        )]);
+        let xs = missing(&tight, &needs());
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].action, "lambda:InvokeFunction");
+        assert!(tight.wildcards().is_empty());
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let policy = Policy::new(Entity::Function);
+        let sids: Vec<String> = policy.wildcards().into_iter().map(|w| w.0).collect();
+        assert!(sids.iter().any(|s| s.contains("LambdaState")));
+        assert!(!sids.iter().any(|s| s.contains("LambdaFunction")));
+    }
+}
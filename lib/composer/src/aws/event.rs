@@ -118,6 +118,14 @@ pub fn make_targets(
         .as_ref()
         .filter(|q| !q.is_empty())
         .map(|q| template::sqs_arn(q));
+    if let Some(q) = &espec.dead_letter_queue {
+        if q.ends_with(".fifo") {
+            panic!(
+                "Event {}: dead_letter_queue {} must not be FIFO",
+                event_name, q
+            );
+        }
+    }
     let retry_attempts = espec.retries;
     let maximum_event_age_in_seconds = espec.maximum_event_age_in_seconds;
 
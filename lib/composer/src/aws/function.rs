@@ -1,6 +1,7 @@
 pub mod build;
 mod code;
 pub mod fs;
+pub mod rollout;
 pub mod runtime;
 mod target;
 
@@ -13,6 +14,7 @@ use compiler::spec::{
 use configurator::Config;
 use kit as u;
 use kit::*;
+pub use rollout::Rollout;
 pub use runtime::Runtime;
 use serde_derive::{
     Deserialize,
@@ -38,6 +40,8 @@ pub struct Function {
     pub targets: Vec<Target>,
     pub shared: bool,
     pub aux_files: Vec<String>,
+    #[serde(default)]
+    pub rollout: Option<Rollout>,
 }
 
 fn is_singular_function_dir() -> bool {
@@ -147,6 +151,10 @@ impl Function {
             shared: false,
             tasks: fspec.tasks,
             aux_files: aux_files,
+            rollout: match &fspec.rollout {
+                Some(r) => Some(Rollout::new(r)),
+                None => None,
+            },
         }
     }
 
@@ -196,6 +204,10 @@ impl Function {
             shared: false,
             tasks: fspec.tasks.clone(),
             aux_files: aux_files,
+            rollout: match &fspec.rollout {
+                Some(r) => Some(Rollout::new(r)),
+                None => None,
+            },
         }
     }
 
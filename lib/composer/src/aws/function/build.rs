@@ -4,6 +4,7 @@ use compiler::spec::function::{
     BuildSpec,
     Lang,
 };
+use kit as u;
 use kit::*;
 use serde_derive::{
     Deserialize,
@@ -44,7 +45,20 @@ fn infer_kind(package_type: &str) -> BuildKind {
     }
 }
 
-fn as_default_package_manager(lang: &Lang) -> String {
+// JVM functions build with the tool their project file names
+fn as_jvm_package_manager(dir: &str) -> String {
+    if u::path_exists(dir, "deps.edn") {
+        String::from("clojure")
+    } else if u::path_exists(dir, "project.clj") {
+        String::from("lein")
+    } else if u::path_exists(dir, "build.gradle") || u::path_exists(dir, "build.gradle.kts") {
+        String::from("gradle")
+    } else {
+        String::from("maven")
+    }
+}
+
+fn as_default_package_manager(lang: &Lang, dir: &str) -> String {
     match lang {
         Lang::Python => match std::env::var("TC_PYTHON_PACKAGE_MANAGER") {
             Ok(r) => r,
@@ -52,6 +66,7 @@ fn as_default_package_manager(lang: &Lang) -> String {
         },
         Lang::Ruby => String::from("bundler"),
         Lang::Rust => String::from("cargo"),
+        Lang::Clojure => as_jvm_package_manager(dir),
         _ => String::from("default"),
     }
 }
@@ -128,7 +143,7 @@ impl Build {
 
                 package_manager: match b.package_manager {
                     Some(p) => p,
-                    None => as_default_package_manager(&runtime.lang.to_lang()),
+                    None => as_default_package_manager(&runtime.lang.to_lang(), dir),
                 },
             },
             None => {
@@ -154,7 +169,7 @@ impl Build {
                     build_role_arn: String::from(""),
                     bucket: String::from(""),
                     image_name: String::from(""),
-                    package_manager: as_default_package_manager(&runtime.lang.to_lang()),
+                    package_manager: as_default_package_manager(&runtime.lang.to_lang(), dir),
                 }
             }
         }
@@ -0,0 +1,100 @@
+use compiler::spec::function::RolloutSpec;
+use kit as u;
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Strategy {
+    Canary,
+    BlueGreen,
+}
+
+/// Period, in ms, of the metrics that judge each step
+pub const METRIC_PERIOD: i64 = 60_000;
+
+/// Gradual shift of an alias to a newly published version. Each step is
+/// watched for `interval` ms before the next one
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Rollout {
+    pub strategy: Strategy,
+    pub steps: Vec<u8>,
+    pub interval: i64,
+    pub alias: Option<String>,
+    pub max_error_rate: f64,
+    pub max_throttle_rate: f64,
+    pub min_invocations: u32,
+}
+
+fn make_steps(strategy: &Strategy, given: &[u8]) -> Vec<u8> {
+    let mut steps: Vec<u8> = match (strategy, given.is_empty()) {
+        (Strategy::BlueGreen, _) => vec![100],
+        (Strategy::Canary, true) => vec![10, 50, 100],
+        (Strategy::Canary, false) => given.iter().map(|s| *s.min(&100)).collect(),
+    };
+    steps.sort();
+    steps.dedup();
+    if steps.last() != Some(&100) {
+        steps.push(100);
+    }
+    steps
+}
+
+impl Rollout {
+    pub fn new(spec: &RolloutSpec) -> Rollout {
+        let strategy = match spec.strategy.as_deref() {
+            Some("blue-green") | Some("blue_green") | Some("bluegreen") => Strategy::BlueGreen,
+            _ => Strategy::Canary,
+        };
+        let interval = match &spec.interval {
+            Some(i) => match u::duration_millis(i) {
+                Some(ms) => ms,
+                None => panic!("Invalid rollout interval {}", i),
+            },
+            None => 300_000,
+        };
+        Rollout {
+            steps: make_steps(&strategy, &spec.steps),
+            strategy: strategy,
+            interval: interval,
+            alias: spec.alias.clone(),
+            max_error_rate: spec.max_error_rate.unwrap_or(1.0),
+            max_throttle_rate: spec.max_throttle_rate.unwrap_or(1.0),
+            min_invocations: spec.min_invocations.unwrap_or(10),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(strategy: Option<&str>, steps: Vec<u8>) -> RolloutSpec {
+        RolloutSpec {
+            strategy: strategy.map(|s| s.to_string()),
+            steps: steps,
+            interval: Some("2m".to_string()),
+            alias: None,
+            max_error_rate: None,
+            max_throttle_rate: None,
+            min_invocations: None,
+        }
+    }
+
+    #[test]
+    fn test_rollout() {
+        let r = Rollout::new(&spec(None, vec![]));
+        assert_eq!(r.strategy, Strategy::Canary);
+        assert_eq!(r.steps, vec![10, 50, 100]);
+        assert_eq!(r.interval, 120_000);
+        assert_eq!(r.min_invocations, 10);
+
+        let r = Rollout::new(&spec(Some("canary"), vec![25, 5, 120]));
+        assert_eq!(r.steps, vec![5, 25, 100]);
+
+        let r = Rollout::new(&spec(Some("blue-green"), vec![10]));
+        assert_eq!(r.strategy, Strategy::BlueGreen);
+        assert_eq!(r.steps, vec![100]);
+    }
+}
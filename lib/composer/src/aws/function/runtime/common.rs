@@ -89,7 +89,10 @@ pub fn infer_lang(dir: &str) -> LangRuntime {
         LangRuntime::Node22
     } else if pe("Gemfile") || pe("handler.rb") {
         LangRuntime::Ruby32
-    } else if pe("deps.edn") {
+    } else if ["deps.edn", "project.clj", "pom.xml", "build.gradle", "build.gradle.kts"]
+        .iter()
+        .any(|f| pe(f))
+    {
         LangRuntime::Java21
     } else {
         LangRuntime::Python310
@@ -12,6 +12,7 @@ use compiler::{
     BuildKind,
     FunctionSpec,
     InfraSpec,
+    LangRuntime,
     RuntimeSpec,
     function::{
         AssetsSpec,
@@ -217,7 +218,11 @@ pub fn make(
         memory_size: *memory_size,
         timeout: *timeout,
         cpu: None,
-        snapstart: u::opt_as_bool(r.snapstart),
+        // JVM functions cold start slowly, so they snapshot by default
+        snapstart: match r.snapstart {
+            Some(s) => s,
+            None => r.lang == LangRuntime::Java21,
+        },
         role: role,
         enable_network: if let Some(n) = r.network { n } else { false },
         enable_fs: enable_fs,
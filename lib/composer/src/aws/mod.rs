@@ -9,5 +9,6 @@ pub mod queue;
 pub mod role;
 pub mod route;
 pub mod schedule;
+pub mod store;
 pub mod template;
 pub mod transducer;
@@ -1,7 +1,10 @@
 use super::template;
 use compiler::{
     Entity,
-    spec::QueueSpec,
+    spec::{
+        QueueSpec,
+        queue::DeadLetterSpec,
+    },
 };
 use serde_derive::{
     Deserialize,
@@ -14,16 +17,58 @@ pub struct Target {
     pub name: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeadLetter {
+    pub name: String,
+    pub arn: String,
+    pub max_receive_count: i32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Queue {
     pub name: String,
     pub should_create: bool,
     pub arn: String,
     pub targets: Vec<Target>,
+    #[serde(default)]
+    pub fifo: bool,
+    #[serde(default)]
+    pub content_dedup: bool,
+    pub visibility_timeout: Option<i32>,
+    pub retention: Option<i32>,
+    pub dlq: Option<DeadLetter>,
+    pub batch_size: Option<i32>,
+    pub batch_window: Option<i32>,
+    #[serde(default)]
+    pub partial_batch_failures: bool,
+}
+
+// FIFO queues must carry the .fifo suffix, and so must their DLQs
+fn fifo_name(name: &str, fifo: bool) -> String {
+    if fifo && !name.ends_with(".fifo") {
+        format!("{}.fifo", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn make_dlq(name: &str, fifo: bool, spec: &DeadLetterSpec) -> DeadLetter {
+    let dlq_name = match &spec.name {
+        Some(n) => fifo_name(n, fifo),
+        None => fifo_name(&format!("{}-dlq", name.trim_end_matches(".fifo")), fifo),
+    };
+    DeadLetter {
+        arn: template::sqs_arn(&dlq_name),
+        name: dlq_name,
+        max_receive_count: spec.max_receive_count.unwrap_or(5),
+    }
 }
 
 impl Queue {
     pub fn new(name: &str, qspec: &QueueSpec) -> Queue {
+        if qspec.fifo && qspec.batch_window.is_some() {
+            panic!("Queue {}: FIFO queues take no batch_window", name);
+        }
         let mut targets: Vec<Target> = vec![];
         if let Some(f) = &qspec.function {
             let t = Target {
@@ -33,19 +78,57 @@ impl Queue {
             targets.push(t);
         }
 
-        match &qspec.name {
-            Some(n) => Queue {
-                name: String::from(n),
-                should_create: false,
-                arn: template::sqs_arn(&n),
-                targets: targets,
-            },
-            None => Queue {
-                name: String::from(name),
-                should_create: true,
-                arn: template::sqs_arn(&name),
-                targets: targets,
-            },
+        let (name, should_create) = match &qspec.name {
+            Some(n) => (n.to_string(), false),
+            None => (fifo_name(name, qspec.fifo), true),
+        };
+        let dlq = match &qspec.dlq {
+            Some(d) => Some(make_dlq(&name, qspec.fifo, d)),
+            None => None,
+        };
+
+        Queue {
+            arn: template::sqs_arn(&name),
+            name: name,
+            should_create: should_create,
+            targets: targets,
+            fifo: qspec.fifo,
+            content_dedup: qspec.content_dedup,
+            visibility_timeout: qspec.visibility_timeout,
+            retention: qspec.retention,
+            dlq: dlq,
+            batch_size: qspec.batch_size,
+            batch_window: qspec.batch_window,
+            partial_batch_failures: qspec.partial_batch_failures,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fifo_queue_with_dlq() {
+        let yaml = r#"
+function: consumer
+fifo: true
+dlq: {max_receive_count: 3}
+batch_size: 10
+"#;
+        let spec: QueueSpec = serde_yaml::from_str(yaml).unwrap();
+        let queue = Queue::new("orders", &spec);
+        assert_eq!(queue.name, "orders.fifo");
+        let dlq = queue.dlq.unwrap();
+        assert_eq!(dlq.name, "orders-dlq.fifo");
+        assert_eq!(dlq.max_receive_count, 3);
+        assert_eq!(queue.batch_size, Some(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "FIFO queues take no batch_window")]
+    fn fifo_queue_rejects_batch_window() {
+        let spec: QueueSpec = serde_yaml::from_str("fifo: true\nbatch_window: 5\n").unwrap();
+        Queue::new("orders", &spec);
+    }
+}
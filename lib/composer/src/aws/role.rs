@@ -240,6 +240,47 @@ impl Role {
         }
     }
 
+    /// Extends the role with an additional statement. Base roles are
+    /// shared across functions, so granting on one forks a dedicated
+    /// role for the given function. Provided roles are left untouched.
+    pub fn grant(
+        &self,
+        namespace: &str,
+        function_name: &str,
+        sid: &str,
+        actions: Vec<String>,
+        resources: Vec<String>,
+    ) -> Role {
+        match self.kind {
+            Kind::Provided => {
+                tracing::debug!("Not granting {} on provided role {}", sid, &self.name);
+                self.clone()
+            }
+            Kind::Override => Role {
+                policy: self.policy.grant(sid, actions, resources),
+                ..self.clone()
+            },
+            Kind::Base => {
+                let abbr = if function_name.chars().count() > 10 {
+                    u::abbreviate(function_name, "-")
+                } else {
+                    function_name.to_string()
+                };
+                let name = format!("tc-{}-{}-{{{{sandbox}}}}", namespace, abbr);
+                Role {
+                    name: s!(&name),
+                    kind: Kind::Override,
+                    path: String::from(""),
+                    trust: self.trust.clone(),
+                    arn: template::role_arn(&name),
+                    policy: self.policy.grant(sid, actions, resources),
+                    policy_name: s!(&name),
+                    policy_arn: template::policy_arn(&name),
+                }
+            }
+        }
+    }
+
     pub fn entity_role_arn(entity: Entity) -> String {
         match std::env::var("TC_LEGACY_ROLES") {
             Ok(_) => {
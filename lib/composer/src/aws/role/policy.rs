@@ -295,6 +295,44 @@ fn make_appsync_actions() -> Vec<Action> {
     ]
 }
 
+// IAM wildcard match where `*` matches any run and `?` any character
+fn glob(pattern: &str, s: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    let (mut i, mut j) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while j < s.len() {
+        if i < p.len() && (p[i] == '?' || p[i] == s[j]) {
+            i += 1;
+            j += 1;
+        } else if i < p.len() && p[i] == '*' {
+            star = Some((i, j));
+            i += 1;
+        } else if let Some((si, sj)) = star {
+            i = si + 1;
+            j = sj + 1;
+            star = Some((si, sj + 1));
+        } else {
+            return false;
+        }
+    }
+    p[i..].iter().all(|c| *c == '*')
+}
+
+impl Action {
+    fn allows(&self, action: &str, resource: &str) -> bool {
+        let action = action.to_lowercase();
+        self.effect == "Allow"
+            && self.action.iter().any(|a| glob(&a.to_lowercase(), &action))
+            && self.resource.iter().any(|r| glob(r, resource))
+    }
+
+    fn is_wildcard(&self) -> bool {
+        self.action.iter().any(|a| a == "*" || a.ends_with(":*"))
+            || self.resource.iter().any(|r| r == "*")
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Policy {
     #[serde(rename(serialize = "Version", deserialize = "Version"))]
@@ -304,6 +342,52 @@ pub struct Policy {
 }
 
 impl Policy {
+    /// Actions the policy allows
+    pub fn allowed_actions(&self) -> Vec<String> {
+        let mut xs: Vec<String> = vec![];
+        for a in &self.statement {
+            if a.effect == "Allow" {
+                xs.extend(a.action.clone());
+            }
+        }
+        xs
+    }
+
+    /// Whether some statement allows the action on the resource
+    pub fn allows(&self, action: &str, resource: &str) -> bool {
+        self.statement.iter().any(|a| a.allows(action, resource))
+    }
+
+    /// Allowed statements with `*` actions or resources, as
+    /// (sid, actions, resources)
+    pub fn wildcards(&self) -> Vec<(String, Vec<String>, Vec<String>)> {
+        let mut xs: Vec<(String, Vec<String>, Vec<String>)> = vec![];
+        for a in &self.statement {
+            if a.effect == "Allow" && a.is_wildcard() {
+                let sid = maybe_string(a.sid.clone(), "");
+                xs.push((sid, a.action.clone(), a.resource.clone()));
+            }
+        }
+        xs
+    }
+
+    /// Policy made of the given (sid, actions, resources) statements
+    pub fn from_statements(xs: Vec<(String, Vec<String>, Vec<String>)>) -> Policy {
+        let mut statement: Vec<Action> = vec![];
+        for (sid, actions, resources) in xs {
+            statement.push(Action {
+                action: actions,
+                effect: s!("Allow"),
+                resource: resources,
+                sid: Some(sid),
+            });
+        }
+        Policy {
+            version: s!("2012-10-17"),
+            statement: statement,
+        }
+    }
+
     pub fn new(entity: Entity) -> Policy {
         let actions = match entity {
             Entity::Function => make_lambda_actions(),
@@ -345,6 +429,22 @@ impl Policy {
         }
     }
 
+    pub fn grant(&self, sid: &str, actions: Vec<String>, resources: Vec<String>) -> Policy {
+        let action = Action {
+            action: actions,
+            effect: s!("Allow"),
+            resource: resources,
+            sid: make_sid(sid),
+        };
+
+        let mut statement: Vec<Action> = self.statement.clone();
+        statement.push(action);
+        Policy {
+            version: self.version.clone(),
+            statement: statement,
+        }
+    }
+
     pub fn to_string(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
@@ -65,6 +65,9 @@ pub struct Target {
     pub entity: Entity,
     pub name: String,
     pub arn: String,
+    /// Rollout alias of a function target, when not the fqn
+    #[serde(default)]
+    pub alias: Option<String>,
     pub request_params: HashMap<String, String>,
     pub response_params: HashMap<String, String>,
 }
@@ -108,10 +111,15 @@ fn make_target(
 ) -> Target {
     if let Some(f) = &rspec.function {
         let name = find_function(&f, fns);
+        let alias = fns
+            .get(f)
+            .and_then(|x| x.rollout.as_ref())
+            .and_then(|r| r.alias.clone());
         return Target {
             entity: Entity::Function,
             name: name.clone(),
             arn: template::lambda_arn(&name),
+            alias: alias,
             request_params: match &rspec.request_params {
                 Some(r) => r.clone(),
                 None => {
@@ -153,6 +161,7 @@ fn make_target(
             entity: Entity::Event,
             name: s!(ev),
             arn: String::from(""),
+            alias: None,
             request_params: req,
             response_params: HashMap::new(),
         };
@@ -161,6 +170,17 @@ fn make_target(
         if let Some(queue) = queues.get(q) {
             req.insert(s!("QueueUrl"), template::sqs_url(&queue.name));
             req.insert(s!("MessageBody"), format!("{{{{payload}}}}"));
+            // FIFO queues need a group, one per queue unless the route
+            // maps it, and a dedup id unless the queue dedups by content
+            if queue.fifo {
+                req.insert(s!("MessageGroupId"), s!(q));
+                if !queue.content_dedup {
+                    req.insert(s!("MessageDeduplicationId"), s!("$context.requestId"));
+                }
+            }
+            if let Some(given) = &rspec.request_params {
+                req.extend(given.clone());
+            }
         } else {
             panic!("No queue defined {}", &q)
         }
@@ -168,6 +188,7 @@ fn make_target(
             entity: Entity::Queue,
             name: s!(q),
             arn: String::from(""),
+            alias: None,
             request_params: req,
             response_params: HashMap::new(),
         };
@@ -182,6 +203,7 @@ fn make_target(
             entity: Entity::State,
             name: fqn.to_string(),
             arn: arn,
+            alias: None,
             request_params: req,
             response_params: HashMap::new(),
         };
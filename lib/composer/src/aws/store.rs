@@ -0,0 +1,183 @@
+use super::template;
+use crate::aws::function::Function;
+use compiler::{
+    Entity,
+    spec::{
+        StoreKind,
+        StoreSpec,
+    },
+};
+use kit::*;
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Store {
+    pub name: String,
+    pub kind: StoreKind,
+    pub should_create: bool,
+    pub arn: String,
+    pub hash_key: Option<String>,
+    pub range_key: Option<String>,
+    pub functions: Vec<String>,
+    pub readonly: bool,
+}
+
+fn make_name(namespace: &str, name: &str, spec: &StoreSpec) -> String {
+    match spec.kind {
+        StoreKind::Dynamodb => match &spec.table {
+            Some(t) => format!("{}_{{{{sandbox}}}}", t),
+            None => format!("{}_{}_{{{{sandbox}}}}", namespace, name),
+        },
+        StoreKind::S3 => {
+            let base = match &spec.bucket {
+                Some(b) => b.to_string(),
+                None => format!("{}-{}", namespace, name),
+            };
+            format!("{}-{{{{sandbox}}}}", base.replace("_", "-").to_lowercase())
+        }
+    }
+}
+
+fn make_arn(kind: &StoreKind, name: &str) -> String {
+    match kind {
+        StoreKind::Dynamodb => template::dynamodb_arn(name),
+        StoreKind::S3 => template::s3_arn(name),
+    }
+}
+
+impl Store {
+    pub fn new(namespace: &str, name: &str, spec: &StoreSpec) -> Store {
+        let (store_name, should_create) = match &spec.name {
+            Some(n) => (n.to_string(), false),
+            None => (make_name(namespace, name, spec), true),
+        };
+        if should_create && spec.kind == StoreKind::Dynamodb && spec.hash_key.is_none() {
+            panic!(
+                "Store {}: no hash_key defined for table {}",
+                name, &store_name
+            );
+        }
+
+        Store {
+            arn: make_arn(&spec.kind, &store_name),
+            name: store_name,
+            kind: spec.kind.clone(),
+            should_create: should_create,
+            hash_key: spec.hash_key.clone(),
+            range_key: spec.range_key.clone(),
+            functions: spec.functions.clone(),
+            readonly: spec.readonly,
+        }
+    }
+
+    pub fn actions(&self) -> Vec<String> {
+        match (&self.kind, self.readonly) {
+            (StoreKind::Dynamodb, true) => v![
+                "dynamodb:GetItem",
+                "dynamodb:BatchGetItem",
+                "dynamodb:Query",
+                "dynamodb:Scan",
+                "dynamodb:DescribeTable"
+            ],
+            (StoreKind::Dynamodb, false) => v![
+                "dynamodb:GetItem",
+                "dynamodb:BatchGetItem",
+                "dynamodb:Query",
+                "dynamodb:Scan",
+                "dynamodb:DescribeTable",
+                "dynamodb:PutItem",
+                "dynamodb:UpdateItem",
+                "dynamodb:DeleteItem",
+                "dynamodb:BatchWriteItem",
+                "dynamodb:ConditionCheckItem"
+            ],
+            (StoreKind::S3, true) => v!["s3:GetObject", "s3:ListBucket"],
+            (StoreKind::S3, false) => v![
+                "s3:GetObject",
+                "s3:ListBucket",
+                "s3:PutObject",
+                "s3:DeleteObject"
+            ],
+        }
+    }
+
+    pub fn resources(&self) -> Vec<String> {
+        match self.kind {
+            StoreKind::Dynamodb => vec![self.arn.clone(), format!("{}/index/*", &self.arn)],
+            StoreKind::S3 => vec![self.arn.clone(), format!("{}/*", &self.arn)],
+        }
+    }
+
+    fn sid(&self, store_name: &str) -> String {
+        let name: String = store_name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        format!("Store{}", name)
+    }
+}
+
+pub fn make_all(namespace: &str, specs: &Option<HashMap<String, StoreSpec>>) -> HashMap<String, Store> {
+    let mut h: HashMap<String, Store> = HashMap::new();
+    if let Some(xs) = specs {
+        for (name, spec) in xs {
+            tracing::debug!("store {}", &name);
+            h.insert(name.to_string(), Store::new(namespace, &name, &spec));
+        }
+    }
+    h
+}
+
+fn references(function: &Function, store_name: &str, store: &Store) -> bool {
+    let targeted = function
+        .targets
+        .iter()
+        .any(|t| t.entity == Entity::Store && t.name == store_name);
+    targeted || store.functions.contains(&function.name)
+}
+
+/// Grants every function that references a store (via a `store`
+/// target or the store's `functions` list) access to it in its role
+pub fn grant(
+    namespace: &str,
+    functions: &mut HashMap<String, Function>,
+    stores: &HashMap<String, Store>,
+) {
+    for (_, f) in functions.iter_mut() {
+        for (store_name, store) in stores {
+            if references(f, store_name, store) {
+                tracing::debug!("Granting {} access to store {}", &f.name, store_name);
+                f.runtime.role = f.runtime.role.grant(
+                    namespace,
+                    &f.name,
+                    &store.sid(store_name),
+                    store.actions(),
+                    store.resources(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "no hash_key defined")]
+    fn table_requires_hash_key() {
+        let spec: StoreSpec = serde_yaml::from_str("kind: dynamodb\n").unwrap();
+        Store::new("orders", "ledger", &spec);
+    }
+
+    #[test]
+    fn referenced_table_needs_no_hash_key() {
+        let spec: StoreSpec = serde_yaml::from_str("kind: dynamodb\nname: ledger\n").unwrap();
+        let store = Store::new("orders", "ledger", &spec);
+        assert!(!store.should_create);
+    }
+}
@@ -90,3 +90,14 @@ pub fn sqs_url(name: &str) -> String {
         name
     )
 }
+
+pub fn dynamodb_arn(table: &str) -> String {
+    format!(
+        "arn:aws:dynamodb:{{{{region}}}}:{{{{account}}}}:table/{}",
+        table
+    )
+}
+
+pub fn s3_arn(bucket: &str) -> String {
+    format!("arn:aws:s3:::{}", bucket)
+}
@@ -228,6 +228,7 @@ fn make_function(namespace: &str, name: &str, fqn: &str) -> Function {
         tasks: HashMap::new(),
         shared: false,
         aux_files: vec![],
+        rollout: None,
     }
 }
 
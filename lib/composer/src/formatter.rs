@@ -94,6 +94,7 @@ pub fn pprint_entity(topology: &Topology, entity: Entity) {
         Entity::Queue => u::pp_json(topology.queues.clone()),
         Entity::Channel => u::pp_json(topology.channels.clone()),
         Entity::Page => u::pp_json(topology.pages.clone()),
+        Entity::Store => u::pp_json(topology.stores.clone()),
         Entity::State => {
             if let Some(f) = &topology.flow {
                 let out = serde_yaml::to_string(&f).unwrap();
@@ -249,9 +249,9 @@ impl RepoIndex {
         }
     }
 
-    /// True iff `dir/topology.yml` exists.
+    /// True iff `dir/topology.yml` or `dir/topology.lisp` exists.
     pub fn is_topology_dir(&self, dir: &str) -> bool {
-        self.path_exists(dir, "topology.yml")
+        self.path_exists(dir, "topology.yml") || self.path_exists(dir, "topology.lisp")
     }
 
     /// Mirrors composer/topology.rs::is_inferred_dir but answers from
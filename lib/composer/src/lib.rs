@@ -1,3 +1,4 @@
+pub mod audit;
 pub mod aws;
 pub mod formatter;
 
@@ -39,6 +40,7 @@ pub use aws::{
         Throttling,
     },
     schedule::Schedule,
+    store::Store,
     transducer::Transducer,
 };
 use compiler::{
@@ -258,6 +260,7 @@ pub fn entities_of(topology: &Topology) -> Vec<Entity> {
         functions,
         pages,
         mutations,
+        stores,
         flow,
         ..
     } = topology;
@@ -281,6 +284,9 @@ pub fn entities_of(topology: &Topology) -> Vec<Entity> {
     if queues.len() > 0 {
         xs.push(Entity::Queue)
     }
+    if stores.len() > 0 {
+        xs.push(Entity::Store)
+    }
     if let Some(_f) = flow {
         xs.push(Entity::State);
     }
@@ -2,6 +2,7 @@ use super::{
     index,
     version,
 };
+use configurator::Config;
 use kit as u;
 use kit::*;
 use std::collections::HashMap;
@@ -29,14 +30,42 @@ fn load_tags(infra_dir: &str) -> HashMap<String, String> {
     }
 }
 
+// stdout of a git command, empty when it fails, e.g. outside a repo
+fn git(args: &[&str]) -> String {
+    let out = std::process::Command::new("git")
+        .args(args)
+        .current_dir(u::pwd())
+        .output();
+    match out {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).trim().to_string(),
+        _ => String::from(""),
+    }
+}
+
 fn git_author() -> String {
+    use std::sync::OnceLock;
+    static CACHE: OnceLock<String> = OnceLock::new();
+    CACHE.get_or_init(|| git(&["config", "user.name"])).clone()
+}
+
+fn git_branch() -> String {
     use std::sync::OnceLock;
     static CACHE: OnceLock<String> = OnceLock::new();
     CACHE
-        .get_or_init(|| sh("git config user.name", &u::pwd()))
+        .get_or_init(|| git(&["rev-parse", "--abbrev-ref", "HEAD"]))
         .clone()
 }
 
+// TC_SANDBOX_TTL takes precedence over deployer.sandbox_ttl in config
+fn sandbox_ttl() -> String {
+    match std::env::var("TC_SANDBOX_TTL") {
+        Ok(ttl) => ttl,
+        Err(_) => Config::new().deployer.sandbox_ttl,
+    }
+}
+
+/// Tags applied to every resource in a sandbox. `created_at` is kept
+/// from the first deploy as updates do not apply it
 pub fn make(namespace: &str, infra_dir: &str) -> HashMap<String, String> {
     let tc_version = option_env!("PROJECT_VERSION")
         .unwrap_or(env!("CARGO_PKG_VERSION"))
@@ -51,6 +80,16 @@ pub fn make(namespace: &str, infra_dir: &str) -> HashMap<String, String> {
     h.insert(s!("updated_at"), u::utc_now());
     h.insert(s!("updated_by"), git_author());
     h.insert(s!("tc_version"), tc_version);
+    h.insert(s!("created_at"), u::utc_now());
+    h.insert(s!("created_by"), git_author());
+    let branch = git_branch();
+    if !branch.is_empty() {
+        h.insert(s!("branch"), branch);
+    }
+    let ttl = sandbox_ttl();
+    if !ttl.is_empty() {
+        h.insert(s!("ttl"), ttl);
+    }
 
     let given_tags = load_tags(infra_dir);
     h.extend(given_tags);
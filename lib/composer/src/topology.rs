@@ -15,16 +15,19 @@ pub use crate::aws::{
     role::Role,
     route::Route,
     schedule::Schedule,
+    store::Store,
     transducer::Transducer,
 };
 use crate::{
     aws::{
         channel,
         event,
+        function::rollout,
         mutation,
         page,
         pool,
         schedule,
+        store,
         template,
     },
     hooks,
@@ -86,6 +89,8 @@ pub struct Topology {
     pub channels: HashMap<String, Channel>,
     pub pools: HashMap<String, Pool>,
     pub pages: HashMap<String, Page>,
+    #[serde(default)]
+    pub stores: HashMap<String, Store>,
     pub tags: HashMap<String, String>,
     pub flow: Option<Flow>,
     pub config: Config,
@@ -121,7 +126,8 @@ pub fn is_topology_dir(dir: &str) -> bool {
         return idx.is_topology_dir(dir);
     }
     let topology_file = format!("{}/topology.yml", dir);
-    Path::new(&topology_file).exists()
+    let lisp_file = format!("{}/topology.lisp", dir);
+    Path::new(&topology_file).exists() || Path::new(&lisp_file).exists()
 }
 
 fn parent_topology_file(dir: &str) -> Option<String> {
@@ -727,6 +733,106 @@ fn find_kind(
     }
 }
 
+// names of the entities, other than routes, that invoke the function
+fn invokers_of(
+    is_fn: &dyn Fn(&str) -> bool,
+    events: &HashMap<String, Event>,
+    queues: &HashMap<String, Queue>,
+    mutations: &HashMap<String, Mutation>,
+    pools: &HashMap<String, Pool>,
+    schedules: &HashMap<String, Schedule>,
+    flow: &Option<Flow>,
+) -> Vec<String> {
+    let mut xs: Vec<String> = vec![];
+    for (k, e) in events {
+        if e.targets
+            .iter()
+            .any(|t| t.entity == Entity::Function && is_fn(&t.name))
+        {
+            xs.push(format!("event {}", k));
+        }
+    }
+    for (k, q) in queues {
+        if q.targets
+            .iter()
+            .any(|t| t.entity == Entity::Function && is_fn(&t.name))
+        {
+            xs.push(format!("queue {}", k));
+        }
+    }
+    for (k, m) in mutations {
+        if m.resolvers
+            .values()
+            .any(|r| r.entity == Entity::Function && is_fn(&r.target_name))
+        {
+            xs.push(format!("mutation {}", k));
+        }
+    }
+    for (k, p) in pools {
+        if p.triggers.values().any(|t| is_fn(t)) {
+            xs.push(format!("pool {}", k));
+        }
+    }
+    for (k, sc) in schedules {
+        if is_fn(u::split_last(&sc.target_arn, ":").as_str()) {
+            xs.push(format!("schedule {}", k));
+        }
+    }
+    if let Some(f) = flow {
+        if has_function(&f.definition, is_fn) {
+            xs.push(format!("state {}", &f.name));
+        }
+    }
+    xs
+}
+
+fn has_function(v: &Value, is_fn: &dyn Fn(&str) -> bool) -> bool {
+    match v {
+        Value::String(s) => is_fn(s) || is_fn(u::split_last(s, ":").as_str()),
+        Value::Array(xs) => xs.iter().any(|x| has_function(x, is_fn)),
+        Value::Object(m) => m.values().any(|x| has_function(x, is_fn)),
+        _ => false,
+    }
+}
+
+// only routes invoke the rollout alias. Other invokers call `$LATEST`,
+// which is overwritten before the first step is watched. A step shorter
+// than the metric period has no datapoints to judge it by
+fn check_rollouts(
+    functions: &HashMap<String, Function>,
+    events: &HashMap<String, Event>,
+    queues: &HashMap<String, Queue>,
+    mutations: &HashMap<String, Mutation>,
+    pools: &HashMap<String, Pool>,
+    schedules: &HashMap<String, Schedule>,
+    flow: &Option<Flow>,
+) -> Result<(), String> {
+    for (name, f) in functions {
+        let rollout = match &f.rollout {
+            Some(r) => r,
+            None => continue,
+        };
+        if rollout.interval < rollout::METRIC_PERIOD {
+            return Err(format!(
+                "Function {}: rollout interval must be at least {}s, the metric period",
+                name,
+                rollout::METRIC_PERIOD / 1000
+            ));
+        }
+        let templated = template::maybe_namespace(name);
+        let is_fn = |s: &str| s == templated || s == f.fqn;
+        let invokers = invokers_of(&is_fn, events, queues, mutations, pools, schedules, flow);
+        if !invokers.is_empty() {
+            return Err(format!(
+                "Function {}: rollout needs route-only invokers, but {} invoke $LATEST",
+                name,
+                invokers.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn concurrency(maybe_concurrency: &Option<i32>) -> i32 {
     match maybe_concurrency {
         Some(b) => *b,
@@ -756,6 +862,9 @@ fn make(
     let interned = intern_functions(&namespace, &infra_dir, &spec);
     functions.extend(interned);
 
+    let stores = store::make_all(&namespace, &spec.stores);
+    store::grant(&namespace, &mut functions, &stores);
+
     let version = version::current_semver(root_ns, root_dir);
     let fqn = template::topology_fqn(&namespace, spec.hyphenated_names);
     let flow = Flow::new(dir, &infra_dir, &fqn, &spec);
@@ -769,6 +878,13 @@ fn make(
     let queues = make_queues(&spec, &config);
     let routes = make_routes(&spec, &fqn, &functions, &events, &queues, &infra_dir);
     let channels = make_channels(&spec, &config);
+    let pools = make_pools(&spec, &config);
+    let schedules = schedule::make_all(&namespace, &infra_dir);
+    if let Err(e) = check_rollouts(
+        &functions, &events, &queues, &mutations, &pools, &schedules, &flow,
+    ) {
+        compiler::validation_error(e);
+    }
 
     let maybe_transducer = Transducer::new(&namespace, &functions, &events, &mutations, &channels);
 
@@ -801,13 +917,14 @@ fn make(
         tests: make_test(spec.tests.clone(), &functions),
         all_functions: functions.clone(),
         functions: functions,
-        schedules: schedule::make_all(&namespace, &infra_dir),
+        schedules: schedules,
         queues: queues,
         mutations: mutations,
         channels: channels,
-        pools: make_pools(&spec, &config),
+        pools: pools,
         tags: tag::make(&spec.name, &infra_dir),
         pages: page::make_all(dir, &spec, &infra_dir, &config),
+        stores: stores,
         flow: flow,
         config: Config::new(),
         transducer: maybe_transducer,
@@ -869,6 +986,7 @@ fn make_standalone(root_ns: &str, dir: &str) -> Topology {
         tags: tag::make(&namespace, ""),
         schedules: HashMap::new(),
         pages: HashMap::new(),
+        stores: HashMap::new(),
         tests: HashMap::new(),
         config: Config::new(),
         transducer: None,
@@ -41,6 +41,7 @@ fn fake_fspec(name: &str) -> FunctionSpec {
         targets: None,
         aux_files: None,
         shared: None,
+        rollout: None,
     }
 }
 
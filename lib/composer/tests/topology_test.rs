@@ -348,3 +348,211 @@ fn non_recursive_mode_does_not_promote() {
         "non-recursive mode has no child nodes"
     );
 }
+
+#[test]
+fn stores_grant_access_to_referencing_functions() {
+    let outer = TempDir::new().unwrap();
+    let root = outer.path();
+
+    write_topology_yml(
+        root,
+        "name: store-grants\n\
+         kind: step-function\n\
+         stores:\n  \
+         orders:\n    kind: dynamodb\n    hash_key: id\n    functions: [writer]\n  \
+         archive:\n    kind: s3\n    name: existing-archive\n    readonly: true\n",
+    );
+    write_shared_function(&root.join("writer"), "writer", "store_grants_writer");
+
+    let topology = Topology::new(root.to_str().unwrap(), "", true, false);
+
+    let orders = topology.stores.get("orders").expect("orders store");
+    assert!(orders.should_create);
+    assert_eq!(orders.name, "store-grants_orders_{{sandbox}}");
+
+    let archive = topology.stores.get("archive").expect("archive store");
+    assert!(!archive.should_create, "named stores are referenced, not created");
+    assert_eq!(archive.name, "existing-archive");
+
+    let writer = topology.functions.get("writer").expect("writer function");
+    let policy = serde_json::to_string(&writer.runtime.role.policy).unwrap();
+    assert!(policy.contains("dynamodb:PutItem"), "writer role: {}", policy);
+    assert!(!policy.contains("existing-archive"), "writer role: {}", policy);
+    assert_ne!(writer.runtime.role.name, "tc-base-function-{{sandbox}}");
+}
+
+const LISP_TENANTS_YAML: &str = "name: x-lisp-tenants
+routes:
+  /api/acme:
+    method: POST
+    function: acme-loader
+  /api/globex:
+    method: POST
+    function: globex-loader
+functions:
+  acme-loader:
+    uri: ./loader
+    event: Loaded
+  globex-loader:
+    uri: ./loader
+    event: Loaded
+  indexer:
+    uri: ./loader
+  notifier:
+    uri: ./loader
+events:
+  Loaded:
+    producer: default
+    functions:
+      - indexer
+      - notifier
+";
+
+// tags carry the time of composition
+fn compose_value(dir: &std::path::Path) -> serde_json::Value {
+    let mut t = Topology::new(dir.to_str().unwrap(), "", false, false);
+    t.tags.clear();
+    serde_json::to_value(&t).unwrap()
+}
+
+#[test]
+fn lisp_example_composes_like_its_yaml_twin() {
+    let example = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../examples/composition/lisp-tenants");
+    let outer = TempDir::new().unwrap();
+    let dir = outer.path().join("lisp-tenants");
+    fs::create_dir_all(dir.join("loader")).unwrap();
+    fs::copy(
+        example.join("loader/handler.py"),
+        dir.join("loader/handler.py"),
+    )
+    .unwrap();
+
+    fs::copy(example.join("topology.lisp"), dir.join("topology.lisp")).unwrap();
+    let from_lisp = compose_value(&dir);
+
+    fs::remove_file(dir.join("topology.lisp")).unwrap();
+    write_topology_yml(&dir, LISP_TENANTS_YAML);
+    let from_yaml = compose_value(&dir);
+
+    assert_eq!(from_lisp, from_yaml);
+    assert_eq!(
+        from_lisp["events"]["Loaded"]["targets"]
+            .as_array()
+            .map(|xs| xs.len()),
+        Some(2),
+        "Loaded fans out to indexer and notifier"
+    );
+}
+
+#[test]
+fn lisp_include_macros_expand_like_yaml() {
+    let outer = TempDir::new().unwrap();
+    let dir = outer.path().join("lisp-include");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("queues.yml"), "jobs:\n  fifo: true\n").unwrap();
+
+    fs::write(
+        dir.join("topology.lisp"),
+        "(topology \"x-lisp-include\" [queues \"!include ./queues.yml\"])",
+    )
+    .unwrap();
+    let from_lisp = compose_value(&dir);
+
+    fs::remove_file(dir.join("topology.lisp")).unwrap();
+    write_topology_yml(
+        &dir,
+        "name: x-lisp-include\nqueues: !include ./queues.yml\n",
+    );
+    let from_yaml = compose_value(&dir);
+
+    assert_eq!(from_lisp, from_yaml);
+    assert_eq!(from_lisp["queues"]["jobs"]["fifo"], true);
+}
+
+#[test]
+fn route_to_fifo_queue_sets_message_group() {
+    let outer = TempDir::new().unwrap();
+    let root = outer.path().join("orders");
+    write_topology_yml(
+        &root,
+        "name: route-fifo\n\
+         routes:\n  \
+         /api/orders:\n    method: POST\n    queue: orders\n  \
+         /api/audits:\n    method: POST\n    queue: audits\n\
+         queues:\n  \
+         orders:\n    fifo: true\n  \
+         audits:\n    fifo: true\n    content_dedup: true\n",
+    );
+    let topology = Topology::new(root.to_str().unwrap(), "", false, false);
+
+    let orders = &topology.routes.get("/api/orders").unwrap().target;
+    assert_eq!(
+        orders.request_params.get("MessageGroupId"),
+        Some(&s!("orders"))
+    );
+    assert_eq!(
+        orders.request_params.get("MessageDeduplicationId"),
+        Some(&s!("$context.requestId"))
+    );
+
+    let audits = &topology.routes.get("/api/audits").unwrap().target;
+    assert!(audits.request_params.contains_key("MessageGroupId"));
+    assert!(!audits.request_params.contains_key("MessageDeduplicationId"));
+}
+
+fn write_rollout_topology(dir: &std::path::Path, extra: &str) {
+    write_topology_yml(
+        dir,
+        &format!(
+            "name: rollout-invokers\n\
+             routes:\n  \
+             /api/pay:\n    method: POST\n    function: payer\n{extra}"
+        ),
+    );
+    write_shared_function(&dir.join("payer"), "payer", "rollout_invokers_payer");
+    let spec = fs::read_to_string(dir.join("payer/function.yml")).unwrap();
+    fs::write(
+        dir.join("payer/function.yml"),
+        format!("{spec}rollout:\n  steps: [10, 100]\n"),
+    )
+    .unwrap();
+}
+
+#[test]
+fn rollout_of_routed_function_composes() {
+    let outer = TempDir::new().unwrap();
+    let root = outer.path().join("pay");
+    write_rollout_topology(&root, "");
+    let topology = Topology::new(root.to_str().unwrap(), "", false, false);
+    let payer = topology.functions.get("payer").unwrap();
+    assert_eq!(payer.rollout.as_ref().unwrap().steps, vec![10, 100]);
+}
+
+#[test]
+#[should_panic(expected = "validation error")]
+fn rollout_rejects_event_invokers() {
+    let outer = TempDir::new().unwrap();
+    let root = outer.path().join("pay");
+    write_rollout_topology(
+        &root,
+        "events:\n  \
+         Charged:\n    producer: default\n    function: payer\n",
+    );
+    Topology::new(root.to_str().unwrap(), "", false, false);
+}
+
+#[test]
+#[should_panic(expected = "validation error")]
+fn rollout_rejects_intervals_below_metric_period() {
+    let outer = TempDir::new().unwrap();
+    let root = outer.path().join("pay");
+    write_rollout_topology(&root, "");
+    let spec = fs::read_to_string(root.join("payer/function.yml")).unwrap();
+    fs::write(
+        root.join("payer/function.yml"),
+        format!("{spec}  interval: 30s\n"),
+    )
+    .unwrap();
+    Topology::new(root.to_str().unwrap(), "", false, false);
+}
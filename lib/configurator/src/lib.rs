@@ -150,6 +150,10 @@ pub struct Deployer {
     #[derivative(Default(value = "default()"))]
     #[serde(default = "default")]
     pub fallback: String,
+
+    #[derivative(Default(value = "default()"))]
+    #[serde(default = "default")]
+    pub sandbox_ttl: String,
 }
 
 #[derive(Derivative, Serialize, Deserialize, Clone)]
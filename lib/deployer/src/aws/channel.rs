@@ -16,7 +16,17 @@ pub async fn create(auth: &Auth, channels: &HashMap<String, Channel>) {
     }
 }
 
-pub async fn delete(_auth: &Auth, _channels: &HashMap<String, Channel>) {}
+pub async fn delete(auth: &Auth, channels: &HashMap<String, Channel>) {
+    let client = appsync::make_client(&auth).await;
+    for (_, channel) in channels {
+        appsync::events::delete_channel(&client, &channel.api_name, &channel.name).await;
+    }
+}
+
+pub async fn exists(auth: &Auth, channel: &Channel) -> bool {
+    let client = appsync::make_client(&auth).await;
+    appsync::events::find_channel(&client, &channel.api_name, &channel.name).await
+}
 
 pub async fn update(_auth: &Auth, _channels: &HashMap<String, Channel>, _c: &str) {}
 
@@ -43,3 +53,37 @@ pub async fn config(
         _ => HashMap::new(),
     }
 }
+
+/// Tags the events apis of the channels frozen. Events apis cannot be
+/// disabled, so a frozen channel only guards against deploys
+pub async fn freeze(auth: &Auth, channels: &HashMap<String, Channel>) {
+    let client = appsync::make_client(auth).await;
+    for (_, channel) in channels {
+        if let Some(arn) = appsync::events::find_api_arn(&client, &channel.api_name).await {
+            println!("Freezing channel {}", &channel.name);
+            let _ = appsync::update_tags(&client, &arn, kv("freeze", "true")).await;
+        }
+    }
+}
+
+pub async fn unfreeze(auth: &Auth, channels: &HashMap<String, Channel>) {
+    let client = appsync::make_client(auth).await;
+    for (_, channel) in channels {
+        if let Some(arn) = appsync::events::find_api_arn(&client, &channel.api_name).await {
+            println!("Unfreezing channel {}", &channel.name);
+            let _ = appsync::update_tags(&client, &arn, kv("freeze", "false")).await;
+        }
+    }
+}
+
+pub async fn is_frozen(auth: &Auth, channels: &HashMap<String, Channel>) -> bool {
+    let client = appsync::make_client(auth).await;
+    for (_, channel) in channels {
+        if let Some(arn) = appsync::events::find_api_arn(&client, &channel.api_name).await {
+            if appsync::get_tag(&client, &arn, s!("freeze")).await == "true" {
+                return true;
+            }
+        }
+    }
+    false
+}
@@ -1,11 +1,13 @@
 use colored::Colorize;
 use compiler::Entity;
 use composer::Event;
+use kit as u;
 use provider::{
     Auth,
     aws::{
         appsync,
         eventbridge,
+        eventbridge::EventClient,
         lambda,
     },
 };
@@ -68,8 +70,14 @@ async fn create_event(auth: &Auth, event: &Event, tags: &HashMap<String, String>
         Err(_) => (),
     }
 
+    let frozen = is_rule_frozen(&client, &bus, &rule_name).await;
     let pattern = serde_json::to_string(&pattern).unwrap();
     let _rule_arn = eventbridge::create_rule(&client, &bus, &rule_name, &pattern, tags).await;
+    // put_rule enables the rule, so keep a frozen one disabled
+    if frozen {
+        println!("Event {} is frozen, keeping it disabled", &name);
+        eventbridge::disable_rule(&client, &bus, &rule_name).await;
+    }
 
     println!(
         "Creating event: {} ({})",
@@ -154,6 +162,13 @@ pub async fn delete(auth: &Auth, events: &HashMap<String, Event>) {
     }
 }
 
+pub async fn exists(auth: &Auth, event: &Event) -> bool {
+    let client = eventbridge::make_client(&auth).await;
+    eventbridge::describe_rule(&client, &event.bus, &event.rule_name)
+        .await
+        .is_some()
+}
+
 pub async fn update(
     auth: &Auth,
     events: &HashMap<String, Event>,
@@ -173,14 +188,56 @@ pub async fn create_dry_run(events: &HashMap<String, Event>) {
     }
 }
 
-pub async fn freeze(_auth: &Auth, _fqn: &str) {
-    println!("Unimplemented")
+async fn is_rule_frozen(client: &EventClient, bus: &str, rule_name: &str) -> bool {
+    match eventbridge::find_rule_arn(client, bus, rule_name).await {
+        Some(arn) => eventbridge::get_tag(client, &arn, "freeze").await == "true",
+        None => false,
+    }
+}
+
+/// Disables the rules of the events and tags them frozen
+pub async fn freeze(auth: &Auth, events: &HashMap<String, Event>) {
+    let client = eventbridge::make_client(auth).await;
+    for (_, event) in events {
+        if event.skip {
+            continue;
+        }
+        let Event { rule_name, bus, .. } = event;
+        if let Some(arn) = eventbridge::find_rule_arn(&client, bus, rule_name).await {
+            let version = eventbridge::get_tag(&client, &arn, "version").await;
+            if &version != "0.0.1" && !&version.is_empty() {
+                println!("Freezing event {} ({})", rule_name, version);
+                eventbridge::disable_rule(&client, bus, rule_name).await;
+                eventbridge::update_tags(&client, &arn, u::kv("freeze", "true")).await;
+            }
+        }
+    }
 }
 
-pub async fn unfreeze(_auth: &Auth, _fqn: &str) {
-    println!("Unimplemented")
+pub async fn unfreeze(auth: &Auth, events: &HashMap<String, Event>) {
+    let client = eventbridge::make_client(auth).await;
+    for (_, event) in events {
+        if event.skip {
+            continue;
+        }
+        let Event { rule_name, bus, .. } = event;
+        if let Some(arn) = eventbridge::find_rule_arn(&client, bus, rule_name).await {
+            let version = eventbridge::get_tag(&client, &arn, "version").await;
+            if &version != "0.0.1" && !&version.is_empty() {
+                println!("Unfreezing event {} ({})", rule_name, version);
+                eventbridge::enable_rule(&client, bus, rule_name).await;
+                eventbridge::update_tags(&client, &arn, u::kv("freeze", "false")).await;
+            }
+        }
+    }
 }
 
-pub async fn is_frozen(_auth: &Auth, _fqn: &str) -> bool {
+pub async fn is_frozen(auth: &Auth, events: &HashMap<String, Event>) -> bool {
+    let client = eventbridge::make_client(auth).await;
+    for (_, event) in events {
+        if !event.skip && is_rule_frozen(&client, &event.bus, &event.rule_name).await {
+            return true;
+        }
+    }
     false
 }
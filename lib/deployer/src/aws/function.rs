@@ -1,12 +1,13 @@
 use crate::role;
 use compiler::spec::function::Provider;
 use composer::Function;
-use futures::stream::FuturesUnordered;
 use provider::Auth;
 use std::collections::HashMap;
+use tokio::task::JoinHandle;
 mod agentcore;
 pub mod lambda;
 mod microvm;
+mod rollout;
 use tabled::Tabled;
 
 async fn maybe_build(auth: &Auth, function: &Function) {
@@ -22,15 +23,32 @@ async fn create_function(
 ) -> String {
     maybe_build(auth, &f).await;
     match f.runtime.provider {
-        Provider::Lambda => {
-            let client = lambda::make_client(auth).await;
-            lambda::create(&client, &f, tags, force).await
-        }
+        Provider::Lambda => match &f.rollout {
+            Some(r) => rollout::run(auth, &f, r, tags, force).await,
+            None => {
+                let client = lambda::make_client(auth).await;
+                lambda::create(&client, &f, tags, force).await
+            }
+        },
         Provider::MicroVm => microvm::create(auth, &f, tags).await,
         Provider::AgentCore => agentcore::create(auth, &f, tags).await,
     }
 }
 
+// waits for all tasks and fails with the errors of the ones that panicked,
+// so that the deploy step fails too
+async fn join_all(tasks: Vec<JoinHandle<()>>, what: &str) {
+    let mut errors: Vec<String> = vec![];
+    for task in tasks {
+        if let Err(e) = task.await {
+            errors.push(e.to_string());
+        }
+    }
+    if !errors.is_empty() {
+        panic!("Failed to {} {} functions: {}", what, errors.len(), errors.join("; "));
+    }
+}
+
 fn get_chunk_size(concurrency: i32) -> usize {
     match std::env::var("TC_DEPLOYER_CONCURRENCY") {
         Ok(n) => n.parse::<usize>().unwrap(),
@@ -50,21 +68,19 @@ pub async fn create(
     let chunks: Vec<&[String]> = names.chunks(csize).collect();
 
     for chunk in chunks {
-        let futs = FuturesUnordered::new();
+        let mut tasks = vec![];
 
         println!("Chunking functions: {:?}", &chunk.len());
         for name in chunk {
             let f = fns.get(name).unwrap().clone();
             let a = auth.clone();
             let t = tags.clone();
-            let fut = tokio::spawn(async move {
+            let h = tokio::spawn(async move {
                 create_function(&a, f, &t, force).await;
             });
-            futs.push(fut);
-        }
-        for task in futs {
-            let _ = task.await;
+            tasks.push(h);
         }
+        join_all(tasks, "create").await;
     }
 }
 
@@ -83,9 +99,7 @@ pub async fn update_code(
         });
         tasks.push(h);
     }
-    for task in tasks {
-        let _ = task.await;
-    }
+    join_all(tasks, "update").await;
 }
 
 pub async fn delete(auth: &Auth, fns: &HashMap<String, Function>, force: bool) {
@@ -128,9 +142,7 @@ pub async fn sync_roles(auth: &Auth, fns: &HashMap<String, Function>) {
         let h = tokio::spawn(async move { lambda::sync_role(&c, &f).await });
         tasks.push(h);
     }
-    for task in tasks {
-        let _ = task.await;
-    }
+    join_all(tasks, "sync roles of").await;
 }
 
 pub async fn update_dir(
@@ -177,6 +189,23 @@ pub async fn create_dry_run(fns: &HashMap<String, Function>) {
     }
 }
 
+pub async fn exists(auth: &Auth, fqn: &str) -> bool {
+    let client = lambda::make_client(auth).await;
+    lambda::exists(&client, fqn).await
+}
+
+/// Publishes the deployed function so that an update can be undone
+pub async fn snapshot(auth: &Auth, fqn: &str) -> Result<String, String> {
+    let client = lambda::make_client(auth).await;
+    lambda::snapshot(&client, fqn).await
+}
+
+/// Puts back the code and configuration of a snapshot
+pub async fn restore(auth: &Auth, fqn: &str, version: &str) -> Result<(), String> {
+    let client = lambda::make_client(auth).await;
+    lambda::restore(&client, fqn, version).await
+}
+
 pub async fn is_frozen(auth: &Auth, fqn: &str) -> bool {
     let client = lambda::make_client(auth).await;
     let arn = auth.lambda_arn(fqn);
@@ -126,13 +126,18 @@ pub async fn create(
         lambda.clone().create_or_update(client).await
     };
 
-    if f.runtime.snapstart {
+    // rollouts publish their own version and shift the alias gradually
+    if f.runtime.snapstart && f.rollout.is_none() {
         lambda.publish_version(client).await;
     }
 
     id
 }
 
+pub async fn publish(client: &Client, f: &Function, tags: &HashMap<String, String>) -> String {
+    make(f, tags, false).publish(client).await
+}
+
 pub async fn update_tags(client: &Client, arn: &str, tags: &HashMap<String, String>) {
     lambda::update_tags(client, &arn, tags).await;
 }
@@ -223,6 +228,19 @@ pub async fn is_frozen(client: &Client, arn: &str) -> bool {
     v == "true"
 }
 
+pub async fn exists(client: &Client, fqn: &str) -> bool {
+    lambda::find_config(client, fqn).await.is_some()
+}
+
+/// Version holding `$LATEST` as deployed before an update
+pub async fn snapshot(client: &Client, fqn: &str) -> Result<String, String> {
+    lambda::publish_latest(client, fqn).await
+}
+
+pub async fn restore(client: &Client, fqn: &str, version: &str) -> Result<(), String> {
+    lambda::restore_version(client, fqn, version).await
+}
+
 pub async fn delete(client: &Client, f: &Function) {
     let function = make(f, &HashMap::new(), false);
     function.delete(client).await.unwrap();
@@ -0,0 +1,209 @@
+use super::lambda;
+use colored::Colorize;
+use composer::{
+    Function,
+    aws::function::Rollout,
+};
+use kit as u;
+use provider::{
+    Auth,
+    aws::{
+        lambda as aws_lambda,
+        lambda::LambdaClient as Client,
+        metrics,
+    },
+};
+use std::{
+    collections::HashMap,
+    time::Duration,
+};
+
+/// Invocations, errors and throttles of the new version during a step
+struct Usage {
+    invocations: f64,
+    errors: f64,
+    throttles: f64,
+}
+
+fn rate(n: f64, total: f64) -> f64 {
+    if total > 0.0 { n * 100.0 / total } else { 0.0 }
+}
+
+// a step the new version barely served is inconclusive, and counts as a
+// breach like one whose metrics cannot be read
+fn breach(rollout: &Rollout, usage: &Usage) -> Option<String> {
+    if usage.invocations < rollout.min_invocations as f64 {
+        return Some(format!(
+            "inconclusive, {} invocations < {}",
+            usage.invocations, rollout.min_invocations
+        ));
+    }
+    let total = usage.invocations + usage.throttles;
+    let errors = rate(usage.errors, usage.invocations);
+    let throttles = rate(usage.throttles, total);
+    if errors > rollout.max_error_rate {
+        Some(format!("error rate {:.2}% > {}%", errors, rollout.max_error_rate))
+    } else if throttles > rollout.max_throttle_rate {
+        Some(format!("throttle rate {:.2}% > {}%", throttles, rollout.max_throttle_rate))
+    } else {
+        None
+    }
+}
+
+// metrics that cannot be read count as a breach, as the step is unwatched
+async fn find_usage(
+    auth: &Auth,
+    fqn: &str,
+    alias: &str,
+    version: &str,
+    start: i64,
+) -> Result<Usage, String> {
+    let client = metrics::make_client(auth).await;
+    let end = u::current_millis();
+    let resource = format!("{}:{}", fqn, alias);
+    let executed = [
+        ("FunctionName", fqn),
+        ("Resource", resource.as_str()),
+        ("ExecutedVersion", version),
+    ];
+    // throttled requests never execute, so they are only known per alias
+    let requested = [("FunctionName", fqn), ("Resource", resource.as_str())];
+    let ns = "AWS/Lambda";
+    Ok(Usage {
+        invocations: metrics::sum(&client, ns, "Invocations", &executed, start, end).await?,
+        errors: metrics::sum(&client, ns, "Errors", &executed, start, end).await?,
+        throttles: metrics::sum(&client, ns, "Throttles", &requested, start, end).await?,
+    })
+}
+
+async fn shift(
+    client: &Client,
+    fqn: &str,
+    alias: &str,
+    stable: &str,
+    version: &str,
+    step: u8,
+) -> Result<(), String> {
+    if step >= 100 {
+        aws_lambda::route_alias(client, fqn, alias, version, None).await?;
+    } else {
+        let weight = step as f64 / 100.0;
+        aws_lambda::route_alias(client, fqn, alias, stable, Some((version, weight))).await?;
+    }
+    println!("Shifted {}% of {} to version {}", step, alias.cyan(), version);
+    Ok(())
+}
+
+// reverts the alias and `$LATEST` to the stable version
+async fn revert(client: &Client, fqn: &str, alias: &str, stable: &str) -> Result<(), String> {
+    aws_lambda::route_alias(client, fqn, alias, stable, None).await?;
+    aws_lambda::restore_code(client, fqn, stable).await
+}
+
+// steps through the rollout, reverting to the stable version as soon as a
+// step breaches a threshold
+async fn roll(
+    auth: &Auth,
+    client: &Client,
+    f: &Function,
+    rollout: &Rollout,
+    alias: &str,
+    stable: &str,
+    version: &str,
+) -> Result<(), String> {
+    for step in &rollout.steps {
+        shift(client, &f.fqn, alias, stable, version, *step).await?;
+        let start = u::current_millis();
+        tokio::time::sleep(Duration::from_millis(rollout.interval as u64)).await;
+        let reason = match find_usage(auth, &f.fqn, alias, version, start).await {
+            Ok(usage) => breach(rollout, &usage),
+            Err(e) => Some(format!("metrics unavailable: {}", e)),
+        };
+        if let Some(reason) = reason {
+            revert(client, &f.fqn, alias, stable).await?;
+            println!("{} {} to version {}", "Rolled back".red(), &f.fqn, stable);
+            return Err(format!("Rollout of {} failed: {}", &f.fqn, reason));
+        }
+    }
+    println!("Rolled out {} version {}", &f.fqn, version.green());
+    Ok(())
+}
+
+/// Deploys the function, publishes a version and shifts the alias to it
+/// step by step. The first version is aliased right away. A rollout that
+/// is rolled back fails the deploy
+pub async fn run(
+    auth: &Auth,
+    f: &Function,
+    rollout: &Rollout,
+    tags: &HashMap<String, String>,
+    force: bool,
+) -> String {
+    let client = lambda::make_client(auth).await;
+    let alias = match &rollout.alias {
+        Some(a) => a.clone(),
+        None => f.fqn.clone(),
+    };
+    let stable = aws_lambda::find_alias_version(&client, &f.fqn, &alias).await;
+    let id = lambda::create(&client, f, tags, force).await;
+    let version = lambda::publish(&client, f, tags).await;
+
+    let res = match stable {
+        Some(s) if s != version => roll(auth, &client, f, rollout, &alias, &s, &version).await,
+        Some(_) => {
+            println!("No new version of {}, skipping rollout", &f.fqn);
+            Ok(())
+        }
+        None => aws_lambda::route_alias(&client, &f.fqn, &alias, &version, None)
+            .await
+            .map(|_| println!("Aliased {} to version {}", alias.cyan(), version)),
+    };
+    match res {
+        Ok(_) => id,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use composer::aws::function::rollout::Strategy;
+
+    fn rollout() -> Rollout {
+        Rollout {
+            strategy: Strategy::Canary,
+            steps: vec![10, 50, 100],
+            interval: 60_000,
+            alias: None,
+            max_error_rate: 1.0,
+            max_throttle_rate: 1.0,
+            min_invocations: 10,
+        }
+    }
+
+    fn usage(invocations: f64, errors: f64, throttles: f64) -> Usage {
+        Usage {
+            invocations: invocations,
+            errors: errors,
+            throttles: throttles,
+        }
+    }
+
+    #[test]
+    fn test_breach() {
+        let r = rollout();
+        let reason = |u: Usage| breach(&r, &u).unwrap_or_default();
+        assert_eq!(breach(&r, &usage(200.0, 1.0, 0.0)), None);
+        assert!(reason(usage(200.0, 5.0, 0.0)).starts_with("error rate"));
+        assert!(reason(usage(200.0, 0.0, 10.0)).starts_with("throttle rate"));
+    }
+
+    #[test]
+    fn test_breach_without_invocations() {
+        let r = rollout();
+        let reason = |u: Usage| breach(&r, &u).unwrap_or_default();
+        assert!(reason(usage(0.0, 0.0, 0.0)).starts_with("inconclusive"));
+        assert!(reason(usage(9.0, 0.0, 0.0)).starts_with("inconclusive"));
+        assert_eq!(breach(&r, &usage(10.0, 0.0, 0.0)), None);
+    }
+}
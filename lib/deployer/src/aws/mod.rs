@@ -10,4 +10,5 @@ pub mod role;
 pub mod route;
 pub mod schedule;
 pub mod state;
+pub mod store;
 pub mod transducer;
@@ -122,6 +122,11 @@ pub async fn delete(auth: &Auth, mutations: &HashMap<String, Mutation>) {
     }
 }
 
+pub async fn exists(auth: &Auth, mutation: &Mutation) -> bool {
+    let client = appsync::make_client(auth).await;
+    appsync::find_api(&client, &mutation.api_name).await.is_some()
+}
+
 pub async fn update(_auth: &Auth, _mutations: &HashMap<String, Mutation>, _c: &str) {
     todo!()
 }
@@ -145,9 +145,9 @@ async fn find_or_create_cert(auth: &Auth, domain: &str, token: &str) -> String {
         println!("Creating cert {}", domain);
         acm::request_cert(&client, domain, token).await
     };
-    u::sleep(1000);
+    u::sleep_async(1000).await;
     if !acm::is_cert_issued(&client, &cert_arn).await {
-        u::sleep(10000);
+        u::sleep_async(10000).await;
         let validation_records = acm::get_domain_validation_records(&client, &cert_arn).await;
         let route53_client = route53::make_client(auth).await;
         for rec in validation_records {
@@ -239,7 +239,9 @@ async fn build_and_upload(auth: &Auth, name: &str, page: &Page, config: &HashMap
     }
     let s3_client = s3::make_client(auth).await;
 
-    s3::find_or_create_bucket(&s3_client, bucket).await;
+    if let Err(e) = s3::find_or_create_bucket(&s3_client, bucket, &HashMap::new()).await {
+        panic!("Failed to create bucket {}: {}", bucket, e)
+    }
 
     if bucket.is_empty() {
         panic!("Bucket not configured. Set TC_PAGES_BUCKET, in config or in topology")
@@ -17,10 +17,37 @@ async fn _create_lambda_producer(auth: &Auth, name: &str, sqs_arn: &str) {
     }
 }
 
-async fn create_lambda_consumer(auth: &Auth, name: &str, sqs_arn: &str) {
+// FIFO queues take no batching window; others go back to none when it
+// is removed from the spec
+fn batch_config(queue: &Queue) -> lambda::BatchConfig {
+    lambda::BatchConfig {
+        size: queue.batch_size.unwrap_or(1),
+        window: match queue.fifo {
+            true => None,
+            false => Some(queue.batch_window.unwrap_or(0)),
+        },
+        partial_failures: queue.partial_batch_failures,
+    }
+}
+
+fn queue_config(queue: &Queue) -> sqs::QueueConfig {
+    sqs::QueueConfig {
+        fifo: queue.fifo,
+        content_dedup: queue.content_dedup,
+        visibility_timeout: queue.visibility_timeout,
+        retention: queue.retention,
+        redrive: match &queue.dlq {
+            Some(d) => Some((d.arn.clone(), d.max_receive_count)),
+            None => None,
+        },
+    }
+}
+
+async fn create_lambda_consumer(auth: &Auth, name: &str, queue: &Queue) {
     let lambda_client = lambda::make_client(&auth).await;
+    let sqs_arn = &queue.arn;
     println!("Updating function: {} (consumer)", name);
-    lambda::create_event_source(&lambda_client, name, &sqs_arn).await;
+    lambda::create_event_source(&lambda_client, name, sqs_arn, &batch_config(queue)).await;
     let _ = lambda::add_permission(
         lambda_client.clone(),
         name,
@@ -32,22 +59,34 @@ async fn create_lambda_consumer(auth: &Auth, name: &str, sqs_arn: &str) {
     lambda::update_event_invoke_config(&lambda_client, name).await;
 }
 
-pub async fn create(auth: &Auth, queues: &HashMap<String, Queue>) {
+async fn create_queue(auth: &Auth, queue: &Queue) {
     let client = sqs::make_client(&auth).await;
-    for (_, queue) in queues {
-        if queue.should_create {
-            sqs::create_queue(&client, &queue.name).await;
+    if queue.should_create {
+        // the dead-letter queue must exist before it is referenced
+        if let Some(dlq) = &queue.dlq {
+            let cfg = sqs::QueueConfig {
+                fifo: queue.fifo,
+                ..Default::default()
+            };
+            sqs::create_queue(&client, &dlq.name, &cfg).await;
         }
-        for target in &queue.targets {
-            println!("Creating queue: {}", &queue.name);
-            match target.entity {
-                Entity::Function => create_lambda_consumer(auth, &target.name, &queue.arn).await,
-                _ => (),
-            }
+        sqs::create_queue(&client, &queue.name, &queue_config(queue)).await;
+    }
+    for target in &queue.targets {
+        println!("Creating queue: {}", &queue.name);
+        match target.entity {
+            Entity::Function => create_lambda_consumer(auth, &target.name, queue).await,
+            _ => (),
         }
     }
 }
 
+pub async fn create(auth: &Auth, queues: &HashMap<String, Queue>) {
+    for (_, queue) in queues {
+        create_queue(auth, queue).await;
+    }
+}
+
 async fn delete_lambda_consumer(auth: &Auth, name: &str, queue_arn: &str) {
     let lambda_client = lambda::make_client(&auth).await;
     lambda::delete_event_source(&lambda_client, name, queue_arn).await
@@ -69,6 +108,50 @@ pub async fn delete(auth: &Auth, queues: &HashMap<String, Queue>) {
     }
 }
 
-pub async fn update(_auth: &Auth, _queues: &HashMap<String, Queue>, _c: &str) {
-    todo!()
+pub async fn exists(auth: &Auth, queue: &Queue) -> bool {
+    let client = sqs::make_client(&auth).await;
+    sqs::find_queue_url(&client, &queue.name).await.is_some()
+}
+
+// attributes a deploy may change. A missing redrive policy is kept as
+// empty, which clears one added since
+const RESTORABLE: [&str; 5] = [
+    "VisibilityTimeout",
+    "MessageRetentionPeriod",
+    "DelaySeconds",
+    "ReceiveMessageWaitTimeSeconds",
+    "RedrivePolicy",
+];
+
+/// Attributes of the deployed queue that a deploy may change
+pub async fn snapshot(auth: &Auth, queue: &Queue) -> Option<HashMap<String, String>> {
+    let client = sqs::make_client(&auth).await;
+    let url = sqs::find_queue_url(&client, &queue.name).await?;
+    let attrs = sqs::get_queue_attributes(&client, &url).await;
+    Some(
+        RESTORABLE
+            .iter()
+            .map(|k| (k.to_string(), attrs.get(*k).cloned().unwrap_or_default()))
+            .filter(|(k, v)| !v.is_empty() || k == "RedrivePolicy")
+            .collect(),
+    )
+}
+
+pub async fn restore(
+    auth: &Auth,
+    queue: &Queue,
+    attrs: &HashMap<String, String>,
+) -> Result<(), String> {
+    let client = sqs::make_client(&auth).await;
+    match sqs::find_queue_url(&client, &queue.name).await {
+        Some(url) => sqs::set_queue_attributes(&client, &url, attrs).await,
+        None => Err(format!("Queue {} not found", &queue.name)),
+    }
+}
+
+pub async fn update(auth: &Auth, queues: &HashMap<String, Queue>, component: &str) {
+    match queues.get(component) {
+        Some(queue) => create_queue(auth, queue).await,
+        None => println!("Queue {} not found", component),
+    }
 }
@@ -8,12 +8,22 @@ use std::collections::{
     HashMap,
     hash_map::Entry,
 };
+use tabled::Tabled;
 
 pub async fn list(auth: &Auth, sandbox: &str) -> Vec<String> {
     let client = resourcetag::make_client(auth).await;
     resourcetag::get_resources(&client, "sandbox", sandbox).await
 }
 
+/// Arns of the resources a topology deployed in the sandbox
+pub async fn list_namespace(auth: &Auth, sandbox: &str, namespace: &str) -> Vec<String> {
+    let client = resourcetag::make_client(auth).await;
+    let tags = [("sandbox", sandbox), ("namespace", namespace)];
+    let mut arns = resourcetag::get_resources_by_tags(&client, &tags).await;
+    arns.sort();
+    arns
+}
+
 pub async fn list_all(auth: &Auth) -> Vec<(String, String, String)> {
     let client = resourcetag::make_client(auth).await;
     resourcetag::get_all_resources(&client).await
@@ -103,3 +113,130 @@ pub async fn delete_arns(auth: &Auth, grouped: HashMap<Entity, Vec<String>>) {
         }
     }
 }
+
+/// A topology deployed in a sandbox, as recorded in the tags of its
+/// resources
+#[derive(Tabled, Clone, Debug)]
+pub struct SandboxInfo {
+    pub name: String,
+    pub namespace: String,
+    pub created_by: String,
+    pub branch: String,
+    pub created_at: String,
+    pub ttl: String,
+    #[tabled(skip)]
+    pub created_ms: i64,
+}
+
+fn sandbox_of(tags: &HashMap<String, String>) -> Option<SandboxInfo> {
+    let created_at = kit::safe_unwrap(tags.get("created_at"));
+    let created_ms = match kit::utc_millis(&created_at) {
+        Some(ms) => ms,
+        None => return None,
+    };
+    match (tags.get("sandbox"), tags.get("ttl")) {
+        (Some(name), Some(ttl)) => Some(SandboxInfo {
+            name: name.clone(),
+            namespace: kit::safe_unwrap(tags.get("namespace")),
+            created_by: kit::safe_unwrap(tags.get("created_by")),
+            branch: kit::safe_unwrap(tags.get("branch")),
+            created_at: created_at,
+            ttl: ttl.clone(),
+            created_ms: created_ms,
+        }),
+        _ => None,
+    }
+}
+
+// the oldest resource of a topology in a sandbox marks its creation.
+// Topologies sharing a sandbox expire on their own
+fn oldest(resources: Vec<(String, HashMap<String, String>)>) -> Vec<SandboxInfo> {
+    let mut h: HashMap<(String, String), SandboxInfo> = HashMap::new();
+    for (_, tags) in resources {
+        if let Some(s) = sandbox_of(&tags) {
+            match h.entry((s.namespace.clone(), s.name.clone())) {
+                Entry::Vacant(e) => {
+                    e.insert(s);
+                }
+                Entry::Occupied(mut e) => {
+                    if s.created_ms < e.get().created_ms {
+                        e.insert(s);
+                    }
+                }
+            }
+        }
+    }
+    h.into_values().collect()
+}
+
+fn expired(resources: Vec<(String, HashMap<String, String>)>, now: i64) -> Vec<SandboxInfo> {
+    let mut xs: Vec<SandboxInfo> = vec![];
+    for s in oldest(resources) {
+        if let Some(ttl) = kit::duration_millis(&s.ttl) {
+            if s.created_ms + ttl < now {
+                xs.push(s);
+            }
+        }
+    }
+    xs.sort_by(|a, b| (&a.name, &a.namespace).cmp(&(&b.name, &b.namespace)));
+    xs
+}
+
+/// Topologies whose sandbox outlived its ttl
+pub async fn list_expired(auth: &Auth) -> Result<Vec<SandboxInfo>, String> {
+    let client = resourcetag::make_client(auth).await;
+    let resources = resourcetag::get_tagged_resources(&client, "ttl").await?;
+    Ok(expired(resources, kit::current_millis()))
+}
+
+/// When the topology was first deployed in the sandbox, if it was
+pub async fn created_at(auth: &Auth, namespace: &str, sandbox: &str) -> Option<String> {
+    let client = resourcetag::make_client(auth).await;
+    let tags = [("sandbox", sandbox), ("namespace", namespace)];
+    match resourcetag::get_resource_tags(&client, &tags).await {
+        Ok(resources) => oldest(resources).into_iter().next().map(|s| s.created_at),
+        Err(e) => {
+            tracing::debug!("Could not look up {} in {}: {}", namespace, sandbox, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tagged(
+        namespace: &str,
+        sandbox: &str,
+        created_at: &str,
+    ) -> (String, HashMap<String, String>) {
+        let arn = format!(
+            "arn:aws:lambda:us-east-1:123:function:{}_{}",
+            namespace, sandbox
+        );
+        let mut h: HashMap<String, String> = HashMap::new();
+        h.insert(String::from("namespace"), String::from(namespace));
+        h.insert(String::from("sandbox"), String::from(sandbox));
+        h.insert(String::from("created_at"), String::from(created_at));
+        h.insert(String::from("ttl"), String::from("1d"));
+        (arn, h)
+    }
+
+    #[test]
+    fn test_expired() {
+        let now = kit::utc_millis("01:10:2025-00:00:00").unwrap();
+        let resources = vec![
+            tagged("etl", "dev", "01:01:2025-00:00:00"),
+            tagged("etl", "dev", "01:09:2025-12:00:00"),
+            tagged("api", "dev", "01:09:2025-12:00:00"),
+            tagged("etl", "qa", "01:09:2025-12:00:00"),
+        ];
+        let xs = expired(resources, now);
+        // api shares the sandbox with etl but was created within its ttl
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].namespace, "etl");
+        assert_eq!(xs[0].name, "dev");
+        assert_eq!(xs[0].created_at, "01:01:2025-00:00:00");
+    }
+}
@@ -18,30 +18,59 @@ use provider::{
         gateway,
         gateway::{
             Client,
+            DeployedIntegration,
+            DeployedRoute,
             GatewayCors as Cors,
         },
         lambda,
         lambda::LambdaClient,
+        resourcetag,
         route53,
     },
 };
-use std::collections::HashMap;
+use std::collections::{
+    HashMap,
+    HashSet,
+};
 
 async fn add_target_permission(auth: &Auth, api_id: &str, target: &Target) {
-    let Target { entity, arn, .. } = target;
+    let Target {
+        entity,
+        name,
+        arn,
+        alias,
+        ..
+    } = target;
     match entity {
         Entity::Function => {
             let client = lambda::make_client(auth).await;
             let source_arn = auth.api_arn(api_id);
             let principal = "apigateway.amazonaws.com";
+            // an alias has its own policy, so it needs the permission too
+            let alias = alias.as_deref().unwrap_or(name);
+            if let Some(alias_arn) = lambda::find_named_alias_arn(&client, name, alias).await {
+                let _ = lambda::add_permission(
+                    client.clone(),
+                    &alias_arn,
+                    principal,
+                    &source_arn,
+                    api_id,
+                )
+                .await;
+            }
             let _ = lambda::add_permission(client, arn, principal, &source_arn, api_id).await;
         }
         _ => (),
     }
 }
 
-async fn find_alias_arn(client: &LambdaClient, arn: &str) -> String {
-    let maybe_alias_arn = lambda::find_alias_arn(&client, arn).await;
+// the rollout alias of the target, by default the fqn alias
+async fn find_alias_arn(client: &LambdaClient, target: &Target) -> String {
+    let Target {
+        name, arn, alias, ..
+    } = target;
+    let alias = alias.as_deref().unwrap_or(name);
+    let maybe_alias_arn = lambda::find_named_alias_arn(&client, name, alias).await;
     match maybe_alias_arn {
         Some(a) => {
             println!("Using alias function={}", &a);
@@ -57,10 +86,10 @@ async fn create_integration(
     api_id: &str,
     route: &Route,
     target: &Target,
+    owner: &str,
 ) -> String {
     let Target {
         entity,
-        arn,
         request_params,
         ..
     } = target;
@@ -68,16 +97,17 @@ async fn create_integration(
     let Route {
         role_arn,
         method,
+        path,
         is_async,
         ..
     } = route;
 
-    let int_name = format!("{}-{}", entity.to_str(), method);
+    let route_key = gateway::route_key(method, path);
 
     match entity {
         Entity::Function => {
             let lc = lambda::make_client(auth).await;
-            let alias_arn = find_alias_arn(&lc, arn).await;
+            let alias_arn = find_alias_arn(&lc, target).await;
             gateway::create_lambda_integration(
                 client,
                 api_id,
@@ -85,6 +115,7 @@ async fn create_integration(
                 role_arn,
                 *is_async,
                 request_params.clone(),
+                owner,
             )
             .await
         }
@@ -92,10 +123,11 @@ async fn create_integration(
             gateway::create_sfn_integration(
                 client,
                 api_id,
-                &int_name,
+                &route_key,
                 role_arn,
                 *is_async,
                 request_params.clone(),
+                owner,
             )
             .await
         }
@@ -103,9 +135,10 @@ async fn create_integration(
             gateway::create_event_integration(
                 client,
                 api_id,
-                &int_name,
+                &route_key,
                 role_arn,
                 request_params.clone(),
+                owner,
             )
             .await
         }
@@ -113,9 +146,10 @@ async fn create_integration(
             gateway::create_sqs_integration(
                 client,
                 api_id,
-                &int_name,
+                &route_key,
                 role_arn,
                 request_params.clone(),
+                owner,
             )
             .await
         }
@@ -149,10 +183,15 @@ async fn create_route(
     api_id: &str,
     auth_id: Option<String>,
     auth_kind: &str,
+    owner: &str,
 ) {
     add_target_permission(auth, &api_id, &route.target).await;
     let client = gateway::make_client(auth).await;
-    let integration_id = create_integration(auth, &client, &api_id, route, &route.target).await;
+    let route_key = gateway::route_key(&route.method, &route.path);
+    // a route whose target kind changed leaves its old integration behind
+    let previous = gateway::find_route_integration(&client, api_id, &route_key, "").await;
+    let integration_id =
+        create_integration(auth, &client, &api_id, route, &route.target, owner).await;
     gateway::create_or_update_route(
         &client,
         &api_id,
@@ -163,6 +202,18 @@ async fn create_route(
         auth_kind,
     )
     .await;
+    if let Some(p) = previous {
+        if p != integration_id {
+            gateway::delete_integration_by_id(&client, api_id, &p).await;
+        }
+    }
+}
+
+// marks the integrations of the routes a namespace deploys in a sandbox,
+// so that pruning leaves routes of other namespaces alone
+fn make_owner(tags: &HashMap<String, String>, sandbox: &str) -> String {
+    let namespace = u::safe_unwrap(tags.get("namespace"));
+    format!("tc:{}/{}", namespace, sandbox)
 }
 
 async fn create_cognito_pool(auth: &Auth, pool_name: &str) -> (String, String) {
@@ -270,9 +321,9 @@ async fn find_or_create_cert(auth: &Auth, domain: &str, token: &str) -> String {
         println!("Creating cert {}", domain);
         acm::request_cert(&client, domain, token).await
     };
-    u::sleep(1000);
+    u::sleep_async(1000).await;
     if !acm::is_cert_issued(&client, &cert_arn).await {
-        u::sleep(10000);
+        u::sleep_async(10000).await;
         let validation_records = acm::get_domain_validation_records(&client, &cert_arn).await;
         let route53_client = route53::make_client(auth).await;
         for rec in validation_records {
@@ -521,6 +572,7 @@ pub async fn create(
             create_or_update_gateways(&auth, gateways.clone(), tags, sandbox).await;
 
         let mut url: String = String::from("");
+        let owner = make_owner(tags, sandbox);
 
         for (name, route) in routes {
             if !&route.skip && name != "default" {
@@ -537,13 +589,11 @@ pub async fn create(
 
                     tracing::debug!("Creating route {} {}", &route.method, &route.path);
                     let res_route = resolve_route(&gw, &route);
-                    match route.authorizer {
-                        Some(_) => {
-                            create_route(auth, &res_route, &api_id, auth_id.clone(), &auth_kind)
-                                .await
-                        }
-                        None => create_route(auth, &res_route, &api_id, None, &auth_kind).await,
+                    let auth_id = match route.authorizer {
+                        Some(_) => auth_id.clone(),
+                        None => None,
                     };
+                    create_route(auth, &res_route, &api_id, auth_id, &auth_kind, &owner).await;
 
                     let gateway_arn = auth.api_gateway_arn(&api_id);
 
@@ -559,20 +609,10 @@ pub async fn create(
     }
 }
 
-async fn delete_integration(client: &Client, api_id: &str, method: &str, target: &Target) {
-    let Target { entity, arn, .. } = target;
-    let int_name = format!("{}-{}", entity.to_str(), method);
-    match entity {
-        Entity::Function => gateway::delete_lambda_integration(client, api_id, arn).await,
-        Entity::State => gateway::delete_sfn_integration(client, api_id, &int_name).await,
-        Entity::Event => gateway::delete_event_integration(client, api_id, &int_name).await,
-        Entity::Queue => gateway::delete_sqs_integration(client, api_id, &int_name).await,
-        _ => (),
-    }
-}
-
 async fn delete_route(client: &Client, api_id: &str, route: &Route) {
-    let route_key = format!("{} {}", &route.method, &route.path);
+    let route_key = gateway::route_key(&route.method, &route.path);
+    // integrations other than lambda ones belong to their route
+    let integration = gateway::find_route_integration(client, api_id, &route_key, "").await;
     let route_id = gateway::find_route(client, api_id, &route_key).await;
     match route_id {
         Some(rid) => {
@@ -580,7 +620,45 @@ async fn delete_route(client: &Client, api_id: &str, route: &Route) {
         }
         _ => (),
     }
-    delete_integration(client, &api_id, &route.method, &route.target).await;
+    match route.target.entity {
+        Entity::Function => {
+            gateway::delete_lambda_integration(client, api_id, &route.target.arn).await
+        }
+        _ => {
+            if let Some(id) = integration {
+                gateway::delete_integration_by_id(client, api_id, &id).await;
+            }
+        }
+    }
+}
+
+/// Whether the route is deployed on its gateway
+pub async fn exists(
+    auth: &Auth,
+    routes: &HashMap<String, Route>,
+    name: &str,
+    sandbox: &str,
+) -> bool {
+    let route = match routes.get(name) {
+        Some(r) => r,
+        None => return false,
+    };
+    let gateways = collate_gateways(routes, &auth.name, sandbox);
+    let gw = match gateways.get(&route.gateway) {
+        Some(gw) => gw,
+        None => return false,
+    };
+    let client = gateway::make_client(auth).await;
+    match gateway::find_api(&client, &route.gateway).await {
+        Some(api_id) => {
+            let r = resolve_route(gw, route);
+            let route_key = gateway::route_key(&r.method, &r.path);
+            gateway::find_route(&client, &api_id, &route_key)
+                .await
+                .is_some()
+        }
+        None => false,
+    }
 }
 
 pub async fn delete(auth: &Auth, routes: &HashMap<String, Route>, sandbox: &str, force: bool) {
@@ -616,7 +694,206 @@ pub async fn delete(auth: &Auth, routes: &HashMap<String, Route>, sandbox: &str,
     }
 }
 
-pub async fn update(_auth: &Auth, _mutations: &HashMap<String, Route>, _c: &str) {}
+// function name of a lambda integration uri, which is the function or
+// alias arn
+fn function_of_uri(uri: &str) -> Option<String> {
+    let (_, rest) = uri.split_once(":function:")?;
+    rest.split(':').next().map(|x| x.to_string())
+}
+
+/// What pruning removes from a gateway
+#[derive(Debug, Default, PartialEq)]
+struct Stale {
+    routes: Vec<String>,
+    integrations: Vec<String>,
+    authorizers: Vec<String>,
+    // function or alias arns the routes invoked
+    functions: Vec<String>,
+}
+
+// Routes no longer in the topology that this namespace deployed, known
+// by the owner stamped on their integration. Integrations deployed before
+// they carried an owner are known by invoking one of the namespace's own
+// functions. Other namespaces share vertical gateways, so their routes are
+// left alone, as are integrations, authorizers and invoke permissions
+// that a remaining route still uses
+fn select_stale(
+    deployed: &[DeployedRoute],
+    keys: &HashSet<String>,
+    ints: &HashMap<String, DeployedIntegration>,
+    owner: &str,
+    owned: &HashSet<String>,
+) -> Stale {
+    let uri =
+        |d: &DeployedRoute| -> Option<String> { ints.get(d.integration_id.as_ref()?)?.uri.clone() };
+    let is_owned = |d: &DeployedRoute| -> bool {
+        let int = match d.integration_id.as_ref().and_then(|i| ints.get(i)) {
+            Some(int) => int,
+            None => return false,
+        };
+        match &int.owner {
+            Some(o) => o == owner,
+            None => int
+                .uri
+                .as_ref()
+                .and_then(|u| function_of_uri(u))
+                .is_some_and(|f| owned.contains(&f)),
+        }
+    };
+    let (stale, kept): (Vec<&DeployedRoute>, Vec<&DeployedRoute>) = deployed
+        .iter()
+        .partition(|d| !keys.contains(&d.key) && is_owned(d));
+
+    let kept_integrations: HashSet<&String> =
+        kept.iter().filter_map(|d| d.integration_id.as_ref()).collect();
+    let kept_authorizers: HashSet<&String> =
+        kept.iter().filter_map(|d| d.authorizer_id.as_ref()).collect();
+    let kept_functions: HashSet<String> = kept
+        .iter()
+        .filter_map(|d| uri(d))
+        .filter_map(|u| function_of_uri(&u))
+        .collect();
+
+    let mut x = Stale::default();
+    for d in stale {
+        x.routes.push(d.id.clone());
+        if let Some(i) = &d.integration_id {
+            if !kept_integrations.contains(i) {
+                x.integrations.push(i.clone());
+            }
+        }
+        if let Some(a) = &d.authorizer_id {
+            if !kept_authorizers.contains(a) {
+                x.authorizers.push(a.clone());
+            }
+        }
+        if let Some(u) = uri(d) {
+            if let Some(f) = function_of_uri(&u) {
+                if !kept_functions.contains(&f) {
+                    x.functions.push(u);
+                }
+            }
+        }
+    }
+    x.integrations = x.integrations.into_iter().unique().collect();
+    x.authorizers = x.authorizers.into_iter().unique().collect();
+    x.functions = x.functions.into_iter().unique().collect();
+    x
+}
+
+// functions of the topology in this sandbox, by tag
+async fn owned_functions(auth: &Auth, namespace: &str, sandbox: &str) -> HashSet<String> {
+    let client = resourcetag::make_client(auth).await;
+    let tags = [("namespace", namespace), ("sandbox", sandbox)];
+    resourcetag::get_resources_by_tags(&client, &tags)
+        .await
+        .iter()
+        .filter_map(|arn| function_of_uri(arn))
+        .collect()
+}
+
+// removes routes the topology no longer has from its gateways, with
+// their integrations, authorizers and invoke permissions
+async fn prune(
+    auth: &Auth,
+    routes: &HashMap<String, Route>,
+    tags: &HashMap<String, String>,
+    sandbox: &str,
+) {
+    let client = gateway::make_client(auth).await;
+    let namespace = u::safe_unwrap(tags.get("namespace"));
+    let gateways = collate_gateways(routes, &auth.name, sandbox);
+    let owned = owned_functions(auth, &namespace, sandbox).await;
+    let owner = make_owner(tags, sandbox);
+
+    for (name, gw) in &gateways {
+        let api_id = match gateway::find_api(&client, name).await {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let mut keys: HashSet<String> = HashSet::new();
+        for (rname, route) in routes {
+            if !route.skip && rname != "default" && &route.gateway == name {
+                let r = resolve_route(gw, route);
+                keys.insert(gateway::route_key(&r.method, &r.path));
+            }
+        }
+
+        let deployed = gateway::list_deployed_routes(&client, &api_id).await;
+        let ints = gateway::list_integrations(&client, &api_id).await;
+        let stale = select_stale(&deployed, &keys, &ints, &owner, &owned);
+        if stale.routes.is_empty() {
+            continue;
+        }
+        for d in deployed.iter().filter(|d| stale.routes.contains(&d.id)) {
+            println!("Deleting route {}", &d.key);
+            let _ = gateway::delete_route(&client, &api_id, &d.id).await;
+        }
+        for i in &stale.integrations {
+            gateway::delete_integration_by_id(&client, &api_id, i).await;
+        }
+        let names = gateway::list_authorizers(&client, &api_id).await;
+        for a in &stale.authorizers {
+            println!("Deleting authorizer {}", u::safe_unwrap(names.get(a)));
+            gateway::delete_authorizer_by_id(&client, &api_id, a).await;
+        }
+        // permissions are granted on the function and its alias with the
+        // api id as statement id
+        let lc = lambda::make_client(auth).await;
+        for arn in &stale.functions {
+            lambda::remove_permission(&lc, arn, &api_id).await;
+            if let Some(f) = function_of_uri(arn) {
+                lambda::remove_permission(&lc, &f, &api_id).await;
+            }
+        }
+        gateway::create_deployment(&client, &api_id, &gw.stage).await;
+    }
+}
+
+/// Reconciles the deployed routes with the given ones. Gateways, stages,
+/// authorizers, integrations and routes are created or updated in place.
+/// Updating all routes also deletes the ones removed from the topology;
+/// a component updates just that route
+pub async fn update(
+    auth: &Auth,
+    routes: &HashMap<String, Route>,
+    tags: &HashMap<String, String>,
+    sandbox: &str,
+    component: Option<&str>,
+) {
+    match component {
+        Some(c) => {
+            if !routes.contains_key(c) {
+                println!("No route {} in topology", c);
+                return;
+            }
+            let selected: HashMap<String, Route> = routes
+                .iter()
+                .filter(|(name, _)| name.as_str() == c || name.as_str() == "default")
+                .map(|(name, r)| (name.clone(), r.clone()))
+                .collect();
+            create(auth, &selected, tags, sandbox).await;
+        }
+        None => {
+            create(auth, routes, tags, sandbox).await;
+            prune(auth, routes, tags, sandbox).await;
+        }
+    }
+}
+
+/// Deletes routes by key, as deployed on the given gateway
+pub async fn delete_keys(auth: &Auth, gateway_name: &str, keys: Vec<String>) {
+    let client = gateway::make_client(auth).await;
+    if let Some(api_id) = gateway::find_api(&client, gateway_name).await {
+        for key in keys {
+            println!("Deleting route {}", &key);
+            if let Some(rid) = gateway::find_route(&client, &api_id, &key).await {
+                let _ = gateway::delete_route(&client, &api_id, &rid).await;
+            }
+        }
+    }
+}
 
 pub async fn create_dry_run(routes: &HashMap<String, Route>) {
     for (_, route) in routes {
@@ -691,3 +968,89 @@ pub async fn is_frozen(auth: &Auth, fqn: &str) -> bool {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deployed(key: &str, id: &str, int: &str, authorizer: Option<&str>) -> DeployedRoute {
+        DeployedRoute {
+            key: s!(key),
+            id: s!(id),
+            integration_id: Some(s!(int)),
+            authorizer_id: authorizer.map(|a| s!(a)),
+        }
+    }
+
+    fn uri(name: &str) -> String {
+        format!("arn:aws:lambda:us-east-1:123:function:{}:live", name)
+    }
+
+    fn int(uri: Option<String>, owner: Option<&str>) -> DeployedIntegration {
+        DeployedIntegration {
+            uri: uri,
+            subtype: None,
+            owner: owner.map(|o| s!(o)),
+        }
+    }
+
+    #[test]
+    fn test_select_stale() {
+        let routes = vec![
+            deployed("GET /a", "r1", "i1", Some("a1")),
+            deployed("GET /b", "r2", "i2", Some("a1")),
+            deployed("GET /c", "r3", "i2", Some("a2")),
+            deployed("GET /x", "r4", "i3", Some("a3")),
+            deployed("POST /s", "r5", "i4", None),
+            deployed("POST /y", "r6", "i5", None),
+        ];
+        let owner = "tc:etl/dev";
+        let mut ints: HashMap<String, DeployedIntegration> = HashMap::new();
+        ints.insert(s!("i1"), int(Some(uri("etl_a_dev")), Some(owner)));
+        ints.insert(s!("i2"), int(Some(uri("etl_b_dev")), Some(owner)));
+        ints.insert(
+            s!("i3"),
+            int(Some(uri("other_x_dev")), Some("tc:other/dev")),
+        );
+        ints.insert(s!("i4"), int(None, Some(owner)));
+        ints.insert(s!("i5"), int(None, Some("tc:other/dev")));
+        let keys: HashSet<String> = [s!("GET /b")].into_iter().collect();
+
+        let stale = select_stale(&routes, &keys, &ints, owner, &HashSet::new());
+        // GET /x and POST /y belong to another namespace on a shared gateway
+        assert_eq!(stale.routes, vec![s!("r1"), s!("r3"), s!("r5")]);
+        // i2 still serves GET /b, a1 is still used by it
+        assert_eq!(stale.integrations, vec![s!("i1"), s!("i4")]);
+        assert_eq!(stale.authorizers, vec![s!("a2")]);
+        assert_eq!(stale.functions, vec![uri("etl_a_dev")]);
+    }
+
+    #[test]
+    fn test_select_stale_without_owner() {
+        let routes = vec![
+            deployed("GET /a", "r1", "i1", None),
+            deployed("GET /x", "r2", "i2", None),
+            deployed("POST /s", "r3", "i3", None),
+        ];
+        let mut ints: HashMap<String, DeployedIntegration> = HashMap::new();
+        ints.insert(s!("i1"), int(Some(uri("etl_a_dev")), None));
+        ints.insert(s!("i2"), int(Some(uri("other_x_dev")), None));
+        ints.insert(s!("i3"), int(None, None));
+        let owned: HashSet<String> = [s!("etl_a_dev")].into_iter().collect();
+
+        let stale = select_stale(&routes, &HashSet::new(), &ints, "tc:etl/dev", &owned);
+        assert_eq!(stale.routes, vec![s!("r1")]);
+        assert_eq!(stale.functions, vec![uri("etl_a_dev")]);
+
+        let stale = select_stale(
+            &routes,
+            &HashSet::new(),
+            &ints,
+            "tc:etl/dev",
+            &HashSet::new(),
+        );
+        assert_eq!(stale, Stale::default());
+        assert_eq!(function_of_uri(&uri("etl_a_dev")), Some(s!("etl_a_dev")));
+        assert_eq!(function_of_uri("arn:aws:states:x"), None);
+    }
+}
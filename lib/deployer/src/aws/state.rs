@@ -61,6 +61,24 @@ pub async fn create(auth: &Auth, flow: &Flow, tags: &HashMap<String, String>) {
     }
 }
 
+pub async fn exists(auth: &Auth, flow: &Flow) -> bool {
+    let client = sfn::make_client(auth).await;
+    sfn::describe_state_machine(&client, &flow.arn).await.is_some()
+}
+
+/// Definition and role of the deployed state machine
+pub async fn snapshot(auth: &Auth, flow: &Flow) -> Option<(String, String)> {
+    let client = sfn::make_client(auth).await;
+    sfn::describe_state_machine(&client, &flow.arn)
+        .await
+        .map(|(definition, _, role)| (definition, role))
+}
+
+pub async fn restore(auth: &Auth, flow: &Flow, definition: &str, role: &str) -> Result<(), String> {
+    let client = sfn::make_client(auth).await;
+    sfn::restore_state_machine(&client, &flow.arn, definition, role).await
+}
+
 pub async fn delete(auth: &Auth, flow: &Flow) {
     let Flow {
         name,
@@ -0,0 +1,125 @@
+use compiler::spec::StoreKind;
+use composer::Store;
+use provider::{
+    Auth,
+    aws::{
+        dynamodb,
+        s3,
+    },
+};
+use std::collections::HashMap;
+
+async fn create_table(
+    auth: &Auth,
+    store: &Store,
+    tags: &HashMap<String, String>,
+) -> Result<(), String> {
+    let client = dynamodb::make_client(auth).await;
+    match &store.hash_key {
+        Some(hash_key) => {
+            dynamodb::find_or_create_table(
+                &client,
+                &store.name,
+                hash_key,
+                store.range_key.clone(),
+                tags,
+            )
+            .await
+        }
+        None => Err(format!("No hash_key defined for table {}", &store.name)),
+    }
+}
+
+async fn create_bucket(
+    auth: &Auth,
+    store: &Store,
+    tags: &HashMap<String, String>,
+) -> Result<(), String> {
+    let client = s3::make_client(auth).await;
+    s3::find_or_create_bucket(&client, &store.name, tags).await
+}
+
+async fn create_store(
+    auth: &Auth,
+    store: &Store,
+    tags: &HashMap<String, String>,
+) -> Result<(), String> {
+    if !store.should_create {
+        return Ok(());
+    }
+    let res = match store.kind {
+        StoreKind::Dynamodb => create_table(auth, store, tags).await,
+        StoreKind::S3 => create_bucket(auth, store, tags).await,
+    };
+    match res {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to create store {}: {}", &store.name, e)),
+    }
+}
+
+pub async fn create(
+    auth: &Auth,
+    stores: &HashMap<String, Store>,
+    tags: &HashMap<String, String>,
+) -> Result<(), String> {
+    for (_, store) in stores {
+        create_store(auth, store, tags).await?;
+    }
+    Ok(())
+}
+
+pub async fn update(
+    auth: &Auth,
+    stores: &HashMap<String, Store>,
+    tags: &HashMap<String, String>,
+    component: &str,
+) -> Result<(), String> {
+    match stores.get(component) {
+        Some(store) => create_store(auth, store, tags).await,
+        None => {
+            println!("Store {} not found", component);
+            Ok(())
+        }
+    }
+}
+
+pub async fn exists(auth: &Auth, store: &Store) -> bool {
+    match store.kind {
+        StoreKind::Dynamodb => {
+            let client = dynamodb::make_client(auth).await;
+            dynamodb::table_exists(&client, &store.name).await
+        }
+        StoreKind::S3 => {
+            let client = s3::make_client(auth).await;
+            s3::bucket_exists(&client, &store.name).await
+        }
+    }
+}
+
+async fn delete_store(auth: &Auth, store: &Store) -> Result<(), String> {
+    if !store.should_create {
+        return Ok(());
+    }
+    let res = match store.kind {
+        StoreKind::Dynamodb => {
+            let client = dynamodb::make_client(auth).await;
+            println!("Deleting table {}", &store.name);
+            dynamodb::delete_table(&client, &store.name).await
+        }
+        StoreKind::S3 => {
+            let client = s3::make_client(auth).await;
+            s3::delete_bucket(&client, &store.name).await
+        }
+    };
+    match res {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to delete store {}: {}", &store.name, e)),
+    }
+}
+
+pub async fn delete(auth: &Auth, stores: &HashMap<String, Store>) -> Result<(), String> {
+    for (_, store) in stores {
+        delete_store(auth, store).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use composer::Topology;
+use std::{
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    time::Duration,
+};
+
+/// A deploy step of one topology
+#[derive(Clone, Debug, PartialEq)]
+pub struct Task {
+    pub namespace: String,
+    pub step: &'static str,
+}
+
+// steps of the same topology a step waits for
+fn step_deps(step: &str) -> Vec<&'static str> {
+    match step {
+        "roles" => vec!["base-roles"],
+        "functions" => vec!["roles"],
+        "function-roles" => vec!["functions"],
+        "channels" => vec!["functions"],
+        "mutations" => vec!["roles", "functions"],
+        "queues" => vec!["functions"],
+        "events" => vec!["functions", "queues", "mutations", "channels"],
+        "pools" => vec!["functions"],
+        "routes" => vec!["functions", "queues", "events"],
+        "schedules" => vec!["functions"],
+        "states" => vec!["roles", "functions", "mutations"],
+        "pages" => vec!["routes", "mutations", "channels", "pools"],
+        "transducer" => vec![
+            "functions",
+            "events",
+            "routes",
+            "mutations",
+            "channels",
+            "states",
+            "pages",
+        ],
+        _ => vec![],
+    }
+}
+
+// steps of the root that may target functions of its nodes
+fn spans_nodes(step: &str) -> bool {
+    match step {
+        "function-roles" | "events" | "routes" | "states" | "pages" | "transducer" => true,
+        _ => false,
+    }
+}
+
+/// Deploy steps of a topology and its nodes, with the steps each one
+/// waits for. Nodes are independent of each other
+pub struct Dag {
+    pub tasks: Vec<Task>,
+    pub deps: Vec<Vec<usize>>,
+}
+
+impl Dag {
+    pub fn new(topology: &Topology, steps: &[&'static str]) -> Dag {
+        let nodes: Vec<String> = topology
+            .nodes
+            .values()
+            .map(|n| n.namespace.clone())
+            .collect();
+        Dag::of(&topology.namespace, nodes, steps)
+    }
+
+    fn of(root: &str, mut nodes: Vec<String>, steps: &[&'static str]) -> Dag {
+        nodes.sort();
+        let mut namespaces = vec![root.to_string()];
+        namespaces.extend(nodes.clone());
+
+        let mut tasks: Vec<Task> = vec![];
+        for ns in &namespaces {
+            for step in steps {
+                tasks.push(Task {
+                    namespace: ns.clone(),
+                    step: step,
+                });
+            }
+        }
+        let index = |ns: &str, step: &str| {
+            tasks
+                .iter()
+                .position(|t| t.namespace == ns && t.step == step)
+        };
+
+        let mut deps: Vec<Vec<usize>> = vec![];
+        for task in &tasks {
+            let mut xs: Vec<usize> = vec![];
+            for d in step_deps(task.step) {
+                if let Some(i) = index(&task.namespace, d) {
+                    xs.push(i);
+                }
+            }
+            if task.namespace == root && spans_nodes(task.step) {
+                for ns in &nodes {
+                    if let Some(i) = index(ns, "functions") {
+                        xs.push(i);
+                    }
+                }
+            }
+            // base roles are shared, so topologies update them in turn
+            if task.step == "base-roles" {
+                let pos = namespaces.iter().position(|n| n == &task.namespace);
+                if let Some(p) = pos {
+                    if p > 0 {
+                        if let Some(i) = index(&namespaces[p - 1], "base-roles") {
+                            xs.push(i);
+                        }
+                    }
+                }
+            }
+            deps.push(xs);
+        }
+        Dag {
+            tasks: tasks,
+            deps: deps,
+        }
+    }
+
+    /// Tasks not yet started whose dependencies are all done
+    pub fn ready(&self, done: &HashSet<usize>, started: &HashSet<usize>) -> Vec<usize> {
+        (0..self.tasks.len())
+            .filter(|i| !started.contains(i) && !done.contains(i))
+            .filter(|i| self.deps[*i].iter().all(|d| done.contains(d)))
+            .collect()
+    }
+
+    /// Longest chain of dependent tasks by duration. `order` is the order
+    /// tasks finished in, which is a topological order
+    pub fn critical_path(
+        &self,
+        order: &[usize],
+        durations: &HashMap<usize, Duration>,
+    ) -> (Vec<usize>, Duration) {
+        let mut dist: HashMap<usize, Duration> = HashMap::new();
+        let mut prev: HashMap<usize, usize> = HashMap::new();
+        for i in order {
+            let own = durations.get(i).cloned().unwrap_or_default();
+            let mut best = Duration::ZERO;
+            for d in &self.deps[*i] {
+                if let Some(x) = dist.get(d) {
+                    if *x >= best {
+                        best = *x;
+                        prev.insert(*i, *d);
+                    }
+                }
+            }
+            dist.insert(*i, best + own);
+        }
+        let last = dist.iter().max_by_key(|(_, v)| **v).map(|(k, v)| (*k, *v));
+        match last {
+            Some((mut i, total)) => {
+                let mut path = vec![i];
+                while let Some(p) = prev.get(&i) {
+                    path.push(*p);
+                    i = *p;
+                }
+                path.reverse();
+                (path, total)
+            }
+            None => (vec![], Duration::ZERO),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEPS: [&str; 4] = ["base-roles", "roles", "functions", "routes"];
+
+    // a/base-roles 0, a/roles 1, a/functions 2, a/routes 3,
+    // b/base-roles 4, b/roles 5, b/functions 6, b/routes 7
+    fn dag() -> Dag {
+        Dag::of("a", vec![String::from("b")], &STEPS)
+    }
+
+    fn set(xs: &[usize]) -> HashSet<usize> {
+        xs.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_new() {
+        let dag = dag();
+        assert_eq!(dag.tasks.len(), 8);
+        assert_eq!(dag.tasks[6].namespace, "b");
+        assert_eq!(dag.tasks[6].step, "functions");
+        assert_eq!(dag.deps[0], Vec::<usize>::new());
+        assert_eq!(dag.deps[1], vec![0]);
+        // routes of the root wait for the functions of its nodes
+        assert_eq!(dag.deps[3], vec![2, 6]);
+        assert_eq!(dag.deps[7], vec![6]);
+        // base roles are updated in turn
+        assert_eq!(dag.deps[4], vec![0]);
+    }
+
+    #[test]
+    fn test_ready() {
+        let dag = dag();
+        assert_eq!(dag.ready(&set(&[]), &set(&[])), vec![0]);
+        assert_eq!(dag.ready(&set(&[0]), &set(&[])), vec![1, 4]);
+        assert_eq!(dag.ready(&set(&[0]), &set(&[1])), vec![4]);
+        assert_eq!(dag.ready(&set(&[0, 1, 2, 4, 5]), &set(&[])), vec![6]);
+        assert_eq!(dag.ready(&set(&[0, 1, 2, 4, 5, 6]), &set(&[])), vec![3, 7]);
+    }
+
+    #[test]
+    fn test_critical_path() {
+        let dag = dag();
+        let order = vec![0, 1, 4, 2, 5, 6, 3, 7];
+        let mut durations: HashMap<usize, Duration> =
+            order.iter().map(|i| (*i, Duration::from_secs(1))).collect();
+        durations.insert(6, Duration::from_secs(5));
+        durations.insert(3, Duration::from_secs(2));
+        let (path, total) = dag.critical_path(&order, &durations);
+        assert_eq!(path, vec![0, 4, 5, 6, 3]);
+        assert_eq!(total, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_critical_path_empty() {
+        let dag = Dag::of("a", vec![], &[]);
+        assert_eq!(
+            dag.critical_path(&[], &HashMap::new()),
+            (vec![], Duration::ZERO)
+        );
+    }
+}
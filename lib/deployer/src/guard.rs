@@ -1,4 +1,5 @@
 use super::aws::{
+    channel,
     event,
     function,
     mutation,
@@ -14,14 +15,21 @@ use question::{
 };
 
 pub async fn is_frozen(auth: &Auth, topology: &Topology) -> bool {
-    let Topology { fqn, kind, .. } = topology;
-    match kind {
+    let Topology {
+        fqn,
+        kind,
+        events,
+        channels,
+        ..
+    } = topology;
+    let frozen = match kind {
         TopologyKind::StepFunction => state::is_frozen(auth, fqn).await,
         TopologyKind::Function => function::is_frozen(auth, fqn).await,
         TopologyKind::Graphql => mutation::is_frozen(auth, fqn).await,
         TopologyKind::Routed => route::is_frozen(auth, fqn).await,
-        TopologyKind::Evented => event::is_frozen(auth, fqn).await,
-    }
+        TopologyKind::Evented => event::is_frozen(auth, events).await,
+    };
+    frozen || channel::is_frozen(auth, channels).await
 }
 
 pub async fn should_abort(auth: &Auth, sandbox: &str, _topology: &Topology) -> bool {
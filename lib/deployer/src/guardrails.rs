@@ -0,0 +1,380 @@
+use colored::Colorize;
+use composer::{
+    Topology,
+    aws::role::Kind,
+};
+use kit as u;
+use provider::{
+    Auth,
+    aws::ssm,
+};
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use std::process::exit;
+
+/// Rules a topology must pass before it is deployed, read from
+/// `infrastructure/tc/guardrails.yml` (or `TC_GUARDRAILS_PATH`). Rules
+/// named in `warn` are reported without failing the deploy
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Guardrails {
+    pub max_memory: Option<i32>,
+    pub max_timeout: Option<i32>,
+    #[serde(default)]
+    pub banned_runtimes: Vec<String>,
+    /// Tags the topology must carry. Functions and other resources are
+    /// deployed with the topology's tags and have none of their own, so
+    /// only the topology and its nodes are checked
+    #[serde(default)]
+    pub required_tags: Vec<String>,
+    #[serde(default)]
+    pub no_wildcard_actions: bool,
+    #[serde(default)]
+    pub require_route_authorizer: bool,
+    #[serde(default)]
+    pub require_event_dlq: bool,
+    #[serde(default)]
+    pub warn: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Violation {
+    pub rule: String,
+    pub resource: String,
+    pub message: String,
+    pub level: Level,
+}
+
+// parameters hold at most 8KB, older overrides are dropped to fit
+const OVERRIDES_LIMIT: usize = 8192;
+
+#[derive(Serialize)]
+struct Override<'a> {
+    at: String,
+    namespace: String,
+    sandbox: String,
+    user: String,
+    reason: String,
+    violations: &'a [Violation],
+}
+
+fn is_wildcard(action: &str) -> bool {
+    action == "*" || action.ends_with(":*")
+}
+
+impl Guardrails {
+    pub fn load() -> Option<Guardrails> {
+        let path = match std::env::var("TC_GUARDRAILS_PATH") {
+            Ok(p) => u::expand_path(&p),
+            Err(_) => format!("{}/infrastructure/tc/guardrails.yml", u::root()),
+        };
+        if !u::file_exists(&path) {
+            return None;
+        }
+        match serde_yaml::from_str(&u::slurp(&path)) {
+            Ok(g) => Some(g),
+            Err(e) => {
+                eprintln!("Unable to load guardrails from `{}`: {}", &path, e);
+                exit(1);
+            }
+        }
+    }
+
+    fn violation(&self, rule: &str, resource: &str, message: String) -> Violation {
+        let level = if self.warn.iter().any(|w| w == rule) {
+            Level::Warning
+        } else {
+            Level::Error
+        };
+        Violation {
+            rule: rule.to_string(),
+            resource: resource.to_string(),
+            message: message,
+            level: level,
+        }
+    }
+
+    fn check(&self, topology: &Topology, xs: &mut Vec<Violation>) {
+        let Topology {
+            namespace,
+            functions,
+            roles,
+            routes,
+            events,
+            tags,
+            ..
+        } = topology;
+
+        for tag in &self.required_tags {
+            if !tags.contains_key(tag) {
+                let msg = format!("missing tag {}", tag);
+                xs.push(self.violation("required_tags", namespace, msg));
+            }
+        }
+
+        for (name, f) in functions {
+            let resource = format!("{}/functions/{}", namespace, name);
+            let runtime = &f.runtime;
+            if let (Some(max), Some(m)) = (self.max_memory, runtime.memory_size) {
+                if m > max {
+                    let msg = format!("memory {} exceeds {}", m, max);
+                    xs.push(self.violation("max_memory", &resource, msg));
+                }
+            }
+            if let (Some(max), Some(t)) = (self.max_timeout, runtime.timeout) {
+                if t > max {
+                    let msg = format!("timeout {} exceeds {}", t, max);
+                    xs.push(self.violation("max_timeout", &resource, msg));
+                }
+            }
+            let lang = runtime.lang.to_str();
+            if self.banned_runtimes.contains(&lang) {
+                let msg = format!("runtime {} is banned", lang);
+                xs.push(self.violation("banned_runtimes", &resource, msg));
+            }
+        }
+
+        if self.no_wildcard_actions {
+            for (name, role) in roles {
+                // base roles are tc's own
+                if role.kind == Kind::Base {
+                    continue;
+                }
+                let resource = format!("{}/roles/{}", namespace, name);
+                for action in role.policy.allowed_actions() {
+                    if is_wildcard(&action) {
+                        let msg = format!("allows wildcard action {}", action);
+                        xs.push(self.violation("no_wildcard_actions", &resource, msg));
+                    }
+                }
+            }
+        }
+
+        if self.require_route_authorizer {
+            for (name, route) in routes {
+                if !route.skip && name != "default" && route.authorizer.is_none() {
+                    let resource = format!("{}/routes/{}", namespace, name);
+                    let msg = format!("{} {} has no authorizer", &route.method, &route.path);
+                    xs.push(self.violation("require_route_authorizer", &resource, msg));
+                }
+            }
+        }
+
+        if self.require_event_dlq {
+            for (name, event) in events {
+                for target in &event.targets {
+                    if !event.skip && target.dead_letter_arn.is_none() {
+                        let resource = format!("{}/events/{}", namespace, name);
+                        let msg = format!("target {} has no dead letter queue", &target.id);
+                        xs.push(self.violation("require_event_dlq", &resource, msg));
+                    }
+                }
+            }
+        }
+
+        for node in topology.nodes.values() {
+            self.check(node, xs);
+        }
+    }
+
+    pub fn evaluate(&self, topology: &Topology) -> Vec<Violation> {
+        let mut xs: Vec<Violation> = vec![];
+        self.check(topology, &mut xs);
+        xs
+    }
+}
+
+fn overrides_key(namespace: &str, sandbox: &str) -> String {
+    format!("/tc/{}/{}/guardrails/overrides", namespace, sandbox)
+}
+
+// appends the record to the log, dropping the oldest records until the
+// log fits in a parameter
+fn append(log: &str, record: Value) -> String {
+    let mut records: Vec<Value> = serde_json::from_str(log).unwrap_or_default();
+    records.push(record);
+    let mut data = serde_json::to_string(&records).unwrap();
+    while data.len() > OVERRIDES_LIMIT && records.len() > 1 {
+        records.remove(0);
+        data = serde_json::to_string(&records).unwrap();
+    }
+    data
+}
+
+// overrides are kept in an SSM parameter of the sandbox so they can be
+// audited from anywhere. An override that cannot be recorded is refused
+async fn record_override(auth: &Auth, topology: &Topology, reason: &str, violations: &[Violation]) {
+    let record = Override {
+        at: u::utc_now(),
+        namespace: topology.namespace.clone(),
+        sandbox: topology.sandbox.clone(),
+        user: u::safe_unwrap(topology.tags.get("updated_by")),
+        reason: reason.to_string(),
+        violations: violations,
+    };
+    let record = serde_json::to_value(&record).unwrap();
+    tracing::warn!("guardrails overridden: {}", &record);
+    let key = overrides_key(&topology.namespace, &topology.sandbox);
+    let client = ssm::make_client(auth).await;
+    let log = ssm::get(client.clone(), &key).await.unwrap_or_default();
+    match ssm::put(&client, &key, &append(&log, record)).await {
+        Ok(_) => println!("Guardrails overridden ({}), recorded in {}", reason, &key),
+        Err(e) => {
+            println!(
+                "{} to record guardrails override in {}: {}",
+                "Failed".red(),
+                &key,
+                e
+            );
+            exit(1);
+        }
+    }
+}
+
+/// Evaluates the guardrails against the topology before a deploy. Errors
+/// abort unless `TC_GUARDRAILS_OVERRIDE` gives a reason for overriding them
+pub async fn enforce(auth: &Auth, topology: &Topology) {
+    let guardrails = match Guardrails::load() {
+        Some(g) => g,
+        None => return,
+    };
+    let violations = guardrails.evaluate(topology);
+    for v in &violations {
+        let level = match v.level {
+            Level::Error => "error".red(),
+            Level::Warning => "warning".yellow(),
+        };
+        println!("{}: {} [{}] {}", level, &v.resource, &v.rule, &v.message);
+    }
+    let errors: Vec<Violation> = violations
+        .into_iter()
+        .filter(|v| v.level == Level::Error)
+        .collect();
+    if errors.is_empty() {
+        return;
+    }
+    match std::env::var("TC_GUARDRAILS_OVERRIDE") {
+        Ok(reason) if !reason.is_empty() => record_override(auth, topology, &reason, &errors).await,
+        _ => {
+            println!(
+                "{} guardrail violations. Set TC_GUARDRAILS_OVERRIDE=<reason> to deploy anyway",
+                errors.len()
+            );
+            exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    // a topology with a python3.10 function given 1024MB and 900s
+    fn topology() -> (TempDir, Topology) {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("rails");
+        let function_dir = root.join("loader");
+        fs::create_dir_all(&function_dir).unwrap();
+        fs::write(root.join("topology.yml"), "name: rails\n").unwrap();
+        let vars = dir.path().join("loader.json");
+        fs::write(
+            &vars,
+            r#"{"default": {"memory_size": 1024, "timeout": 900}}"#,
+        )
+        .unwrap();
+        fs::write(
+            function_dir.join("function.yml"),
+            format!(
+                "name: loader\n\
+                 runtime:\n  \
+                 lang: python3.10\n  \
+                 handler: handler.handler\n  \
+                 package_type: zip\n  \
+                 layers: []\n  \
+                 vars_file: {}\n\
+                 build:\n  \
+                 kind: Code\n  \
+                 command: echo build\n",
+                vars.display()
+            ),
+        )
+        .unwrap();
+        fs::write(function_dir.join("handler.py"), "").unwrap();
+        let topology = Topology::new(root.to_str().unwrap(), "", false, false);
+        (dir, topology)
+    }
+
+    fn evaluate(rules: &str, topology: &Topology) -> Vec<Violation> {
+        let guardrails: Guardrails = serde_yaml::from_str(rules).unwrap();
+        guardrails.evaluate(topology)
+    }
+
+    fn rules_of(xs: &[Violation]) -> Vec<&str> {
+        xs.iter().map(|v| v.rule.as_str()).collect()
+    }
+
+    #[test]
+    fn test_memory_and_timeout_caps() {
+        let (_dir, t) = topology();
+        let xs = evaluate("max_memory: 512\nmax_timeout: 300\n", &t);
+        let mut rules = rules_of(&xs);
+        rules.sort();
+        assert_eq!(rules, vec!["max_memory", "max_timeout"]);
+        assert!(xs.iter().all(|v| v.resource == "rails/functions/loader"));
+        assert!(xs.iter().all(|v| v.level == Level::Error));
+
+        assert!(evaluate("max_memory: 1024\nmax_timeout: 900\n", &t).is_empty());
+    }
+
+    #[test]
+    fn test_banned_runtimes() {
+        let (_dir, t) = topology();
+        let xs = evaluate("banned_runtimes: [python3.10, node20]\n", &t);
+        assert_eq!(rules_of(&xs), vec!["banned_runtimes"]);
+        assert_eq!(xs[0].message, "runtime python3.10 is banned");
+
+        assert!(evaluate("banned_runtimes: [python3.9]\n", &t).is_empty());
+    }
+
+    #[test]
+    fn test_required_tags() {
+        let (_dir, t) = topology();
+        let xs = evaluate("required_tags: [namespace, team]\n", &t);
+        assert_eq!(rules_of(&xs), vec!["required_tags"]);
+        assert_eq!(xs[0].resource, "rails");
+        assert_eq!(xs[0].message, "missing tag team");
+    }
+
+    #[test]
+    fn test_warn() {
+        let (_dir, t) = topology();
+        let xs = evaluate("max_memory: 512\nwarn: [max_memory]\n", &t);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].level, Level::Warning);
+    }
+
+    #[test]
+    fn test_append() {
+        let record = |n: usize| serde_json::json!({ "reason": "x".repeat(n) });
+        let log = append("", record(1));
+        let log = append(&log, record(2));
+        let records: Vec<Value> = serde_json::from_str(&log).unwrap();
+        assert_eq!(records.len(), 2);
+
+        // the oldest records go first once the log outgrows a parameter
+        let log = append(&log, record(OVERRIDES_LIMIT - 100));
+        let records: Vec<Value> = serde_json::from_str(&log).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(log.len() <= OVERRIDES_LIMIT);
+    }
+}
@@ -0,0 +1,237 @@
+use colored::Colorize;
+use composer::Topology;
+use kit as u;
+use provider::{
+    Auth,
+    aws::ssm,
+};
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Status {
+    Done,
+    Failed,
+    RolledBack,
+}
+
+/// State of a component before a deploy updated it, enough to put it
+/// back on rollback
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Prior {
+    /// Published version holding the code and configuration
+    Function { version: String },
+    State { definition: String, role: String },
+    /// Queue attributes, keyed by attribute name
+    Queue { attributes: HashMap<String, String> },
+}
+
+/// A deploy step of one topology, with the components of the step, keyed
+/// as in the topology, that did not exist before it ran and the prior
+/// state of those it updated
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub namespace: String,
+    pub step: String,
+    pub status: Status,
+    pub created: Vec<String>,
+    #[serde(default)]
+    pub prior: HashMap<String, Prior>,
+    pub error: Option<String>,
+    pub at: String,
+}
+
+/// Record of a topology deploy, kept in /tmp/tc/journal and in an SSM
+/// parameter of the sandbox so that another machine can resume it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Journal {
+    pub namespace: String,
+    pub sandbox: String,
+    pub version: String,
+    pub started_at: String,
+    #[serde(default)]
+    pub fresh: Vec<String>,
+    pub entries: Vec<Entry>,
+}
+
+// advanced parameters hold at most 8KB, larger journals are split
+// across parameters
+const SSM_LIMIT: usize = 8192;
+
+// splits `data` into parts of at most `size` bytes, on char boundaries
+fn split(data: &str, size: usize) -> Vec<&str> {
+    let mut parts: Vec<&str> = vec![];
+    let mut rest = data;
+    while rest.len() > size {
+        let mut i = size;
+        while !rest.is_char_boundary(i) {
+            i -= 1;
+        }
+        // a char wider than a part goes whole
+        if i == 0 {
+            i = rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+        }
+        let (part, tail) = rest.split_at(i);
+        parts.push(part);
+        rest = tail;
+    }
+    if !rest.is_empty() || parts.is_empty() {
+        parts.push(rest);
+    }
+    parts
+}
+
+// the journal parameter of a split journal holds the number of parts
+#[derive(Serialize, Deserialize)]
+struct Parts {
+    parts: usize,
+}
+
+fn path_of(namespace: &str, sandbox: &str) -> String {
+    format!("/tmp/tc/journal/{}.{}.json", namespace, sandbox)
+}
+
+fn key_of(namespace: &str, sandbox: &str) -> String {
+    format!("/tc/{}/{}/journal", namespace, sandbox)
+}
+
+fn part_key(key: &str, i: usize) -> String {
+    format!("{}.{}", key, i)
+}
+
+async fn get_remote(auth: &Auth, key: &str) -> String {
+    let client = ssm::make_client(auth).await;
+    let data = ssm::get(client.clone(), key).await.unwrap_or_default();
+    match serde_json::from_str::<Parts>(&data) {
+        Ok(p) => {
+            let mut joined = String::new();
+            for i in 0..p.parts {
+                let part = ssm::get(client.clone(), &part_key(key, i)).await;
+                joined.push_str(&part.unwrap_or_default());
+            }
+            joined
+        }
+        Err(_) => data,
+    }
+}
+
+async fn put_remote(auth: &Auth, key: &str, data: &str) -> Result<(), String> {
+    let client = ssm::make_client(auth).await;
+    let parts = split(data, SSM_LIMIT);
+    if parts.len() == 1 {
+        return ssm::put(&client, key, data).await;
+    }
+    for (i, part) in parts.iter().enumerate() {
+        ssm::put(&client, &part_key(key, i), part).await?;
+    }
+    let manifest = serde_json::to_string(&Parts { parts: parts.len() }).unwrap();
+    ssm::put(&client, key, &manifest).await
+}
+
+impl Journal {
+    pub fn new(topology: &Topology) -> Journal {
+        Journal {
+            namespace: topology.namespace.clone(),
+            sandbox: topology.sandbox.clone(),
+            version: topology.version.clone(),
+            started_at: u::utc_now(),
+            fresh: vec![],
+            entries: vec![],
+        }
+    }
+
+    pub async fn load(auth: &Auth, namespace: &str, sandbox: &str) -> Option<Journal> {
+        let path = path_of(namespace, sandbox);
+        let data = if u::file_exists(&path) {
+            u::slurp(&path)
+        } else {
+            get_remote(auth, &key_of(namespace, sandbox)).await
+        };
+        match serde_json::from_str(&data) {
+            Ok(j) => Some(j),
+            Err(_) => None,
+        }
+    }
+
+    pub async fn save(&self, auth: &Auth) {
+        let data = serde_json::to_string(self).unwrap();
+        u::mkdir("/tmp/tc/journal");
+        u::write_str(&path_of(&self.namespace, &self.sandbox), &data);
+        let key = key_of(&self.namespace, &self.sandbox);
+        if let Err(e) = put_remote(auth, &key, &data).await {
+            // a stale remote journal must not be resumed elsewhere
+            let client = ssm::make_client(auth).await;
+            ssm::delete(&client, &key).await;
+            println!(
+                "{} to save journal {}, it is kept in {} only: {}",
+                "Failed".red(),
+                &key,
+                path_of(&self.namespace, &self.sandbox),
+                e
+            );
+        }
+    }
+
+    pub fn is_done(&self, namespace: &str, step: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.namespace == namespace && e.step == step && e.status == Status::Done)
+    }
+
+    pub fn failed(&self) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.status == Status::Failed)
+    }
+
+    /// A topology is fresh when it had no resources when the deploy began
+    pub fn is_fresh(&self, namespace: &str) -> bool {
+        self.fresh.iter().any(|n| n == namespace)
+    }
+
+    pub fn record(&mut self, entry: Entry) {
+        let pos = self
+            .entries
+            .iter()
+            .position(|e| e.namespace == entry.namespace && e.step == entry.step);
+        match pos {
+            Some(i) => self.entries[i] = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub fn mark(&mut self, namespace: &str, step: &str, status: Status) {
+        for e in self.entries.iter_mut() {
+            if e.namespace == namespace && e.step == step {
+                e.status = status.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("abcdef", 4), vec!["abcd", "ef"]);
+        assert_eq!(split("abcd", 4), vec!["abcd"]);
+        assert_eq!(split("", 4), vec![""]);
+        // never splits a char
+        assert_eq!(split("aé€", 2), vec!["a", "é", "€"]);
+        assert_eq!(split("aé€", 4), vec!["aé", "€"]);
+    }
+
+    #[test]
+    fn test_prior() {
+        let prior = Prior::Function {
+            version: String::from("3"),
+        };
+        let s = serde_json::to_string(&prior).unwrap();
+        assert_eq!(s, r#"{"kind":"function","version":"3"}"#);
+        assert_eq!(serde_json::from_str::<Prior>(&s).unwrap(), prior);
+    }
+}
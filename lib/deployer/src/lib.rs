@@ -1,5 +1,8 @@
 mod aws;
+mod dag;
 pub mod guard;
+pub mod guardrails;
+pub mod journal;
 use aws::{
     channel,
     event,
@@ -13,6 +16,7 @@ use aws::{
     route,
     schedule,
     state,
+    store,
     transducer,
 };
 use colored::Colorize;
@@ -24,21 +28,75 @@ use composer::{
     Function,
     Topology,
 };
+use dag::Dag;
+use futures::{
+    FutureExt,
+    StreamExt,
+    stream::FuturesUnordered,
+};
+use journal::{
+    Entry,
+    Journal,
+    Prior,
+    Status,
+};
 use kit::*;
 use provider::Auth;
 use std::{
-    collections::HashMap,
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    panic::AssertUnwindSafe,
     str::FromStr,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 use tabled::{
     Style,
     Table,
 };
 
-pub async fn create(auth: &Auth, topology: &Topology, concurrency: Option<i32>, force: bool) {
+// steps of a topology deploy, in the order they run
+const STEPS: [&str; 15] = [
+    "base-roles",
+    "roles",
+    "stores",
+    "functions",
+    "function-roles",
+    "channels",
+    "mutations",
+    "queues",
+    "events",
+    "pools",
+    "routes",
+    "schedules",
+    "pages",
+    "states",
+    "transducer",
+];
+
+fn announce(auth: &Auth, topology: &Topology) {
+    println!(
+        "Creating functor {}@{}.{}/{}",
+        &topology.namespace.green(),
+        &topology.sandbox.cyan(),
+        &auth.name.blue(),
+        &topology.version
+    );
+}
+
+async fn create_step(
+    auth: &Auth,
+    topology: &Topology,
+    step: &str,
+    concurrency: Option<i32>,
+    force: bool,
+) {
     let Topology {
         namespace,
-        version,
         sandbox,
         functions,
         all_functions,
@@ -51,6 +109,7 @@ pub async fn create(auth: &Auth, topology: &Topology, concurrency: Option<i32>,
         channels,
         schedules,
         pages,
+        stores,
         flow,
         roles,
         base_roles,
@@ -63,39 +122,558 @@ pub async fn create(auth: &Auth, topology: &Topology, concurrency: Option<i32>,
         None => topology.concurrency,
     };
 
-    println!(
-        "Creating functor {}@{}.{}/{}",
-        &namespace.green(),
-        &sandbox.cyan(),
-        &auth.name.blue(),
-        &version
-    );
+    match step {
+        "base-roles" => {
+            if namespace == "base" || sandbox != "stable" {
+                role::update_base_roles(auth, base_roles, tags).await;
+            }
+        }
+        "roles" => role::create_or_update(auth, &sandbox, roles, tags).await,
+        // panics, so that the step is journaled as failed
+        "stores" => {
+            if let Err(e) = store::create(&auth, stores, &tags).await {
+                panic!("{}", e);
+            }
+        }
+        "functions" => function::create(auth, functions, &tags, concurrency, force).await,
+        "function-roles" => function::sync_roles(auth, all_functions).await,
+        "channels" => channel::create(&auth, channels).await,
+        "mutations" => mutation::create(&auth, mutations, &tags).await,
+        "queues" => queue::create(&auth, queues).await,
+        "events" => event::create(&auth, events, &tags).await,
+        "pools" => pool::create(&auth, pools).await,
+        "routes" => route::create(&auth, routes, &tags, sandbox).await,
+        "schedules" => schedule::create(&auth, schedules).await,
+        "pages" => {
+            let cfg = make_config(&auth, topology).await;
+            page::create(&auth, pages, &cfg, sandbox).await;
+        }
+        "states" => {
+            if let Some(f) = flow {
+                state::create(&auth, &f, tags).await;
+            }
+        }
+        "transducer" => {
+            if let Some(trn) = transducer {
+                let cfg = make_config(&auth, topology).await;
+                transducer::create(auth, functions, &trn, &cfg).await;
+            }
+        }
+        _ => (),
+    }
+}
 
-    if namespace == "base" || sandbox != "stable" {
-        role::update_base_roles(auth, base_roles, tags).await;
+// outside of a journaled deploy a failed step ends the command
+fn exit_on_error(res: Result<(), String>) {
+    if let Err(e) = res {
+        println!("{}", e.red());
+        std::process::exit(1);
     }
+}
 
-    role::create_or_update(auth, &sandbox, roles, tags).await;
-    function::create(auth, functions, &tags, concurrency, force).await;
-    function::sync_roles(auth, all_functions).await;
-    channel::create(&auth, channels).await;
-    mutation::create(&auth, mutations, &tags).await;
-    queue::create(&auth, queues).await;
-    event::create(&auth, events, &tags).await;
-    pool::create(&auth, pools).await;
-    route::create(&auth, routes, &tags, sandbox).await;
-    schedule::create(&auth, schedules).await;
-    let cfg = make_config(&auth, topology).await;
-    page::create(&auth, pages, &cfg, sandbox).await;
-    if let Some(f) = flow {
-        state::create(&auth, &f, tags).await;
+// undoes a step of a topology that did not exist before the deploy
+async fn delete_step(auth: &Auth, topology: &Topology, step: &str) {
+    let Topology {
+        sandbox,
+        functions,
+        routes,
+        events,
+        queues,
+        mutations,
+        pools,
+        channels,
+        schedules,
+        pages,
+        stores,
+        flow,
+        roles,
+        transducer,
+        ..
+    } = topology;
+
+    match step {
+        "roles" => role::delete(auth, roles).await,
+        "stores" => {
+            if let Err(e) = store::delete(auth, stores).await {
+                panic!("{}", e);
+            }
+        }
+        "functions" => function::delete(auth, functions, false).await,
+        "channels" => channel::delete(auth, channels).await,
+        "mutations" => mutation::delete(auth, mutations).await,
+        "queues" => queue::delete(auth, queues).await,
+        "events" => event::delete(auth, events).await,
+        "pools" => pool::delete(auth, pools).await,
+        "routes" => route::delete(auth, routes, sandbox, false).await,
+        "schedules" => schedule::delete(auth, schedules).await,
+        "pages" => page::delete(auth, pages).await,
+        "states" => {
+            if let Some(f) = flow {
+                state::delete(auth, f).await;
+            }
+        }
+        "transducer" => {
+            if let Some(trns) = transducer {
+                transducer::delete(auth, &trns).await;
+            }
+        }
+        _ => (),
     }
-    if let Some(trn) = transducer {
-        let cfg = make_config(&auth, topology).await;
-        transducer::create(auth, functions, &trn, &cfg).await;
+}
+
+// components of a step, keyed as in the topology. Only steps whose
+// components can be told apart and deleted on their own are listed
+fn components(topology: &Topology, step: &str) -> Vec<String> {
+    match step {
+        "stores" => topology
+            .stores
+            .iter()
+            .filter(|(_, s)| s.should_create)
+            .map(|(k, _)| k.clone())
+            .collect(),
+        "functions" => topology.functions.keys().cloned().collect(),
+        "channels" => topology.channels.keys().cloned().collect(),
+        "mutations" => topology.mutations.keys().cloned().collect(),
+        "queues" => topology
+            .queues
+            .iter()
+            .filter(|(_, q)| q.should_create)
+            .map(|(k, _)| k.clone())
+            .collect(),
+        "events" => topology.events.keys().cloned().collect(),
+        "routes" => topology
+            .routes
+            .iter()
+            .filter(|(k, r)| *k != "default" && !r.skip)
+            .map(|(k, _)| k.clone())
+            .collect(),
+        "states" => topology.flow.iter().map(|f| f.name.clone()).collect(),
+        _ => vec![],
     }
 }
 
+async fn exists(auth: &Auth, topology: &Topology, step: &str, key: &str) -> bool {
+    match step {
+        "stores" => match topology.stores.get(key) {
+            Some(s) => store::exists(auth, s).await,
+            None => false,
+        },
+        "functions" => match topology.functions.get(key) {
+            Some(f) => function::exists(auth, &f.fqn).await,
+            None => false,
+        },
+        "channels" => match topology.channels.get(key) {
+            Some(c) => channel::exists(auth, c).await,
+            None => false,
+        },
+        "mutations" => match topology.mutations.get(key) {
+            Some(m) => mutation::exists(auth, m).await,
+            None => false,
+        },
+        "queues" => match topology.queues.get(key) {
+            Some(q) => queue::exists(auth, q).await,
+            None => false,
+        },
+        "events" => match topology.events.get(key) {
+            Some(e) => event::exists(auth, e).await,
+            None => false,
+        },
+        "routes" => route::exists(auth, &topology.routes, key, &topology.sandbox).await,
+        "states" => match &topology.flow {
+            Some(f) => state::exists(auth, f).await,
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+// state of a deployed component that a rollback can put back
+async fn snapshot(auth: &Auth, topology: &Topology, step: &str, key: &str) -> Option<Prior> {
+    match step {
+        "functions" => {
+            let f = topology.functions.get(key)?;
+            match function::snapshot(auth, &f.fqn).await {
+                Ok(version) => Some(Prior::Function { version: version }),
+                Err(e) => {
+                    println!(
+                        "Failed to snapshot {}, it cannot be restored: {}",
+                        &f.fqn, e
+                    );
+                    None
+                }
+            }
+        }
+        "queues" => {
+            let q = topology.queues.get(key)?;
+            let attributes = queue::snapshot(auth, q).await?;
+            Some(Prior::Queue {
+                attributes: attributes,
+            })
+        }
+        "states" => {
+            let f = topology.flow.as_ref()?;
+            let (definition, role) = state::snapshot(auth, f).await?;
+            Some(Prior::State {
+                definition: definition,
+                role: role,
+            })
+        }
+        _ => None,
+    }
+}
+
+// components of the step that are not deployed yet, and the prior state
+// of those that are
+async fn inspect(
+    auth: &Auth,
+    topology: &Topology,
+    step: &str,
+) -> (Vec<String>, HashMap<String, Prior>) {
+    let mut created: Vec<String> = vec![];
+    let mut prior: HashMap<String, Prior> = HashMap::new();
+    for key in components(topology, step) {
+        if !exists(auth, topology, step, &key).await {
+            created.push(key);
+        } else if let Some(p) = snapshot(auth, topology, step, &key).await {
+            prior.insert(key, p);
+        }
+    }
+    created.sort();
+    (created, prior)
+}
+
+// puts back the components a step updated
+async fn restore(auth: &Auth, topology: &Topology, step: &str, prior: &HashMap<String, Prior>) {
+    for (key, p) in prior {
+        let res = match p {
+            Prior::Function { version } => match topology.functions.get(key) {
+                Some(f) => function::restore(auth, &f.fqn, version).await,
+                None => continue,
+            },
+            Prior::Queue { attributes } => match topology.queues.get(key) {
+                Some(q) => queue::restore(auth, q, attributes).await,
+                None => continue,
+            },
+            Prior::State { definition, role } => match &topology.flow {
+                Some(f) => state::restore(auth, f, definition, role).await,
+                None => continue,
+            },
+        };
+        match res {
+            Ok(()) => println!("Restored {}/{}", step, key),
+            Err(e) => println!("{} to restore {}/{}: {}", "Failed".red(), step, key, e),
+        }
+    }
+}
+
+// the topology reduced to the given components of a step
+fn narrow_step(topology: &Topology, step: &str, keys: &[String]) -> Topology {
+    let has = |k: &String| keys.contains(k);
+    let mut t = topology.clone();
+    match step {
+        "functions" => t.functions.retain(|k, _| has(k)),
+        "channels" => t.channels.retain(|k, _| has(k)),
+        "mutations" => t.mutations.retain(|k, _| has(k)),
+        "queues" => t.queues.retain(|k, _| has(k)),
+        "events" => t.events.retain(|k, _| has(k)),
+        "routes" => t.routes.retain(|k, _| has(k)),
+        "states" => t.flow = t.flow.filter(|f| has(&f.name)),
+        "stores" => t.stores.retain(|k, _| has(k)),
+        _ => (),
+    }
+    t
+}
+
+// A topology keeps the creation time of its first deploy in the sandbox,
+// so that creating it again does not extend its ttl
+async fn keep_created_at(auth: &Auth, topology: &Topology) -> Topology {
+    let mut t = topology.clone();
+    if let Some(c) = resource::created_at(auth, &t.namespace, &t.sandbox).await {
+        t.tags.insert(String::from("created_at"), c);
+    }
+    for node in t.nodes.values_mut() {
+        if let Some(c) = resource::created_at(auth, &node.namespace, &node.sandbox).await {
+            node.tags.insert(String::from("created_at"), c);
+        }
+    }
+    t
+}
+
+pub async fn create(auth: &Auth, topology: &Topology, concurrency: Option<i32>, force: bool) {
+    let topology = &keep_created_at(auth, topology).await;
+    announce(auth, topology);
+    for step in STEPS {
+        create_step(auth, topology, step, concurrency, force).await;
+    }
+}
+
+fn panic_message(e: Box<dyn std::any::Any + Send>) -> String {
+    match e.downcast_ref::<String>() {
+        Some(s) => s.clone(),
+        None => match e.downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => String::from("panicked"),
+        },
+    }
+}
+
+fn find_topology<'a>(topology: &'a Topology, namespace: &str) -> Option<&'a Topology> {
+    if topology.namespace == namespace {
+        Some(topology)
+    } else {
+        topology.nodes.values().find(|n| n.namespace == namespace)
+    }
+}
+
+const DEFAULT_PARALLELISM: usize = 4;
+
+// steps run at a time, independent of the function chunk size
+fn parallelism() -> usize {
+    match std::env::var("TC_DEPLOY_PARALLELISM") {
+        Ok(n) => match n.parse::<usize>() {
+            Ok(p) if p > 0 => p,
+            _ => {
+                eprintln!(
+                    "{} TC_DEPLOY_PARALLELISM={} is not a positive number, using {}",
+                    "Warning:".yellow(),
+                    &n,
+                    DEFAULT_PARALLELISM
+                );
+                DEFAULT_PARALLELISM
+            }
+        },
+        Err(_) => DEFAULT_PARALLELISM,
+    }
+}
+
+struct Outcome {
+    index: usize,
+    created: Vec<String>,
+    prior: HashMap<String, Prior>,
+    error: Option<String>,
+    elapsed: Duration,
+}
+
+async fn run_task(
+    auth: &Auth,
+    topology: &Topology,
+    index: usize,
+    step: &str,
+    concurrency: Option<i32>,
+    force: bool,
+) -> Outcome {
+    let start = Instant::now();
+    // checked per component, as steps of the same topology run side by side
+    let (created, prior) = inspect(auth, topology, step).await;
+    let res = AssertUnwindSafe(create_step(auth, topology, step, concurrency, force))
+        .catch_unwind()
+        .await;
+    Outcome {
+        index: index,
+        created: created,
+        prior: prior,
+        error: match res {
+            Ok(_) => None,
+            Err(e) => Some(panic_message(e)),
+        },
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Creates the topology and its nodes, running steps as soon as the steps
+/// they depend on are done, journaling every step. With `resume`, steps
+/// the last failed deploy completed are skipped. Returns the failed step,
+/// if any
+pub async fn deploy(
+    auth: &Auth,
+    topology: &Topology,
+    concurrency: Option<i32>,
+    force: bool,
+    resume: bool,
+) -> Option<Entry> {
+    let topology = &keep_created_at(auth, topology).await;
+    let Topology {
+        namespace, sandbox, ..
+    } = topology;
+    let last = match resume {
+        true => Journal::load(auth, namespace, sandbox).await,
+        false => None,
+    };
+    let mut journal = match last {
+        Some(j) if j.failed().is_some() => {
+            if let Some(e) = j.failed() {
+                println!("Resuming from {}/{}", &e.namespace, &e.step.cyan());
+            }
+            j
+        }
+        _ => {
+            if resume {
+                println!("No failed deploy to resume, creating afresh");
+            }
+            let mut j = Journal::new(topology);
+            for t in std::iter::once(topology).chain(topology.nodes.values()) {
+                let arns = resource::list_namespace(auth, sandbox, &t.namespace).await;
+                if arns.is_empty() {
+                    j.fresh.push(t.namespace.clone());
+                }
+            }
+            j
+        }
+    };
+
+    announce(auth, topology);
+    for node in topology.nodes.values() {
+        announce(auth, node);
+    }
+
+    let dag = Dag::new(topology, &STEPS);
+    let total = dag.tasks.len();
+    let mut done: HashSet<usize> = HashSet::new();
+    for (i, task) in dag.tasks.iter().enumerate() {
+        if journal.is_done(&task.namespace, task.step) {
+            done.insert(i);
+        }
+    }
+    if !done.is_empty() {
+        println!("Skipping {} steps done in the last deploy", done.len());
+    }
+
+    let start = Instant::now();
+    let limit = parallelism();
+    let mut started: HashSet<usize> = HashSet::new();
+    let mut order: Vec<usize> = vec![];
+    let mut durations: HashMap<usize, Duration> = HashMap::new();
+    let mut failed = false;
+    let mut running = FuturesUnordered::new();
+    loop {
+        if !failed {
+            for i in dag.ready(&done, &started) {
+                if running.len() >= limit {
+                    break;
+                }
+                let task = &dag.tasks[i];
+                if let Some(t) = find_topology(topology, &task.namespace) {
+                    started.insert(i);
+                    running.push(run_task(auth, t, i, task.step, concurrency, force));
+                }
+            }
+        }
+        let outcome = match running.next().await {
+            Some(o) => o,
+            None => break,
+        };
+        let task = &dag.tasks[outcome.index];
+        let status = match outcome.error {
+            Some(_) => Status::Failed,
+            None => Status::Done,
+        };
+        journal.record(Entry {
+            namespace: task.namespace.clone(),
+            step: task.step.to_string(),
+            status: status.clone(),
+            created: outcome.created,
+            prior: outcome.prior,
+            error: outcome.error,
+            at: kit::utc_now(),
+        });
+        journal.save(auth).await;
+        if status == Status::Failed {
+            failed = true;
+            println!("{} {}/{}", "Failed".red(), &task.namespace, task.step);
+            continue;
+        }
+        done.insert(outcome.index);
+        order.push(outcome.index);
+        durations.insert(outcome.index, outcome.elapsed);
+        println!(
+            "[{}/{}] {}/{} {} ({:#}, {} running)",
+            done.len(),
+            total,
+            &task.namespace,
+            task.step,
+            "done".green(),
+            kit::time_format(outcome.elapsed),
+            running.len()
+        );
+    }
+    if failed {
+        return journal.failed().cloned();
+    }
+
+    let (path, length) = dag.critical_path(&order, &durations);
+    let steps: Vec<String> = path
+        .iter()
+        .map(|i| format!("{}/{}", &dag.tasks[*i].namespace, dag.tasks[*i].step))
+        .collect();
+    println!("Critical path: {} ({:#})", steps.join(" -> "), kit::time_format(length));
+    println!("Deployed in {:#}", kit::time_format(start.elapsed()));
+    None
+}
+
+/// Undoes the steps of the last journaled deploy, latest first. A topology
+/// that had no resources before the deploy is torn down step by step;
+/// otherwise only components a step created are deleted. Functions, queues
+/// and state machines a step updated are put back as they were, other
+/// updated components are left as they are. Stores are only deleted when
+/// the deploy created them
+pub async fn rollback(auth: &Auth, topology: &Topology) {
+    let Topology {
+        namespace, sandbox, ..
+    } = topology;
+    let mut journal = match Journal::load(auth, namespace, sandbox).await {
+        Some(j) => j,
+        None => {
+            println!("No deploy journal for {}@{}", namespace, sandbox);
+            return;
+        }
+    };
+    let entries: Vec<Entry> = journal
+        .entries
+        .iter()
+        .rev()
+        .filter(|e| e.status != Status::RolledBack)
+        .cloned()
+        .collect();
+    if entries.is_empty() {
+        println!("Nothing to roll back");
+        return;
+    }
+    for entry in entries {
+        let t = match find_topology(topology, &entry.namespace) {
+            Some(t) => t,
+            None => {
+                println!("Skipping {}/{}, not in topology", &entry.namespace, &entry.step);
+                continue;
+            }
+        };
+        println!("Rolling back {}/{}", &entry.namespace, &entry.step.cyan());
+        // stores may be found rather than created, even in a fresh topology
+        let undo = async {
+            if journal.is_fresh(&entry.namespace) && entry.step != "stores" {
+                delete_step(auth, t, &entry.step).await;
+            } else if !entry.created.is_empty() {
+                let created = narrow_step(t, &entry.step, &entry.created);
+                delete_step(auth, &created, &entry.step).await;
+            }
+        };
+        // the steps rolled back so far are saved before giving up
+        if let Err(e) = AssertUnwindSafe(undo).catch_unwind().await {
+            journal.save(auth).await;
+            println!(
+                "{} to roll back {}/{}: {}",
+                "Failed".red(),
+                &entry.namespace,
+                &entry.step,
+                panic_message(e)
+            );
+            std::process::exit(1);
+        }
+        restore(auth, t, &entry.step, &entry.prior).await;
+        journal.mark(&entry.namespace, &entry.step, Status::RolledBack);
+    }
+    journal.save(auth).await;
+}
+
 async fn update_function(
     auth: &Auth,
     namespace: &str,
@@ -131,6 +709,7 @@ async fn update_topology(auth: &Auth, topology: &Topology) {
         pools,
         routes,
         pages,
+        stores,
         roles,
         transducer,
         ..
@@ -145,6 +724,7 @@ async fn update_topology(auth: &Auth, topology: &Topology) {
     );
 
     role::create_or_update(&auth, &sandbox, roles, tags).await;
+    exit_on_error(store::create(&auth, stores, &tags).await);
     function::update_code(&auth, functions, &tags).await;
     function::sync_roles(&auth, all_functions).await;
     mutation::create(&auth, mutations, &tags).await;
@@ -152,7 +732,7 @@ async fn update_topology(auth: &Auth, topology: &Topology) {
     event::create(&auth, events, &tags).await;
     queue::create(&auth, queues).await;
     pool::create(&auth, pools).await;
-    route::create(&auth, routes, &tags, sandbox).await;
+    route::update(&auth, routes, &tags, sandbox, None).await;
     let cfg = make_config(&auth, topology).await;
     page::create(&auth, pages, &cfg, &sandbox).await;
     if let Some(f) = flow {
@@ -181,6 +761,7 @@ async fn update_entity(auth: &Auth, topology: &Topology, entity: Entity) {
         channels,
         pools,
         pages,
+        stores,
         ..
     } = topology;
 
@@ -200,7 +781,8 @@ async fn update_entity(auth: &Auth, topology: &Topology, entity: Entity) {
         Entity::Channel => channel::create(&auth, channels).await,
         Entity::Schedule => schedule::create(&auth, schedules).await,
         Entity::Trigger => pool::create(&auth, pools).await,
-        Entity::Route => route::create(&auth, routes, tags, sandbox).await,
+        Entity::Route => route::update(&auth, routes, tags, sandbox, None).await,
+        Entity::Store => exit_on_error(store::create(&auth, stores, tags).await),
         Entity::Page => {
             let cfg = make_config(&auth, topology).await;
             page::create(&auth, pages, &cfg, &sandbox).await;
@@ -229,6 +811,7 @@ async fn update_component(auth: &Auth, topology: &Topology, entity: Entity, comp
         nodes,
         pools,
         pages,
+        stores,
         ..
     } = topology;
 
@@ -254,7 +837,8 @@ async fn update_component(auth: &Auth, topology: &Topology, entity: Entity, comp
         Entity::Channel => channel::update(&auth, channels, component).await,
         Entity::Schedule => schedule::update(&auth, schedules).await,
         Entity::Trigger => pool::update(&auth, pools, component).await,
-        Entity::Route => route::update(&auth, routes, component).await,
+        Entity::Route => route::update(&auth, routes, tags, sandbox, Some(component)).await,
+        Entity::Store => exit_on_error(store::update(&auth, stores, tags, component).await),
         Entity::Page => {
             let cfg = make_config(&auth, topology).await;
             page::update(&auth, pages, component, &cfg, &sandbox).await;
@@ -279,6 +863,7 @@ async fn delete(auth: &Auth, topology: &Topology, force: bool) {
         roles,
         queues,
         pages,
+        stores,
         transducer,
         ..
     } = topology;
@@ -299,6 +884,7 @@ async fn delete(auth: &Auth, topology: &Topology, force: bool) {
     route::delete(&auth, routes, sandbox, force).await;
     mutation::delete(&auth, mutations).await;
     queue::delete(&auth, queues).await;
+    exit_on_error(store::delete(&auth, stores).await);
     page::delete(&auth, pages).await;
     if let Some(trns) = transducer {
         transducer::delete(auth, &trns).await;
@@ -320,6 +906,7 @@ async fn delete_entity(auth: &Auth, topology: &Topology, entity: Entity) {
         queues,
         channels,
         pages,
+        stores,
         ..
     } = topology;
 
@@ -342,6 +929,7 @@ async fn delete_entity(auth: &Auth, topology: &Topology, entity: Entity) {
         Entity::Queue => queue::delete(&auth, queues).await,
         Entity::Channel => channel::delete(&auth, channels).await,
         Entity::Page => page::delete(&auth, pages).await,
+        Entity::Store => exit_on_error(store::delete(&auth, stores).await),
         Entity::State => {
             if let Some(f) = flow {
                 state::delete(&auth, f).await;
@@ -372,6 +960,10 @@ async fn delete_component(auth: &Auth, topology: &Topology, entity: Entity, comp
 // pub interfaces
 
 pub async fn try_update(auth: &Auth, topology: &Topology, maybe_entity: &Option<String>) {
+    // updates keep the creation time the sandbox was tagged with
+    let mut topology = topology.clone();
+    topology.tags.remove("created_at");
+    let topology = &topology;
     match maybe_entity {
         Some(e) => {
             let (entity, component) = Entity::as_entity_component(&e);
@@ -419,25 +1011,39 @@ pub async fn try_delete(
 }
 
 pub async fn freeze(auth: &Auth, topology: &Topology) {
-    let Topology { fqn, kind, .. } = topology;
+    let Topology {
+        fqn,
+        kind,
+        events,
+        channels,
+        ..
+    } = topology;
     match kind {
         TopologyKind::StepFunction => state::freeze(auth, fqn).await,
         TopologyKind::Function => function::freeze(auth, fqn).await,
         TopologyKind::Graphql => mutation::freeze(auth, fqn).await,
         TopologyKind::Routed => route::freeze(auth, fqn).await,
-        TopologyKind::Evented => event::freeze(auth, fqn).await,
+        TopologyKind::Evented => event::freeze(auth, events).await,
     }
+    channel::freeze(auth, channels).await;
 }
 
 pub async fn unfreeze(auth: &Auth, topology: &Topology) {
-    let Topology { fqn, kind, .. } = topology;
+    let Topology {
+        fqn,
+        kind,
+        events,
+        channels,
+        ..
+    } = topology;
     match kind {
         TopologyKind::StepFunction => state::unfreeze(auth, fqn).await,
         TopologyKind::Function => function::unfreeze(auth, fqn).await,
         TopologyKind::Graphql => mutation::unfreeze(auth, fqn).await,
         TopologyKind::Routed => route::unfreeze(auth, fqn).await,
-        TopologyKind::Evented => event::unfreeze(auth, fqn).await,
+        TopologyKind::Evented => event::unfreeze(auth, events).await,
     }
+    channel::unfreeze(auth, channels).await;
 }
 
 pub async fn try_list(auth: &Auth, topology: &Topology, maybe_entity: &Option<String>) {
@@ -498,19 +1104,108 @@ pub async fn list_all_resources(auth: &Auth) {
     }
 }
 
-pub async fn prune(auth: &Auth, sandbox: &str, filter: Option<String>) {
+async fn prune_sandbox(auth: &Auth, sandbox: &str, filter: Option<String>, confirm: bool) {
     let arns = resource::list(auth, sandbox).await;
     let grouped_arns = resource::filter_arns(arns.clone(), filter);
     let grouped = resource::group_entities(grouped_arns);
     let roles = role::list(auth, &format!("-{}", sandbox)).await;
     println!("{}, roles: {}", resource::count_of(&grouped), &roles.len());
-    let cont = guard::prompt("Do you want to delete these resources in given sandbox ?");
+    if confirm {
+        let cont = guard::prompt("Do you want to delete these resources in given sandbox ?");
+        if !cont {
+            std::process::exit(1);
+        }
+    }
+    resource::delete_arns(auth, grouped).await;
+
+    role::delete_roles(auth, roles).await;
+}
+
+pub async fn prune(auth: &Auth, sandbox: &str, filter: Option<String>) {
+    prune_sandbox(auth, sandbox, filter, true).await
+}
+
+// tears down a topology in the sandbox, along with its roles. Shared
+// roles go with the last topology of the sandbox
+async fn prune_namespace(auth: &Auth, sandbox: &str, namespace: &str) {
+    let arns = resource::list_namespace(auth, sandbox, namespace).await;
+    let grouped = resource::group_entities(arns);
+    let mut roles = role::list(auth, &format!("-{}", sandbox)).await;
+    println!("{}, roles: {}", resource::count_of(&grouped), &roles.len());
+    resource::delete_arns(auth, grouped).await;
+
+    if !resource::list(auth, sandbox).await.is_empty() {
+        let prefix = format!("tc-{}-", namespace);
+        roles.retain(|(name, _)| name.starts_with(&prefix));
+    }
+    role::delete_roles(auth, roles).await;
+}
+
+/// Tears down every topology whose sandbox outlived the ttl it was
+/// created with. The stable sandbox is never pruned
+pub async fn prune_expired(auth: &Auth, stable_sandbox: &str, dry_run: bool) {
+    let sandboxes: Vec<resource::SandboxInfo> = match resource::list_expired(auth).await {
+        Ok(xs) => xs
+            .into_iter()
+            .filter(|s| s.name != "stable" && s.name != stable_sandbox)
+            .collect(),
+        Err(e) => {
+            println!("Failed to list sandboxes: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if sandboxes.is_empty() {
+        println!("No expired sandboxes");
+        return;
+    }
+    let table = Table::new(sandboxes.clone())
+        .with(Style::psql())
+        .to_string();
+    println!("{}", table);
+    if dry_run {
+        return;
+    }
+    let cont = guard::prompt("Do you want to delete these sandboxes ?");
     if !cont {
         std::process::exit(1);
     }
+    for s in sandboxes {
+        println!("Pruning {} in sandbox {}", &s.namespace, &s.name.cyan());
+        prune_namespace(auth, &s.name, &s.namespace).await;
+    }
+}
+
+/// Deploys every resource of the given topology and its nodes. Used to
+/// apply a plan, where the topology only holds the planned resources.
+/// Schedules are not part of a topology update, so they are created here
+pub async fn apply(auth: &Auth, topology: &Topology) {
+    let mut root = topology.clone();
+    root.tags.remove("created_at");
+    update_topology(auth, &root).await;
+    schedule::create(auth, &root.schedules).await;
+    for node in topology.nodes.values() {
+        let mut node = node.clone();
+        node.tags.remove("created_at");
+        update_topology(auth, &node).await;
+        schedule::create(auth, &node.schedules).await;
+    }
+}
+
+/// Deletes functions and state machines by arn
+pub async fn delete_resources(auth: &Auth, arns: Vec<String>) {
+    let grouped = resource::group_entities(arns);
     resource::delete_arns(auth, grouped).await;
+}
 
-    role::delete_roles(auth, roles).await;
+/// Deletes routes grouped by the gateway they are deployed on
+pub async fn delete_routes(auth: &Auth, routes: Vec<(String, String)>) {
+    let mut h: HashMap<String, Vec<String>> = HashMap::new();
+    for (gateway, key) in routes {
+        h.entry(gateway).or_default().push(key);
+    }
+    for (gateway, keys) in h {
+        route::delete_keys(auth, &gateway, keys).await;
+    }
 }
 
 pub async fn make_config(auth: &Auth, topology: &Topology) -> HashMap<String, String> {
@@ -9,11 +9,22 @@ use provider::{
 };
 use std::collections::HashMap;
 
+fn is_python(lang: &str) -> bool {
+    matches!(
+        lang,
+        "python3.9" | "python3.10" | "python3.11" | "python3.12" | "python3.13"
+    )
+}
+
+fn unsupported(lang: &str) -> String {
+    format!("Emulating {} functions is not supported, use an image build", lang)
+}
+
 fn gen_entry_point(lang: &str) -> String {
     match lang {
-        "python3.10" | "python3.9" => format!(
+        l if is_python(l) => format!(
             r"#!/bin/sh
-exec /usr/local/bin/aws-lambda-rie /var/lang/bin/python3.10 -m awslambdaric $@
+exec /usr/local/bin/aws-lambda-rie /var/lang/bin/{l} -m awslambdaric $@
 "
         ),
         "ruby3.2" => {
@@ -27,9 +38,9 @@ exec /usr/local/bin/aws-lambda-rie aws_lambda_ric $@ $@
     }
 }
 
-fn docker_build_cmd(name: &str, lang: &str, handler: &str) -> String {
+fn docker_build_cmd(name: &str, lang: &str, handler: &str) -> Result<String, String> {
     match lang {
-        "ruby3.2" => format!(
+        "ruby3.2" => Ok(format!(
             r#"docker build -t build_{name} -f- . <<EOF
 FROM public.ecr.aws/sam/build-ruby3.2:1.103.0-20231116224730
 COPY ./entry_script.sh /entry_script.sh
@@ -37,60 +48,48 @@ RUN chmod +x /entry_script.sh
 ENTRYPOINT [ "/entry_script.sh","{handler}" ]
 EOF
 "#
-        ),
-        "python3.10" => format!(
-            r#"docker build -t build_{name} -f- . <<EOF
-FROM public.ecr.aws/sam/build-python3.10:latest
-RUN pip install boto3 -q -q -q --exists-action i
-COPY ./entry_script.sh /entry_script.sh
-RUN chmod +x /entry_script.sh
-ENTRYPOINT [ "/entry_script.sh", "{handler}" ]
-EOF
-"#
-        ),
-
-        "python3.12" => format!(
+        )),
+        l if is_python(l) => Ok(format!(
             r#"docker build -t build_{name} -f- . <<EOF
-FROM public.ecr.aws/sam/build-python3.12:latest
-RUN pip install boto3 -q -q -q --exists-action i
+FROM public.ecr.aws/sam/build-{l}:latest
+RUN pip install boto3 awslambdaric -q -q -q --exists-action i
 COPY ./entry_script.sh /entry_script.sh
 RUN chmod +x /entry_script.sh
 ENTRYPOINT [ "/entry_script.sh", "{handler}" ]
 EOF
 "#
-        ),
-
-        "rust" | "janet" => format!(
+        )),
+        "rust" | "janet" => Ok(format!(
             r#"docker build -t build_{name} -f- . <<EOF
 FROM public.ecr.aws/lambda/provided:al2023
 COPY bootstrap /var/runtime
 CMD [ "{handler}" ]
 EOF
 "#
-        ),
-        _ => panic!("unknown..."),
+        )),
+        _ => Err(unsupported(lang)),
     }
 }
 
-fn docker_run_cmd(name: &str, lang: &str) -> String {
+fn docker_run_cmd(name: &str, lang: &str, port: u16, region: &str) -> Result<String, String> {
     let env = match std::env::var("AWS_PROFILE") {
         Ok(e) => e,
         Err(_) => s!("dev"),
     };
     match lang {
-        "ruby3.2" => format!(
-            "docker run -p 9000:8080 -v $(pwd)/build:/opt -v $(pwd):/var/task -e BUNDLE_CACHE_PATH=/opt/ruby/lib -e GEM_PATH=/opt/ruby/gems/3.2.0 -v $HOME/.aws:/root/aws:ro -e RUBYLIB=/opt/ruby/lib -e LD_LIBRARY_PATH=/usr/lib64:/opt/lib -e BUNDLE_GEMFILE=/opt/ruby/Gemfile -e AWS_REGION=us-west-2 -e Environment={env} -e AWS_PROFILE={env} -e POWERTOOLS_METRICS_NAMESPACE=dev build_{name}"
-        ),
+        "ruby3.2" => Ok(format!(
+            "docker run -p {port}:8080 -v $(pwd)/build:/opt -v $(pwd):/var/task -e BUNDLE_CACHE_PATH=/opt/ruby/lib -e GEM_PATH=/opt/ruby/gems/3.2.0 -v $HOME/.aws:/root/aws:ro -e RUBYLIB=/opt/ruby/lib -e LD_LIBRARY_PATH=/usr/lib64:/opt/lib -e BUNDLE_GEMFILE=/opt/ruby/Gemfile -e AWS_REGION={region} -e Environment={env} -e AWS_PROFILE={env} -e POWERTOOLS_METRICS_NAMESPACE=dev build_{name}"
+        )),
 
-        "python3.10" => format!(
-            "docker run -p 9000:8080 -v $(pwd)/build:/opt -w /var/task -v $(pwd):/var/task -e LD_LIBRARY_PATH=/usr/lib64:/opt/lib -v $HOME/.aws:/root/aws:ro -e AWS_REGION=us-west-2 -e Environment={env} -e AWS_PROFILE={env} -e PYTHONPATH=/opt/python:/var/runtime:/python:/python -e POWERTOOLS_METRICS_NAMESPACE=dev build_{name}"
-        ),
+        l if is_python(l) => Ok(format!(
+            "docker run -p {port}:8080 -v $(pwd)/build:/opt -w /var/task -v $(pwd):/var/task -e LD_LIBRARY_PATH=/usr/lib64:/opt/lib -v $HOME/.aws:/root/aws:ro -e AWS_REGION={region} -e Environment={env} -e AWS_PROFILE={env} -e PYTHONPATH=/opt/python:/var/runtime:/python:/python -e POWERTOOLS_METRICS_NAMESPACE=dev build_{name}"
+        )),
 
-        "rust" | "janet" => format!(
-            "docker run --rm -p 9000:8080 -e AWS_REGION=us-west-2 -e Environment=dev build_{name}"
-        ),
+        "rust" | "janet" => Ok(format!(
+            "docker run --rm -p {port}:8080 -e AWS_REGION={region} -e Environment=dev build_{name}"
+        )),
 
-        _ => s!(""),
+        _ => Err(unsupported(lang)),
     }
 }
 
@@ -126,12 +125,12 @@ async fn download(url: &str, target_dir: &str) {
     u::sh(&format!("rm -rf {}", tmp_zip_file), &u::pwd());
 }
 
-async fn download_layers(auth: &Auth, layers: Vec<String>) {
+async fn download_layers(auth: &Auth, dir: &str, layers: Vec<String>) {
     let client = aws::lambda::make_client(auth).await;
     let config = Config::new();
     let resolved_layers = resolve_layers(auth, &config, layers).await;
-    let target_dir = format!("{}/build", &u::pwd());
-    u::sh(&format!("rm -rf {}", &target_dir), &u::pwd());
+    let target_dir = format!("{}/build", dir);
+    u::sh(&format!("rm -rf {}", &target_dir), dir);
     for layer in resolved_layers {
         println!("Fetching layer: {}", &layer);
         let maybe_url = aws::layer::get_code_url(&client, &layer).await;
@@ -142,16 +141,21 @@ async fn download_layers(auth: &Auth, layers: Vec<String>) {
     }
 }
 
-pub async fn run(auth: &Auth, dir: &str, function: &Function) {
+async fn build(auth: &Auth, dir: &str, function: &Function, image: &str) -> Result<(), String> {
     let Function { runtime, name, .. } = function;
     let lang = runtime.lang.to_str();
-    download_layers(auth, runtime.layers.clone()).await;
+    let handler = &runtime.handler;
+    let b_cmd = docker_build_cmd(image, &lang, &handler)?;
+    if auth.is_local() {
+        if !runtime.layers.is_empty() {
+            println!("Skipping layers for {} (no aws profile)", &name);
+        }
+    } else {
+        download_layers(auth, dir, runtime.layers.clone()).await;
+    }
 
     let entry = gen_entry_point(&lang);
-    u::write_str("entry_script.sh", &entry);
-
-    let name = &name;
-    let handler = &runtime.handler;
+    u::write_str(&format!("{}/entry_script.sh", dir), &entry);
 
     println!(
         "Building emulator: {} ({}) {}",
@@ -159,12 +163,55 @@ pub async fn run(auth: &Auth, dir: &str, function: &Function) {
         &lang,
         &handler.green()
     );
-    let b_cmd = docker_build_cmd(&name, &lang, &handler);
     u::sh(&b_cmd, dir);
+    Ok(())
+}
+
+fn container_name(name: &str) -> String {
+    format!("tc_emulator_{}", name)
+}
+
+/// Builds and starts the function container in the background, with
+/// the given environment, and returns the container name. Container
+/// and image are named after the emulator's key of the function
+pub async fn start(
+    auth: &Auth,
+    function: &Function,
+    key: &str,
+    port: u16,
+    env: &HashMap<String, String>,
+) -> Result<String, String> {
+    let dir = &function.dir;
+    let lang = function.runtime.lang.to_str();
+    let image = super::docker_name(key);
+    let cmd = docker_run_cmd(&image, &lang, port, &auth.region)?;
+    build(auth, dir, function, &image).await?;
+
+    let container = container_name(&image);
+    let mut opts = format!(
+        "-d --name {} --add-host=host.docker.internal:host-gateway",
+        &container
+    );
+    opts.push_str(&super::env_opts(env));
+    let cmd = cmd.replacen("docker run", &format!("docker run {}", opts), 1);
+    u::sh(&format!("docker rm -f {}", &container), dir);
+    u::sh(&cmd, dir);
+    u::sh("rm -f entry_script.sh", dir);
+    Ok(container)
+}
+
+pub fn stop(container: &str) {
+    u::sh(&format!("docker rm -f {}", container), &u::pwd());
+}
+
+pub async fn run(auth: &Auth, dir: &str, function: &Function) -> Result<(), String> {
+    let lang = function.runtime.lang.to_str();
+    let cmd = docker_run_cmd(&function.name, &lang, 9000, &auth.region)?;
+    build(auth, dir, function, &function.name).await?;
 
-    let cmd = docker_run_cmd(&name, &lang);
     println!("Function Endpoint: http://localhost:9000/2015-03-31/functions/function/invocations");
 
     u::runcmd_stream(&cmd, dir);
     u::sh("rm -f entry_script.sh", dir);
+    Ok(())
 }
@@ -51,3 +51,40 @@ pub async fn run(auth: &Auth, dir: &str, function: &Function, _shell: bool) {
     let b_cmd = format!("docker run --rm -it --entrypoint bash {}", &code_image_uri);
     u::runcmd_stream(&b_cmd, dir);
 }
+
+/// Starts the function image in the background, with the given
+/// environment, and returns the container name
+pub async fn start(
+    auth: &Auth,
+    function: &Function,
+    key: &str,
+    port: u16,
+    env: &HashMap<String, String>,
+) -> String {
+    let config = Config::new();
+    let repo = match std::env::var("TC_ECR_REPO") {
+        Ok(r) => r,
+        Err(_) => config.aws.ecr.repo.clone(),
+    };
+    let code_image_uri = render_uri(&function.runtime.uri, &repo);
+    let dir = &function.dir;
+
+    if !auth.is_local() {
+        aws::ecr::login(auth, dir).await;
+    }
+
+    let container = format!("tc_emulator_{}", super::docker_name(key));
+    let mut opts = format!(
+        "-d --name {} -p {}:8080 --add-host=host.docker.internal:host-gateway",
+        &container, port
+    );
+    opts.push_str(&super::env_opts(env));
+    println!(
+        "Starting emulator: {} from {}",
+        &function.name.cyan(),
+        &code_image_uri
+    );
+    u::sh(&format!("docker rm -f {}", &container), dir);
+    u::sh(&format!("docker run {} {}", opts, &code_image_uri), dir);
+    container
+}
@@ -1,2 +1,76 @@
 pub mod default;
 pub mod image;
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// `docker run` options setting the environment. Values are single
+/// quoted for the shell, quotes within them included
+pub fn env_opts(env: &HashMap<String, String>) -> String {
+    let mut opts = String::new();
+    for (k, v) in env {
+        opts.push_str(&format!(" -e '{}={}'", k, v.replace('\'', "'\\''")));
+    }
+    opts
+}
+
+/// Container and image name of an emulated function. Functions of
+/// nodes are keyed `{ns}/{name}` when their name collides, and docker
+/// rejects `/` in names
+pub fn docker_name(key: &str) -> String {
+    key.replace('/', "_").to_lowercase()
+}
+
+pub fn invocation_url(port: u16) -> String {
+    format!(
+        "http://localhost:{}/2015-03-31/functions/function/invocations",
+        port
+    )
+}
+
+/// Invokes a function running in a local RIE container
+pub async fn invoke(port: u16, payload: &Value) -> Result<Value, String> {
+    let client = reqwest::Client::new();
+    let res = client
+        .post(invocation_url(port))
+        .body(payload.to_string())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let text = res.text().await.map_err(|e| e.to_string())?;
+    match serde_json::from_str(&text) {
+        Ok(v) => Ok(v),
+        Err(_) => Ok(Value::String(text)),
+    }
+}
+
+/// Waits for the container's port to accept connections
+pub async fn wait_until_ready(port: u16, timeout_secs: u64) -> bool {
+    for _ in 0..(timeout_secs * 4) {
+        if tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_ok()
+        {
+            return true;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_name() {
+        assert_eq!(docker_name("orders/api"), "orders_api");
+        assert_eq!(docker_name("Api"), "api");
+    }
+
+    #[test]
+    fn quotes_env_values() {
+        let env = HashMap::from([(String::from("GREETING"), String::from("it's 'here'"))]);
+        assert_eq!(env_opts(&env), r#" -e 'GREETING=it'\''s '\''here'\'''"#);
+    }
+}
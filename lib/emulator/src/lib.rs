@@ -1,5 +1,6 @@
 mod function;
 mod state;
+mod topology;
 
 use compiler::{
     BuildKind,
@@ -8,8 +9,23 @@ use compiler::{
 use composer::Topology;
 use kit as u;
 use provider::aws::Auth;
+pub use state::{
+    Failure,
+    Stubs,
+    Tasks,
+    execute,
+};
+pub use topology::{
+    Emulator,
+    Session,
+};
 
-pub async fn emulate(auth: &Auth, topology: &Topology, entity_component: &str, shell: bool) {
+pub async fn emulate(
+    auth: &Auth,
+    topology: &Topology,
+    entity_component: &str,
+    shell: bool,
+) -> Result<(), String> {
     let (entity, component) = Entity::as_entity_component(entity_component);
     match entity {
         Entity::Function => {
@@ -24,15 +40,24 @@ pub async fn emulate(auth: &Auth, topology: &Topology, entity_component: &str, s
                     }
                 }
             };
-            if let Some(func) = maybe_function {
-                let kind = &func.build.kind;
-                match kind {
-                    BuildKind::Image => function::image::run(auth, &dir, &func, shell).await,
+            match maybe_function {
+                Some(func) => match func.build.kind {
+                    BuildKind::Image => {
+                        function::image::run(auth, &dir, &func, shell).await;
+                        Ok(())
+                    }
                     _ => function::default::run(auth, &dir, &func).await,
-                }
+                },
+                None => Err(format!("No function found to emulate in {}", &dir)),
             }
         }
-        Entity::State => state::run(&auth).await,
-        _ => todo!(),
+        Entity::State => topology::run(auth, topology).await,
+        _ => Err(format!("Cannot emulate {}", entity_component)),
     }
 }
+
+/// Emulates all functions, routes, events, queues and channels of the
+/// topology together
+pub async fn emulate_topology(auth: &Auth, topology: &Topology) -> Result<(), String> {
+    topology::run(auth, topology).await
+}
@@ -0,0 +1,184 @@
+use super::{
+    Failure,
+    path,
+};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        // timestamps are RFC3339 and compare lexically
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+fn type_matches(op: &str, v: &Value) -> bool {
+    match op {
+        "String" => v.is_string(),
+        "Numeric" => v.is_number(),
+        "Boolean" => v.is_boolean(),
+        "Timestamp" => v.as_str().map(|s| s.contains('T')).unwrap_or(false),
+        _ => false,
+    }
+}
+
+// glob matching with `*`, as used by StringMatches
+fn glob(pattern: &str, s: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == s;
+    }
+    let mut rest = s;
+    for (i, part) in parts.iter().enumerate() {
+        if i == 0 {
+            match rest.strip_prefix(part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                Some(idx) => rest = &rest[idx + part.len()..],
+                None => return false,
+            }
+        }
+    }
+    true
+}
+
+fn apply(op: &str, v: &Value, expected: &Value) -> bool {
+    let kind = ["String", "Numeric", "Boolean", "Timestamp"]
+        .into_iter()
+        .find(|k| op.starts_with(k));
+    let kind = match kind {
+        Some(k) => k,
+        None => return false,
+    };
+    if !type_matches(kind, v) {
+        return false;
+    }
+    match &op[kind.len()..] {
+        "Equals" => match compare(v, expected) {
+            Some(o) => o == Ordering::Equal,
+            None => v == expected,
+        },
+        "LessThan" => compare(v, expected) == Some(Ordering::Less),
+        "GreaterThan" => compare(v, expected) == Some(Ordering::Greater),
+        "LessThanEquals" => matches!(compare(v, expected), Some(Ordering::Less | Ordering::Equal)),
+        "GreaterThanEquals" => {
+            matches!(
+                compare(v, expected),
+                Some(Ordering::Greater | Ordering::Equal)
+            )
+        }
+        "Matches" => match (v.as_str(), expected.as_str()) {
+            (Some(s), Some(p)) => glob(p, s),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Evaluates a choice rule against the state input
+pub fn eval(rule: &Value, input: &Value, context: &Value) -> Result<bool, Failure> {
+    if let Some(Value::Array(rules)) = rule.get("And") {
+        for r in rules {
+            if !eval(r, input, context)? {
+                return Ok(false);
+            }
+        }
+        return Ok(true);
+    }
+    if let Some(Value::Array(rules)) = rule.get("Or") {
+        for r in rules {
+            if eval(r, input, context)? {
+                return Ok(true);
+            }
+        }
+        return Ok(false);
+    }
+    if let Some(r) = rule.get("Not") {
+        return Ok(!eval(r, input, context)?);
+    }
+
+    let variable = rule
+        .get("Variable")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| Failure::runtime("Choice rule has no Variable"))?;
+    let value = path::read(variable, input, context).ok();
+
+    if let Some(Value::Bool(expected)) = rule.get("IsPresent") {
+        return Ok(value.is_some() == *expected);
+    }
+    let value = match value {
+        Some(v) => v,
+        None => {
+            return Err(Failure::runtime(&format!(
+                "Invalid path '{}': The choice state's condition path references an invalid value",
+                variable
+            )));
+        }
+    };
+
+    let checks = [
+        ("IsNull", value.is_null()),
+        ("IsString", value.is_string()),
+        ("IsNumeric", value.is_number()),
+        ("IsBoolean", value.is_boolean()),
+        ("IsTimestamp", type_matches("Timestamp", &value)),
+    ];
+    for (op, actual) in checks {
+        if let Some(Value::Bool(expected)) = rule.get(op) {
+            return Ok(actual == *expected);
+        }
+    }
+
+    let obj = rule.as_object().unwrap();
+    for (k, expected) in obj {
+        if k == "Variable" || k == "Next" {
+            continue;
+        }
+        return match k.strip_suffix("Path") {
+            Some(op) if op != "Variable" => {
+                let p = expected.as_str().unwrap_or_default();
+                let other = path::read(p, input, context)?;
+                Ok(apply(op, &value, &other))
+            }
+            _ => Ok(apply(k, &value, expected)),
+        };
+    }
+    Err(Failure::runtime("Choice rule has no comparison"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn evaluates_comparisons() {
+        let input = json!({"type": "alpha", "n": 5, "limit": 10, "ok": true});
+        let ctx = json!({});
+        let rule = |r: Value| eval(&r, &input, &ctx).unwrap();
+        assert!(rule(json!({"Variable": "$.type", "StringEquals": "alpha"})));
+        assert!(!rule(json!({"Variable": "$.type", "StringEquals": "beta"})));
+        assert!(rule(json!({"Variable": "$.type", "StringMatches": "al*"})));
+        assert!(rule(
+            json!({"Variable": "$.n", "NumericLessThanPath": "$.limit"})
+        ));
+        assert!(rule(
+            json!({"Variable": "$.n", "NumericGreaterThanEquals": 5})
+        ));
+        assert!(rule(json!({"Variable": "$.ok", "BooleanEquals": true})));
+        assert!(rule(json!({"Variable": "$.missing", "IsPresent": false})));
+        assert!(rule(json!({
+            "And": [
+                {"Variable": "$.n", "NumericEquals": 5},
+                {"Not": {"Variable": "$.type", "StringEquals": "beta"}}
+            ]
+        })));
+    }
+}
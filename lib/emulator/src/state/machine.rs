@@ -0,0 +1,476 @@
+use super::{
+    Failure,
+    Tasks,
+    choice,
+    path,
+};
+use kit::*;
+use serde_json::{
+    Map,
+    Value,
+    json,
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    time::Duration,
+};
+
+const MAX_TRANSITIONS: usize = 10000;
+
+type Outcome<'a> = Pin<Box<dyn Future<Output = Result<Value, Failure>> + Send + 'a>>;
+
+fn str_field<'a>(state: &'a Value, key: &str) -> Option<&'a str> {
+    state.get(key).and_then(|v| v.as_str())
+}
+
+fn skip_waits() -> bool {
+    std::env::var("TC_SKIP_WAIT").is_ok()
+}
+
+// InputPath and OutputPath: absent is `$`, null is `{}`
+fn filter(state: &Value, key: &str, value: &Value, context: &Value) -> Result<Value, Failure> {
+    match state.get(key) {
+        None => Ok(value.clone()),
+        Some(Value::Null) => Ok(json!({})),
+        Some(Value::String(p)) => path::read(p, value, context),
+        Some(_) => Err(Failure::runtime(&format!("{} must be a path", key))),
+    }
+}
+
+fn apply_result_path(state: &Value, raw_input: &Value, result: Value) -> Result<Value, Failure> {
+    match state.get("ResultPath") {
+        None => Ok(result),
+        Some(Value::Null) => Ok(raw_input.clone()),
+        Some(Value::String(p)) => path::write(p, raw_input, result),
+        Some(_) => Err(Failure::runtime("ResultPath must be a path")),
+    }
+}
+
+fn error_matches(names: &Value, error: &str) -> bool {
+    match names {
+        Value::Array(xs) => xs.iter().any(|n| match n.as_str() {
+            Some("States.ALL") => true,
+            Some("States.TaskFailed") => error != "States.Timeout",
+            Some(n) => n == error,
+            None => false,
+        }),
+        _ => false,
+    }
+}
+
+fn with_state(context: &Value, name: &str) -> Value {
+    let mut ctx = context.clone();
+    ctx["State"] = json!({
+        "Name": name,
+        "EnteredTime": chrono::Utc::now().to_rfc3339(),
+        "RetryCount": 0
+    });
+    ctx
+}
+
+async fn sleep(seconds: f64) {
+    if !skip_waits() && seconds > 0.0 {
+        tokio::time::sleep(Duration::from_secs_f64(seconds)).await;
+    }
+}
+
+/// Runs a state machine (or a Map iterator / Parallel branch) to
+/// completion
+pub fn run<'a>(
+    machine: &'a Value,
+    input: Value,
+    context: Value,
+    tasks: &'a dyn Tasks,
+) -> Outcome<'a> {
+    Box::pin(async move {
+        let states = machine
+            .get("States")
+            .and_then(|s| s.as_object())
+            .ok_or_else(|| Failure::runtime("Definition has no States"))?;
+        let mut current = str_field(machine, "StartAt")
+            .ok_or_else(|| Failure::runtime("Definition has no StartAt"))?
+            .to_string();
+        let mut input = input;
+
+        for _ in 0..MAX_TRANSITIONS {
+            let state = states
+                .get(&current)
+                .ok_or_else(|| Failure::runtime(&format!("State {} not found", &current)))?;
+            let ctx = with_state(&context, &current);
+            tracing::debug!("state {} input {}", &current, &input);
+
+            let (output, next) = match step(state, &input, &ctx, tasks).await {
+                Ok(x) => x,
+                Err(failure) => match catch(state, &input, &failure)? {
+                    Some(x) => x,
+                    None => return Err(failure),
+                },
+            };
+            match next {
+                Some(n) => {
+                    current = n;
+                    input = output;
+                }
+                None => return Ok(output),
+            }
+        }
+        Err(Failure::runtime(
+            "Maximum number of state transitions exceeded",
+        ))
+    })
+}
+
+fn next_of(state: &Value) -> Option<String> {
+    match state.get("End") {
+        Some(Value::Bool(true)) => None,
+        _ => str_field(state, "Next").map(|s| s.to_string()),
+    }
+}
+
+fn catch(
+    state: &Value,
+    raw_input: &Value,
+    failure: &Failure,
+) -> Result<Option<(Value, Option<String>)>, Failure> {
+    let catchers = match state.get("Catch") {
+        Some(Value::Array(xs)) => xs,
+        _ => return Ok(None),
+    };
+    for catcher in catchers {
+        if error_matches(&catcher["ErrorEquals"], &failure.error) {
+            let error_output = json!({"Error": &failure.error, "Cause": &failure.cause});
+            let output = match catcher.get("ResultPath") {
+                None => error_output,
+                Some(Value::Null) => raw_input.clone(),
+                Some(Value::String(p)) => path::write(p, raw_input, error_output)?,
+                Some(_) => return Err(Failure::runtime("ResultPath must be a path")),
+            };
+            let next = str_field(catcher, "Next").map(|s| s.to_string());
+            return Ok(Some((output, next)));
+        }
+    }
+    Ok(None)
+}
+
+// runs the action with the state's Retry policy. Each attempt gets the
+// context with `$$.State.RetryCount` set to the retries made so far
+async fn retry<'a, F>(state: &Value, context: &Value, mut action: F) -> Result<Value, Failure>
+where
+    F: FnMut(Value) -> Outcome<'a>,
+{
+    let retriers: Vec<Value> = match state.get("Retry") {
+        Some(Value::Array(xs)) => xs.clone(),
+        _ => vec![],
+    };
+    let mut attempts: Vec<u64> = vec![0; retriers.len()];
+    let mut ctx = context.clone();
+    loop {
+        ctx["State"]["RetryCount"] = json!(attempts.iter().sum::<u64>());
+        let failure = match action(ctx.clone()).await {
+            Ok(v) => return Ok(v),
+            Err(f) => f,
+        };
+        let found = retriers
+            .iter()
+            .position(|r| error_matches(&r["ErrorEquals"], &failure.error));
+        let i = match found {
+            Some(i) => i,
+            None => return Err(failure),
+        };
+        let retrier = &retriers[i];
+        let max_attempts = retrier["MaxAttempts"].as_u64().unwrap_or(3);
+        if attempts[i] >= max_attempts {
+            return Err(failure);
+        }
+        let interval = retrier["IntervalSeconds"].as_f64().unwrap_or(1.0);
+        let rate = retrier["BackoffRate"].as_f64().unwrap_or(2.0);
+        let mut delay = interval * rate.powi(attempts[i] as i32);
+        if let Some(max) = retrier["MaxDelaySeconds"].as_f64() {
+            delay = delay.min(max);
+        }
+        attempts[i] += 1;
+        tracing::debug!("retrying {} (attempt {})", &failure.error, attempts[i]);
+        sleep(delay).await;
+    }
+}
+
+fn select_result(state: &Value, result: Value, context: &Value) -> Result<Value, Failure> {
+    match state.get("ResultSelector") {
+        Some(selector) => path::render(selector, &result, context),
+        None => Ok(result),
+    }
+}
+
+fn parameters(state: &Value, key: &str, input: &Value, context: &Value) -> Result<Value, Failure> {
+    match state.get(key) {
+        Some(p) => path::render(p, input, context),
+        None => Ok(input.clone()),
+    }
+}
+
+async fn task(
+    state: &Value,
+    input: &Value,
+    context: &Value,
+    tasks: &dyn Tasks,
+) -> Result<Value, Failure> {
+    let resource =
+        str_field(state, "Resource").ok_or_else(|| Failure::runtime("Task has no Resource"))?;
+    let timeout = state["TimeoutSeconds"].as_u64();
+    retry(state, context, |ctx| {
+        Box::pin(async move {
+            let effective = parameters(state, "Parameters", input, &ctx)?;
+            let call = super::call(resource, effective, tasks);
+            match timeout {
+                Some(secs) => match tokio::time::timeout(Duration::from_secs(secs), call).await {
+                    Ok(r) => r,
+                    Err(_) => Err(Failure::new("States.Timeout", "Task timed out")),
+                },
+                None => call.await,
+            }
+        })
+    })
+    .await
+    .and_then(|r| select_result(state, r, context))
+}
+
+async fn map(
+    state: &Value,
+    input: &Value,
+    context: &Value,
+    tasks: &dyn Tasks,
+) -> Result<Value, Failure> {
+    let items = match state.get("ItemsPath").and_then(|p| p.as_str()) {
+        Some(p) => path::read(p, input, context)?,
+        None => input.clone(),
+    };
+    let items = match items {
+        Value::Array(xs) => xs,
+        _ => return Err(Failure::runtime("Map ItemsPath must reference an array")),
+    };
+    let iterator = state
+        .get("ItemProcessor")
+        .or(state.get("Iterator"))
+        .ok_or_else(|| Failure::runtime("Map has no ItemProcessor"))?;
+    let selector = if state.get("ItemSelector").is_some() {
+        "ItemSelector"
+    } else {
+        "Parameters"
+    };
+
+    let tolerated_count = state["ToleratedFailureCount"].as_u64();
+    let tolerated_pct = state["ToleratedFailurePercentage"].as_f64();
+    let total = items.len();
+    let mut failures = 0;
+    let mut results: Vec<Value> = vec![];
+
+    for (index, item) in items.into_iter().enumerate() {
+        let mut ctx = context.clone();
+        ctx["Map"] = json!({"Item": {"Index": index, "Value": &item}});
+        let item_input = match state.get(selector) {
+            Some(p) => path::render(p, input, &ctx)?,
+            None => item,
+        };
+        match run(iterator, item_input, context.clone(), tasks).await {
+            Ok(v) => results.push(v),
+            Err(f) => {
+                if tolerated_count.is_none() && tolerated_pct.is_none() {
+                    return Err(f);
+                }
+                failures += 1;
+                results.push(json!({"Error": &f.error, "Cause": &f.cause}));
+            }
+        }
+    }
+
+    let exceeded_count = tolerated_count
+        .map(|c| failures as u64 > c)
+        .unwrap_or(false);
+    let exceeded_pct = tolerated_pct
+        .map(|p| total > 0 && (failures as f64 * 100.0 / total as f64) > p)
+        .unwrap_or(false);
+    if exceeded_count || exceeded_pct {
+        return Err(Failure::new(
+            "States.ExceedToleratedFailureThreshold",
+            &format!("{} of {} items failed", failures, total),
+        ));
+    }
+    Ok(Value::Array(results))
+}
+
+async fn parallel(
+    state: &Value,
+    input: &Value,
+    context: &Value,
+    tasks: &dyn Tasks,
+) -> Result<Value, Failure> {
+    let branches = match state.get("Branches") {
+        Some(Value::Array(xs)) => xs,
+        _ => return Err(Failure::runtime("Parallel has no Branches")),
+    };
+    let mut results: Vec<Value> = vec![];
+    for branch in branches {
+        results.push(run(branch, input.clone(), context.clone(), tasks).await?);
+    }
+    Ok(Value::Array(results))
+}
+
+async fn wait(state: &Value, input: &Value, context: &Value) -> Result<(), Failure> {
+    let seconds = if let Some(s) = state["Seconds"].as_f64() {
+        s
+    } else if let Some(p) = str_field(state, "SecondsPath") {
+        path::read(p, input, context)?
+            .as_f64()
+            .ok_or_else(|| Failure::runtime("SecondsPath must reference a number"))?
+    } else {
+        let ts = match str_field(state, "TimestampPath") {
+            Some(p) => path::read(p, input, context)?
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            None => str_field(state, "Timestamp")
+                .unwrap_or_default()
+                .to_string(),
+        };
+        match chrono::DateTime::parse_from_rfc3339(&ts) {
+            Ok(t) => (t.timestamp_millis() - chrono::Utc::now().timestamp_millis()) as f64 / 1000.0,
+            Err(_) => return Err(Failure::runtime(&format!("Invalid timestamp {}", ts))),
+        }
+    };
+    sleep(seconds).await;
+    Ok(())
+}
+
+// executes one state and returns its output and the next state
+async fn step(
+    state: &Value,
+    raw_input: &Value,
+    context: &Value,
+    tasks: &dyn Tasks,
+) -> Result<(Value, Option<String>), Failure> {
+    let kind = str_field(state, "Type").unwrap_or_default();
+    let input = filter(state, "InputPath", raw_input, context)?;
+
+    match kind {
+        "Pass" => {
+            let effective = parameters(state, "Parameters", &input, context)?;
+            let result = match state.get("Result") {
+                Some(r) => r.clone(),
+                None => effective,
+            };
+            let output = apply_result_path(state, raw_input, result)?;
+            Ok((
+                filter(state, "OutputPath", &output, context)?,
+                next_of(state),
+            ))
+        }
+        "Task" => {
+            let result = task(state, &input, context, tasks).await?;
+            let output = apply_result_path(state, raw_input, result)?;
+            Ok((
+                filter(state, "OutputPath", &output, context)?,
+                next_of(state),
+            ))
+        }
+        "Map" => {
+            let input = &input;
+            let result = retry(state, context, |ctx| {
+                Box::pin(async move { map(state, input, &ctx, tasks).await })
+            })
+            .await?;
+            let result = select_result(state, result, context)?;
+            let output = apply_result_path(state, raw_input, result)?;
+            Ok((
+                filter(state, "OutputPath", &output, context)?,
+                next_of(state),
+            ))
+        }
+        "Parallel" => {
+            let input = &input;
+            let result = retry(state, context, |ctx| {
+                Box::pin(async move {
+                    let effective = parameters(state, "Parameters", input, &ctx)?;
+                    parallel(state, &effective, &ctx, tasks).await
+                })
+            })
+            .await?;
+            let result = select_result(state, result, context)?;
+            let output = apply_result_path(state, raw_input, result)?;
+            Ok((
+                filter(state, "OutputPath", &output, context)?,
+                next_of(state),
+            ))
+        }
+        "Choice" => {
+            let choices = match state.get("Choices") {
+                Some(Value::Array(xs)) => xs,
+                _ => return Err(Failure::runtime("Choice has no Choices")),
+            };
+            let mut next: Option<String> = None;
+            for rule in choices {
+                if choice::eval(rule, &input, context)? {
+                    next = str_field(rule, "Next").map(|s| s.to_string());
+                    break;
+                }
+            }
+            let next = match next.or(str_field(state, "Default").map(|s| s.to_string())) {
+                Some(n) => n,
+                None => {
+                    return Err(Failure::new("States.NoChoiceMatched", "No Matches!"));
+                }
+            };
+            Ok((filter(state, "OutputPath", &input, context)?, Some(next)))
+        }
+        "Wait" => {
+            wait(state, &input, context).await?;
+            Ok((
+                filter(state, "OutputPath", &input, context)?,
+                next_of(state),
+            ))
+        }
+        "Succeed" => Ok((filter(state, "OutputPath", &input, context)?, None)),
+        "Fail" => {
+            let error = match str_field(state, "ErrorPath") {
+                Some(p) => path::read(p, &input, context)?
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                None => str_field(state, "Error")
+                    .unwrap_or("States.Fail")
+                    .to_string(),
+            };
+            let cause = match str_field(state, "CausePath") {
+                Some(p) => path::read(p, &input, context)?
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                None => str_field(state, "Cause").unwrap_or_default().to_string(),
+            };
+            Err(Failure::new(&error, &cause))
+        }
+        _ => Err(Failure::runtime(&format!(
+            "Unsupported state type {}",
+            kind
+        ))),
+    }
+}
+
+pub fn make_context(name: &str, input: &Value) -> Value {
+    let id = kit::uuid_str();
+    let mut execution = Map::new();
+    execution.insert(
+        s!("Id"),
+        json!(format!(
+            "arn:aws:states:local:000000000000:execution:{}:{}",
+            name, &id
+        )),
+    );
+    execution.insert(s!("Name"), json!(id));
+    execution.insert(s!("Input"), input.clone());
+    execution.insert(s!("StartTime"), json!(chrono::Utc::now().to_rfc3339()));
+    json!({
+        "Execution": execution,
+        "StateMachine": {"Id": name, "Name": name}
+    })
+}
@@ -0,0 +1,396 @@
+mod choice;
+mod machine;
+mod path;
+
+use serde_json::{
+    Value,
+    json,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+};
+
+/// An ASL error, as seen by Retry and Catch
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub error: String,
+    pub cause: String,
+}
+
+impl Failure {
+    pub fn new(error: &str, cause: &str) -> Failure {
+        Failure {
+            error: error.to_string(),
+            cause: cause.to_string(),
+        }
+    }
+
+    pub fn runtime(cause: &str) -> Failure {
+        Failure::new("States.Runtime", cause)
+    }
+}
+
+pub type Resolved<'a> = Pin<Box<dyn Future<Output = Result<Value, Failure>> + Send + 'a>>;
+
+/// Executes the Task states of a state machine. The topology emulator
+/// invokes local functions, `Stubs` answers with canned responses
+pub trait Tasks: Sync {
+    fn invoke<'a>(&'a self, function: &'a str, payload: Value) -> Resolved<'a>;
+
+    fn start_execution<'a>(&'a self, machine: &'a str, _input: Value) -> Resolved<'a> {
+        Box::pin(async move {
+            Err(Failure::new(
+                "States.TaskFailed",
+                &format!("Nested execution {} is not emulated", machine),
+            ))
+        })
+    }
+
+    fn call<'a>(&'a self, resource: &'a str, _params: Value) -> Resolved<'a> {
+        Box::pin(async move {
+            Err(Failure::new(
+                "States.TaskFailed",
+                &format!("Resource {} is not emulated", resource),
+            ))
+        })
+    }
+}
+
+/// Canned task responses keyed by function name, arn or resource.
+/// A response with an `Error` key fails the task with that error
+pub struct Stubs<'a> {
+    pub responses: HashMap<String, Value>,
+    pub fallback: Option<&'a dyn Tasks>,
+}
+
+impl<'a> Stubs<'a> {
+    pub fn new(responses: HashMap<String, Value>) -> Stubs<'a> {
+        Stubs {
+            responses: responses,
+            fallback: None,
+        }
+    }
+
+    pub fn read(path: &str) -> Stubs<'a> {
+        let data = kit::slurp(path);
+        let responses: HashMap<String, Value> =
+            serde_json::from_str(&data).unwrap_or_else(|_| panic!("Invalid stubs file {}", path));
+        Stubs::new(responses)
+    }
+
+    // keys match the full name, the last segment of an arn or a `_`
+    // separated part of a namespaced or sandboxed name
+    fn find(&self, name: &str) -> Option<&Value> {
+        if let Some(v) = self.responses.get(name) {
+            return Some(v);
+        }
+        let short = name.rsplit(':').next().unwrap_or(name);
+        self.responses.iter().find_map(|(k, v)| {
+            let matched = short == k
+                || short.starts_with(&format!("{}_", k))
+                || short.ends_with(&format!("_{}", k))
+                || short.contains(&format!("_{}_", k));
+            if matched { Some(v) } else { None }
+        })
+    }
+
+    /// The stub response for a task, if any
+    pub fn respond(&self, name: &str) -> Option<Result<Value, Failure>> {
+        self.find(name).map(|v| match v.get("Error") {
+            Some(e) => Err(Failure::new(
+                e.as_str().unwrap_or("States.TaskFailed"),
+                v["Cause"].as_str().unwrap_or_default(),
+            )),
+            None => Ok(v.clone()),
+        })
+    }
+}
+
+fn unstubbed(name: &str) -> Failure {
+    Failure::new(
+        "States.TaskFailed",
+        &format!("No stub response for {}", name),
+    )
+}
+
+impl Tasks for Stubs<'_> {
+    fn invoke<'a>(&'a self, function: &'a str, payload: Value) -> Resolved<'a> {
+        Box::pin(async move {
+            match (self.respond(function), self.fallback) {
+                (Some(r), _) => r,
+                (None, Some(f)) => f.invoke(function, payload).await,
+                (None, None) => Err(unstubbed(function)),
+            }
+        })
+    }
+
+    fn start_execution<'a>(&'a self, machine: &'a str, input: Value) -> Resolved<'a> {
+        Box::pin(async move {
+            match (self.respond(machine), self.fallback) {
+                (Some(r), _) => r,
+                (None, Some(f)) => f.start_execution(machine, input).await,
+                (None, None) => Err(unstubbed(machine)),
+            }
+        })
+    }
+
+    fn call<'a>(&'a self, resource: &'a str, params: Value) -> Resolved<'a> {
+        Box::pin(async move {
+            match (self.respond(resource), self.fallback) {
+                (Some(r), _) => r,
+                (None, Some(f)) => f.call(resource, params).await,
+                (None, None) => Err(unstubbed(resource)),
+            }
+        })
+    }
+}
+
+// dispatches a Task resource arn to the task executor
+fn call<'a>(resource: &'a str, params: Value, tasks: &'a dyn Tasks) -> Resolved<'a> {
+    Box::pin(async move {
+        let service = resource
+            .strip_prefix("arn:aws:states:::")
+            .unwrap_or_default();
+        if service.starts_with("lambda:invoke") {
+            let function = params["FunctionName"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let payload = params.get("Payload").cloned().unwrap_or(json!({}));
+            let output = tasks.invoke(&function, payload).await?;
+            Ok(json!({"Payload": output, "StatusCode": 200}))
+        } else if service.starts_with("states:startExecution") {
+            let machine = params["StateMachineArn"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let input = match params.get("Input") {
+                Some(Value::String(s)) => serde_json::from_str(s).unwrap_or(json!(s)),
+                Some(v) => v.clone(),
+                None => json!({}),
+            };
+            let output = tasks.start_execution(&machine, input).await?;
+            if service.ends_with(".sync:2") {
+                Ok(json!({"Output": output, "Status": "SUCCEEDED"}))
+            } else if service.ends_with(".sync") {
+                Ok(json!({"Output": output.to_string(), "Status": "SUCCEEDED"}))
+            } else {
+                Ok(json!({"ExecutionArn": format!("{}:{}", &machine, kit::uuid_str())}))
+            }
+        } else if resource.contains(":lambda:") && resource.contains(":function:") {
+            tasks.invoke(resource, params).await
+        } else {
+            tasks.call(resource, params).await
+        }
+    })
+}
+
+/// Executes a state machine definition to completion and returns its
+/// output or the error it failed with
+pub async fn execute(
+    name: &str,
+    definition: &Value,
+    input: Value,
+    tasks: &dyn Tasks,
+) -> Result<Value, Failure> {
+    let context = machine::make_context(name, &input);
+    machine::run(definition, input, context, tasks).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stubs(responses: Value) -> Stubs<'static> {
+        Stubs::new(serde_json::from_value(responses).unwrap())
+    }
+
+    async fn run(definition: Value, input: Value, tasks: &Stubs<'_>) -> Result<Value, Failure> {
+        execute("test", &definition, input, tasks).await
+    }
+
+    #[tokio::test]
+    async fn runs_tasks_with_paths() {
+        let definition = json!({
+            "StartAt": "Fetch",
+            "States": {
+                "Fetch": {
+                    "Type": "Task",
+                    "Resource": "arn:aws:states:::lambda:invoke",
+                    "Parameters": {"FunctionName": "fetch", "Payload.$": "$.order"},
+                    "ResultSelector": {"items.$": "$.Payload.items"},
+                    "ResultPath": "$.fetched",
+                    "Next": "Done"
+                },
+                "Done": {"Type": "Pass", "OutputPath": "$.fetched", "End": true}
+            }
+        });
+        let tasks = stubs(json!({"fetch": {"items": [1, 2]}}));
+        let output = run(definition, json!({"order": {"id": 1}}), &tasks)
+            .await
+            .unwrap();
+        assert_eq!(output, json!({"items": [1, 2]}));
+    }
+
+    #[tokio::test]
+    async fn branches_on_choices() {
+        let definition = json!({
+            "StartAt": "Check",
+            "States": {
+                "Check": {
+                    "Type": "Choice",
+                    "Choices": [{"Variable": "$.n", "NumericGreaterThan": 10, "Next": "Big"}],
+                    "Default": "Small"
+                },
+                "Big": {"Type": "Pass", "Result": "big", "End": true},
+                "Small": {"Type": "Pass", "Result": "small", "End": true}
+            }
+        });
+        let tasks = stubs(json!({}));
+        assert_eq!(
+            run(definition.clone(), json!({"n": 11}), &tasks)
+                .await
+                .unwrap(),
+            json!("big")
+        );
+        assert_eq!(
+            run(definition, json!({"n": 1}), &tasks).await.unwrap(),
+            json!("small")
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_items_and_runs_branches() {
+        let definition = json!({
+            "StartAt": "Each",
+            "States": {
+                "Each": {
+                    "Type": "Map",
+                    "ItemsPath": "$.xs",
+                    "ItemSelector": {"x.$": "$$.Map.Item.Value", "i.$": "$$.Map.Item.Index"},
+                    "ItemProcessor": {
+                        "StartAt": "Id",
+                        "States": {"Id": {"Type": "Pass", "OutputPath": "$.i", "End": true}}
+                    },
+                    "ResultPath": "$.indices",
+                    "Next": "Both"
+                },
+                "Both": {
+                    "Type": "Parallel",
+                    "Branches": [
+                        {"StartAt": "A", "States": {"A": {"Type": "Pass", "Result": 1, "End": true}}},
+                        {"StartAt": "B", "States": {"B": {"Type": "Wait", "Seconds": 0, "End": true}}}
+                    ],
+                    "ResultPath": "$.branches",
+                    "End": true
+                }
+            }
+        });
+        let tasks = stubs(json!({}));
+        let output = run(definition, json!({"xs": ["a", "b"]}), &tasks)
+            .await
+            .unwrap();
+        assert_eq!(output["indices"], json!([0, 1]));
+        assert_eq!(output["branches"][0], json!(1));
+    }
+
+    #[tokio::test]
+    async fn catches_failures_retry_does_not_match() {
+        let definition = json!({
+            "StartAt": "Charge",
+            "States": {
+                "Charge": {
+                    "Type": "Task",
+                    "Resource": "arn:aws:lambda:us-east-1:123456789012:function:charge_dev",
+                    "Retry": [{"ErrorEquals": ["Throttled"], "MaxAttempts": 2}],
+                    "Catch": [{"ErrorEquals": ["States.ALL"], "ResultPath": "$.error", "Next": "Refund"}],
+                    "End": true
+                },
+                "Refund": {"Type": "Fail", "Error": "Refunded", "CausePath": "$.error.Cause"}
+            }
+        });
+        let tasks =
+            stubs(json!({"charge": {"Error": "CardDeclined", "Cause": "insufficient funds"}}));
+        let failure = run(definition, json!({}), &tasks).await.unwrap_err();
+        assert_eq!(failure, Failure::new("Refunded", "insufficient funds"));
+    }
+
+    // fails with Throttled until the given attempt and records the
+    // payload of each invocation
+    struct Flaky {
+        succeeds_at: usize,
+        payloads: std::sync::Mutex<Vec<Value>>,
+    }
+
+    impl Tasks for Flaky {
+        fn invoke<'a>(&'a self, _function: &'a str, payload: Value) -> Resolved<'a> {
+            Box::pin(async move {
+                let mut payloads = self.payloads.lock().unwrap();
+                payloads.push(payload);
+                if payloads.len() < self.succeeds_at {
+                    Err(Failure::new("Throttled", "rate exceeded"))
+                } else {
+                    Ok(json!("charged"))
+                }
+            })
+        }
+    }
+
+    fn charge(max_attempts: u64) -> Value {
+        json!({
+            "StartAt": "Charge",
+            "States": {
+                "Charge": {
+                    "Type": "Task",
+                    "Resource": "arn:aws:states:::lambda:invoke",
+                    "Parameters": {
+                        "FunctionName": "charge",
+                        "Payload": {"attempt.$": "$$.State.RetryCount"}
+                    },
+                    "Retry": [{
+                        "ErrorEquals": ["Throttled"],
+                        "IntervalSeconds": 0,
+                        "MaxAttempts": max_attempts
+                    }],
+                    "Catch": [{"ErrorEquals": ["States.ALL"], "ResultPath": "$.error", "Next": "GiveUp"}],
+                    "OutputPath": "$.Payload",
+                    "End": true
+                },
+                "GiveUp": {"Type": "Fail", "Error": "GaveUp", "CausePath": "$.error.Cause"}
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn retries_matching_failures() {
+        let tasks = Flaky {
+            succeeds_at: 3,
+            payloads: std::sync::Mutex::new(vec![]),
+        };
+        let output = execute("test", &charge(2), json!({}), &tasks)
+            .await
+            .unwrap();
+        assert_eq!(output, json!("charged"));
+        let payloads = tasks.payloads.lock().unwrap().clone();
+        assert_eq!(
+            payloads,
+            vec![
+                json!({"attempt": 0}),
+                json!({"attempt": 1}),
+                json!({"attempt": 2})
+            ]
+        );
+
+        let tasks = Flaky {
+            succeeds_at: 4,
+            payloads: std::sync::Mutex::new(vec![]),
+        };
+        let failure = execute("test", &charge(2), json!({}), &tasks)
+            .await
+            .unwrap_err();
+        assert_eq!(failure, Failure::new("GaveUp", "rate exceeded"));
+        assert_eq!(tasks.payloads.lock().unwrap().len(), 3);
+    }
+}
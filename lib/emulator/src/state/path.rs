@@ -0,0 +1,344 @@
+use super::Failure;
+use serde_json::{
+    Map,
+    Value,
+    json,
+};
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    All,
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>, Failure> {
+    let invalid = || Failure::runtime(&format!("Invalid path {}", path));
+    let rest = path.strip_prefix('$').ok_or_else(invalid)?;
+    let chars: Vec<char> = rest.chars().collect();
+    let mut segments: Vec<Segment> = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                let start = i + 1;
+                let mut j = start;
+                while j < chars.len() && chars[j] != '.' && chars[j] != '[' {
+                    j += 1;
+                }
+                let key: String = chars[start..j].iter().collect();
+                if key.is_empty() {
+                    return Err(invalid());
+                }
+                if key == "*" {
+                    segments.push(Segment::All);
+                } else {
+                    segments.push(Segment::Key(key));
+                }
+                i = j;
+            }
+            '[' => {
+                let end = (i..chars.len())
+                    .find(|k| chars[*k] == ']')
+                    .ok_or_else(invalid)?;
+                let inner: String = chars[i + 1..end].iter().collect();
+                let inner = inner.trim();
+                if inner == "*" {
+                    segments.push(Segment::All);
+                } else if let Ok(n) = inner.parse::<usize>() {
+                    segments.push(Segment::Index(n));
+                } else {
+                    let key = inner.trim_matches(|c| c == '\'' || c == '"');
+                    segments.push(Segment::Key(key.to_string()));
+                }
+                i = end + 1;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(segments)
+}
+
+fn select(value: &Value, segments: &[Segment]) -> Option<Value> {
+    match segments.split_first() {
+        None => Some(value.clone()),
+        Some((Segment::Key(k), rest)) => select(value.get(k)?, rest),
+        Some((Segment::Index(n), rest)) => select(value.get(*n)?, rest),
+        Some((Segment::All, rest)) => {
+            let items: Vec<&Value> = match value {
+                Value::Array(xs) => xs.iter().collect(),
+                Value::Object(m) => m.values().collect(),
+                _ => return None,
+            };
+            Some(Value::Array(
+                items.into_iter().filter_map(|v| select(v, rest)).collect(),
+            ))
+        }
+    }
+}
+
+/// Reads a reference path. Paths starting with `$$` read the context
+/// object
+pub fn read(path: &str, input: &Value, context: &Value) -> Result<Value, Failure> {
+    let (root, path) = match path.strip_prefix("$$") {
+        Some(p) => (context, format!("${}", p)),
+        None => (input, path.to_string()),
+    };
+    let segments = parse(&path)?;
+    select(root, &segments).ok_or_else(|| {
+        Failure::runtime(&format!(
+            "Invalid path '{}': the path references an invalid value",
+            &path
+        ))
+    })
+}
+
+/// Returns a copy of the input with the value set at the path,
+/// creating intermediate objects as needed
+pub fn write(path: &str, input: &Value, value: Value) -> Result<Value, Failure> {
+    let segments = parse(path)?;
+    let mut root = input.clone();
+    let mut cursor = &mut root;
+    for segment in &segments {
+        cursor = match segment {
+            Segment::Key(k) => {
+                if !cursor.is_object() {
+                    *cursor = Value::Object(Map::new());
+                }
+                cursor
+                    .as_object_mut()
+                    .unwrap()
+                    .entry(k.to_string())
+                    .or_insert(Value::Null)
+            }
+            Segment::Index(n) => match cursor.get_mut(*n) {
+                Some(v) => v,
+                None => {
+                    return Err(Failure::runtime(&format!(
+                        "Unable to apply ResultPath {}",
+                        path
+                    )));
+                }
+            },
+            Segment::All => {
+                return Err(Failure::runtime(&format!(
+                    "ResultPath {} must be a reference path",
+                    path
+                )));
+            }
+        };
+    }
+    *cursor = value;
+    Ok(root)
+}
+
+// splits intrinsic function arguments on top-level commas
+fn split_args(s: &str) -> Vec<String> {
+    let mut args: Vec<String> = vec![];
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut current = kit::empty();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => {
+                current.push(c);
+                if let Some(n) = chars.next() {
+                    current.push(n);
+                }
+            }
+            '\'' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            '(' if !quoted => {
+                depth += 1;
+                current.push(c);
+            }
+            ')' if !quoted => {
+                depth -= 1;
+                current.push(c);
+            }
+            ',' if !quoted && depth == 0 => {
+                args.push(current.trim().to_string());
+                current = kit::empty();
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        args.push(current.trim().to_string());
+    }
+    args
+}
+
+fn unquote(s: &str) -> String {
+    let inner = &s[1..s.len() - 1];
+    inner
+        .replace("\\'", "'")
+        .replace("\\{", "{")
+        .replace("\\}", "}")
+        .replace("\\\\", "\\")
+}
+
+fn eval_arg(arg: &str, input: &Value, context: &Value) -> Result<Value, Failure> {
+    if arg.starts_with('\'') && arg.ends_with('\'') && arg.len() >= 2 {
+        Ok(Value::String(unquote(arg)))
+    } else if arg.starts_with('$') {
+        read(arg, input, context)
+    } else if arg.starts_with("States.") {
+        intrinsic(arg, input, context)
+    } else {
+        serde_json::from_str(arg)
+            .map_err(|_| Failure::runtime(&format!("Invalid intrinsic argument {}", arg)))
+    }
+}
+
+fn as_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.to_string(),
+        v => v.to_string(),
+    }
+}
+
+/// Evaluates intrinsic functions like `States.Format('{}', $.name)`
+pub fn intrinsic(expr: &str, input: &Value, context: &Value) -> Result<Value, Failure> {
+    let invalid = || Failure::new("States.IntrinsicFailure", &format!("Invalid {}", expr));
+    let open = expr.find('(').ok_or_else(invalid)?;
+    if !expr.ends_with(')') {
+        return Err(invalid());
+    }
+    let name = &expr[..open];
+    let args = split_args(&expr[open + 1..expr.len() - 1])
+        .iter()
+        .map(|a| eval_arg(a, input, context))
+        .collect::<Result<Vec<Value>, Failure>>()?;
+
+    match name {
+        "States.Format" => {
+            let template = args.first().and_then(|t| t.as_str()).ok_or_else(invalid)?;
+            let mut out = kit::empty();
+            let mut rest = args.iter().skip(1);
+            let mut parts = template.split("{}").peekable();
+            while let Some(part) = parts.next() {
+                out.push_str(part);
+                if parts.peek().is_some() {
+                    out.push_str(&as_text(rest.next().ok_or_else(invalid)?));
+                }
+            }
+            Ok(Value::String(out))
+        }
+        "States.StringToJson" => {
+            let s = args.first().and_then(|s| s.as_str()).ok_or_else(invalid)?;
+            serde_json::from_str(s).map_err(|_| invalid())
+        }
+        "States.JsonToString" => Ok(Value::String(args.first().ok_or_else(invalid)?.to_string())),
+        "States.Array" => Ok(Value::Array(args)),
+        "States.ArrayLength" => match args.first() {
+            Some(Value::Array(xs)) => Ok(json!(xs.len())),
+            _ => Err(invalid()),
+        },
+        "States.ArrayGetItem" => match (args.first(), args.get(1).and_then(|n| n.as_u64())) {
+            (Some(Value::Array(xs)), Some(n)) => xs.get(n as usize).cloned().ok_or_else(invalid),
+            _ => Err(invalid()),
+        },
+        "States.ArrayContains" => match (args.first(), args.get(1)) {
+            (Some(Value::Array(xs)), Some(v)) => Ok(Value::Bool(xs.contains(v))),
+            _ => Err(invalid()),
+        },
+        "States.MathAdd" => match (
+            args.first().and_then(|n| n.as_i64()),
+            args.get(1).and_then(|n| n.as_i64()),
+        ) {
+            (Some(a), Some(b)) => Ok(json!(a + b)),
+            _ => Err(invalid()),
+        },
+        "States.UUID" => Ok(Value::String(kit::uuid_str())),
+        _ => Err(Failure::new(
+            "States.IntrinsicFailure",
+            &format!("{} is not supported", name),
+        )),
+    }
+}
+
+/// Builds the payload of `Parameters`, `ItemSelector` and
+/// `ResultSelector`, where keys ending in `.$` are paths or intrinsics
+pub fn render(template: &Value, input: &Value, context: &Value) -> Result<Value, Failure> {
+    match template {
+        Value::Object(m) => {
+            let mut out = Map::new();
+            for (k, v) in m {
+                match (k.strip_suffix(".$"), v) {
+                    (Some(key), Value::String(expr)) => {
+                        let value = if expr.starts_with("States.") {
+                            intrinsic(expr, input, context)?
+                        } else {
+                            read(expr, input, context)?
+                        };
+                        out.insert(key.to_string(), value);
+                    }
+                    _ => {
+                        out.insert(k.to_string(), render(v, input, context)?);
+                    }
+                }
+            }
+            Ok(Value::Object(out))
+        }
+        Value::Array(xs) => Ok(Value::Array(
+            xs.iter()
+                .map(|x| render(x, input, context))
+                .collect::<Result<Vec<Value>, Failure>>()?,
+        )),
+        v => Ok(v.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_paths() {
+        let input = json!({"a": {"b": [1, {"c": "x"}]}, "list": [{"id": 1}, {"id": 2}]});
+        let ctx = json!({"Execution": {"Id": "e1"}});
+        assert_eq!(read("$", &input, &ctx).unwrap(), input);
+        assert_eq!(read("$.a.b[1].c", &input, &ctx).unwrap(), json!("x"));
+        assert_eq!(read("$['a']['b'][0]", &input, &ctx).unwrap(), json!(1));
+        assert_eq!(read("$.list[*].id", &input, &ctx).unwrap(), json!([1, 2]));
+        assert_eq!(read("$$.Execution.Id", &input, &ctx).unwrap(), json!("e1"));
+        assert!(read("$.missing", &input, &ctx).is_err());
+    }
+
+    #[test]
+    fn writes_result_paths() {
+        let input = json!({"a": 1});
+        assert_eq!(
+            write("$.result.value", &input, json!(2)).unwrap(),
+            json!({"a": 1, "result": {"value": 2}})
+        );
+        assert_eq!(write("$", &input, json!(3)).unwrap(), json!(3));
+    }
+
+    #[test]
+    fn renders_parameters_and_intrinsics() {
+        let input = json!({"name": "tc", "items": [1, 2, 3], "raw": "{\"k\": 1}"});
+        let ctx = json!({});
+        let template = json!({
+            "static": "v",
+            "name.$": "$.name",
+            "greeting.$": "States.Format('hello {}, {} items', $.name, States.ArrayLength($.items))",
+            "parsed.$": "States.StringToJson($.raw)",
+            "nested": {"first.$": "States.ArrayGetItem($.items, 0)"}
+        });
+        assert_eq!(
+            render(&template, &input, &ctx).unwrap(),
+            json!({
+                "static": "v",
+                "name": "tc",
+                "greeting": "hello tc, 3 items",
+                "parsed": {"k": 1},
+                "nested": {"first": 1}
+            })
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use super::{
+    Emulator,
+    dispatch,
+};
+use colored::Colorize;
+use compiler::Entity;
+use composer::Target;
+use serde_json::{
+    Map,
+    Value,
+};
+use std::sync::Arc;
+
+fn match_number(rule: &[Value], n: f64) -> bool {
+    rule.chunks(2).all(|pair| match pair {
+        [Value::String(op), Value::Number(x)] => {
+            let x = x.as_f64().unwrap_or(0.0);
+            match op.as_ref() {
+                "=" => n == x,
+                "<" => n < x,
+                "<=" => n <= x,
+                ">" => n > x,
+                ">=" => n >= x,
+                _ => false,
+            }
+        }
+        _ => false,
+    })
+}
+
+// a single element of a pattern array against a single event value
+fn match_scalar(rule: &Value, value: &Value) -> bool {
+    match rule {
+        Value::Object(m) => {
+            let (op, arg) = match m.iter().next() {
+                Some(x) => x,
+                None => return false,
+            };
+            match (op.as_ref(), arg, value) {
+                ("prefix", Value::String(p), Value::String(s)) => s.starts_with(p.as_str()),
+                ("suffix", Value::String(p), Value::String(s)) => s.ends_with(p.as_str()),
+                ("equals-ignore-case", Value::String(p), Value::String(s)) => {
+                    s.to_lowercase() == p.to_lowercase()
+                }
+                ("anything-but", Value::Array(xs), v) => !xs.contains(v),
+                ("anything-but", x, v) => x != v,
+                ("numeric", Value::Array(xs), Value::Number(n)) => {
+                    match_number(xs, n.as_f64().unwrap_or(0.0))
+                }
+                ("exists", Value::Bool(b), _) => *b,
+                _ => false,
+            }
+        }
+        _ => rule == value,
+    }
+}
+
+fn match_field(rule: &Value, value: Option<&Value>) -> bool {
+    match (rule, value) {
+        (Value::Object(sub), Some(Value::Object(v))) => match_object(sub, v),
+        (Value::Object(_), _) => false,
+        (Value::Array(alternatives), None) => alternatives.iter().any(|alt| match alt {
+            Value::Object(m) => m.get("exists") == Some(&Value::Bool(false)),
+            _ => false,
+        }),
+        (Value::Array(alternatives), Some(Value::Array(vs))) => alternatives
+            .iter()
+            .any(|alt| vs.iter().any(|v| match_scalar(alt, v))),
+        (Value::Array(alternatives), Some(v)) => {
+            alternatives.iter().any(|alt| match_scalar(alt, v))
+        }
+        (r, v) => Some(r) == v,
+    }
+}
+
+fn match_object(pattern: &Map<String, Value>, event: &Map<String, Value>) -> bool {
+    pattern
+        .iter()
+        .all(|(k, rule)| match_field(rule, event.get(k)))
+}
+
+/// Matches an event against an EventBridge event pattern
+pub fn matches(pattern: &Value, event: &Value) -> bool {
+    match (pattern, event) {
+        (Value::Object(p), Value::Object(e)) => match_object(p, e),
+        _ => false,
+    }
+}
+
+fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim_start_matches("$").trim_start_matches(".");
+    if path.is_empty() {
+        return Some(value);
+    }
+    path.split(".").try_fold(value, |v, k| v.get(k))
+}
+
+// applies the target's input transformer, if any
+fn make_input(target: &Target, event: &Value) -> Value {
+    let paths = match &target.input_paths_map {
+        Some(p) => p,
+        None => return event.clone(),
+    };
+    match &target.input_template {
+        Some(template) => {
+            let mut s = template.clone();
+            for (k, path) in paths {
+                let v = json_path(event, path).cloned().unwrap_or(Value::Null);
+                s = s.replace(&format!("<{}>", k), &v.to_string());
+            }
+            serde_json::from_str(&s).unwrap_or(Value::String(s))
+        }
+        None => {
+            // channel targets only forward the detail
+            match paths.get("detail") {
+                Some(path) if paths.len() == 1 => {
+                    json_path(event, path).cloned().unwrap_or(Value::Null)
+                }
+                _ => {
+                    let mut m = Map::new();
+                    for (k, path) in paths {
+                        let v = json_path(event, path).cloned().unwrap_or(Value::Null);
+                        m.insert(k.to_string(), v);
+                    }
+                    Value::Object(m)
+                }
+            }
+        }
+    }
+}
+
+// event channel targets are named after the namespace, the channel
+// name is in the target id
+fn target_name(target: &Target) -> String {
+    match target.entity {
+        Entity::Channel => match target.id.split_once("_channel_") {
+            Some((c, _)) => c.to_string(),
+            None => target.name.clone(),
+        },
+        _ => target.name.clone(),
+    }
+}
+
+/// Puts an event on the bus and fans it out to the targets of every
+/// rule that matches
+pub fn put(emulator: &Arc<Emulator>, event: Value) {
+    let detail_type = event["detail-type"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let mut matched = false;
+    for (name, e) in &emulator.events {
+        if e.skip {
+            continue;
+        }
+        let pattern = serde_json::to_value(&e.pattern).unwrap();
+        if matches(&pattern, &event) {
+            matched = true;
+            println!(
+                "{} event {} -> rule {}",
+                "emulator:".cyan(),
+                &detail_type,
+                name
+            );
+            for target in &e.targets {
+                let input = make_input(target, &event);
+                let pending = dispatch(
+                    emulator.clone(),
+                    target.entity.clone(),
+                    target_name(target),
+                    input,
+                );
+                tokio::spawn(pending);
+            }
+        }
+    }
+    if !matched {
+        println!(
+            "{} event {} matched no rules",
+            "emulator:".yellow(),
+            &detail_type
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event() -> Value {
+        json!({
+            "detail-type": "OrderCreated",
+            "source": "shop",
+            "detail": {
+                "id": "ord-123",
+                "amount": 42,
+                "metadata": {"type": "retail"}
+            }
+        })
+    }
+
+    #[test]
+    fn matches_detail_type_and_source() {
+        let pattern = json!({"detail-type": ["OrderCreated"], "source": ["shop"]});
+        assert!(matches(&pattern, &event()));
+        let pattern = json!({"detail-type": ["OrderCreated"], "source": ["other"]});
+        assert!(!matches(&pattern, &event()));
+    }
+
+    #[test]
+    fn matches_nested_detail() {
+        let pattern = json!({"detail": {"metadata": {"type": ["retail", "wholesale"]}}});
+        assert!(matches(&pattern, &event()));
+        let pattern = json!({"detail": {"metadata": {"type": ["wholesale"]}}});
+        assert!(!matches(&pattern, &event()));
+    }
+
+    #[test]
+    fn matches_content_filters() {
+        let pattern = json!({"detail": {"id": [{"prefix": "ord-"}]}});
+        assert!(matches(&pattern, &event()));
+        let pattern = json!({"detail": {"amount": [{"numeric": [">", 10, "<=", 42]}]}});
+        assert!(matches(&pattern, &event()));
+        let pattern = json!({"detail": {"amount": [{"anything-but": [42]}]}});
+        assert!(!matches(&pattern, &event()));
+        let pattern = json!({"detail": {"missing": [{"exists": false}]}});
+        assert!(matches(&pattern, &event()));
+        let pattern = json!({"detail": {"id": [{"exists": false}]}});
+        assert!(!matches(&pattern, &event()));
+    }
+
+    #[test]
+    fn resolves_input_paths() {
+        assert_eq!(json_path(&event(), "$.detail.id"), Some(&json!("ord-123")));
+        assert_eq!(json_path(&event(), "$"), Some(&event()));
+    }
+}
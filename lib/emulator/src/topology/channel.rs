@@ -0,0 +1,233 @@
+use super::Emulator;
+use base64::{
+    Engine as _,
+    engine::general_purpose,
+};
+use colored::Colorize;
+use serde_json::Value;
+use sha1::{
+    Digest,
+    Sha1,
+};
+use std::sync::Arc;
+use tokio::{
+    io::{
+        AsyncRead,
+        AsyncReadExt,
+        AsyncWriteExt,
+    },
+    net::{
+        TcpListener,
+        TcpStream,
+    },
+    sync::mpsc,
+};
+
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+// client messages are small json payloads, anything larger is dropped
+const MAX_FRAME: usize = 1 << 20;
+
+fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(WS_GUID.as_bytes());
+    general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    let len = payload.len();
+    if len < 126 {
+        frame.push(len as u8);
+    } else if len <= u16::MAX as usize {
+        frame.push(126);
+        frame.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        frame.push(127);
+        frame.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+// reads one (masked) client frame
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Option<(u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head).await.ok()?;
+    let opcode = head[0] & 0x0F;
+    let masked = head[1] & 0x80 != 0;
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut b = [0u8; 2];
+            reader.read_exact(&mut b).await.ok()?;
+            u16::from_be_bytes(b) as usize
+        }
+        127 => {
+            let mut b = [0u8; 8];
+            reader.read_exact(&mut b).await.ok()?;
+            u64::from_be_bytes(b) as usize
+        }
+        n => n as usize,
+    };
+    if len > MAX_FRAME {
+        return None;
+    }
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask).await.ok()?;
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await.ok()?;
+    if masked {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+    Some((opcode, payload))
+}
+
+// reads the upgrade request and returns the requested path and key
+async fn read_handshake(stream: &mut TcpStream) -> Option<(String, String)> {
+    let mut buf: Vec<u8> = vec![];
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 || buf.len() > 16384 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let request = String::from_utf8_lossy(&buf).to_string();
+    let mut lines = request.lines();
+    let path = lines.next()?.split_whitespace().nth(1)?.to_string();
+    let key = lines.find_map(|line| {
+        let (k, v) = line.split_once(":")?;
+        if k.trim().eq_ignore_ascii_case("sec-websocket-key") {
+            Some(v.trim().to_string())
+        } else {
+            None
+        }
+    })?;
+    Some((path, key))
+}
+
+async fn handle(emulator: Arc<Emulator>, mut stream: TcpStream) {
+    let (path, key) = match read_handshake(&mut stream).await {
+        Some(x) => x,
+        None => return,
+    };
+    let name = path.trim_start_matches("/").to_string();
+    let mut subscription = match emulator.subscribe_channel(&name) {
+        Some(s) => s,
+        None => {
+            let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\n\r\n").await;
+            return;
+        }
+    };
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    );
+    if stream.write_all(response.as_bytes()).await.is_err() {
+        return;
+    }
+    println!("{} subscribed to channel {}", "emulator:".cyan(), &name);
+
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+
+    let writer_task = tokio::spawn(async move {
+        loop {
+            let frame = tokio::select! {
+                msg = subscription.recv() => match msg {
+                    Ok(m) => encode_frame(OP_TEXT, m.as_bytes()),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                },
+                control = rx.recv() => match control {
+                    Some(f) => f,
+                    None => break,
+                },
+            };
+            if writer.write_all(&frame).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some((opcode, payload)) = read_frame(&mut reader).await {
+        match opcode {
+            OP_TEXT => {
+                let text = String::from_utf8_lossy(&payload).to_string();
+                let message = match serde_json::from_str(&text) {
+                    Ok(v) => v,
+                    Err(_) => Value::String(text),
+                };
+                emulator.publish_channel(&name, &message);
+            }
+            OP_PING => {
+                let _ = tx.send(encode_frame(OP_PONG, &payload));
+            }
+            OP_CLOSE => {
+                let _ = tx.send(encode_frame(OP_CLOSE, &[]));
+                break;
+            }
+            _ => (),
+        }
+    }
+    drop(tx);
+    let _ = writer_task.await;
+}
+
+/// Serves channels as websockets at ws://localhost:<port>/<channel>.
+/// Messages from clients and channel targets are broadcast to every
+/// subscriber of the channel
+pub async fn serve(emulator: Arc<Emulator>, listener: TcpListener) {
+    loop {
+        if let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle(emulator.clone(), stream));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_accept_key() {
+        // example from RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_frame() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let mut head = vec![0x80 | OP_TEXT, 0x80 | 127];
+        head.extend_from_slice(&u64::MAX.to_be_bytes());
+        client.write_all(&head).await.unwrap();
+        assert!(read_frame(&mut server).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn reads_masked_frame() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let mask = [1u8, 2, 3, 4];
+        let mut frame = vec![0x80 | OP_TEXT, 0x80 | 2];
+        frame.extend_from_slice(&mask);
+        frame.extend_from_slice(&[b'h' ^ 1, b'i' ^ 2]);
+        client.write_all(&frame).await.unwrap();
+        let (opcode, payload) = read_frame(&mut server).await.unwrap();
+        assert_eq!(opcode, OP_TEXT);
+        assert_eq!(payload, b"hi");
+    }
+}
@@ -0,0 +1,661 @@
+mod bus;
+mod channel;
+mod queue;
+mod router;
+
+use crate::{
+    function,
+    state::{
+        self,
+        Failure,
+        Resolved,
+        Stubs,
+        Tasks,
+    },
+};
+use colored::Colorize;
+use compiler::{
+    BuildKind,
+    Entity,
+};
+use composer::{
+    Channel,
+    Event,
+    Flow,
+    Function,
+    Queue,
+    Route,
+    Topology,
+};
+use provider::Auth;
+use serde_json::{
+    Value,
+    json,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        RwLock,
+    },
+};
+use tokio::{
+    net::TcpListener,
+    sync::{
+        broadcast,
+        mpsc,
+    },
+    task::JoinHandle,
+};
+
+const DEFAULT_PORT: u16 = 8080;
+const FUNCTION_BASE_PORT: u16 = 9001;
+
+type Pending = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+pub struct Emulator {
+    pub namespace: String,
+    pub region: String,
+    pub account: String,
+    pub functions: HashMap<String, Function>,
+    pub ports: HashMap<String, u16>,
+    pub events: HashMap<String, Event>,
+    pub routes: HashMap<String, Route>,
+    pub queues: HashMap<String, Queue>,
+    pub channels: HashMap<String, Channel>,
+    pub flows: HashMap<String, Flow>,
+    stubs: RwLock<Stubs<'static>>,
+    queue_txs: HashMap<String, mpsc::UnboundedSender<Value>>,
+    channel_txs: HashMap<String, broadcast::Sender<String>>,
+}
+
+fn router_port() -> u16 {
+    match std::env::var("TC_EMULATOR_PORT") {
+        Ok(p) => p.parse().unwrap_or(DEFAULT_PORT),
+        Err(_) => DEFAULT_PORT,
+    }
+}
+
+// functions, events, queues and channels of all nodes are flattened
+// into one namespace, as they would be in a single account. A name that
+// another node's entity already took is qualified with the node's
+// namespace, so neither is lost
+fn flatten<T: Clone>(
+    topology: &Topology,
+    f: fn(&Topology) -> &HashMap<String, T>,
+    id: fn(&T) -> String,
+) -> HashMap<String, T> {
+    let mut h: HashMap<String, T> = f(topology).clone();
+    let mut nodes: Vec<&Topology> = topology.nodes.values().collect();
+    nodes.sort_by(|a, b| a.namespace.cmp(&b.namespace));
+    for node in nodes {
+        for (k, v) in flatten(node, f, id) {
+            match h.get(&k) {
+                Some(x) if id(x) == id(&v) => (),
+                Some(_) => {
+                    h.insert(format!("{}/{}", &node.namespace, k), v);
+                }
+                None => {
+                    h.insert(k, v);
+                }
+            }
+        }
+    }
+    h
+}
+
+fn flows(topology: &Topology) -> HashMap<String, Flow> {
+    let mut h: HashMap<String, Flow> = HashMap::new();
+    if let Some(f) = &topology.flow {
+        h.insert(f.name.clone(), f.clone());
+    }
+    for (_, node) in &topology.nodes {
+        h.extend(flows(node));
+    }
+    h
+}
+
+// responses for functions that should not run, for instance those
+// calling external services
+fn read_stubs() -> Stubs<'static> {
+    match std::env::var("TC_EMULATOR_STUBS") {
+        Ok(path) => Stubs::read(&path),
+        Err(_) => Stubs::new(HashMap::new()),
+    }
+}
+
+fn find_key<T>(
+    xs: &HashMap<String, T>,
+    name: &str,
+    aliases: fn(&T) -> Vec<String>,
+) -> Option<String> {
+    if xs.contains_key(name) {
+        return Some(name.to_string());
+    }
+    for (k, v) in xs {
+        if aliases(v).iter().any(|a| a == name) {
+            return Some(k.to_string());
+        }
+    }
+    None
+}
+
+fn function_aliases(f: &Function) -> Vec<String> {
+    vec![f.fqn.clone(), f.arn.clone(), f.actual_name.clone()]
+}
+
+fn queue_aliases(q: &Queue) -> Vec<String> {
+    vec![q.name.clone(), q.arn.clone()]
+}
+
+fn channel_aliases(c: &Channel) -> Vec<String> {
+    vec![c.name.clone()]
+}
+
+fn flow_aliases(f: &Flow) -> Vec<String> {
+    vec![f.arn.clone()]
+}
+
+impl Emulator {
+    fn new(
+        auth: &Auth,
+        topology: &Topology,
+        stubs: Stubs<'static>,
+    ) -> (Emulator, HashMap<String, mpsc::UnboundedReceiver<Value>>) {
+        let functions = flatten(topology, |t| &t.functions, |f| f.fqn.clone());
+        let queues = flatten(topology, |t| &t.queues, |q| q.arn.clone());
+        let channels = flatten(topology, |t| &t.channels, |c| c.name.clone());
+
+        let mut names: Vec<&String> = functions.keys().collect();
+        names.sort();
+        let mut ports: HashMap<String, u16> = HashMap::new();
+        for (i, name) in names.into_iter().enumerate() {
+            ports.insert(name.to_string(), FUNCTION_BASE_PORT + i as u16);
+        }
+
+        let mut queue_txs = HashMap::new();
+        let mut queue_rxs = HashMap::new();
+        for name in queues.keys() {
+            let (tx, rx) = mpsc::unbounded_channel();
+            queue_txs.insert(name.to_string(), tx);
+            queue_rxs.insert(name.to_string(), rx);
+        }
+
+        let mut channel_txs = HashMap::new();
+        for name in channels.keys() {
+            let (tx, _) = broadcast::channel(256);
+            channel_txs.insert(name.to_string(), tx);
+        }
+
+        let emulator = Emulator {
+            namespace: topology.namespace.clone(),
+            region: auth.region.clone(),
+            account: auth.account.clone(),
+            functions: functions,
+            ports: ports,
+            events: flatten(topology, |t| &t.events, |e| e.rule_name.clone()),
+            routes: flatten(
+                topology,
+                |t| &t.routes,
+                |r| format!("{} {} {}", &r.method, &r.path, &r.target.name),
+            ),
+            queues: queues,
+            channels: channels,
+            flows: flows(topology),
+            stubs: RwLock::new(stubs),
+            queue_txs: queue_txs,
+            channel_txs: channel_txs,
+        };
+        (emulator, queue_rxs)
+    }
+
+    /// The stub response for a function, state machine or resource
+    pub fn respond(&self, name: &str) -> Option<Result<Value, Failure>> {
+        self.stubs.read().unwrap().respond(name)
+    }
+
+    /// Replaces the stubs, so that a running session can be reused
+    /// with different mocks
+    pub fn set_stubs(&self, stubs: Stubs<'static>) {
+        *self.stubs.write().unwrap() = stubs;
+    }
+
+    pub fn find_function(&self, name: &str) -> Option<String> {
+        let name = match name.split_once(":function:") {
+            Some((_, n)) => n,
+            None => name,
+        };
+        find_key(&self.functions, name, function_aliases)
+    }
+
+    pub fn find_queue(&self, name: &str) -> Option<String> {
+        // queue urls end with the queue name
+        let name = match name.rsplit_once("/") {
+            Some((_, n)) => n,
+            None => name,
+        };
+        find_key(&self.queues, name, queue_aliases)
+    }
+
+    pub fn find_channel(&self, name: &str) -> Option<String> {
+        find_key(&self.channels, name, channel_aliases)
+    }
+
+    pub fn find_flow(&self, name: &str) -> Option<String> {
+        find_key(&self.flows, name, flow_aliases)
+    }
+
+    /// Invokes a function without forwarding its output, as a state
+    /// machine Task does. Stubbed functions answer with their stub,
+    /// failures the way Lambda reports function errors
+    pub async fn call(&self, name: &str, payload: Value) -> Result<(String, Value), String> {
+        let key = match self.find_function(name) {
+            Some(k) => k,
+            None => return Err(format!("function {} not found", name)),
+        };
+        match self.respond(&key) {
+            Some(Ok(output)) => return Ok((key, output)),
+            Some(Err(e)) => {
+                return Ok((key, json!({"errorType": e.error, "errorMessage": e.cause})));
+            }
+            None => (),
+        }
+        let port = *self.ports.get(&key).unwrap();
+        println!("{} invoke {}", "emulator:".cyan(), &key);
+        match function::invoke(port, &payload).await {
+            Ok(output) => Ok((key, output)),
+            Err(e) => Err(format!("{} failed: {}", &key, e)),
+        }
+    }
+
+    /// Invokes a function and forwards its output to the function's
+    /// targets, the way the transducer does when deployed
+    pub async fn invoke(self: &Arc<Self>, name: &str, payload: Value) -> Option<Value> {
+        match self.call(name, payload).await {
+            Ok((key, output)) => {
+                let f = self.functions.get(&key).unwrap();
+                let input = transducer_input(&output);
+                for target in &f.targets {
+                    let pending = dispatch(
+                        self.clone(),
+                        target.entity.clone(),
+                        target.name.clone(),
+                        input.clone(),
+                    );
+                    tokio::spawn(pending);
+                }
+                Some(output)
+            }
+            Err(e) => {
+                println!("{} {}", "emulator:".red(), e);
+                None
+            }
+        }
+    }
+
+    /// Runs a state machine natively. Task states invoke the emulated
+    /// functions unless a stub response is given for them
+    pub async fn execute(&self, name: &str, input: Value) -> Result<Value, Failure> {
+        let key = match self.find_flow(name) {
+            Some(k) => k,
+            None => {
+                return Err(Failure::runtime(&format!(
+                    "state machine {} not found",
+                    name
+                )));
+            }
+        };
+        if let Some(res) = self.respond(&key) {
+            return res;
+        }
+        let flow = self.flows.get(&key).unwrap();
+        println!("{} execute {}", "emulator:".cyan(), &key);
+        let res = state::execute(&key, &flow.definition, input, self).await;
+        if let Err(e) = &res {
+            println!(
+                "{} {} failed: {} {}",
+                "emulator:".red(),
+                &key,
+                &e.error,
+                &e.cause
+            );
+        }
+        res
+    }
+
+    pub fn send_message(&self, name: &str, message: Value) -> bool {
+        match self.find_queue(name) {
+            Some(k) => {
+                println!("{} send {}", "emulator:".cyan(), &k);
+                self.queue_txs.get(&k).unwrap().send(message).is_ok()
+            }
+            None => {
+                println!("{} queue {} not found", "emulator:".red(), name);
+                false
+            }
+        }
+    }
+
+    pub fn publish_channel(&self, name: &str, message: &Value) {
+        match self.find_channel(name) {
+            Some(k) => {
+                println!("{} publish {}", "emulator:".cyan(), &k);
+                // no subscribers is not an error
+                let _ = self.channel_txs.get(&k).unwrap().send(message.to_string());
+            }
+            None => println!("{} channel {} not found", "emulator:".red(), name),
+        }
+    }
+
+    pub fn subscribe_channel(&self, name: &str) -> Option<broadcast::Receiver<String>> {
+        self.find_channel(name)
+            .map(|k| self.channel_txs.get(&k).unwrap().subscribe())
+    }
+
+    pub fn make_event(&self, source: &str, detail_type: &str, detail: Value) -> Value {
+        json!({
+            "version": "0",
+            "id": kit::uuid_str(),
+            "detail-type": detail_type,
+            "source": source,
+            "account": &self.account,
+            "time": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "region": &self.region,
+            "resources": [],
+            "detail": detail
+        })
+    }
+
+    /// Puts an event on the emulated bus and returns its id
+    pub fn put_event(self: &Arc<Self>, source: &str, detail_type: &str, detail: Value) -> String {
+        let event = self.make_event(source, detail_type, detail);
+        let id = event["id"].as_str().unwrap_or_default().to_string();
+        bus::put(self, event);
+        id
+    }
+
+    /// Sends a request to a route the way the router does and returns
+    /// the response body
+    pub async fn request(self: &Arc<Self>, route: &Route, body: &str) -> Value {
+        router::request(self, route, body).await
+    }
+
+    fn event_source(&self, event_name: &str) -> String {
+        match self.events.get(event_name) {
+            Some(e) => match e.pattern.source.first() {
+                Some(Value::String(s)) => s.to_string(),
+                _ => String::from("default"),
+            },
+            None => String::from("default"),
+        }
+    }
+}
+
+impl Tasks for Emulator {
+    fn invoke<'a>(&'a self, function: &'a str, payload: Value) -> Resolved<'a> {
+        Box::pin(async move {
+            match self.call(function, payload).await {
+                Ok((_, output)) => match output.get("errorType").and_then(|e| e.as_str()) {
+                    Some(error) => Err(Failure::new(
+                        error,
+                        output["errorMessage"].as_str().unwrap_or_default(),
+                    )),
+                    None => Ok(output),
+                },
+                Err(e) => Err(Failure::new("Lambda.ServiceException", &e)),
+            }
+        })
+    }
+
+    fn start_execution<'a>(&'a self, machine: &'a str, input: Value) -> Resolved<'a> {
+        Box::pin(self.execute(machine, input))
+    }
+
+    fn call<'a>(&'a self, resource: &'a str, params: Value) -> Resolved<'a> {
+        Box::pin(async move {
+            match self.respond(resource) {
+                Some(res) => res,
+                None => Err(Failure::new(
+                    "States.TaskFailed",
+                    &format!(
+                        "Resource {} is not emulated, stub it ({})",
+                        resource, params
+                    ),
+                )),
+            }
+        })
+    }
+}
+
+fn transducer_input(output: &Value) -> Value {
+    match (output.get("detail"), output.get("detail-type")) {
+        (Some(detail), Some(_)) => detail.clone(),
+        _ => output.clone(),
+    }
+}
+
+/// Delivers a payload to a target entity
+pub fn dispatch(emulator: Arc<Emulator>, entity: Entity, name: String, payload: Value) -> Pending {
+    Box::pin(async move {
+        match entity {
+            Entity::Function => {
+                emulator.invoke(&name, payload).await;
+            }
+            Entity::Event => {
+                let source = emulator.event_source(&name);
+                let event = emulator.make_event(&source, &name, payload);
+                bus::put(&emulator, event);
+            }
+            Entity::Queue => {
+                emulator.send_message(&name, payload);
+            }
+            Entity::Channel => emulator.publish_channel(&name, &payload),
+            Entity::State => {
+                let _ = emulator.execute(&name, payload).await;
+            }
+            _ => println!(
+                "{} {} targets are not emulated, skipping {}",
+                "emulator:".yellow(),
+                entity.to_str(),
+                &name
+            ),
+        }
+    })
+}
+
+// stubbed functions are not started
+async fn start_functions(
+    auth: &Auth,
+    emulator: &Emulator,
+    port: u16,
+) -> Result<Vec<String>, String> {
+    let endpoint = format!("http://host.docker.internal:{}", port);
+    let mut containers: Vec<String> = vec![];
+    let mut started: Vec<&String> = vec![];
+    for (name, f) in &emulator.functions {
+        if emulator.respond(name).is_some() {
+            continue;
+        }
+        started.push(name);
+        let fport = *emulator.ports.get(name).unwrap();
+        let mut env = f.runtime.environment.clone();
+        env.insert(
+            String::from("AWS_ENDPOINT_URL_EVENTBRIDGE"),
+            endpoint.clone(),
+        );
+        env.insert(String::from("AWS_ENDPOINT_URL_SQS"), endpoint.clone());
+        env.insert(String::from("AWS_ENDPOINT_URL_SFN"), endpoint.clone());
+        let started = match f.build.kind {
+            BuildKind::Image => Ok(function::image::start(auth, f, name, fport, &env).await),
+            _ => function::default::start(auth, f, name, fport, &env).await,
+        };
+        match started {
+            Ok(container) => containers.push(container),
+            Err(e) => {
+                for container in containers {
+                    function::default::stop(&container);
+                }
+                return Err(format!("{}: {}", name, e));
+            }
+        }
+    }
+    for name in started {
+        let fport = emulator.ports.get(name).unwrap();
+        if !function::wait_until_ready(*fport, 60).await {
+            println!("{} {} did not start on {}", "emulator:".red(), name, fport);
+        }
+    }
+    Ok(containers)
+}
+
+fn print_endpoints(emulator: &Emulator, port: u16) {
+    for (name, fport) in &emulator.ports {
+        println!(
+            "function {} -> {}",
+            name.cyan(),
+            function::invocation_url(*fport)
+        );
+    }
+    for (_, route) in &emulator.routes {
+        println!(
+            "route {} http://localhost:{}{}",
+            &route.method.green(),
+            port,
+            &route.path
+        );
+    }
+    for (name, _) in &emulator.channels {
+        println!(
+            "channel {} ws://localhost:{}/{}",
+            name.cyan(),
+            port + 1,
+            name
+        );
+    }
+    for (name, _) in &emulator.flows {
+        println!("state machine {}", name.cyan());
+    }
+    println!("events/sqs/sfn endpoint http://localhost:{}", port);
+}
+
+async fn bind(port: u16) -> Result<TcpListener, String> {
+    TcpListener::bind(("0.0.0.0", port))
+        .await
+        .map_err(|e| format!("Cannot listen on port {}: {}", port, e))
+}
+
+/// A running emulator with its function containers and servers
+pub struct Session {
+    pub emulator: Arc<Emulator>,
+    containers: Vec<String>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Session {
+    /// Starts the functions that are not stubbed, the queue consumers,
+    /// the channel server and the router
+    pub async fn start(
+        auth: &Auth,
+        topology: &Topology,
+        stubs: Stubs<'static>,
+    ) -> Result<Session, String> {
+        let port = router_port();
+        let router = bind(port).await?;
+        let channels = bind(port + 1).await?;
+        let (emulator, queue_rxs) = Emulator::new(auth, topology, stubs);
+        let containers = start_functions(auth, &emulator, port).await?;
+
+        let emulator = Arc::new(emulator);
+        let mut tasks = queue::start(&emulator, queue_rxs);
+        tasks.push(tokio::spawn(channel::serve(emulator.clone(), channels)));
+        tasks.push(tokio::spawn(router::serve(emulator.clone(), router)));
+        Ok(Session {
+            emulator: emulator,
+            containers: containers,
+            tasks: tasks,
+        })
+    }
+
+    pub fn stop(self) {
+        for task in self.tasks {
+            task.abort();
+        }
+        for container in self.containers {
+            function::default::stop(&container);
+        }
+    }
+}
+
+/// Emulates the whole topology: functions run in local containers,
+/// routes are served by a local router, events are matched and fanned
+/// out in-process, queues buffer messages and channels are websockets
+pub async fn run(auth: &Auth, topology: &Topology) -> Result<(), String> {
+    println!("Emulating topology {}", &topology.namespace.green());
+    let session = Session::start(auth, topology, read_stubs()).await?;
+    print_endpoints(&session.emulator, router_port());
+
+    tokio::signal::ctrl_c().await.unwrap();
+    println!("Stopping emulator");
+    session.stop();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_function(dir: &std::path::Path, name: &str, fqn: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(
+            dir.join("function.yml"),
+            format!(
+                "name: {name}\n\
+                 fqn: {fqn}\n\
+                 runtime:\n  \
+                 lang: python3.10\n  \
+                 handler: handler.handler\n  \
+                 package_type: zip\n  \
+                 layers: []\n\
+                 build:\n  \
+                 kind: Code\n  \
+                 command: echo build\n"
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("handler.py"), "").unwrap();
+    }
+
+    #[test]
+    fn test_flatten_node_functions() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(
+            root.join("topology.yml"),
+            "name: shop\nkind: step-function\n",
+        )
+        .unwrap();
+        for ns in ["orders", "payments"] {
+            fs::create_dir_all(root.join(ns)).unwrap();
+            fs::write(
+                root.join(ns).join("topology.yml"),
+                format!("name: {ns}\nkind: step-function\n"),
+            )
+            .unwrap();
+            write_function(&root.join(ns).join("api"), "api", &format!("{ns}_api"));
+        }
+        let topology = Topology::new(root.to_str().unwrap(), "", true, false);
+
+        let functions = flatten(&topology, |t| &t.functions, |f| f.fqn.clone());
+        let mut fqns: Vec<&String> = functions.values().map(|f| &f.fqn).collect();
+        fqns.sort();
+        assert_eq!(fqns, vec!["orders_api", "payments_api"]);
+
+        let mut names: Vec<String> = functions.keys().map(|k| function::docker_name(k)).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names, vec!["api", "payments_api"]);
+    }
+}
@@ -0,0 +1,78 @@
+use super::{
+    Emulator,
+    dispatch,
+};
+use compiler::Entity;
+use serde_json::{
+    Value,
+    json,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+};
+use tokio::{
+    sync::mpsc::UnboundedReceiver,
+    task::JoinHandle,
+};
+
+fn as_body(message: &Value) -> String {
+    match message {
+        Value::String(s) => s.to_string(),
+        v => v.to_string(),
+    }
+}
+
+fn make_record(arn: &str, region: &str, message: &Value) -> Value {
+    json!({
+        "messageId": kit::uuid_str(),
+        "receiptHandle": kit::uuid_str(),
+        "body": as_body(message),
+        "attributes": {
+            "ApproximateReceiveCount": "1",
+            "SentTimestamp": kit::current_millis().to_string(),
+        },
+        "messageAttributes": {},
+        "eventSource": "aws:sqs",
+        "eventSourceARN": arn,
+        "awsRegion": region
+    })
+}
+
+async fn consume(emulator: Arc<Emulator>, name: String, mut rx: UnboundedReceiver<Value>) {
+    let queue = emulator.queues.get(&name).unwrap().clone();
+    while let Some(message) = rx.recv().await {
+        if queue.targets.is_empty() {
+            tracing::debug!("queue {} has no consumers, dropping message", &name);
+            continue;
+        }
+        for target in &queue.targets {
+            let payload = match target.entity {
+                Entity::Function => json!({
+                    "Records": [make_record(&queue.arn, &emulator.region, &message)]
+                }),
+                _ => message.clone(),
+            };
+            dispatch(
+                emulator.clone(),
+                target.entity.clone(),
+                target.name.clone(),
+                payload,
+            )
+            .await;
+        }
+    }
+}
+
+/// Starts one consumer per queue. Messages are delivered one at a
+/// time, in the order they were sent
+pub fn start(
+    emulator: &Arc<Emulator>,
+    receivers: HashMap<String, UnboundedReceiver<Value>>,
+) -> Vec<JoinHandle<()>> {
+    let mut consumers = vec![];
+    for (name, rx) in receivers {
+        consumers.push(tokio::spawn(consume(emulator.clone(), name, rx)));
+    }
+    consumers
+}
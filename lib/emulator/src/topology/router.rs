@@ -0,0 +1,502 @@
+use super::{
+    Emulator,
+    bus,
+    dispatch,
+};
+use bytes::Bytes;
+use colored::Colorize;
+use compiler::Entity;
+use composer::Route;
+use http_body_util::{
+    BodyExt,
+    Full,
+};
+use hyper::{
+    Request,
+    Response,
+    StatusCode,
+    body::Incoming,
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use kit::*;
+use md5::{
+    Digest,
+    Md5,
+};
+use serde_json::{
+    Map,
+    Value,
+    json,
+};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::Arc,
+};
+use tokio::net::TcpListener;
+
+type Reply = Response<Full<Bytes>>;
+
+fn reply(status: StatusCode, body: &Value) -> Reply {
+    let body = match body {
+        Value::String(s) => s.to_string(),
+        v => v.to_string(),
+    };
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .unwrap()
+}
+
+/// Matches a request path against a route path with `{param}` and
+/// greedy `{proxy+}` segments
+fn match_path(pattern: &str, path: &str) -> Option<HashMap<String, String>> {
+    let mut params: HashMap<String, String> = HashMap::new();
+    let ps: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+    let xs: Vec<&str> = path.trim_matches('/').split('/').collect();
+    for (i, p) in ps.iter().enumerate() {
+        if p.starts_with('{') && p.ends_with("+}") {
+            let rest = xs.get(i..).map(|r| r.join("/")).unwrap_or_default();
+            params.insert(
+                p.trim_matches(|c| c == '{' || c == '}' || c == '+')
+                    .to_string(),
+                rest,
+            );
+            return Some(params);
+        }
+        let x = xs.get(i)?;
+        if p.starts_with('{') && p.ends_with('}') {
+            params.insert(
+                p.trim_matches(|c| c == '{' || c == '}').to_string(),
+                x.to_string(),
+            );
+        } else if p != x {
+            return None;
+        }
+    }
+    if xs.len() == ps.len() {
+        Some(params)
+    } else {
+        None
+    }
+}
+
+// static segments outrank `{param}` ones, which outrank a greedy
+// `{proxy+}`, segment by segment
+fn rank(pattern: &str) -> Vec<u8> {
+    pattern
+        .trim_matches('/')
+        .split('/')
+        .map(|p| {
+            if p.starts_with('{') && p.ends_with("+}") {
+                0
+            } else if p.starts_with('{') && p.ends_with('}') {
+                1
+            } else {
+                2
+            }
+        })
+        .collect()
+}
+
+/// The most specific route matching the request, as API Gateway picks
+/// it. An exact method outranks `ANY`
+fn find_route<'a>(
+    routes: &'a HashMap<String, Route>,
+    method: &str,
+    path: &str,
+) -> Option<(&'a Route, HashMap<String, String>)> {
+    routes
+        .values()
+        .filter(|r| r.method == method || r.method == "ANY")
+        .filter_map(|r| match_path(&r.path, path).map(|params| (r, params)))
+        .max_by(|(a, _), (b, _)| {
+            (rank(&a.path), a.method == method, &b.path).cmp(&(
+                rank(&b.path),
+                b.method == method,
+                &a.path,
+            ))
+        })
+}
+
+fn parse_query(query: Option<&str>) -> Map<String, Value> {
+    let mut m = Map::new();
+    if let Some(q) = query {
+        for pair in q.split('&').filter(|p| !p.is_empty()) {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            m.insert(k.to_string(), Value::String(v.to_string()));
+        }
+    }
+    m
+}
+
+// API Gateway HTTP API (payload format 2.0) event
+fn make_request_event(
+    route: &Route,
+    method: &str,
+    path: &str,
+    query: Option<&str>,
+    headers: Map<String, Value>,
+    params: HashMap<String, String>,
+    body: &str,
+) -> Value {
+    json!({
+        "version": "2.0",
+        "routeKey": format!("{} {}", &route.method, &route.path),
+        "rawPath": path,
+        "rawQueryString": query.unwrap_or_default(),
+        "headers": headers,
+        "queryStringParameters": parse_query(query),
+        "pathParameters": params,
+        "requestContext": {
+            "http": {
+                "method": method,
+                "path": path,
+                "sourceIp": "127.0.0.1"
+            },
+            "requestId": kit::uuid_str(),
+            "stage": &route.stage
+        },
+        "body": body,
+        "isBase64Encoded": false
+    })
+}
+
+fn as_json(body: &str) -> Value {
+    serde_json::from_str(body).unwrap_or(Value::String(body.to_string()))
+}
+
+// lambda proxy responses carry their own status and body
+fn function_reply(output: Value) -> Reply {
+    match output.get("statusCode").and_then(|s| s.as_u64()) {
+        Some(code) => {
+            let status = StatusCode::from_u16(code as u16).unwrap_or(StatusCode::OK);
+            let body = output.get("body").cloned().unwrap_or(Value::Null);
+            let mut res = reply(status, &body);
+            if let Some(Value::Object(hs)) = output.get("headers") {
+                for (k, v) in hs {
+                    if let (Ok(name), Some(val)) = (
+                        hyper::header::HeaderName::from_bytes(k.as_bytes()),
+                        v.as_str().and_then(|s| s.parse().ok()),
+                    ) {
+                        res.headers_mut().insert(name, val);
+                    }
+                }
+            }
+            res
+        }
+        None => reply(StatusCode::OK, &output),
+    }
+}
+
+async fn call_route(emulator: &Arc<Emulator>, route: &Route, event: Value, body: &str) -> Reply {
+    let target = &route.target;
+    match target.entity {
+        Entity::Function if route.is_async => {
+            tokio::spawn(dispatch(
+                emulator.clone(),
+                Entity::Function,
+                target.name.clone(),
+                event,
+            ));
+            reply(StatusCode::ACCEPTED, &json!({"status": "accepted"}))
+        }
+        Entity::Function => match emulator.invoke(&target.name, event).await {
+            Some(output) => function_reply(output),
+            None => reply(
+                StatusCode::BAD_GATEWAY,
+                &json!({"message": "Internal Server Error"}),
+            ),
+        },
+        _ => {
+            let payload = as_json(body);
+            let input = match payload.get("detail") {
+                Some(d) => d.clone(),
+                None => payload,
+            };
+            tokio::spawn(dispatch(
+                emulator.clone(),
+                target.entity.clone(),
+                target.name.clone(),
+                input,
+            ));
+            reply(StatusCode::OK, &json!({"status": "accepted"}))
+        }
+    }
+}
+
+/// Calls the route target with a request event for the route path
+pub async fn request(emulator: &Arc<Emulator>, route: &Route, body: &str) -> Value {
+    let mut headers = Map::new();
+    headers.insert(s!("content-type"), json!("application/json"));
+    let event = make_request_event(
+        route,
+        &route.method,
+        &route.path,
+        None,
+        headers,
+        HashMap::new(),
+        body,
+    );
+    let res = call_route(emulator, route, event, body).await;
+    let bytes = match res.into_body().collect().await {
+        Ok(b) => b.to_bytes(),
+        Err(_) => Bytes::new(),
+    };
+    as_json(&String::from_utf8_lossy(&bytes))
+}
+
+fn put_events(emulator: &Arc<Emulator>, body: &Value) -> Reply {
+    let mut ids: Vec<Value> = vec![];
+    if let Some(Value::Array(entries)) = body.get("Entries") {
+        for entry in entries {
+            let source = entry["Source"].as_str().unwrap_or("default");
+            let detail_type = entry["DetailType"].as_str().unwrap_or_default();
+            let detail = match &entry["Detail"] {
+                Value::String(s) => as_json(s),
+                v => v.clone(),
+            };
+            let event = emulator.make_event(source, detail_type, detail);
+            ids.push(json!({"EventId": &event["id"]}));
+            bus::put(emulator, event);
+        }
+    }
+    reply(
+        StatusCode::OK,
+        &json!({"FailedEntryCount": 0, "Entries": ids}),
+    )
+}
+
+fn send_message(emulator: &Arc<Emulator>, body: &Value) -> Reply {
+    let url = body["QueueUrl"].as_str().unwrap_or_default();
+    let message = body["MessageBody"].as_str().unwrap_or_default();
+    if emulator.send_message(url, Value::String(message.to_string())) {
+        let md5 = Md5::digest(message.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        reply(
+            StatusCode::OK,
+            &json!({"MessageId": kit::uuid_str(), "MD5OfMessageBody": md5}),
+        )
+    } else {
+        reply(
+            StatusCode::BAD_REQUEST,
+            &json!({"__type": "com.amazonaws.sqs#QueueDoesNotExist", "message": url}),
+        )
+    }
+}
+
+fn execution_input(body: &Value) -> Value {
+    match &body["input"] {
+        Value::String(s) => as_json(s),
+        Value::Null => json!({}),
+        v => v.clone(),
+    }
+}
+
+fn execution_arn(emulator: &Emulator, machine: &str) -> String {
+    format!(
+        "arn:aws:states:{}:{}:execution:{}:{}",
+        &emulator.region,
+        &emulator.account,
+        machine,
+        kit::uuid_str()
+    )
+}
+
+fn start_execution(emulator: &Arc<Emulator>, body: &Value) -> Reply {
+    let machine = body["stateMachineArn"].as_str().unwrap_or_default();
+    match emulator.find_flow(machine) {
+        Some(key) => {
+            let arn = execution_arn(emulator, &key);
+            tokio::spawn(dispatch(
+                emulator.clone(),
+                Entity::State,
+                key,
+                execution_input(body),
+            ));
+            reply(
+                StatusCode::OK,
+                &json!({"executionArn": arn, "startDate": chrono::Utc::now().timestamp()}),
+            )
+        }
+        None => reply(
+            StatusCode::BAD_REQUEST,
+            &json!({"__type": "StateMachineDoesNotExist", "message": machine}),
+        ),
+    }
+}
+
+async fn start_sync_execution(emulator: &Arc<Emulator>, body: &Value) -> Reply {
+    let machine = body["stateMachineArn"].as_str().unwrap_or_default();
+    let arn = execution_arn(emulator, machine);
+    let input = execution_input(body);
+    let mut res = json!({
+        "executionArn": arn,
+        "stateMachineArn": machine,
+        "input": input.to_string(),
+        "startDate": chrono::Utc::now().timestamp()
+    });
+    match emulator.execute(machine, input).await {
+        Ok(output) => {
+            res["status"] = json!("SUCCEEDED");
+            res["output"] = json!(output.to_string());
+        }
+        Err(e) => {
+            res["status"] = json!("FAILED");
+            res["error"] = json!(e.error);
+            res["cause"] = json!(e.cause);
+        }
+    }
+    res["stopDate"] = json!(chrono::Utc::now().timestamp());
+    reply(StatusCode::OK, &res)
+}
+
+async fn handle(emulator: Arc<Emulator>, req: Request<Incoming>) -> Result<Reply, Infallible> {
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().map(|q| q.to_string());
+    let amz_target = req
+        .headers()
+        .get("x-amz-target")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let mut headers = Map::new();
+    for (k, v) in req.headers() {
+        if let Ok(s) = v.to_str() {
+            headers.insert(k.to_string(), Value::String(s.to_string()));
+        }
+    }
+    let body = match req.into_body().collect().await {
+        Ok(b) => String::from_utf8_lossy(&b.to_bytes()).to_string(),
+        Err(_) => kit::empty(),
+    };
+
+    // functions talk to the emulator through AWS_ENDPOINT_URL_*
+    if let Some(t) = amz_target {
+        let res = match t.as_ref() {
+            "AWSEvents.PutEvents" => put_events(&emulator, &as_json(&body)),
+            "AmazonSQS.SendMessage" => send_message(&emulator, &as_json(&body)),
+            "AWSStepFunctions.StartExecution" => start_execution(&emulator, &as_json(&body)),
+            "AWSStepFunctions.StartSyncExecution" => {
+                start_sync_execution(&emulator, &as_json(&body)).await
+            }
+            _ => reply(
+                StatusCode::BAD_REQUEST,
+                &json!({"message": format!("{} is not emulated", t)}),
+            ),
+        };
+        return Ok(res);
+    }
+
+    println!("{} {} {}", "emulator:".cyan(), &method, &path);
+    let res = match find_route(&emulator.routes, &method, &path) {
+        Some((route, params)) => {
+            let event = make_request_event(
+                route,
+                &method,
+                &path,
+                query.as_deref(),
+                headers,
+                params,
+                &body,
+            );
+            call_route(&emulator, route, event, &body).await
+        }
+        None => reply(StatusCode::NOT_FOUND, &json!({"message": "Not Found"})),
+    };
+    Ok(res)
+}
+
+pub async fn serve(emulator: Arc<Emulator>, listener: TcpListener) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(_) => continue,
+        };
+        let emulator = emulator.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle(emulator.clone(), req));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("emulator connection error: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_route_paths() {
+        assert!(match_path("/api/etl", "/api/etl").is_some());
+        assert!(match_path("/api/etl", "/api/etl/1").is_none());
+        let params = match_path("/api/orders/{id}", "/api/orders/42").unwrap();
+        assert_eq!(params.get("id").unwrap(), "42");
+        let params = match_path("/files/{proxy+}", "/files/a/b.txt").unwrap();
+        assert_eq!(params.get("proxy").unwrap(), "a/b.txt");
+    }
+
+    fn route(method: &str, path: &str) -> Route {
+        serde_json::from_value(json!({
+            "skip": false,
+            "method": method,
+            "path": path,
+            "gateway": "api",
+            "authorizer": null,
+            "role_arn": "",
+            "stage": "$default",
+            "stage_variables": {},
+            "is_async": false,
+            "cors": null,
+            "target": {
+                "entity": "Function",
+                "name": path,
+                "arn": "",
+                "request_params": {},
+                "response_params": {}
+            },
+            "domains": {},
+            "throttling": {},
+            "verticals": {}
+        }))
+        .unwrap()
+    }
+
+    fn routes(xs: Vec<(&str, &str)>) -> HashMap<String, Route> {
+        xs.into_iter()
+            .map(|(method, path)| (format!("{} {}", method, path), route(method, path)))
+            .collect()
+    }
+
+    fn found(routes: &HashMap<String, Route>, method: &str, path: &str) -> String {
+        let (r, _) = find_route(routes, method, path).unwrap();
+        format!("{} {}", &r.method, &r.path)
+    }
+
+    #[test]
+    fn finds_the_most_specific_route() {
+        let rs = routes(vec![
+            ("ANY", "/{proxy+}"),
+            ("GET", "/api/{proxy+}"),
+            ("GET", "/api/orders/{id}"),
+            ("GET", "/api/orders/latest"),
+            ("ANY", "/api/orders/latest"),
+        ]);
+        let latest = "/api/orders/latest";
+        assert_eq!(found(&rs, "GET", latest), "GET /api/orders/latest");
+        assert_eq!(found(&rs, "POST", latest), "ANY /api/orders/latest");
+        assert_eq!(found(&rs, "GET", "/api/orders/42"), "GET /api/orders/{id}");
+        assert_eq!(found(&rs, "GET", "/api/users/42"), "GET /api/{proxy+}");
+        assert_eq!(found(&rs, "POST", "/api/users/42"), "ANY /{proxy+}");
+        assert!(find_route(&routes(vec![("GET", "/api")]), "POST", "/api").is_none());
+    }
+}
@@ -13,8 +13,8 @@ use ratatui::widgets::Widget;
 use ratatui::Frame;
 use crate::color::ansi_to_spans;
 
-struct JsonWidget {
-    content: String,
+pub(crate) struct JsonWidget {
+    pub(crate) content: String,
 }
 
 impl Widget for JsonWidget {
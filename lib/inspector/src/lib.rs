@@ -11,9 +11,16 @@ use composer::Topology;
 
 mod color;
 mod detail;
+mod timeline;
 mod tree;
 
 use detail::Detail;
+pub use timeline::Node;
+
+trait View<Id> {
+    fn state(&mut self) -> &mut TreeState<Id>;
+    fn draw(&mut self, frame: &mut Frame);
+}
 
 #[must_use]
 struct App<'a> {
@@ -44,6 +51,13 @@ impl<'a> App<'a> {
         }
     }
 
+}
+
+impl<'a> View<&'a str> for App<'a> {
+    fn state(&mut self) -> &mut TreeState<&'a str> {
+        &mut self.state
+    }
+
     fn draw(&mut self, frame: &mut Frame) {
         let area = frame.area();
 
@@ -94,13 +108,15 @@ impl<'a> App<'a> {
     }
 }
 
-fn run_app<B>(
+fn run_app<B, Id, V>(
     terminal: &mut Terminal<B>,
-    mut app: App
+    mut app: V
 ) -> Result<(), B::Error>
 where
     B: Backend,
     B::Error: From<std::io::Error>,
+    Id: Clone + PartialEq + Eq + core::hash::Hash,
+    V: View<Id>,
 {
     const DEBOUNCE: Duration = Duration::from_millis(20); // 50 FPS
 
@@ -118,23 +134,23 @@ where
                         return Ok(());
                     }
                     KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Char('\n' | ' ') => app.state.toggle_selected(),
-                    KeyCode::Left => app.state.key_left(),
-                    KeyCode::Right => app.state.key_right(),
-                    KeyCode::Down => app.state.key_down(),
-                    KeyCode::Up => app.state.key_up(),
-                    KeyCode::Esc => app.state.select(Vec::new()),
-                    KeyCode::Home => app.state.select_first(),
-                    KeyCode::End => app.state.select_last(),
-                    KeyCode::PageDown => app.state.scroll_down(3),
-                    KeyCode::PageUp => app.state.scroll_up(3),
+                    KeyCode::Char('\n' | ' ') => app.state().toggle_selected(),
+                    KeyCode::Left => app.state().key_left(),
+                    KeyCode::Right => app.state().key_right(),
+                    KeyCode::Down => app.state().key_down(),
+                    KeyCode::Up => app.state().key_up(),
+                    KeyCode::Esc => app.state().select(Vec::new()),
+                    KeyCode::Home => app.state().select_first(),
+                    KeyCode::End => app.state().select_last(),
+                    KeyCode::PageDown => app.state().scroll_down(3),
+                    KeyCode::PageUp => app.state().scroll_up(3),
                     _ => false,
                 },
                 Event::Mouse(mouse) => match mouse.kind {
-                    MouseEventKind::ScrollDown => app.state.scroll_down(1),
-                    MouseEventKind::ScrollUp => app.state.scroll_up(1),
+                    MouseEventKind::ScrollDown => app.state().scroll_down(1),
+                    MouseEventKind::ScrollUp => app.state().scroll_up(1),
                     MouseEventKind::Down(_button) => {
-                        app.state.click_at(Position::new(mouse.column, mouse.row))
+                        app.state().click_at(Position::new(mouse.column, mouse.row))
                     }
                     _ => false,
                 },
@@ -164,7 +180,11 @@ where
     }
 }
 
-pub fn run(topology: &Topology) -> std::io::Result<()> {
+fn run_view<Id, V>(app: V) -> std::io::Result<()>
+where
+    Id: Clone + PartialEq + Eq + core::hash::Hash,
+    V: View<Id>,
+{
     // Terminal initialization
     crossterm::terminal::enable_raw_mode()?;
     let mut stdout = std::io::stdout();
@@ -175,8 +195,6 @@ pub fn run(topology: &Topology) -> std::io::Result<()> {
     )?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
 
-    // App
-    let app = App::new(topology);
     let res = run_app(&mut terminal, app);
 
     // restore terminal
@@ -194,3 +212,12 @@ pub fn run(topology: &Topology) -> std::io::Result<()> {
 
     Ok(())
 }
+
+pub fn run(topology: &Topology) -> std::io::Result<()> {
+    run_view(App::new(topology))
+}
+
+/// Browse a timeline of nodes, e.g. the hops of a traced request
+pub fn run_timeline(title: &str, nodes: &[Node]) -> std::io::Result<()> {
+    run_view(timeline::Timeline::new(title, nodes))
+}
@@ -0,0 +1,129 @@
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Scrollbar, ScrollbarOrientation};
+use tui_tree_widget::{Tree, TreeItem, TreeState};
+use colored_json::to_colored_json_auto;
+use serde_json::Value;
+
+use crate::View;
+use crate::detail::JsonWidget;
+
+/// A node of a timeline tree. `detail` is shown as JSON when selected
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub label: String,
+    pub duration: Option<i64>,
+    pub error: bool,
+    pub detail: Value,
+    pub children: Vec<Node>,
+}
+
+fn make_item(index: usize, node: &Node) -> TreeItem<'static, String> {
+    let style = if node.error {
+        Style::new().fg(Color::Red)
+    } else {
+        Style::new()
+    };
+    let mut spans = vec![Span::styled(node.label.clone(), style)];
+    if let Some(d) = node.duration {
+        spans.push(Span::styled(format!("  {}ms", d), Style::new().fg(Color::DarkGray)));
+    }
+    let text = Line::from(spans);
+    let children: Vec<TreeItem<'static, String>> = node.children
+        .iter()
+        .enumerate()
+        .map(|(i, c)| make_item(i, c))
+        .collect();
+    TreeItem::new(index.to_string(), text, children)
+        .expect("all item identifiers are unique")
+}
+
+fn find<'a>(nodes: &'a [Node], path: &[String]) -> Option<&'a Node> {
+    let (first, rest) = path.split_first()?;
+    let node = nodes.get(first.parse::<usize>().ok()?)?;
+    if rest.is_empty() {
+        Some(node)
+    } else {
+        find(&node.children, rest)
+    }
+}
+
+pub struct Timeline {
+    title: String,
+    nodes: Vec<Node>,
+    state: TreeState<String>,
+    items: Vec<TreeItem<'static, String>>,
+}
+
+impl Timeline {
+    pub fn new(title: &str, nodes: &[Node]) -> Self {
+        let mut state = TreeState::default();
+        // timelines are short, so start fully expanded
+        fn open_all(state: &mut TreeState<String>, prefix: Vec<String>, nodes: &[Node]) {
+            for (i, n) in nodes.iter().enumerate() {
+                let mut path = prefix.clone();
+                path.push(i.to_string());
+                state.open(path.clone());
+                open_all(state, path, &n.children);
+            }
+        }
+        open_all(&mut state, vec![], nodes);
+        state.select_first();
+
+        Self {
+            title: title.to_string(),
+            nodes: nodes.to_vec(),
+            state: state,
+            items: nodes.iter().enumerate().map(|(i, n)| make_item(i, n)).collect(),
+        }
+    }
+}
+
+impl View<String> for Timeline {
+    fn state(&mut self) -> &mut TreeState<String> {
+        &mut self.state
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(50),
+                Constraint::Percentage(50)
+            ])
+            .split(area);
+
+        let sidebar = Tree::new(&self.items)
+            .expect("all item identifiers are unique")
+            .block(
+                Block::bordered()
+                    .title(self.title.clone())
+            )
+            .experimental_scrollbar(Some(
+                Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                    .begin_symbol(None)
+                    .track_symbol(None)
+                    .end_symbol(None),
+            ))
+            .highlight_style(
+                Style::new()
+                    .fg(Color::Black)
+                    .bg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("");
+
+        let selected = find(&self.nodes, self.state.selected()).map(|n| n.detail.clone());
+        frame.render_stateful_widget(sidebar, layout[0], &mut self.state);
+
+        if let Some(detail) = selected {
+            let pretty = to_colored_json_auto(&detail).unwrap();
+            let widget = JsonWidget { content: pretty };
+            frame.render_widget(widget, layout[1]);
+        }
+    }
+}
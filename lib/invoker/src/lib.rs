@@ -30,17 +30,38 @@ pub fn read_payload_local(payload: Option<String>) -> String {
     }
 }
 
-fn find_bucket() -> String {
+fn test_bucket() -> Option<String> {
     let cfg = Config::new();
-    let maybe_bucket = cfg.tester.bucket;
-    match maybe_bucket {
+    match cfg.tester.bucket {
+        Some(b) => Some(b),
+        None => std::env::var("TC_TEST_BUCKET").ok(),
+    }
+}
+
+fn find_bucket() -> String {
+    match test_bucket() {
         Some(b) => b,
-        None => match std::env::var("TC_TEST_BUCKET") {
-            Ok(p) => p,
-            Err(_) => panic!("No test bucket specified"),
-        },
+        None => panic!("No test bucket specified"),
     }
 }
+
+/// Reads a payload given as an s3 uri, a `//key` in the test bucket,
+/// a json file or inline json
+pub async fn try_read_payload(auth: &Auth, p: &str) -> Result<String, String> {
+    let uri = if p.starts_with("s3://") {
+        p.to_string()
+    } else if p.starts_with("//") {
+        let bucket = test_bucket().ok_or("No test bucket specified")?;
+        format!("s3://{}/{}", &bucket, u::second(p, "//"))
+    } else if p.ends_with(".json") && u::file_exists(p) {
+        return Ok(u::slurp(p));
+    } else {
+        return Ok(p.to_string());
+    };
+    let client = aws::s3::make_client(auth).await;
+    let (bucket, key) = aws::s3::try_parts_of(&uri)?;
+    aws::s3::try_get_str(&client, &bucket, &key).await
+}
 pub async fn read_payload(auth: &Auth, dir: &str, s: Option<String>) -> String {
     match s {
         Some(p) => {
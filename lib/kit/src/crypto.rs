@@ -3,7 +3,10 @@ use rand::{
     Rng,
     distributions::Alphanumeric,
 };
-use std::fs::read_dir;
+use std::fs::{
+    File,
+    read_dir,
+};
 use uuid::Uuid;
 
 pub fn checksum_dir(dir: &str) -> String {
@@ -17,6 +20,12 @@ pub fn checksum_str(s: &str) -> String {
     digest.to_hex_lowercase()
 }
 
+pub fn checksum_file(path: &str) -> String {
+    let f = File::open(path).unwrap();
+    let digest = sha1::chksum(f).unwrap();
+    digest.to_hex_lowercase()
+}
+
 pub fn randstr() -> String {
     let s: String = rand::thread_rng()
         .sample_iter(&Alphanumeric)
@@ -303,6 +303,12 @@ pub fn sleep(ms: u64) {
     thread::sleep(duration);
 }
 
+/// Like `sleep`, but yields to other tasks on the runtime instead of
+/// blocking its thread
+pub async fn sleep_async(ms: u64) {
+    tokio::time::sleep(Duration::from_millis(ms)).await;
+}
+
 pub fn env_var(var: &str, fallback: &str) -> String {
     match env::var(var) {
         Ok(v) => v,
@@ -12,6 +12,19 @@ pub fn utc_now() -> String {
     date.format("%m:%d:%Y-%H:%M:%S").to_string()
 }
 
+/// Milliseconds since the epoch of a timestamp written by `utc_now`
+pub fn utc_millis(s: &str) -> Option<i64> {
+    match NaiveDateTime::parse_from_str(s, "%m:%d:%Y-%H:%M:%S") {
+        Ok(dt) => Some(dt.and_utc().timestamp_millis()),
+        Err(_) => None,
+    }
+}
+
+/// Milliseconds in a duration like `90m`, `12h` or `7d`
+pub fn duration_millis(s: &str) -> Option<i64> {
+    ms_converter::ms(s).ok()
+}
+
 pub fn current_millis() -> i64 {
     let start = SystemTime::now();
     let since_the_epoch = start
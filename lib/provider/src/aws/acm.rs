@@ -134,7 +134,7 @@ pub async fn wait_until_validated(client: &Client, arn: &str) {
         let cur = get_status(client, arn).await;
         status = cur.clone();
         println!("Waiting for cert to be validated: {:?}...", status);
-        u::sleep(10000);
+        u::sleep_async(10000).await;
     }
 }
 
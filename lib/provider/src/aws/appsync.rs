@@ -754,6 +754,18 @@ pub async fn get_tag(client: &Client, arn: &str, tag: String) -> String {
     }
 }
 
+/// The lambda authorizer the api was created with
+pub async fn find_authorizer_uri(client: &Client, api_id: &str) -> Option<String> {
+    let res = client.get_graphql_api().api_id(api_id).send().await;
+    match res {
+        Ok(r) => r
+            .graphql_api
+            .and_then(|api| api.lambda_authorizer_config)
+            .map(|c| c.authorizer_uri),
+        Err(_) => None,
+    }
+}
+
 pub async fn get_schema(client: &Client, api_id: &str) -> Option<String> {
     let res = client
         .get_introspection_schema()
@@ -93,14 +93,64 @@ pub async fn find_or_create_api(client: &Client, name: &str) -> String {
     }
 }
 
+// an existing channel namespace gets the given handler
 pub async fn create_channel(client: &Client, api_id: &str, name: &str, handler: &str) {
-    let _ = client
+    let r = client
         .create_channel_namespace()
         .api_id(s!(api_id))
         .name(s!(name))
         .code_handlers(s!(handler))
         .send()
         .await;
+    if r.is_err() {
+        let _ = client
+            .update_channel_namespace()
+            .api_id(s!(api_id))
+            .name(s!(name))
+            .code_handlers(s!(handler))
+            .send()
+            .await;
+    }
+}
+
+/// Code handlers of a channel namespace, None when it does not exist
+pub async fn find_channel_handler(client: &Client, api_name: &str, name: &str) -> Option<String> {
+    let api_id = find_api(client, api_name).await?;
+    let r = client
+        .get_channel_namespace()
+        .api_id(api_id)
+        .name(s!(name))
+        .send()
+        .await
+        .ok()?;
+    let handler = r.channel_namespace.and_then(|c| c.code_handlers);
+    Some(handler.unwrap_or_default())
+}
+
+/// Whether the events api has the channel namespace
+pub async fn find_channel(client: &Client, api_name: &str, name: &str) -> bool {
+    match find_api(client, api_name).await {
+        Some(api_id) => client
+            .get_channel_namespace()
+            .api_id(api_id)
+            .name(s!(name))
+            .send()
+            .await
+            .is_ok(),
+        None => false,
+    }
+}
+
+pub async fn delete_channel(client: &Client, api_name: &str, name: &str) {
+    if let Some(api_id) = find_api(client, api_name).await {
+        println!("Deleting channel {}", name);
+        let _ = client
+            .delete_channel_namespace()
+            .api_id(api_id)
+            .name(s!(name))
+            .send()
+            .await;
+    }
 }
 
 async fn get_api_key(client: &Client, api_id: &str) -> Option<String> {
@@ -140,3 +190,11 @@ pub async fn find_api_creds(client: &Client, name: &str) -> Option<ApiCred> {
         None => None,
     }
 }
+
+pub async fn find_api_arn(client: &Client, name: &str) -> Option<String> {
+    let apis = list_apis(client).await;
+    match apis.get(name) {
+        Some(api) => api.api_arn.clone(),
+        None => None,
+    }
+}
@@ -80,3 +80,58 @@ pub async fn _delete_subscription_filter(
         Err(_) => Ok(()),
     }
 }
+
+#[derive(Clone, Debug)]
+pub struct LogEvent {
+    pub id: String,
+    pub group: String,
+    pub stream: String,
+    pub timestamp: i64,
+    pub message: String,
+}
+
+/// Events in a log group since `start_time` (ms since the epoch),
+/// optionally matching a CloudWatch filter pattern. A missing group has
+/// no events
+pub async fn filter_log_events(
+    client: &Client,
+    group: &str,
+    start_time: i64,
+    pattern: Option<String>,
+) -> Result<Vec<LogEvent>, String> {
+    let mut xs: Vec<LogEvent> = vec![];
+    let mut token: Option<String> = None;
+    loop {
+        let r = client
+            .filter_log_events()
+            .log_group_name(s!(group))
+            .start_time(start_time)
+            .set_filter_pattern(pattern.clone())
+            .set_next_token(token.clone())
+            .send()
+            .await;
+
+        match r {
+            Ok(res) => {
+                for e in res.events.unwrap_or_default() {
+                    xs.push(LogEvent {
+                        id: e.event_id.unwrap_or_default(),
+                        group: s!(group),
+                        stream: e.log_stream_name.unwrap_or_default(),
+                        timestamp: e.timestamp.unwrap_or_default(),
+                        message: e.message.unwrap_or_default(),
+                    });
+                }
+                match res.next_token {
+                    Some(t) => token = Some(t),
+                    None => break,
+                }
+            }
+            Err(e) => match e.as_service_error() {
+                Some(se) if se.is_resource_not_found_exception() => break,
+                _ => return Err(format!("{:?}", e)),
+            },
+        }
+    }
+    Ok(xs)
+}
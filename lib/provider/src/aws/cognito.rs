@@ -73,6 +73,39 @@ async fn find_pool(client: &Client, name: &str) -> Option<String> {
     pools.get(name).cloned()
 }
 
+/// Lambda triggers of a pool keyed like `make_lambda_mappings`, None
+/// when the pool does not exist
+pub async fn find_pool_triggers(client: &Client, name: &str) -> Option<HashMap<String, String>> {
+    let id = find_pool(client, name).await?;
+    let res = client
+        .describe_user_pool()
+        .user_pool_id(id)
+        .send()
+        .await
+        .ok()?;
+    let mut h: HashMap<String, String> = HashMap::new();
+    if let Some(c) = res.user_pool.and_then(|p| p.lambda_config) {
+        let triggers = [
+            ("PRE_SIGN_UP", c.pre_sign_up),
+            ("POST_CONFIRMATION", c.post_confirmation),
+            ("PRE_AUTHENTICATION", c.pre_authentication),
+            ("POST_AUTHENTICATION", c.post_authentication),
+            ("CREATE_AUTH_CHALLENGE", c.create_auth_challenge),
+            (
+                "VERIFY_AUTH_CHALLENGE_RESPONSE",
+                c.verify_auth_challenge_response,
+            ),
+            ("CUSTOM_MESSAGE", c.custom_message),
+        ];
+        for (k, v) in triggers {
+            if let Some(arn) = v {
+                h.insert(s!(k), arn);
+            }
+        }
+    }
+    Some(h)
+}
+
 async fn update_pool(
     client: &Client,
     id: &str,
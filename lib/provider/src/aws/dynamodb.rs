@@ -1,20 +1,25 @@
+use super::constants;
+use crate::Auth;
+pub use aws_sdk_dynamodb::Client;
 use aws_sdk_dynamodb::{
-    Client,
     config,
-    config::retry::{RetryConfig, RetryMode},
+    config::retry::{
+        RetryConfig,
+        RetryMode,
+    },
     types::{
         AttributeDefinition,
         BillingMode,
         KeySchemaElement,
         KeyType,
-        ScalarAttributeType
+        ScalarAttributeType,
+        Tag,
+    },
 };
-
-use crate::Auth;
-use super::constants;
+use std::collections::HashMap;
 
 pub async fn make_client(auth: &Auth) -> Client {
-    let shared_config = auth.load().await;
+    let shared_config = &auth.aws_config;
     Client::from_conf(
         config::Builder::from(shared_config)
             .behavior_version(constants::behavior_version())
@@ -24,24 +29,18 @@ pub async fn make_client(auth: &Auth) -> Client {
                     .with_retry_mode(RetryMode::Adaptive)
                     .with_max_attempts(constants::MAX_ATTEMPTS)
                     .with_initial_backoff(constants::INITIAL_BACKOFF)
-                    .with_max_backoff(constants::MAX_BACKOFF)
+                    .with_max_backoff(constants::MAX_BACKOFF),
             )
             .build(),
     )
 }
 
-fn make_hash_key_schema(hash_key: &str) -> KeySchemaElement {
-     KeySchemaElement::builder()
-        .attribute_name(hash_key)
-        .key_type(KeyType::Hash)
-        .build()
-}
-
-fn make_range_key_schema(range_key: &str) -> KeySchemaElement {
+fn make_key_schema(key: &str, key_type: KeyType) -> KeySchemaElement {
     KeySchemaElement::builder()
-        .attribute_name(&range_key)
-        .key_type(KeyType::Range)
+        .attribute_name(key)
+        .key_type(key_type)
         .build()
+        .unwrap()
 }
 
 fn make_attr_definition(key: &str) -> AttributeDefinition {
@@ -49,24 +48,94 @@ fn make_attr_definition(key: &str) -> AttributeDefinition {
         .attribute_name(String::from(key))
         .attribute_type(ScalarAttributeType::S)
         .build()
+        .unwrap()
 }
 
+fn make_tags(kvs: &HashMap<String, String>) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = vec![];
+    for (k, v) in kvs {
+        tags.push(Tag::builder().key(k).value(v).build().unwrap());
+    }
+    tags
+}
 
-struct Schema {
-    hash_key: Option<KeySchemaElement>,
-    range_key: Option<KeySchemaElement>
+pub async fn table_exists(client: &Client, table_name: &str) -> bool {
+    let res = client.describe_table().table_name(table_name).send().await;
+    match res {
+        Ok(_) => true,
+        Err(_) => false,
+    }
 }
 
-pub async fn create_table(client: &Client, table_name: &str, schema: &Schema)  {
-    println!("Creating table {}", table_name);
+/// Key attributes of a table by key type (`HASH`, `RANGE`), None when
+/// the table does not exist
+pub async fn find_keys(client: &Client, table_name: &str) -> Option<HashMap<String, String>> {
     let res = client
+        .describe_table()
+        .table_name(table_name)
+        .send()
+        .await
+        .ok()?;
+    let mut h: HashMap<String, String> = HashMap::new();
+    if let Some(t) = res.table {
+        for k in t.key_schema.unwrap_or_default() {
+            h.insert(k.key_type.as_str().to_string(), k.attribute_name);
+        }
+    }
+    Some(h)
+}
+
+pub async fn create_table(
+    client: &Client,
+    table_name: &str,
+    hash_key: &str,
+    range_key: Option<String>,
+    tags: &HashMap<String, String>,
+) -> Result<(), String> {
+    let mut req = client
         .create_table()
         .table_name(table_name)
-        .key_schema(hash_key_schema)
-        .key_schema(range_key_schema)
-        .attribute_definitions(hash_key_attribute)
-        .attribute_definitions(range_key_attribute)
+        .key_schema(make_key_schema(hash_key, KeyType::Hash))
+        .attribute_definitions(make_attr_definition(hash_key))
         .billing_mode(BillingMode::PayPerRequest)
-        .send()
-        .await;
+        .set_tags(Some(make_tags(tags)));
+
+    if let Some(rk) = range_key {
+        req = req
+            .key_schema(make_key_schema(&rk, KeyType::Range))
+            .attribute_definitions(make_attr_definition(&rk));
+    }
+
+    let res = req.send().await;
+    match res {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+pub async fn find_or_create_table(
+    client: &Client,
+    table_name: &str,
+    hash_key: &str,
+    range_key: Option<String>,
+    tags: &HashMap<String, String>,
+) -> Result<(), String> {
+    if table_exists(client, table_name).await {
+        println!("Checking table: exists {}", table_name);
+        Ok(())
+    } else {
+        println!("Creating table {}", table_name);
+        create_table(client, table_name, hash_key, range_key, tags).await
+    }
+}
+
+pub async fn delete_table(client: &Client, table_name: &str) -> Result<(), String> {
+    if !table_exists(client, table_name).await {
+        return Ok(());
+    }
+    let res = client.delete_table().table_name(table_name).send().await;
+    match res {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
 }
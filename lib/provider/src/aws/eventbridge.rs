@@ -121,6 +121,10 @@ pub fn make_target(
             .set_input_transformer(input_transformer)
             .set_app_sync_parameters(appsync)
             .retry_policy(retry_policy),
+        "sqs" | "queue" => TargetBuilder::default()
+            .id(id)
+            .arn(arn)
+            .retry_policy(retry_policy),
         "channel" => TargetBuilder::default()
             .id(id)
             .arn(String::from(arn))
@@ -142,15 +146,15 @@ pub fn make_target(
     builder.build().unwrap()
 }
 
-pub async fn create_rule(
+pub async fn try_create_rule(
     client: &Client,
     bus: &str,
     rule_name: &str,
     pattern: &str,
     tags: &HashMap<String, String>,
-) -> String {
+) -> Result<String, String> {
     let tags = make_tags(tags.clone());
-    let r = client
+    let res = client
         .put_rule()
         .event_bus_name(s!(bus))
         .name(s!(rule_name))
@@ -158,11 +162,30 @@ pub async fn create_rule(
         .state(RuleState::Enabled)
         .set_tags(Some(tags))
         .send()
-        .await
-        .unwrap();
-    match r.rule_arn {
-        Some(p) => p,
-        None => panic!("oops"),
+        .await;
+    match res {
+        Ok(r) => match r.rule_arn {
+            Some(p) => Ok(p),
+            None => Err(format!("Failed to create rule {}", rule_name)),
+        },
+        Err(e) => Err(format!(
+            "Failed to create rule {}: {}",
+            rule_name,
+            e.into_service_error()
+        )),
+    }
+}
+
+pub async fn create_rule(
+    client: &Client,
+    bus: &str,
+    rule_name: &str,
+    pattern: &str,
+    tags: &HashMap<String, String>,
+) -> String {
+    match try_create_rule(client, bus, rule_name, pattern, tags).await {
+        Ok(arn) => arn,
+        Err(e) => panic!("{}", e),
     }
 }
 
@@ -177,6 +200,45 @@ pub async fn put_target(client: Client, bus: String, rule_name: String, target:
         .unwrap();
 }
 
+pub async fn try_put_targets(
+    client: &Client,
+    bus: &str,
+    rule_name: &str,
+    targets: Vec<Target>,
+) -> Result<(), String> {
+    let res = client
+        .put_targets()
+        .event_bus_name(s!(bus))
+        .rule(s!(rule_name))
+        .set_targets(Some(targets))
+        .send()
+        .await;
+    match res {
+        Ok(r) => {
+            let msgs: Vec<String> = r
+                .failed_entries
+                .unwrap_or_default()
+                .into_iter()
+                .map(|e| e.error_message.unwrap_or_default())
+                .collect();
+            if msgs.is_empty() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Failed to put targets on {}: {}",
+                    rule_name,
+                    msgs.join(", ")
+                ))
+            }
+        }
+        Err(e) => Err(format!(
+            "Failed to put targets on {}: {}",
+            rule_name,
+            e.into_service_error()
+        )),
+    }
+}
+
 pub async fn put_targets(client: &Client, bus: &str, rule_name: &str, targets: Vec<Target>) {
     client
         .put_targets()
@@ -211,6 +273,48 @@ pub async fn delete_rule(client: &Client, bus: &str, rule_name: &str) {
         .unwrap();
 }
 
+/// Removes the targets and then the rule. Errors are returned rather
+/// than raised so that teardown can carry on with other resources
+pub async fn try_delete_rule(
+    client: &Client,
+    bus: &str,
+    rule_name: &str,
+    target_ids: &[&str],
+) -> Result<(), String> {
+    if !target_ids.is_empty() {
+        let res = client
+            .remove_targets()
+            .event_bus_name(s!(bus))
+            .rule(s!(rule_name))
+            .set_ids(Some(target_ids.iter().map(|id| s!(*id)).collect()))
+            .force(true)
+            .send()
+            .await;
+        if let Err(e) = res {
+            return Err(format!(
+                "Failed to remove targets of {}: {}",
+                rule_name,
+                e.into_service_error()
+            ));
+        }
+    }
+    let res = client
+        .delete_rule()
+        .event_bus_name(s!(bus))
+        .name(s!(rule_name))
+        .force(true)
+        .send()
+        .await;
+    match res {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!(
+            "Failed to delete rule {}: {}",
+            rule_name,
+            e.into_service_error()
+        )),
+    }
+}
+
 pub async fn list_targets(client: &Client, bus: &str, rule_name: &str) -> Vec<String> {
     let res = client
         .list_targets_by_rule()
@@ -359,6 +463,31 @@ pub async fn put_event(
         .unwrap()
 }
 
+pub async fn try_put_event(
+    client: Client,
+    bus: &str,
+    detail_type: &str,
+    source: &str,
+    detail: &str,
+) -> Result<String, String> {
+    let event = make_event(bus, detail_type, source, detail);
+    let res = client.put_events().entries(event).send().await;
+    let entry = match res {
+        Ok(r) => r.entries.unwrap_or_default().into_iter().next(),
+        Err(e) => return Err(format!("Failed to put event: {}", e.into_service_error())),
+    };
+    match entry {
+        Some(e) => match e.event_id {
+            Some(id) => Ok(id),
+            None => Err(format!(
+                "Failed to put event: {}",
+                e.error_message.unwrap_or_default()
+            )),
+        },
+        None => Err(s!("Failed to put event")),
+    }
+}
+
 async fn create_bus(client: &Client, name: &str) {
     let res = client.create_event_bus().name(name).send().await.unwrap();
     println!("{:?}", &res);
@@ -381,3 +510,102 @@ pub async fn find_or_create_bus(client: &Client, name: &str) {
         }
     }
 }
+
+/// Event pattern and state of a rule
+pub async fn describe_rule(
+    client: &Client,
+    bus: &str,
+    rule_name: &str,
+) -> Option<(String, String)> {
+    let res = client
+        .describe_rule()
+        .event_bus_name(bus)
+        .name(rule_name)
+        .send()
+        .await;
+    match res {
+        Ok(r) => {
+            let state = match r.state {
+                Some(s) => s.as_str().to_string(),
+                None => s!(""),
+            };
+            Some((maybe_string(r.event_pattern, ""), state))
+        }
+        Err(_) => None,
+    }
+}
+
+pub async fn list_target_arns(client: &Client, bus: &str, rule_name: &str) -> Vec<String> {
+    let res = client
+        .list_targets_by_rule()
+        .event_bus_name(bus)
+        .rule(rule_name)
+        .send()
+        .await;
+    match res {
+        Ok(r) => r
+            .targets
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| t.arn)
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+pub async fn find_rule_arn(client: &Client, bus: &str, rule_name: &str) -> Option<String> {
+    let res = client
+        .describe_rule()
+        .event_bus_name(bus)
+        .name(rule_name)
+        .send()
+        .await;
+    match res {
+        Ok(r) => r.arn,
+        Err(_) => None,
+    }
+}
+
+pub async fn enable_rule(client: &Client, bus: &str, rule_name: &str) {
+    let _ = client
+        .enable_rule()
+        .event_bus_name(bus)
+        .name(rule_name)
+        .send()
+        .await;
+}
+
+pub async fn disable_rule(client: &Client, bus: &str, rule_name: &str) {
+    let _ = client
+        .disable_rule()
+        .event_bus_name(bus)
+        .name(rule_name)
+        .send()
+        .await;
+}
+
+pub async fn update_tags(client: &Client, arn: &str, tags: HashMap<String, String>) {
+    let _ = client
+        .tag_resource()
+        .resource_arn(arn)
+        .set_tags(Some(make_tags(tags)))
+        .send()
+        .await;
+}
+
+pub async fn get_tag(client: &Client, arn: &str, tag: &str) -> String {
+    let res = client.list_tags_for_resource().resource_arn(arn).send().await;
+    match res {
+        Ok(r) => {
+            for t in r.tags.unwrap_or_default() {
+                if t.key == tag {
+                    return t.value;
+                }
+            }
+            s!("")
+        }
+        Err(_) => s!(""),
+    }
+}
+
+pub type EventClient = Client;
@@ -249,6 +249,15 @@ pub async fn update_route(
     }
 }
 
+/// Key a route is deployed under
+pub fn route_key(method: &str, path: &str) -> String {
+    if path == "/" {
+        format!("{} {}", method, path)
+    } else {
+        strip(&format!("{} {}", method, path), "/")
+    }
+}
+
 pub async fn create_or_update_route(
     client: &Client,
     api_id: &str,
@@ -258,11 +267,7 @@ pub async fn create_or_update_route(
     authorizer_id: Option<String>,
     authorizer_kind: &str,
 ) {
-    let route_key = if path == "/" {
-        format!("{} {}", method, path)
-    } else {
-        strip(&format!("{} {}", method, path), "/")
-    };
+    let route_key = route_key(method, path);
     let target = format!("integrations/{}", integration_id);
     let maybe_route = find_route(client, api_id, &route_key).await;
 
@@ -555,6 +560,34 @@ pub async fn create_or_update_stage(
     }
 }
 
+/// Log group the stage writes access logs to, if access logging is on
+pub async fn find_access_log_group(client: &Client, api_id: &str, stage: &str) -> Option<String> {
+    let res = client
+        .get_stage()
+        .api_id(s!(api_id))
+        .stage_name(stage)
+        .send()
+        .await;
+
+    match res {
+        Ok(r) => match r.access_log_settings {
+            Some(s) => match s.destination_arn {
+                Some(arn) => Some(log_group_name(&arn)),
+                None => None,
+            },
+            None => None,
+        },
+        Err(_) => None,
+    }
+}
+
+fn log_group_name(arn: &str) -> String {
+    match arn.split_once(":log-group:") {
+        Some((_, group)) => strip(group, ":*"),
+        None => s!(arn),
+    }
+}
+
 pub async fn create_lambda_integration(
     client: &Client,
     api_id: &str,
@@ -562,57 +595,70 @@ pub async fn create_lambda_integration(
     role: &str,
     is_async: bool,
     request_params: HashMap<String, String>,
+    owner: &str,
 ) -> String {
-    lambda::create_or_update(client, api_id, target_arn, role, is_async, request_params).await
+    lambda::create_or_update(
+        client,
+        api_id,
+        target_arn,
+        role,
+        is_async,
+        request_params,
+        owner,
+    )
+    .await
 }
 
 pub async fn create_sfn_integration(
     client: &Client,
     api_id: &str,
-    name: &str,
+    route_key: &str,
     role: &str,
     is_async: bool,
     request_params: HashMap<String, String>,
+    owner: &str,
 ) -> String {
-    sfn::find_or_create(client, api_id, role, request_params, is_async, name).await
+    let existing = find_route_integration(client, api_id, route_key, sfn::SUBTYPE_PREFIX).await;
+    sfn::create_or_update(
+        client,
+        api_id,
+        existing,
+        role,
+        request_params,
+        is_async,
+        owner,
+    )
+    .await
 }
 
 pub async fn create_event_integration(
     client: &Client,
     api_id: &str,
-    name: &str,
+    route_key: &str,
     role: &str,
     request_params: HashMap<String, String>,
+    owner: &str,
 ) -> String {
-    eventbridge::find_or_create(client, api_id, role, request_params, name).await
+    let existing = find_route_integration(client, api_id, route_key, eventbridge::SUBTYPE).await;
+    eventbridge::create_or_update(client, api_id, existing, role, request_params, owner).await
 }
 
 pub async fn create_sqs_integration(
     client: &Client,
     api_id: &str,
-    name: &str,
+    route_key: &str,
     role: &str,
     request_params: HashMap<String, String>,
+    owner: &str,
 ) -> String {
-    sqs::find_or_create(client, api_id, role, request_params, name).await
+    let existing = find_route_integration(client, api_id, route_key, sqs::SUBTYPE).await;
+    sqs::create_or_update(client, api_id, existing, role, request_params, owner).await
 }
 
 pub async fn delete_lambda_integration(client: &Client, api_id: &str, target_arn: &str) {
     lambda::delete(client, api_id, target_arn).await
 }
 
-pub async fn delete_sfn_integration(client: &Client, api_id: &str, name: &str) {
-    sfn::delete(client, api_id, name).await
-}
-
-pub async fn delete_event_integration(client: &Client, api_id: &str, name: &str) {
-    eventbridge::delete(client, api_id, name).await
-}
-
-pub async fn delete_sqs_integration(client: &Client, api_id: &str, name: &str) {
-    sqs::delete(client, api_id, name).await
-}
-
 pub async fn find_api_id(client: &Client, name: &str) -> Option<String> {
     let r = client
         .get_apis()
@@ -945,3 +991,182 @@ pub async fn find_hosted_zone(client: &Client, domain_name: &str) -> Option<Stri
 }
 
 pub type GatewayCors = aws_sdk_apigatewayv2::types::Cors;
+
+/// Route keys of an api with the authorization type of each route
+pub async fn list_routes(client: &Client, api_id: &str) -> HashMap<String, String> {
+    let r = client
+        .get_routes()
+        .api_id(api_id.to_string())
+        .max_results(s!("2000"))
+        .send()
+        .await
+        .unwrap();
+    let mut h: HashMap<String, String> = HashMap::new();
+    for route in r.items.unwrap_or_default() {
+        let kind = match route.authorization_type {
+            Some(k) => k.as_str().to_string(),
+            None => s!("NONE"),
+        };
+        if let Some(key) = route.route_key {
+            h.insert(key, kind);
+        }
+    }
+    h
+}
+
+/// A deployed route, with the integration and authorizer it uses
+#[derive(Clone, Debug)]
+pub struct DeployedRoute {
+    pub key: String,
+    pub id: String,
+    pub integration_id: Option<String>,
+    pub authorizer_id: Option<String>,
+}
+
+pub async fn list_deployed_routes(client: &Client, api_id: &str) -> Vec<DeployedRoute> {
+    let mut xs: Vec<DeployedRoute> = vec![];
+    let mut token: Option<String> = None;
+    loop {
+        let r = client
+            .get_routes()
+            .api_id(api_id.to_string())
+            .set_next_token(token.clone())
+            .send()
+            .await;
+        let res = match r {
+            Ok(res) => res,
+            Err(_) => break,
+        };
+        for route in res.items.unwrap_or_default() {
+            let integration_id = match route.target {
+                Some(t) => t.strip_prefix("integrations/").map(|x| x.to_string()),
+                None => None,
+            };
+            if let (Some(key), Some(id)) = (route.route_key, route.route_id) {
+                xs.push(DeployedRoute {
+                    key: key,
+                    id: id,
+                    integration_id: integration_id,
+                    authorizer_id: route.authorizer_id,
+                });
+            }
+        }
+        match res.next_token {
+            Some(t) if !t.is_empty() => token = Some(t),
+            _ => break,
+        }
+    }
+    xs
+}
+
+/// A deployed integration. `owner` is the description tc stamps on the
+/// integrations it creates
+#[derive(Clone, Debug, Default)]
+pub struct DeployedIntegration {
+    pub uri: Option<String>,
+    pub subtype: Option<String>,
+    pub owner: Option<String>,
+}
+
+/// Integrations of an api, keyed by integration id
+pub async fn list_integrations(
+    client: &Client,
+    api_id: &str,
+) -> HashMap<String, DeployedIntegration> {
+    let mut h: HashMap<String, DeployedIntegration> = HashMap::new();
+    let mut token: Option<String> = None;
+    loop {
+        let r = client
+            .get_integrations()
+            .api_id(api_id.to_string())
+            .set_next_token(token.clone())
+            .send()
+            .await;
+        let res = match r {
+            Ok(res) => res,
+            Err(_) => break,
+        };
+        for int in res.items.unwrap_or_default() {
+            if let Some(id) = int.integration_id {
+                let d = DeployedIntegration {
+                    uri: int.integration_uri,
+                    subtype: int.integration_subtype,
+                    owner: int.description.filter(|x| !x.is_empty()),
+                };
+                h.insert(id, d);
+            }
+        }
+        match res.next_token {
+            Some(t) if !t.is_empty() => token = Some(t),
+            _ => break,
+        }
+    }
+    h
+}
+
+/// The integration the route with the given key targets, when it is of
+/// the given subtype and no other route uses it. Such an integration
+/// belongs to the route and can be updated or deleted with it
+pub async fn find_route_integration(
+    client: &Client,
+    api_id: &str,
+    route_key: &str,
+    subtype: &str,
+) -> Option<String> {
+    let routes = list_deployed_routes(client, api_id).await;
+    let id = routes
+        .iter()
+        .find(|r| r.key == route_key)?
+        .integration_id
+        .clone()?;
+    let shared = routes
+        .iter()
+        .filter(|r| r.integration_id.as_ref() == Some(&id))
+        .count()
+        > 1;
+    if shared {
+        return None;
+    }
+    let ints = list_integrations(client, api_id).await;
+    let kind = ints.get(&id)?.subtype.clone()?;
+    if kind.starts_with(subtype) {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+pub async fn delete_integration_by_id(client: &Client, api_id: &str, integration_id: &str) {
+    let _ = client
+        .delete_integration()
+        .api_id(s!(api_id))
+        .integration_id(s!(integration_id))
+        .send()
+        .await;
+}
+
+/// Names of the authorizers of an api, keyed by authorizer id
+pub async fn list_authorizers(client: &Client, api_id: &str) -> HashMap<String, String> {
+    let r = client.get_authorizers().api_id(s!(api_id)).send().await;
+    match r {
+        Ok(res) => res
+            .items
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|a| match (a.authorizer_id, a.name) {
+                (Some(id), Some(name)) => Some((id, name)),
+                _ => None,
+            })
+            .collect(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+pub async fn delete_authorizer_by_id(client: &Client, api_id: &str, authorizer_id: &str) {
+    let _ = client
+        .delete_authorizer()
+        .api_id(s!(api_id))
+        .authorizer_id(s!(authorizer_id))
+        .send()
+        .await;
+}
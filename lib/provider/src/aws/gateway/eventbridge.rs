@@ -9,43 +9,16 @@ use aws_sdk_apigatewayv2::{
 use kit::*;
 use std::collections::HashMap;
 
-async fn find(client: &Client, api_id: &str, int_name: &str) -> Option<String> {
-    let r = client
-        .get_integrations()
-        .api_id(api_id.to_string())
-        .max_results(s!("1000"))
-        .send()
-        .await
-        .unwrap();
-    let items = r.items;
-    match items {
-        Some(ints) => {
-            for int in ints.to_vec() {
-                match int.request_parameters {
-                    Some(req) => match req.get("Name") {
-                        Some(name) => {
-                            if name == int_name {
-                                return int.integration_id;
-                            }
-                        }
-                        None => (),
-                    },
-                    None => (),
-                }
-            }
-            return None;
-        }
-        None => None,
-    }
-}
+pub(super) const SUBTYPE: &str = "EventBridge-PutEvents";
 
 async fn create(
     client: &Client,
     api_id: &str,
     role_arn: &str,
-    request_parameters: HashMap<String, String>,
+    request_params: HashMap<String, String>,
+    owner: &str,
 ) -> Result<String, Error> {
-    let subtype = s!("EventBridge-PutEvents");
+    let subtype = s!(SUBTYPE);
 
     let res = client
         .create_integration()
@@ -55,7 +28,8 @@ async fn create(
         .payload_format_version(s!("1.0"))
         .integration_type(IntegrationType::AwsProxy)
         .integration_subtype(subtype)
-        .set_request_parameters(Some(request_parameters))
+        .description(s!(owner))
+        .set_request_parameters(Some(request_params))
         .send()
         .await;
     match res {
@@ -64,33 +38,53 @@ async fn create(
     }
 }
 
-pub async fn find_or_create(
+async fn update(
     client: &Client,
+    id: &str,
     api_id: &str,
     role_arn: &str,
-    request_parameters: HashMap<String, String>,
-    name: &str,
-) -> String {
-    let maybe_int = find(client, api_id, name).await;
-    match maybe_int {
-        Some(id) => id,
-        _ => create(client, api_id, role_arn, request_parameters)
-            .await
-            .unwrap(),
+    request_params: HashMap<String, String>,
+    owner: &str,
+) -> Result<String, Error> {
+    let subtype = s!(SUBTYPE);
+
+    let res = client
+        .update_integration()
+        .api_id(s!(api_id))
+        .integration_id(id)
+        .connection_type(ConnectionType::Internet)
+        .credentials_arn(s!(role_arn))
+        .payload_format_version(s!("1.0"))
+        .integration_type(IntegrationType::AwsProxy)
+        .integration_subtype(subtype)
+        .description(s!(owner))
+        .set_request_parameters(Some(request_params))
+        .send()
+        .await;
+    match res {
+        Ok(r) => Ok(r.integration_id.unwrap()),
+        Err(e) => panic!("{:?}", e),
     }
 }
 
-pub async fn delete(client: &Client, api_id: &str, name: &str) {
-    let maybe_int = find(client, api_id, name).await;
-    match maybe_int {
+/// Updates the integration the route already uses, so changed request
+/// parameters take effect, or creates one
+pub async fn create_or_update(
+    client: &Client,
+    api_id: &str,
+    existing: Option<String>,
+    role_arn: &str,
+    request_params: HashMap<String, String>,
+    owner: &str,
+) -> String {
+    match existing {
         Some(id) => {
-            let _ = client
-                .delete_integration()
-                .api_id(s!(api_id))
-                .integration_id(id)
-                .send()
-                .await;
+            tracing::debug!("Found Event Integration {}", id);
+            let _ = update(client, &id, api_id, role_arn, request_params, owner).await;
+            id
         }
-        _ => (),
+        None => create(client, api_id, role_arn, request_params, owner)
+            .await
+            .unwrap(),
     }
 }
@@ -59,6 +59,7 @@ async fn create(
     role_arn: &str,
     is_async: bool,
     request_params: HashMap<String, String>,
+    owner: &str,
 ) -> Result<String, Error> {
     let req_params = make_request_params(request_params, is_async);
     let res = client
@@ -69,6 +70,7 @@ async fn create(
         .payload_format_version(s!("2.0"))
         .integration_type(IntegrationType::AwsProxy)
         .integration_uri(lambda_arn)
+        .description(s!(owner))
         .set_request_parameters(req_params)
         .send()
         .await;
@@ -86,6 +88,7 @@ async fn update(
     role_arn: &str,
     is_async: bool,
     request_params: HashMap<String, String>,
+    owner: &str,
 ) -> Result<String, Error> {
     let req_params = make_request_params(request_params, is_async);
     let res = client
@@ -97,6 +100,7 @@ async fn update(
         .payload_format_version(s!("2.0"))
         .integration_type(IntegrationType::AwsProxy)
         .integration_uri(lambda_arn)
+        .description(s!(owner))
         .set_request_parameters(req_params)
         .send()
         .await;
@@ -113,6 +117,7 @@ pub async fn create_or_update(
     role_arn: &str,
     is_async: bool,
     request_params: HashMap<String, String>,
+    owner: &str,
 ) -> String {
     let maybe_int = find(client, api_id, lambda_arn).await;
     match maybe_int {
@@ -126,6 +131,7 @@ pub async fn create_or_update(
                 role_arn,
                 is_async,
                 request_params,
+                owner,
             )
             .await;
             id
@@ -138,6 +144,7 @@ pub async fn create_or_update(
                 role_arn,
                 is_async,
                 request_params,
+                owner,
             )
             .await
             .unwrap();
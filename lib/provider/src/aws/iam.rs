@@ -70,7 +70,7 @@ impl Role {
         self.wait_until_attached(client).await;
         // FIXME: iam is eventually consistent. There is no way to know if the role is really
         // useable
-        u::sleep(4000);
+        u::sleep_async(4000).await;
 
         let _ = log_update.render(&format!(
             "Creating role {} ({})",
@@ -310,7 +310,7 @@ impl Role {
         let mut ready = false;
         while !ready {
             ready = self.is_policy_attachable(client).await;
-            u::sleep(1000)
+            u::sleep_async(1000).await
         }
     }
 
@@ -318,7 +318,7 @@ impl Role {
         let mut ready = false;
         while !ready {
             ready = self.is_policy_attached(client).await;
-            u::sleep(2000)
+            u::sleep_async(2000).await
         }
     }
 
@@ -326,7 +326,7 @@ impl Role {
         let mut ready = false;
         while !ready {
             ready = !self.is_policy_attached(client).await;
-            u::sleep(2000)
+            u::sleep_async(2000).await
         }
     }
 }
@@ -417,3 +417,18 @@ pub async fn list_roles(client: &Client) -> Vec<(String, String)> {
 }
 
 pub type IamClient = Client;
+
+/// The trust policy of a role, decoded
+pub async fn find_role_trust(client: &Client, name: &str) -> Option<String> {
+    let res = client.get_role().role_name(name).send().await;
+    match res {
+        Ok(r) => match r.role {
+            Some(role) => match role.assume_role_policy_document {
+                Some(doc) => Some(urlencoding::decode(&doc).expect("UTF-8").to_string()),
+                None => None,
+            },
+            None => None,
+        },
+        Err(_) => None,
+    }
+}
@@ -11,12 +11,14 @@ use aws_sdk_lambda::{
     },
     primitives::Blob,
     types::{
+        AliasRoutingConfiguration,
         Architecture,
         DeadLetterConfig,
         DestinationConfig,
         Environment,
         FileSystemConfig,
         FunctionCode,
+        FunctionResponseType,
         InvocationType,
         LastUpdateStatus,
         LogType,
@@ -191,6 +193,7 @@ pub fn make_snapstart(enable_snap: bool) -> Option<SnapStart> {
 pub fn make_runtime(lang: &str) -> Runtime {
     match lang {
         "java11" => Runtime::Java11,
+        "java21" => Runtime::Java21,
         "go" => "provided.al2023".into(),
         "python3.7" => Runtime::Python37,
         "python3.8" => Runtime::Python38,
@@ -298,7 +301,7 @@ impl Function {
         while state != LastUpdateStatus::Successful {
             state = self.get_update_status(client, name).await;
             let _ = log_update.render(&format!("{} state {}", name, pp_status(&state).blue()));
-            sleep(1000)
+            sleep_async(1000).await
         }
         let _ = log_update.render(&format!("{} state {}", name, pp_status(&state).green()));
     }
@@ -349,7 +352,7 @@ impl Function {
                 name,
                 pp_state(&state).blue()
             ));
-            sleep(800)
+            sleep_async(800).await
         }
         let _ = log_update.render(&format!(
             "Checking function {} ({})",
@@ -387,7 +390,7 @@ impl Function {
         let mut state: LastUpdateStatus = LastUpdateStatus::InProgress;
         while state != LastUpdateStatus::Successful {
             state = self.get_update_status(client, &self.name).await;
-            sleep(800)
+            sleep_async(800).await
         }
 
         let f = self.clone();
@@ -409,7 +412,7 @@ impl Function {
 
         while state != LastUpdateStatus::Successful {
             state = self.get_update_status(client, &self.name).await;
-            sleep(800)
+            sleep_async(800).await
         }
         let id = match res {
             Ok(r) => Ok(r.function_arn.unwrap_or_default()),
@@ -474,7 +477,7 @@ impl Function {
                 name,
                 pp_status(&state).blue()
             ));
-            sleep(500)
+            sleep_async(500).await
         }
         let _ = log_update.render(&format!(
             "Checking function {} ({})",
@@ -561,7 +564,7 @@ impl Function {
                         Err(_) => break,
                     }
                     if state == LastUpdateStatus::InProgress {
-                        sleep(2000);
+                        sleep_async(2000).await;
                     }
                     tries += 1;
                     let _ = log_update.render(&format!(
@@ -569,7 +572,7 @@ impl Function {
                         &self.name,
                         pp_status(&state).blue()
                     ));
-                    sleep(2000)
+                    sleep_async(2000).await
                 }
 
                 let r = self.update_code(client, &arn).await;
@@ -617,7 +620,7 @@ impl Function {
                             pp_state(&state).blue()
                         ));
                     }
-                    sleep(500)
+                    sleep_async(500).await
                 }
                 if state == State::Failed {
                     let _ = log_update.render(&format!(
@@ -695,14 +698,20 @@ impl Function {
             .await;
     }
 
-    pub async fn publish_version(&self, client: &Client) {
+    /// Publishes the deployed code and config as a new version, or
+    /// returns the latest version when nothing changed
+    pub async fn publish(&self, client: &Client) -> String {
         self.wait(client, &self.name).await;
         let res = client
             .publish_version()
             .function_name(&self.name)
             .send()
             .await;
-        let version = res.unwrap().version.unwrap();
+        res.unwrap().version.unwrap()
+    }
+
+    pub async fn publish_version(&self, client: &Client) {
+        let version = self.publish(client).await;
 
         let maybe_alias = self.find_alias(client).await;
         match maybe_alias {
@@ -735,6 +744,15 @@ pub async fn add_permission(
     Ok(())
 }
 
+pub async fn remove_permission(client: &Client, name: &str, statement_id: &str) {
+    let _ = client
+        .remove_permission()
+        .function_name(name.to_string())
+        .statement_id(s!(statement_id))
+        .send()
+        .await;
+}
+
 pub async fn add_permission_basic(
     client: Client,
     name: &str,
@@ -805,17 +823,54 @@ async fn find_event_source(client: &Client, name: &str, source_arn: &str) -> Opt
     }
 }
 
-pub async fn create_event_source(client: &Client, name: &str, source_arn: &str) {
+/// Batch settings of an event source mapping
+#[derive(Clone, Debug)]
+pub struct BatchConfig {
+    pub size: i32,
+    pub window: Option<i32>,
+    pub partial_failures: bool,
+}
+
+fn response_types(partial_failures: bool) -> Vec<FunctionResponseType> {
+    match partial_failures {
+        true => vec![FunctionResponseType::ReportBatchItemFailures],
+        false => vec![],
+    }
+}
+
+pub async fn create_event_source(
+    client: &Client,
+    name: &str,
+    source_arn: &str,
+    batch: &BatchConfig,
+) {
     let maybe_es = find_event_source(client, name, source_arn).await;
     match maybe_es {
-        Some(_) => println!("Event source mapping exists, skipping"),
+        Some(uuid) => {
+            println!("Event source mapping exists, updating batch config");
+            let r = client
+                .update_event_source_mapping()
+                .uuid(uuid)
+                .batch_size(batch.size)
+                .set_maximum_batching_window_in_seconds(batch.window)
+                .set_function_response_types(Some(response_types(batch.partial_failures)))
+                .send()
+                .await;
+
+            match r {
+                Ok(_) => (),
+                Err(_) => panic!("{:?}", r),
+            }
+        }
         None => {
             let r = client
                 .create_event_source_mapping()
                 .function_name(s!(name))
                 .enabled(true)
                 .event_source_arn(s!(source_arn))
-                .batch_size(1)
+                .batch_size(batch.size)
+                .set_maximum_batching_window_in_seconds(batch.window)
+                .set_function_response_types(Some(response_types(batch.partial_failures)))
                 .send()
                 .await;
 
@@ -895,6 +950,11 @@ pub struct Config {
     pub mem_size: i32,
     pub role: String,
     pub package_type: String,
+    pub runtime: String,
+    pub handler: String,
+    pub architecture: String,
+    pub layers: Vec<String>,
+    pub environment: HashMap<String, String>,
 }
 
 /// Update only the IAM role on an existing lambda. Idempotent: if the
@@ -931,7 +991,7 @@ pub async fn update_role(client: &Client, name: &str, role_arn: &str) -> Result<
             Err(_) => break,
         }
         if state == LastUpdateStatus::InProgress {
-            sleep(800);
+            sleep_async(800).await;
         }
         tries += 1;
     }
@@ -955,12 +1015,33 @@ pub async fn find_config(client: &Client, name: &str) -> Option<Config> {
         .await;
     match r {
         Ok(res) => {
+            let environment = match res.environment {
+                Some(e) => e.variables.unwrap_or_default(),
+                None => HashMap::new(),
+            };
+            let architecture = match res.architectures {
+                Some(xs) => match xs.first() {
+                    Some(a) => a.as_str().to_string(),
+                    None => s!(""),
+                },
+                None => s!(""),
+            };
             let cfg = Config {
                 code_size: res.code_size,
                 timeout: res.timeout.unwrap(),
                 mem_size: res.memory_size.unwrap(),
                 role: res.role.unwrap(),
                 package_type: res.package_type.unwrap().to_string(),
+                runtime: maybe_string(res.runtime.map(|r| r.as_str().to_string()), ""),
+                handler: maybe_string(res.handler, ""),
+                architecture: architecture,
+                layers: res
+                    .layers
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|l| l.arn)
+                    .collect(),
+                environment: environment,
             };
             Some(cfg)
         }
@@ -1082,9 +1163,13 @@ pub async fn update_destination(client: &Client, name: &str, target_arn: &str) {
 }
 
 pub async fn find_alias_arn(client: &Client, name: &str) -> Option<String> {
+    find_named_alias_arn(client, name, name).await
+}
+
+pub async fn find_named_alias_arn(client: &Client, name: &str, alias: &str) -> Option<String> {
     let res = client
         .get_alias()
-        .name(name)
+        .name(alias)
         .function_name(name)
         .send()
         .await;
@@ -1094,4 +1179,149 @@ pub async fn find_alias_arn(client: &Client, name: &str) -> Option<String> {
     }
 }
 
+/// Version an alias points to, None when the alias does not exist
+pub async fn find_alias_version(client: &Client, name: &str, alias: &str) -> Option<String> {
+    let res = client
+        .get_alias()
+        .name(alias)
+        .function_name(name)
+        .send()
+        .await;
+    match res {
+        Ok(r) => r.function_version,
+        Err(_) => None,
+    }
+}
+
+/// Points an alias at `version`, sending a `weight` (0 to 1) of the
+/// traffic to the `canary` version when given
+pub async fn route_alias(
+    client: &Client,
+    name: &str,
+    alias: &str,
+    version: &str,
+    canary: Option<(&str, f64)>,
+) -> Result<(), String> {
+    let weights = match canary {
+        Some((v, w)) => Some(HashMap::from([(s!(v), w)])),
+        None => Some(HashMap::new()),
+    };
+    let routing = AliasRoutingConfiguration::builder()
+        .set_additional_version_weights(weights)
+        .build();
+
+    let res = match find_alias_version(client, name, alias).await {
+        Some(_) => client
+            .update_alias()
+            .name(alias)
+            .function_name(name)
+            .function_version(version)
+            .routing_config(routing)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("{:?}", e)),
+        None => client
+            .create_alias()
+            .name(alias)
+            .function_name(name)
+            .function_version(version)
+            .routing_config(routing)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("{:?}", e)),
+    };
+    res
+}
+
+/// Points `$LATEST` back at the code of a published version, for callers
+/// that invoke the unqualified arn
+pub async fn restore_code(client: &Client, name: &str, version: &str) -> Result<(), String> {
+    let res = client
+        .get_function()
+        .function_name(name)
+        .qualifier(version)
+        .send()
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    let code = match res.code {
+        Some(c) => c,
+        None => return Err(format!("No code for {}:{}", name, version)),
+    };
+    let req = client.update_function_code().function_name(name);
+    let req = match (code.image_uri, code.location) {
+        (Some(uri), _) => req.image_uri(uri),
+        (None, Some(url)) => {
+            let path = format!("/tmp/tc/restore-{}-{}.zip", name, version);
+            kit::mkdir("/tmp/tc");
+            kit::download(&url, HashMap::new(), &path).await;
+            let bytes = kit::read_bytes(&path);
+            kit::sh(&format!("rm -f {}", &path), "/tmp");
+            req.zip_file(Blob::new(bytes))
+        }
+        (None, None) => return Err(format!("No code for {}:{}", name, version)),
+    };
+    req.send().await.map(|_| ()).map_err(|e| format!("{:?}", e))
+}
+
+async fn wait_until_updated(client: &Client, name: &str) {
+    loop {
+        let res = client
+            .get_function_configuration()
+            .function_name(name)
+            .send()
+            .await;
+        match res {
+            Ok(c) if c.last_update_status == Some(LastUpdateStatus::InProgress) => {
+                sleep_async(800).await
+            }
+            _ => break,
+        }
+    }
+}
+
+/// Publishes `$LATEST` as it is and returns the version, so that it can
+/// be restored later. Publishing unchanged code returns the latest version
+pub async fn publish_latest(client: &Client, name: &str) -> Result<String, String> {
+    wait_until_updated(client, name).await;
+    let res = client
+        .publish_version()
+        .function_name(name)
+        .send()
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    match res.version {
+        Some(v) => Ok(v),
+        None => Err(format!("No version published for {}", name)),
+    }
+}
+
+/// Points `$LATEST` back at the configuration and code of a published
+/// version
+pub async fn restore_version(client: &Client, name: &str, version: &str) -> Result<(), String> {
+    let cfg = client
+        .get_function_configuration()
+        .function_name(name)
+        .qualifier(version)
+        .send()
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    let vars = cfg.environment.and_then(|e| e.variables);
+    wait_until_updated(client, name).await;
+    client
+        .update_function_configuration()
+        .function_name(name)
+        .set_memory_size(cfg.memory_size)
+        .set_timeout(cfg.timeout)
+        .set_handler(cfg.handler)
+        .set_role(cfg.role)
+        .environment(Environment::builder().set_variables(vars).build())
+        .send()
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    wait_until_updated(client, name).await;
+    restore_code(client, name, version).await
+}
+
 pub type LambdaClient = Client;
@@ -0,0 +1,75 @@
+use super::constants;
+use crate::Auth;
+use aws_sdk_cloudwatch::{
+    Client,
+    config,
+    config::retry::{
+        RetryConfig,
+        RetryMode,
+    },
+    primitives::DateTime,
+    types::{
+        Dimension,
+        Statistic,
+    },
+};
+use kit::*;
+
+pub async fn make_client(auth: &Auth) -> Client {
+    let shared_config = &auth.aws_config;
+    Client::from_conf(
+        config::Builder::from(shared_config)
+            .behavior_version(constants::behavior_version())
+            .timeout_config(constants::timeout_config())
+            .retry_config(
+                RetryConfig::standard()
+                    .with_retry_mode(RetryMode::Adaptive)
+                    .with_max_attempts(constants::MAX_ATTEMPTS)
+                    .with_initial_backoff(constants::INITIAL_BACKOFF)
+                    .with_max_backoff(constants::MAX_BACKOFF),
+            )
+            .build(),
+    )
+}
+
+fn make_dimensions(dimensions: &[(&str, &str)]) -> Vec<Dimension> {
+    dimensions
+        .iter()
+        .map(|(k, v)| Dimension::builder().name(s!(*k)).value(s!(*v)).build())
+        .collect()
+}
+
+/// Period of the datapoints, in seconds
+pub const PERIOD: i32 = 60;
+
+/// Sum of a metric between `start` and `end` (ms since the epoch)
+pub async fn sum(
+    client: &Client,
+    namespace: &str,
+    metric: &str,
+    dimensions: &[(&str, &str)],
+    start: i64,
+    end: i64,
+) -> Result<f64, String> {
+    let res = client
+        .get_metric_statistics()
+        .namespace(s!(namespace))
+        .metric_name(s!(metric))
+        .set_dimensions(Some(make_dimensions(dimensions)))
+        .start_time(DateTime::from_millis(start))
+        .end_time(DateTime::from_millis(end))
+        .period(PERIOD)
+        .statistics(Statistic::Sum)
+        .send()
+        .await;
+
+    match res {
+        Ok(r) => Ok(r
+            .datapoints
+            .unwrap_or_default()
+            .iter()
+            .map(|d| d.sum.unwrap_or_default())
+            .sum()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
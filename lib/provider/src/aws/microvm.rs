@@ -141,7 +141,7 @@ impl MicroVmImage {
         while state != MicrovmImageState::Created {
             state = self.get_state(client, id).await;
             let _ = log_update.render(&format!("{} state {}", self.name, pp_status(&state).blue()));
-            sleep(10000)
+            sleep_async(10000).await
         }
     }
 
@@ -220,7 +220,7 @@ pub async fn terminate(client: &Client, microvm_id: &str) {
         let run = get_microvm(client, &microvm_id).await;
         state = run.state;
         let _ = log_update.render(&format!("microvm state {}", pp_vm_status(&state).red()));
-        sleep(10000)
+        sleep_async(10000).await
     }
 }
 
@@ -345,7 +345,7 @@ pub async fn delete_image(client: &Client, image_name: &str) {
         while state != MicrovmImageState::Deleted {
             state = get_image_state(client, &image_id).await;
             let _ = log_update.render(&format!("microvm image state {}", pp_status(&state).red()));
-            sleep(10000)
+            sleep_async(10000).await
         }
     } else {
         println!("No Image found {}", image_name);
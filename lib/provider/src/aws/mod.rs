@@ -8,12 +8,14 @@ pub mod cloudwatch;
 pub mod codeartifact;
 pub mod cognito;
 mod constants;
+pub mod dynamodb;
 pub mod ecr;
 pub mod efs;
 pub mod eventbridge;
 pub mod gateway;
 pub mod iam;
 pub mod lambda;
+pub mod metrics;
 pub mod layer;
 pub mod microvm;
 pub mod resourcetag;
@@ -70,6 +72,24 @@ impl Auth {
             .await
     }
 
+    /// Auth that never talks to AWS, for emulating topologies offline
+    pub fn local(profile: Option<String>) -> Auth {
+        let region = sts::get_region();
+        Auth {
+            name: profile.unwrap_or("default".to_string()),
+            assume_role: None,
+            aws_config: SdkConfig::builder()
+                .region(aws_config::Region::new(region.clone()))
+                .build(),
+            account: String::from("000000000000"),
+            region: region,
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.account == "000000000000"
+    }
+
     pub async fn assume(&self, profile: Option<String>, assume_role: Option<String>) -> Auth {
         match profile {
             Some(_) => match std::env::var("TC_ASSUME_ROLE") {
@@ -13,6 +13,7 @@ use aws_sdk_resourcegroupstagging::{
         builders::TagFilterBuilder,
     },
 };
+use std::collections::HashMap;
 
 pub async fn make_client(auth: &Auth) -> Client {
     let shared_config = &auth.aws_config;
@@ -193,3 +194,82 @@ pub async fn get_all_resources(client: &Client) -> Vec<(String, String, String)>
     }
     arns
 }
+
+/// Arns of resources carrying all of the given tags
+pub async fn get_resources_by_tags(client: &Client, tags: &[(&str, &str)]) -> Vec<String> {
+    let filters: Vec<TagFilter> = tags.iter().map(|(k, v)| make_filters(k, v)).collect();
+    let mut arns: Vec<String> = vec![];
+    let mut token: Option<String> = None;
+    loop {
+        let res = client
+            .get_resources()
+            .set_pagination_token(token.clone())
+            .resources_per_page(100)
+            .set_tag_filters(Some(filters.clone()))
+            .send()
+            .await;
+        let res = match res {
+            Ok(r) => r,
+            Err(_) => break,
+        };
+        for m in res.resource_tag_mapping_list.unwrap_or_default() {
+            arns.push(m.resource_arn.unwrap_or_default());
+        }
+        match res.pagination_token {
+            Some(t) if !t.is_empty() => token = Some(t),
+            _ => break,
+        }
+    }
+    arns
+}
+
+async fn get_resources_with_tags(
+    client: &Client,
+    filters: Vec<TagFilter>,
+) -> Result<Vec<(String, HashMap<String, String>)>, String> {
+    let mut resources: Vec<(String, HashMap<String, String>)> = vec![];
+    let mut token: Option<String> = None;
+    loop {
+        let res = client
+            .get_resources()
+            .set_pagination_token(token.clone())
+            .resources_per_page(100)
+            .set_tag_filters(Some(filters.clone()))
+            .send()
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+        for m in res.resource_tag_mapping_list.unwrap_or_default() {
+            let tags: HashMap<String, String> = m
+                .tags
+                .unwrap_or_default()
+                .into_iter()
+                .map(|t| (t.key, t.value))
+                .collect();
+            resources.push((m.resource_arn.unwrap_or_default(), tags));
+        }
+        match res.pagination_token {
+            Some(t) if !t.is_empty() => token = Some(t),
+            _ => break,
+        }
+    }
+    Ok(resources)
+}
+
+/// Resources carrying the given tag key, whatever its value, with all
+/// their tags
+pub async fn get_tagged_resources(
+    client: &Client,
+    key: &str,
+) -> Result<Vec<(String, HashMap<String, String>)>, String> {
+    let filters = TagFilterBuilder::default().key(key.to_string()).build();
+    get_resources_with_tags(client, vec![filters]).await
+}
+
+/// Resources carrying all of the given tags, with all their tags
+pub async fn get_resource_tags(
+    client: &Client,
+    tags: &[(&str, &str)],
+) -> Result<Vec<(String, HashMap<String, String>)>, String> {
+    let filters: Vec<TagFilter> = tags.iter().map(|(k, v)| make_filters(k, v)).collect();
+    get_resources_with_tags(client, filters).await
+}
@@ -13,13 +13,20 @@ use aws_sdk_s3::{
     types::{
         BucketLocationConstraint,
         CreateBucketConfiguration,
+        Delete,
         ObjectAttributes,
+        ObjectIdentifier,
+        Tag,
+        Tagging,
         builders::CreateBucketConfigurationBuilder,
     },
 };
 use kit as u;
 use kit::*;
-use std::path::Path;
+use std::{
+    collections::HashMap,
+    path::Path,
+};
 use walkdir::WalkDir;
 
 pub async fn make_client(auth: &Auth) -> Client {
@@ -116,7 +123,7 @@ pub async fn get_bucket_policy(client: &Client, bucket: &str) -> Option<String>
     }
 }
 
-async fn bucket_exists(client: &Client, bucket: &str) -> bool {
+pub async fn bucket_exists(client: &Client, bucket: &str) -> bool {
     let res = client.head_bucket().bucket(bucket).send().await;
     match res {
         Ok(_) => true,
@@ -130,21 +137,62 @@ fn make_bucket_cfg() -> CreateBucketConfiguration {
         .build()
 }
 
-async fn create_bucket(client: &Client, bucket: &str) {
+fn make_tagging(kvs: &HashMap<String, String>) -> Tagging {
+    let mut tags: Vec<Tag> = vec![];
+    for (k, v) in kvs {
+        tags.push(Tag::builder().key(k).value(v).build().unwrap());
+    }
+    Tagging::builder().set_tag_set(Some(tags)).build().unwrap()
+}
+
+async fn tag_bucket(
+    client: &Client,
+    bucket: &str,
+    tags: &HashMap<String, String>,
+) -> Result<(), String> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+    let res = client
+        .put_bucket_tagging()
+        .bucket(bucket)
+        .tagging(make_tagging(tags))
+        .send()
+        .await;
+    match res {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+async fn create_bucket(
+    client: &Client,
+    bucket: &str,
+    tags: &HashMap<String, String>,
+) -> Result<(), String> {
     let cfg = make_bucket_cfg();
     println!("Creating bucket {}", bucket);
-    let _ = client
+    let res = client
         .create_bucket()
         .bucket(bucket)
         .create_bucket_configuration(cfg)
         .send()
-        .await
-        .unwrap();
+        .await;
+    match res {
+        Ok(_) => tag_bucket(client, bucket, tags).await,
+        Err(e) => Err(format!("{:?}", e)),
+    }
 }
 
-pub async fn find_or_create_bucket(client: &Client, bucket: &str) {
-    if !bucket_exists(client, bucket).await {
-        create_bucket(client, bucket).await
+pub async fn find_or_create_bucket(
+    client: &Client,
+    bucket: &str,
+    tags: &HashMap<String, String>,
+) -> Result<(), String> {
+    if bucket_exists(client, bucket).await {
+        Ok(())
+    } else {
+        create_bucket(client, bucket, tags).await
     }
 }
 
@@ -163,6 +211,39 @@ pub async fn get_str(client: &Client, bucket: &str, key: &str) -> String {
     std::str::from_utf8(&bytes).unwrap().to_string()
 }
 
+pub async fn try_get_str(client: &Client, bucket: &str, key: &str) -> Result<String, String> {
+    tracing::debug!("s3:getObject s3://{}/{}", bucket, key);
+    let res = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .response_content_type("application/json")
+        .send()
+        .await;
+    let uri = format!("s3://{}/{}", bucket, key);
+    let result = match res {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Cannot read {}: {}", uri, e.into_service_error())),
+    };
+    match result.body.collect().await {
+        Ok(data) => match String::from_utf8(data.into_bytes().to_vec()) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(format!("{} is not utf-8", uri)),
+        },
+        Err(e) => Err(format!("Cannot read {}: {}", uri, e)),
+    }
+}
+
+pub fn try_parts_of(uri: &str) -> Result<(String, String), String> {
+    match s3uri::from_uri(uri) {
+        Ok(u) => match u.key {
+            Some(key) => Ok((u.bucket, key.to_string())),
+            None => Err(format!("{} has no key", uri)),
+        },
+        Err(_) => Err(format!("Invalid s3 uri {}", uri)),
+    }
+}
+
 pub fn parts_of(uri: &str) -> (String, String) {
     let uri = s3uri::from_uri(uri).unwrap();
     (uri.bucket, uri.key.unwrap().to_string())
@@ -199,6 +280,21 @@ pub async fn upload_file(client: &Client, bucket: &str, file: &str, key: &str) {
         .unwrap();
 }
 
+pub async fn download_file(client: &Client, bucket: &str, key: &str, file: &str) -> bool {
+    tracing::debug!("s3:getObject s3://{}/{}", bucket, key);
+    let res = client.get_object().bucket(bucket).key(key).send().await;
+    match res {
+        Ok(r) => match r.body.collect().await {
+            Ok(data) => {
+                u::write_bytes(file, data.into_bytes().to_vec());
+                true
+            }
+            Err(_) => false,
+        },
+        Err(_) => false,
+    }
+}
+
 pub async fn get_object_size(client: &Client, bucket: &str, key: &str) -> Option<i64> {
     let res = client
         .get_object_attributes()
@@ -213,3 +309,55 @@ pub async fn get_object_size(client: &Client, bucket: &str, key: &str) -> Option
         Err(_) => None,
     }
 }
+
+async fn empty_bucket(client: &Client, bucket: &str) -> Result<(), String> {
+    let mut token: Option<String> = None;
+    loop {
+        let res = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .set_continuation_token(token)
+            .send()
+            .await;
+        let page = match res {
+            Ok(r) => r,
+            Err(e) => return Err(format!("{:?}", e)),
+        };
+        let ids: Vec<ObjectIdentifier> = page
+            .contents()
+            .iter()
+            .filter_map(|o| o.key())
+            .map(|k| ObjectIdentifier::builder().key(k).build().unwrap())
+            .collect();
+        if !ids.is_empty() {
+            let delete = Delete::builder().set_objects(Some(ids)).build().unwrap();
+            let res = client
+                .delete_objects()
+                .bucket(bucket)
+                .delete(delete)
+                .send()
+                .await;
+            if let Err(e) = res {
+                return Err(format!("{:?}", e));
+            }
+        }
+        match page.next_continuation_token() {
+            Some(t) => token = Some(t.to_string()),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Deletes the bucket along with its objects
+pub async fn delete_bucket(client: &Client, bucket: &str) -> Result<(), String> {
+    if !bucket_exists(client, bucket).await {
+        return Ok(());
+    }
+    println!("Deleting bucket {}", bucket);
+    empty_bucket(client, bucket).await?;
+    let res = client.delete_bucket().bucket(bucket).send().await;
+    match res {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
@@ -183,6 +183,24 @@ pub async fn get_schedule(client: &Client, name: &str, group: &str) -> Result<Op
     }
 }
 
+/// Expression and target arn of a schedule, None when it does not exist
+pub async fn find_schedule(client: &Client, name: &str, group: &str) -> Option<(String, String)> {
+    let r = client
+        .get_schedule()
+        .name(name)
+        .group_name(group)
+        .send()
+        .await;
+    match r {
+        Ok(res) => {
+            let expression = res.schedule_expression.unwrap_or_default();
+            let target = res.target.map(|t| t.arn).unwrap_or_default();
+            Some((expression, target))
+        }
+        Err(_) => None,
+    }
+}
+
 pub async fn create_or_update_schedule(
     client: &Client,
     group: &str,
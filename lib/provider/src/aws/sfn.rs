@@ -161,7 +161,7 @@ impl StateMachine {
                         &name,
                         state.as_str().blue()
                     ));
-                    sleep(500)
+                    sleep_async(500).await
                 }
                 let _ = log_update.render(&format!(
                     "Checking state {} ({})",
@@ -189,7 +189,7 @@ impl StateMachine {
 
         // BAFFLING: sfn update is eventually consistent. Active state needs a second or more to
         // reflect
-        sleep(1000);
+        sleep_async(1000).await;
         println!("Updating state {} (definition)", &self.name);
 
         self.client
@@ -257,7 +257,7 @@ impl StateMachine {
                 name,
                 state.as_str().blue()
             ));
-            sleep(500)
+            sleep_async(500).await
         }
         let _ = log_update.render(&format!(
             "Checking states: {} ({})",
@@ -399,3 +399,144 @@ pub async fn list_tags(client: &Client, arn: &str) -> Result<HashMap<String, Str
         Err(_) => Ok(HashMap::new()),
     }
 }
+
+/// Arns of executions of a state machine started at or after `since`
+/// (epoch seconds), oldest first
+pub async fn list_executions_since(client: &Client, arn: &str, since: i64) -> Vec<String> {
+    let res = client
+        .list_executions()
+        .state_machine_arn(arn.to_string())
+        .max_results(50)
+        .send()
+        .await;
+    match res {
+        Ok(r) => {
+            let mut xs: Vec<String> = r
+                .executions
+                .into_iter()
+                .filter(|e| e.start_date.secs() >= since)
+                .map(|e| e.execution_arn)
+                .collect();
+            xs.reverse();
+            xs
+        }
+        Err(_) => vec![],
+    }
+}
+
+/// Status and output of an execution. The output is present only when
+/// the execution succeeded
+pub async fn describe_execution(client: &Client, arn: &str) -> (String, Option<String>) {
+    let res = client
+        .describe_execution()
+        .execution_arn(arn.to_string())
+        .send()
+        .await;
+    match res {
+        Ok(r) => (r.status.as_str().to_string(), r.output),
+        Err(_) => (s!("UNKNOWN"), None),
+    }
+}
+
+/// Definition, type and role of a deployed state machine
+pub async fn describe_state_machine(
+    client: &Client,
+    arn: &str,
+) -> Option<(String, String, String)> {
+    let res = client
+        .describe_state_machine()
+        .state_machine_arn(arn.to_string())
+        .send()
+        .await;
+    match res {
+        Ok(r) => Some((r.definition, r.r#type.as_str().to_string(), r.role_arn)),
+        Err(_) => None,
+    }
+}
+
+/// Puts back the definition and role of a state machine
+pub async fn restore_state_machine(
+    client: &Client,
+    arn: &str,
+    definition: &str,
+    role_arn: &str,
+) -> Result<(), String> {
+    let res = client
+        .update_state_machine()
+        .state_machine_arn(arn.to_string())
+        .definition(definition)
+        .role_arn(role_arn)
+        .send()
+        .await;
+    match res {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+/// A step of an execution as recorded in its history
+#[derive(Clone, Debug)]
+pub struct ExecutionEvent {
+    pub timestamp: i64,
+    pub kind: String,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+fn error_of(error: Option<String>, cause: Option<String>) -> Option<String> {
+    match (error, cause) {
+        (Some(e), Some(c)) => Some(format!("{}: {}", e, c)),
+        (Some(e), None) => Some(e),
+        (None, c) => c,
+    }
+}
+
+/// History of an execution, oldest first
+pub async fn get_execution_history(client: &Client, arn: &str) -> Vec<ExecutionEvent> {
+    let mut xs: Vec<ExecutionEvent> = vec![];
+    let mut token: Option<String> = None;
+    loop {
+        let res = client
+            .get_execution_history()
+            .execution_arn(arn.to_string())
+            .max_results(1000)
+            .set_next_token(token.clone())
+            .send()
+            .await;
+        match res {
+            Ok(r) => {
+                for e in r.events {
+                    let state = match (e.state_entered_event_details, e.state_exited_event_details)
+                    {
+                        (Some(d), _) => Some(d.name),
+                        (_, Some(d)) => Some(d.name),
+                        _ => None,
+                    };
+                    let error = if let Some(d) = e.task_failed_event_details {
+                        error_of(d.error, d.cause)
+                    } else if let Some(d) = e.lambda_function_failed_event_details {
+                        error_of(d.error, d.cause)
+                    } else if let Some(d) = e.execution_failed_event_details {
+                        error_of(d.error, d.cause)
+                    } else if let Some(d) = e.execution_timed_out_event_details {
+                        error_of(d.error, d.cause)
+                    } else {
+                        None
+                    };
+                    xs.push(ExecutionEvent {
+                        timestamp: e.timestamp.to_millis().unwrap_or_default(),
+                        kind: e.r#type.as_str().to_string(),
+                        state: state,
+                        error: error,
+                    });
+                }
+                match r.next_token {
+                    Some(t) => token = Some(t),
+                    None => break,
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    xs
+}
@@ -28,9 +28,37 @@ pub async fn make_client(auth: &Auth) -> Client {
     )
 }
 
-fn make_attributes() -> HashMap<QueueAttributeName, String> {
+/// Queue attributes as configured in the topology. `redrive` is the
+/// dead-letter queue arn with its maxReceiveCount
+#[derive(Clone, Debug, Default)]
+pub struct QueueConfig {
+    pub fifo: bool,
+    pub content_dedup: bool,
+    pub visibility_timeout: Option<i32>,
+    pub retention: Option<i32>,
+    pub redrive: Option<(String, i32)>,
+}
+
+fn make_attributes(cfg: &QueueConfig) -> HashMap<QueueAttributeName, String> {
     let mut m: HashMap<QueueAttributeName, String> = HashMap::new();
-    m.insert(QueueAttributeName::VisibilityTimeout, String::from("900"));
+    let timeout = cfg.visibility_timeout.unwrap_or(900);
+    m.insert(QueueAttributeName::VisibilityTimeout, timeout.to_string());
+    if let Some(r) = cfg.retention {
+        m.insert(QueueAttributeName::MessageRetentionPeriod, r.to_string());
+    }
+    if cfg.fifo {
+        m.insert(
+            QueueAttributeName::ContentBasedDeduplication,
+            cfg.content_dedup.to_string(),
+        );
+    }
+    if let Some((arn, count)) = &cfg.redrive {
+        let policy = serde_json::json!({
+            "deadLetterTargetArn": arn,
+            "maxReceiveCount": count,
+        });
+        m.insert(QueueAttributeName::RedrivePolicy, policy.to_string());
+    }
     m
 }
 
@@ -49,11 +77,41 @@ async fn queue_exists(client: &Client, name: &str) -> bool {
     }
 }
 
-pub async fn create_queue(client: &Client, name: &str) {
-    let attrs = make_attributes();
+async fn update_attributes(
+    client: &Client,
+    name: &str,
+    attrs: HashMap<QueueAttributeName, String>,
+) {
+    if let Some(url) = find_queue_url(client, name).await {
+        let r = client
+            .set_queue_attributes()
+            .queue_url(url)
+            .set_attributes(Some(attrs))
+            .send()
+            .await;
+        match r {
+            Ok(_) => (),
+            Err(_) => panic!("{:?}", r),
+        }
+    }
+}
+
+/// Creates the queue, or updates the attributes of an existing one.
+/// Whether a queue is FIFO can only be set at creation
+pub async fn create_queue(client: &Client, name: &str, cfg: &QueueConfig) {
+    let mut attrs = make_attributes(cfg);
     let exists = queue_exists(client, name).await;
     println!("Checking queue: exists {}", name);
-    if !exists {
+    if exists {
+        // an empty policy removes a dead-letter queue dropped from the spec
+        attrs
+            .entry(QueueAttributeName::RedrivePolicy)
+            .or_insert_with(String::new);
+        update_attributes(client, name, attrs).await;
+    } else {
+        if cfg.fifo {
+            attrs.insert(QueueAttributeName::FifoQueue, String::from("true"));
+        }
         let r = client
             .create_queue()
             .queue_name(String::from(name))
@@ -74,3 +132,101 @@ pub async fn delete_queue(client: &Client, url: &str) {
         .send()
         .await;
 }
+
+pub async fn find_queue_url(client: &Client, name: &str) -> Option<String> {
+    let r = client
+        .get_queue_url()
+        .queue_name(String::from(name))
+        .send()
+        .await;
+    match r {
+        Ok(res) => res.queue_url,
+        Err(_) => None,
+    }
+}
+
+/// All attributes of the queue, keyed by attribute name
+pub async fn get_queue_attributes(client: &Client, url: &str) -> HashMap<String, String> {
+    let r = client
+        .get_queue_attributes()
+        .queue_url(String::from(url))
+        .attribute_names(QueueAttributeName::All)
+        .send()
+        .await;
+    match r {
+        Ok(res) => res
+            .attributes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(k, v)| (k.as_str().to_string(), v))
+            .collect(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// Sets the given attributes, keyed by attribute name
+pub async fn set_queue_attributes(
+    client: &Client,
+    url: &str,
+    attrs: &HashMap<String, String>,
+) -> Result<(), String> {
+    let attrs: HashMap<QueueAttributeName, String> = attrs
+        .iter()
+        .map(|(k, v)| (QueueAttributeName::from(k.as_str()), v.clone()))
+        .collect();
+    let r = client
+        .set_queue_attributes()
+        .queue_url(String::from(url))
+        .set_attributes(Some(attrs))
+        .send()
+        .await;
+    match r {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+/// Creates a short-lived queue with the given access policy and returns
+/// its url
+pub async fn create_probe_queue(client: &Client, name: &str, policy: &str) -> Option<String> {
+    let mut attrs: HashMap<QueueAttributeName, String> = HashMap::new();
+    attrs.insert(QueueAttributeName::Policy, String::from(policy));
+    attrs.insert(QueueAttributeName::MessageRetentionPeriod, String::from("600"));
+    let r = client
+        .create_queue()
+        .queue_name(String::from(name))
+        .set_attributes(Some(attrs))
+        .send()
+        .await;
+    match r {
+        Ok(res) => res.queue_url,
+        Err(_) => None,
+    }
+}
+
+/// Long-polls a queue for up to `wait` seconds. Messages received with
+/// a zero visibility timeout stay available to other consumers
+pub async fn receive_messages(
+    client: &Client,
+    url: &str,
+    wait: i32,
+    visibility: i32,
+) -> Vec<String> {
+    let r = client
+        .receive_message()
+        .queue_url(String::from(url))
+        .max_number_of_messages(10)
+        .wait_time_seconds(wait)
+        .visibility_timeout(visibility)
+        .send()
+        .await;
+    match r {
+        Ok(res) => res
+            .messages
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|m| m.body)
+            .collect(),
+        Err(_) => vec![],
+    }
+}
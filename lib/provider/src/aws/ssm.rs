@@ -8,6 +8,7 @@ use aws_sdk_ssm::{
         RetryConfig,
         RetryMode,
     },
+    types::ParameterTier,
 };
 
 pub async fn make_client(auth: &Auth) -> Client {
@@ -42,3 +43,23 @@ pub async fn get(client: Client, key: &str) -> Result<String, Error> {
 
     Ok(res)
 }
+
+/// Writes a plain string parameter, picking the tier by size
+pub async fn put(client: &Client, key: &str, value: &str) -> Result<(), String> {
+    let r = client
+        .put_parameter()
+        .name(key)
+        .value(value)
+        .overwrite(true)
+        .tier(ParameterTier::IntelligentTiering)
+        .send()
+        .await;
+    match r {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+pub async fn delete(client: &Client, key: &str) {
+    let _ = client.delete_parameter().name(key).send().await;
+}
@@ -0,0 +1,165 @@
+use super::{
+    plan,
+    plan::{
+        Action,
+        Change,
+    },
+    state::Kind,
+};
+use colored::Colorize;
+use composer::Topology;
+use kit as u;
+use kit::*;
+use provider::Auth;
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::HashSet;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Modified,
+    Missing,
+    Unmanaged,
+}
+
+impl Status {
+    pub fn to_str(&self) -> String {
+        match self {
+            Status::Modified => s!("modified"),
+            Status::Missing => s!("missing"),
+            Status::Unmanaged => s!("unmanaged"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FieldDrift {
+    pub path: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Drift {
+    pub kind: Kind,
+    pub namespace: String,
+    pub name: String,
+    pub status: Status,
+    pub fields: Vec<FieldDrift>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Report {
+    pub namespace: String,
+    pub sandbox: String,
+    pub env: String,
+    pub checked_at: String,
+    pub drifts: Vec<Drift>,
+}
+
+// A planned change read the other way around: what the topology
+// expects against what is actually deployed
+fn drift_of(change: Change) -> Drift {
+    let status = match change.action {
+        Action::Update | Action::Replace => Status::Modified,
+        Action::Create => Status::Missing,
+        Action::Delete => Status::Unmanaged,
+    };
+    let fields = match status {
+        Status::Missing => vec![],
+        _ => change
+            .fields
+            .into_iter()
+            .map(|f| FieldDrift {
+                path: f.path,
+                expected: f.after,
+                actual: f.before,
+            })
+            .collect(),
+    };
+    Drift {
+        kind: change.kind,
+        namespace: change.namespace,
+        name: change.name,
+        status: status,
+        fields: fields,
+    }
+}
+
+/// Out-of-band changes to the deployed sandbox. Code changes are not
+/// drift, so function versions are left out
+pub async fn detect(auth: &Auth, topology: &Topology) -> Result<Report, String> {
+    let changes = plan::changes_of(auth, topology, &HashSet::new()).await?;
+    Ok(Report {
+        namespace: topology.namespace.clone(),
+        sandbox: topology.sandbox.clone(),
+        env: topology.env.clone(),
+        checked_at: u::utc_now(),
+        drifts: changes.into_iter().map(drift_of).collect(),
+    })
+}
+
+fn show(v: &Option<String>) -> String {
+    match v {
+        Some(s) => format!("{:?}", s),
+        None => s!("(none)"),
+    }
+}
+
+pub fn render(report: &Report) {
+    if report.drifts.is_empty() {
+        println!("No drift in {}@{}", &report.namespace, &report.sandbox);
+        return;
+    }
+    for d in &report.drifts {
+        let status = match d.status {
+            Status::Modified => d.status.to_str().yellow(),
+            Status::Missing => d.status.to_str().red(),
+            Status::Unmanaged => d.status.to_str().magenta(),
+        };
+        println!("{} {} {}", status, d.kind.to_str().blue(), &d.name);
+        for f in &d.fields {
+            println!(
+                "      {}: expected {}, found {}",
+                &f.path,
+                show(&f.expected),
+                show(&f.actual)
+            );
+        }
+    }
+    println!(
+        "\n{} drifted resource(s) in {}@{}",
+        report.drifts.len(),
+        &report.namespace,
+        &report.sandbox
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::FieldDiff;
+
+    #[test]
+    fn test_drift_of() {
+        let change = Change {
+            kind: Kind::Function,
+            namespace: s!("etl"),
+            name: s!("etl_loader_dev"),
+            action: Action::Update,
+            fields: vec![FieldDiff {
+                path: s!("memory"),
+                before: Some(s!("512")),
+                after: Some(s!("128")),
+                forces_replacement: false,
+            }],
+        };
+        let d = drift_of(change);
+        assert_eq!(d.status, Status::Modified);
+        assert_eq!(d.fields[0].expected, Some(s!("128")));
+        assert_eq!(d.fields[0].actual, Some(s!("512")));
+    }
+}
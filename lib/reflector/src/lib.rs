@@ -1,8 +1,32 @@
 use compiler::Entity;
 use composer::Topology;
+mod drift;
+mod live;
+mod logs;
 mod mutation;
+mod plan;
+mod state;
+mod trace;
+pub use drift::{
+    Drift,
+    Report as DriftReport,
+};
+pub use plan::{
+    Action,
+    Change,
+    FieldDiff,
+    Plan,
+};
 use provider::Auth;
-use std::str::FromStr;
+use std::{
+    collections::HashSet,
+    str::FromStr,
+};
+pub use state::Kind;
+pub use trace::{
+    Hop,
+    Trace,
+};
 
 async fn reflect_entity(auth: &Auth, fqn: &str, entity: Entity) {
     match entity {
@@ -33,3 +57,62 @@ pub async fn reflect(
         println!("Reflecting topology")
     }
 }
+
+pub async fn plan(
+    auth: &Auth,
+    topology: &Topology,
+    modified: HashSet<String>,
+) -> Result<Plan, String> {
+    plan::make(auth, topology, modified).await
+}
+
+pub fn render_plan(plan: &Plan) {
+    plan::render(plan)
+}
+
+pub fn save_plan(plan: &Plan, path: &str) {
+    plan::save(plan, path)
+}
+
+pub fn load_plan(path: &str) -> Option<Plan> {
+    plan::load(path)
+}
+
+pub async fn is_stale(auth: &Auth, plan: &Plan) -> Result<bool, String> {
+    plan::is_stale(auth, plan).await
+}
+
+pub fn narrow_plan(plan: &Plan) -> Topology {
+    plan::narrow(plan)
+}
+
+pub fn arns_to_delete(auth: &Auth, plan: &Plan) -> Vec<String> {
+    plan::arns_to_delete(auth, plan)
+}
+
+pub fn routes_to_delete(plan: &Plan) -> Vec<(String, String)> {
+    plan::routes_to_delete(plan)
+}
+
+pub async fn drift(auth: &Auth, topology: &Topology) -> Result<DriftReport, String> {
+    drift::detect(auth, topology).await
+}
+
+pub fn render_drift(report: &DriftReport) {
+    drift::render(report)
+}
+
+pub async fn tail_logs(
+    auth: &Auth,
+    topology: &Topology,
+    entity: Option<String>,
+    since: &str,
+    follow: bool,
+    filter: Option<String>,
+) {
+    logs::tail(auth, topology, entity, since, follow, filter).await
+}
+
+pub async fn trace(auth: &Auth, topology: &Topology, id: &str, since: &str) -> Trace {
+    trace::trace(auth, topology, id, since).await
+}
@@ -0,0 +1,377 @@
+use super::state::{
+    Fields,
+    Kind,
+    Resource,
+    flatten_str,
+    function_of_arn,
+    join_sorted,
+    route_matches,
+    type_names,
+};
+use composer::Topology;
+use futures::stream::{
+    self,
+    StreamExt,
+};
+use kit as u;
+use kit::*;
+use provider::{
+    Auth,
+    aws::{
+        appsync,
+        cloudfront,
+        cognito,
+        dynamodb,
+        eventbridge,
+        gateway,
+        iam,
+        lambda,
+        resourcetag,
+        s3,
+        scheduler,
+        sfn,
+        sqs,
+    },
+};
+use std::collections::{
+    BTreeMap,
+    HashMap,
+    HashSet,
+};
+
+const CONCURRENCY: usize = 8;
+
+/// Deployed routes of each gateway, keyed by route key
+pub type Routes = HashMap<String, HashMap<String, String>>;
+
+fn field(r: &Resource, key: &str) -> String {
+    u::safe_unwrap(r.fields.get(key))
+}
+
+pub async fn find_routes(auth: &Auth, desired: &[Resource]) -> Routes {
+    let gateways: HashSet<String> = desired
+        .iter()
+        .filter(|r| r.kind == Kind::Route)
+        .map(|r| field(r, "gateway"))
+        .collect();
+    let client = gateway::make_client(auth).await;
+    let mut h: Routes = HashMap::new();
+    for name in gateways {
+        if let Some(api_id) = gateway::find_api(&client, &name).await {
+            h.insert(name, gateway::list_routes(&client, &api_id).await);
+        }
+    }
+    h
+}
+
+async fn find_function(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let client = lambda::make_client(auth).await;
+    let cfg = match lambda::find_config(&client, &r.name).await {
+        Some(c) => c,
+        None => return None,
+    };
+    let mut h: Fields = BTreeMap::new();
+    if !cfg.runtime.is_empty() {
+        h.insert(s!("runtime"), cfg.runtime);
+    }
+    if !cfg.handler.is_empty() {
+        h.insert(s!("handler"), cfg.handler);
+    }
+    if !cfg.layers.is_empty() {
+        h.insert(s!("layers"), join_sorted(&cfg.layers));
+    }
+    h.insert(s!("memory"), cfg.mem_size.to_string());
+    h.insert(s!("timeout"), cfg.timeout.to_string());
+    h.insert(s!("role"), cfg.role);
+    h.insert(s!("package_type"), cfg.package_type);
+    h.insert(s!("architecture"), cfg.architecture);
+    for (k, v) in cfg.environment {
+        h.insert(format!("environment.{}", k), v);
+    }
+    if r.fields.contains_key("version") {
+        let arn = auth.lambda_arn(&r.name);
+        h.insert(s!("version"), lambda::get_tag(&client, &arn, s!("version")).await);
+    }
+    Some(h)
+}
+
+async fn find_role(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let client = iam::make_client(auth).await;
+    let trust = match iam::find_role_trust(&client, &r.name).await {
+        Some(t) => t,
+        None => return None,
+    };
+    let policy_arn = field(r, "policy_arn");
+    let mut h: Fields = BTreeMap::new();
+    if let Some(doc) = iam::find_policy_doc(&client, &r.name, &policy_arn).await {
+        h.insert(s!("policy_arn"), policy_arn);
+        flatten_str("policy", &doc, &mut h);
+    }
+    flatten_str("trust", &trust, &mut h);
+    Some(h)
+}
+
+fn find_route(r: &Resource, routes: &Routes) -> Option<Fields> {
+    let gw = field(r, "gateway");
+    let deployed = match routes.get(&gw) {
+        Some(xs) => xs,
+        None => return None,
+    };
+    match deployed.iter().find(|(key, _)| route_matches(&r.name, key)) {
+        Some((_, authorization)) => {
+            let mut h: Fields = BTreeMap::new();
+            h.insert(s!("gateway"), gw);
+            h.insert(s!("authorization"), authorization.clone());
+            Some(h)
+        }
+        None => None,
+    }
+}
+
+async fn find_event(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let client = eventbridge::make_client(auth).await;
+    let bus = field(r, "bus");
+    let (pattern, state) = match eventbridge::describe_rule(&client, &bus, &r.name).await {
+        Some(x) => x,
+        None => return None,
+    };
+    let targets = eventbridge::list_target_arns(&client, &bus, &r.name).await;
+    let mut h: Fields = BTreeMap::new();
+    h.insert(s!("bus"), bus);
+    h.insert(s!("state"), state);
+    flatten_str("pattern", &pattern, &mut h);
+    h.insert(s!("targets"), join_sorted(&targets));
+    Some(h)
+}
+
+async fn find_state(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let client = sfn::make_client(auth).await;
+    let arn = auth.sfn_arn(&r.name);
+    match sfn::describe_state_machine(&client, &arn).await {
+        Some((definition, kind, role)) => {
+            let mut h: Fields = BTreeMap::new();
+            h.insert(s!("type"), kind);
+            h.insert(s!("role"), role);
+            flatten_str("definition", &definition, &mut h);
+            Some(h)
+        }
+        None => None,
+    }
+}
+
+async fn find_queue(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let client = sqs::make_client(auth).await;
+    let url = sqs::find_queue_url(&client, &r.name).await?;
+    let attrs = sqs::get_queue_attributes(&client, &url).await;
+    let attr = |k: &str| attrs.get(k).cloned();
+    let mut h: Fields = BTreeMap::new();
+    h.insert(s!("fifo"), attr("FifoQueue").unwrap_or(s!("false")));
+    h.insert(s!("visibility_timeout"), u::safe_unwrap(attr("VisibilityTimeout").as_ref()));
+    // retention has an AWS default and is only compared when specified
+    if r.fields.contains_key("retention") {
+        h.insert(s!("retention"), u::safe_unwrap(attr("MessageRetentionPeriod").as_ref()));
+    }
+    if let Some(policy) = attr("RedrivePolicy") {
+        flatten_str("redrive", &policy, &mut h);
+    }
+    Some(h)
+}
+
+async fn find_mutation(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let client = appsync::make_client(auth).await;
+    let api_id = appsync::find_api(&client, &r.name).await?;
+    let mut h: Fields = BTreeMap::new();
+    let authorizer = appsync::find_authorizer_uri(&client, &api_id).await;
+    h.insert(s!("authorizer"), function_of_arn(&u::safe_unwrap(authorizer.as_ref())));
+    // the schema carries builtin types too, so only the desired ones
+    // that it declares are reported
+    let deployed = match appsync::get_schema(&client, &api_id).await {
+        Some(sdl) => type_names(&sdl),
+        None => HashSet::new(),
+    };
+    let names: Vec<String> = field(r, "types")
+        .split(", ")
+        .filter(|n| deployed.contains(*n))
+        .map(|n| n.to_string())
+        .collect();
+    h.insert(s!("types"), join_sorted(&names));
+    Some(h)
+}
+
+async fn find_channel(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let client = appsync::make_client(auth).await;
+    let api = field(r, "api");
+    let handler = appsync::events::find_channel_handler(&client, &api, &r.name).await?;
+    let mut h: Fields = BTreeMap::new();
+    h.insert(s!("api"), api);
+    h.insert(s!("handler"), handler);
+    Some(h)
+}
+
+async fn find_schedule(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let client = scheduler::make_client(auth).await;
+    let group = field(r, "group");
+    let (expression, target) = scheduler::find_schedule(&client, &r.name, &group).await?;
+    let mut h: Fields = BTreeMap::new();
+    h.insert(s!("group"), group);
+    h.insert(s!("expression"), expression);
+    h.insert(s!("target"), target);
+    Some(h)
+}
+
+async fn find_pool(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let client = cognito::make_client(auth).await;
+    let triggers = cognito::find_pool_triggers(&client, &r.name).await?;
+    let mut h: Fields = BTreeMap::new();
+    for (k, arn) in triggers {
+        h.insert(format!("triggers.{}", k), arn);
+    }
+    Some(h)
+}
+
+// a page is missing when neither its bucket nor its distribution exist
+async fn find_page(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let bucket = field(r, "bucket");
+    let s3_client = s3::make_client(auth).await;
+    let cf_client = cloudfront::make_client(auth).await;
+    let mut h: Fields = BTreeMap::new();
+    if s3::bucket_exists(&s3_client, &bucket).await {
+        h.insert(s!("bucket"), bucket);
+    }
+    let distribution = cloudfront::find_distribution(&cf_client, &r.name).await;
+    if distribution.is_some() {
+        h.insert(s!("distribution"), r.name.clone());
+    }
+    if h.is_empty() { None } else { Some(h) }
+}
+
+async fn find_store(auth: &Auth, r: &Resource) -> Option<Fields> {
+    let kind = field(r, "kind");
+    let mut h: Fields = BTreeMap::new();
+    if kind == "s3" {
+        let client = s3::make_client(auth).await;
+        if !s3::bucket_exists(&client, &r.name).await {
+            return None;
+        }
+    } else {
+        let client = dynamodb::make_client(auth).await;
+        let keys = dynamodb::find_keys(&client, &r.name).await?;
+        if let Some(k) = keys.get("HASH") {
+            h.insert(s!("hash_key"), k.clone());
+        }
+        if let Some(k) = keys.get("RANGE") {
+            h.insert(s!("range_key"), k.clone());
+        }
+    }
+    h.insert(s!("kind"), kind);
+    Some(h)
+}
+
+/// Deployed state of a resource, None when it does not exist
+pub async fn find(auth: &Auth, r: &Resource, routes: &Routes) -> Option<Fields> {
+    match r.kind {
+        Kind::Function => find_function(auth, r).await,
+        Kind::Role => find_role(auth, r).await,
+        Kind::Route => find_route(r, routes),
+        Kind::Event => find_event(auth, r).await,
+        Kind::Queue => find_queue(auth, r).await,
+        Kind::Mutation => find_mutation(auth, r).await,
+        Kind::State => find_state(auth, r).await,
+        Kind::Channel => find_channel(auth, r).await,
+        Kind::Schedule => find_schedule(auth, r).await,
+        Kind::Pool => find_pool(auth, r).await,
+        Kind::Page => find_page(auth, r).await,
+        Kind::Store => find_store(auth, r).await,
+    }
+}
+
+pub async fn find_all(
+    auth: &Auth,
+    desired: &[Resource],
+    routes: &Routes,
+) -> Vec<(Resource, Option<Fields>)> {
+    stream::iter(desired.iter().cloned())
+        .map(|r| async move {
+            let live = find(auth, &r, routes).await;
+            (r, live)
+        })
+        .buffered(CONCURRENCY)
+        .collect()
+        .await
+}
+
+fn namespaces_of(topology: &Topology) -> HashSet<String> {
+    let mut h: HashSet<String> = HashSet::new();
+    h.insert(topology.namespace.clone());
+    for node in topology.nodes.values() {
+        h.insert(node.namespace.clone());
+    }
+    h
+}
+
+/// Functions and state machines tagged with the topology and sandbox,
+/// and routes on its own gateways, that are no longer in the desired
+/// state
+pub async fn orphans(
+    auth: &Auth,
+    topology: &Topology,
+    desired: &[Resource],
+    routes: &Routes,
+) -> Result<Vec<Resource>, String> {
+    let namespaces = namespaces_of(topology);
+    let known: HashSet<(Kind, String)> =
+        desired.iter().map(|r| (r.kind.clone(), r.name.clone())).collect();
+
+    let client = resourcetag::make_client(auth).await;
+    let tagged = resourcetag::get_tagged_resources(&client, "namespace").await?;
+
+    let mut xs: Vec<Resource> = vec![];
+    for (arn, tags) in tagged {
+        let ns = u::safe_unwrap(tags.get("namespace"));
+        let sandbox = u::safe_unwrap(tags.get("sandbox"));
+        if !namespaces.contains(&ns) || sandbox != topology.sandbox {
+            continue;
+        }
+        let kind = if arn.contains(":function:") {
+            Kind::Function
+        } else if arn.contains(":stateMachine:") {
+            Kind::State
+        } else {
+            continue;
+        };
+        let name = u::split_last(&arn, ":");
+        if !known.contains(&(kind.clone(), name.clone())) {
+            xs.push(Resource {
+                kind: kind,
+                namespace: ns,
+                name: name,
+                fields: BTreeMap::new(),
+            });
+        }
+    }
+
+    // routes on gateways shared across sandboxes are not ours to delete
+    let managed = format!("_{}", &topology.sandbox);
+    for (gw, deployed) in routes {
+        if !gw.ends_with(&managed) {
+            continue;
+        }
+        for (key, authorization) in deployed {
+            let wanted = desired.iter().any(|r| {
+                r.kind == Kind::Route && field(r, "gateway") == *gw && route_matches(&r.name, key)
+            });
+            if !wanted {
+                let mut h: Fields = BTreeMap::new();
+                h.insert(s!("gateway"), gw.clone());
+                h.insert(s!("authorization"), authorization.clone());
+                xs.push(Resource {
+                    kind: Kind::Route,
+                    namespace: topology.namespace.clone(),
+                    name: key.clone(),
+                    fields: h,
+                });
+            }
+        }
+    }
+    Ok(xs)
+}
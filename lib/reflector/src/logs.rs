@@ -0,0 +1,367 @@
+use colored::{
+    Color,
+    Colorize,
+};
+use compiler::{
+    Entity,
+    spec::function::Provider,
+};
+use composer::Topology;
+use futures::stream::{
+    self,
+    StreamExt,
+};
+use kit as u;
+use kit::*;
+use provider::{
+    Auth,
+    aws::{
+        appsync,
+        cloudwatch,
+        gateway,
+    },
+};
+use std::collections::{
+    BTreeMap,
+    HashMap,
+    HashSet,
+};
+
+const CONCURRENCY: usize = 8;
+const POLL_INTERVAL: u64 = 2;
+
+const COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Blue,
+    Color::BrightRed,
+];
+
+/// A log group and the topology component writing to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    pub entity: Entity,
+    pub component: String,
+    pub group: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Line {
+    pub component: String,
+    pub timestamp: i64,
+    pub message: String,
+}
+
+fn wants(selected: &Option<(Entity, Option<String>)>, entity: Entity, name: &str) -> bool {
+    match selected {
+        Some((e, c)) => {
+            *e == entity
+                && match c {
+                    Some(c) => c == name,
+                    None => true,
+                }
+        }
+        None => true,
+    }
+}
+
+// log groups known without asking AWS: functions and the state machine
+fn static_sources(
+    topology: &Topology,
+    selected: &Option<(Entity, Option<String>)>,
+) -> Vec<Source> {
+    let mut xs: Vec<Source> = vec![];
+    for f in topology.functions.values() {
+        if let Provider::Lambda = f.runtime.provider {
+            if wants(selected, Entity::Function, &f.name) {
+                xs.push(Source {
+                    entity: Entity::Function,
+                    component: f.name.clone(),
+                    group: format!("/aws/lambda/{}", &f.fqn),
+                });
+            }
+        }
+    }
+    if let Some(flow) = &topology.flow {
+        if wants(selected, Entity::State, &flow.name) {
+            xs.push(Source {
+                entity: Entity::State,
+                component: s!("states"),
+                group: flow.log_config.group.clone(),
+            });
+        }
+    }
+    xs
+}
+
+async fn api_sources(
+    auth: &Auth,
+    topology: &Topology,
+    selected: &Option<(Entity, Option<String>)>,
+) -> Vec<Source> {
+    let mut xs: Vec<Source> = vec![];
+
+    let mut stages: BTreeMap<String, String> = BTreeMap::new();
+    for (name, route) in &topology.routes {
+        if !route.skip && wants(selected, Entity::Route, name) {
+            stages.insert(route.gateway.clone(), route.stage.clone());
+        }
+    }
+    if !stages.is_empty() {
+        let client = gateway::make_client(auth).await;
+        for (gw, stage) in stages {
+            if let Some(api_id) = gateway::find_api(&client, &gw).await {
+                // HTTP APIs only log when access logging is configured
+                if let Some(group) = gateway::find_access_log_group(&client, &api_id, &stage).await
+                {
+                    xs.push(Source {
+                        entity: Entity::Route,
+                        component: gw,
+                        group: group,
+                    });
+                }
+            }
+        }
+    }
+
+    let apis: Vec<&String> = topology
+        .mutations
+        .values()
+        .map(|m| &m.api_name)
+        .filter(|name| wants(selected, Entity::Mutation, name))
+        .collect();
+    if !apis.is_empty() {
+        let client = appsync::make_client(auth).await;
+        for name in apis {
+            if let Some(api_id) = appsync::find_api(&client, name).await {
+                xs.push(Source {
+                    entity: Entity::Mutation,
+                    component: name.clone(),
+                    group: format!("/aws/appsync/apis/{}", api_id),
+                });
+            }
+        }
+    }
+    xs
+}
+
+/// Every log group the topology and its nodes write to, narrowed to an
+/// `entity` or `entity/component` when given
+pub async fn sources(auth: &Auth, topology: &Topology, entity: Option<String>) -> Vec<Source> {
+    let selected = match entity {
+        Some(e) => Some(Entity::as_entity_component(&e)),
+        None => None,
+    };
+    let mut xs = static_sources(topology, &selected);
+    xs.extend(api_sources(auth, topology, &selected).await);
+    for node in topology.nodes.values() {
+        xs.extend(static_sources(node, &selected));
+        xs.extend(api_sources(auth, node, &selected).await);
+    }
+    let mut seen: HashSet<String> = HashSet::new();
+    xs.retain(|s| seen.insert(s.group.clone()));
+    xs
+}
+
+/// Read position in a log group: the timestamp to poll from and the ids
+/// of the events already printed at that timestamp
+#[derive(Clone, Debug)]
+struct Cursor {
+    start_time: i64,
+    seen: HashSet<String>,
+}
+
+impl Cursor {
+    fn new(start_time: i64) -> Cursor {
+        Cursor {
+            start_time: start_time,
+            seen: HashSet::new(),
+        }
+    }
+
+    // keeps the unseen events and moves past them. Events may arrive late
+    // within the same millisecond, so the next poll starts at the last
+    // timestamp and only the ids at that timestamp are remembered
+    fn advance(&mut self, events: Vec<cloudwatch::LogEvent>) -> Vec<cloudwatch::LogEvent> {
+        let fresh: Vec<cloudwatch::LogEvent> = events
+            .into_iter()
+            .filter(|e| e.timestamp > self.start_time || !self.seen.contains(&e.id))
+            .collect();
+        if let Some(last) = fresh.iter().map(|e| e.timestamp).max() {
+            if last > self.start_time {
+                self.start_time = last;
+                self.seen.clear();
+            }
+            for e in fresh.iter().filter(|e| e.timestamp == last) {
+                self.seen.insert(e.id.clone());
+            }
+        }
+        fresh
+    }
+}
+
+async fn fetch(
+    auth: &Auth,
+    sources: &[Source],
+    filter: &Option<String>,
+    cursors: &mut HashMap<String, Cursor>,
+) -> Result<Vec<Line>, String> {
+    let client = cloudwatch::make_client(auth).await;
+    let starts: HashMap<String, i64> = cursors
+        .iter()
+        .map(|(group, c)| (group.clone(), c.start_time))
+        .collect();
+    let events: Vec<(&Source, Result<Vec<cloudwatch::LogEvent>, String>)> =
+        stream::iter(sources.iter())
+            .map(|s| {
+                let client = client.clone();
+                let start_time = starts.get(&s.group).copied().unwrap_or_default();
+                async move {
+                    let events = cloudwatch::filter_log_events(
+                        &client,
+                        &s.group,
+                        start_time,
+                        filter.clone(),
+                    )
+                    .await;
+                    (s, events)
+                }
+            })
+            .buffer_unordered(CONCURRENCY)
+            .collect()
+            .await;
+
+    let mut xs: Vec<Line> = vec![];
+    for (source, res) in events {
+        let es = match res {
+            Ok(es) => es,
+            Err(e) => return Err(format!("{}: {}", &source.group, e)),
+        };
+        let cursor = cursors.get_mut(&source.group).unwrap();
+        for e in cursor.advance(es) {
+            xs.push(Line {
+                component: source.component.clone(),
+                timestamp: e.timestamp,
+                message: e.message,
+            });
+        }
+    }
+    xs.sort_by_key(|l| l.timestamp);
+    Ok(xs)
+}
+
+fn color_of(sources: &[Source], component: &str) -> Color {
+    match sources.iter().position(|s| s.component == component) {
+        Some(i) => COLORS[i % COLORS.len()],
+        None => Color::White,
+    }
+}
+
+fn render(sources: &[Source], lines: &[Line]) {
+    let width = sources.iter().map(|s| s.component.len()).max().unwrap_or(0);
+    for line in lines {
+        let time = u::ms_to_dt(line.timestamp).format("%H:%M:%S");
+        let component = format!("{:width$}", &line.component, width = width);
+        println!(
+            "{} {} {}",
+            time.to_string().dimmed(),
+            component.color(color_of(sources, &line.component)),
+            line.message.trim_end()
+        );
+    }
+}
+
+/// Prints the logs of the topology since `since` ago (e.g. `10m`),
+/// interleaved by timestamp. With `follow`, keeps polling for new events
+pub async fn tail(
+    auth: &Auth,
+    topology: &Topology,
+    entity: Option<String>,
+    since: &str,
+    follow: bool,
+    filter: Option<String>,
+) {
+    let sources = sources(auth, topology, entity).await;
+    if sources.is_empty() {
+        println!("No log groups found");
+        return;
+    }
+    let since = match u::duration_millis(since) {
+        Some(ms) => ms,
+        None => panic!("Invalid duration {}", since),
+    };
+    let start_time = u::current_millis() - since;
+    let mut cursors: HashMap<String, Cursor> = sources
+        .iter()
+        .map(|s| (s.group.clone(), Cursor::new(start_time)))
+        .collect();
+    loop {
+        match fetch(auth, &sources, &filter, &mut cursors).await {
+            Ok(lines) => render(&sources, &lines),
+            Err(e) => {
+                println!("Failed to fetch logs of {}", e);
+                std::process::exit(1);
+            }
+        }
+        if !follow {
+            break;
+        }
+        u::sleep(POLL_INTERVAL * 1000);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wants() {
+        let selected = Some(Entity::as_entity_component("function/loader"));
+        assert!(wants(&selected, Entity::Function, "loader"));
+        assert!(!wants(&selected, Entity::Function, "enhancer"));
+        assert!(!wants(&selected, Entity::State, "loader"));
+        let selected = Some(Entity::as_entity_component("function"));
+        assert!(wants(&selected, Entity::Function, "enhancer"));
+        assert!(wants(&None, Entity::Route, "/api"));
+    }
+
+    fn event(id: &str, timestamp: i64) -> cloudwatch::LogEvent {
+        cloudwatch::LogEvent {
+            id: s!(id),
+            group: s!("/aws/lambda/loader"),
+            stream: s!("stream"),
+            timestamp: timestamp,
+            message: s!("msg"),
+        }
+    }
+
+    fn ids(events: &[cloudwatch::LogEvent]) -> Vec<String> {
+        events.iter().map(|e| e.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_cursor_advance() {
+        let mut cursor = Cursor::new(100);
+        let fresh = cursor.advance(vec![event("a", 100), event("b", 105), event("c", 105)]);
+        assert_eq!(ids(&fresh), vec!["a", "b", "c"]);
+        assert_eq!(cursor.start_time, 105);
+        assert_eq!(cursor.seen.len(), 2);
+
+        // the overlapping millisecond only yields late arrivals
+        let fresh = cursor.advance(vec![event("b", 105), event("c", 105), event("d", 105)]);
+        assert_eq!(ids(&fresh), vec!["d"]);
+        assert_eq!(cursor.start_time, 105);
+        assert_eq!(cursor.seen.len(), 3);
+
+        // moving on forgets the ids of the previous millisecond
+        let fresh = cursor.advance(vec![event("d", 105), event("e", 110)]);
+        assert_eq!(ids(&fresh), vec!["e"]);
+        assert_eq!(cursor.start_time, 110);
+        assert_eq!(cursor.seen.len(), 1);
+
+        assert!(cursor.advance(vec![]).is_empty());
+        assert_eq!(cursor.start_time, 110);
+    }
+}
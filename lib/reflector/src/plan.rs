@@ -0,0 +1,410 @@
+use super::{
+    live,
+    state,
+    state::{
+        Fields,
+        Kind,
+        Resource,
+        route_key,
+    },
+};
+use colored::Colorize;
+use composer::Topology;
+use kit as u;
+use kit::*;
+use provider::Auth;
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::{
+        BTreeSet,
+        HashMap,
+        HashSet,
+    },
+    os::unix::fs::PermissionsExt,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Action {
+    Create,
+    Update,
+    Replace,
+    Delete,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FieldDiff {
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub forces_replacement: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Change {
+    pub kind: Kind,
+    pub namespace: String,
+    pub name: String,
+    pub action: Action,
+    pub fields: Vec<FieldDiff>,
+}
+
+/// Changes to the deployed sandbox along with the resolved topology they
+/// were computed from, so the exact same changes can be applied later.
+/// The resolved topology holds function environments with their `ssm:/`
+/// values already read, so a saved plan carries those secrets in clear
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Plan {
+    pub namespace: String,
+    pub sandbox: String,
+    pub env: String,
+    pub created_at: String,
+    pub modified: Vec<String>,
+    pub changes: Vec<Change>,
+    pub topology: Topology,
+}
+
+fn diff_fields(kind: &Kind, desired: &Fields, live: &Fields) -> Vec<FieldDiff> {
+    let keys: BTreeSet<&String> = desired.keys().chain(live.keys()).collect();
+    let mut xs: Vec<FieldDiff> = vec![];
+    for key in keys {
+        let before = live.get(key);
+        let after = desired.get(key);
+        if before != after {
+            let top = key.split(['.', '[']).next().unwrap_or(key);
+            xs.push(FieldDiff {
+                path: key.clone(),
+                before: before.cloned(),
+                after: after.cloned(),
+                forces_replacement: kind.forces_replacement(top),
+            });
+        }
+    }
+    xs
+}
+
+/// The change that brings a live resource to its desired state, None
+/// when they already match
+pub fn diff(desired: &Resource, live: Option<&Fields>) -> Option<Change> {
+    let (action, fields) = match live {
+        Some(l) => {
+            let fields = diff_fields(&desired.kind, &desired.fields, l);
+            if fields.is_empty() {
+                return None;
+            }
+            match fields.iter().any(|f| f.forces_replacement) {
+                true => (Action::Replace, fields),
+                false => (Action::Update, fields),
+            }
+        }
+        None => {
+            let empty: Fields = Fields::new();
+            (Action::Create, diff_fields(&desired.kind, &desired.fields, &empty))
+        }
+    };
+    Some(Change {
+        kind: desired.kind.clone(),
+        namespace: desired.namespace.clone(),
+        name: desired.name.clone(),
+        action: action,
+        fields: fields,
+    })
+}
+
+fn deletion(r: &Resource) -> Change {
+    let fields = r
+        .fields
+        .iter()
+        .map(|(k, v)| FieldDiff {
+            path: k.clone(),
+            before: Some(v.clone()),
+            after: None,
+            forces_replacement: false,
+        })
+        .collect();
+    Change {
+        kind: r.kind.clone(),
+        namespace: r.namespace.clone(),
+        name: r.name.clone(),
+        action: Action::Delete,
+        fields: fields,
+    }
+}
+
+pub(crate) async fn changes_of(
+    auth: &Auth,
+    topology: &Topology,
+    modified: &HashSet<String>,
+) -> Result<Vec<Change>, String> {
+    let desired = state::desired(topology, modified);
+    let routes = live::find_routes(auth, &desired).await;
+    let mut changes: Vec<Change> = vec![];
+    for (r, l) in live::find_all(auth, &desired, &routes).await {
+        if let Some(c) = diff(&r, l.as_ref()) {
+            changes.push(c);
+        }
+    }
+    for r in live::orphans(auth, topology, &desired, &routes).await? {
+        changes.push(deletion(&r));
+    }
+    changes.sort_by_key(|c| (c.namespace.clone(), c.kind.to_str(), c.name.clone()));
+    Ok(changes)
+}
+
+/// Compares the resolved topology with what is deployed in its sandbox
+pub async fn make(
+    auth: &Auth,
+    topology: &Topology,
+    modified: HashSet<String>,
+) -> Result<Plan, String> {
+    let changes = changes_of(auth, topology, &modified).await?;
+    // tables are never dropped by apply, a store whose keys changed has
+    // to be deleted by hand first
+    for c in &changes {
+        if c.kind == Kind::Store && c.action == Action::Replace {
+            return Err(format!(
+                "store {} changes its keys and cannot be replaced, delete it first",
+                &c.name
+            ));
+        }
+    }
+    let mut modified: Vec<String> = modified.into_iter().collect();
+    modified.sort();
+    Ok(Plan {
+        namespace: topology.namespace.clone(),
+        sandbox: topology.sandbox.clone(),
+        env: topology.env.clone(),
+        created_at: u::utc_now(),
+        modified: modified,
+        changes: changes,
+        topology: topology.clone(),
+    })
+}
+
+fn show(v: &Option<String>) -> String {
+    match v {
+        Some(s) => format!("{:?}", s),
+        None => s!("(none)"),
+    }
+}
+
+fn render_field(f: &FieldDiff) -> String {
+    let line = match (&f.before, &f.after) {
+        (None, Some(_)) => format!("{} {}: {}", "+".green(), &f.path, show(&f.after)),
+        (Some(_), None) => format!("{} {}: {}", "-".red(), &f.path, show(&f.before)),
+        _ => format!(
+            "{} {}: {} -> {}",
+            "~".yellow(),
+            &f.path,
+            show(&f.before),
+            show(&f.after)
+        ),
+    };
+    match f.forces_replacement {
+        true => format!("{} {}", line, "(forces replacement)".red()),
+        false => line,
+    }
+}
+
+pub fn render(plan: &Plan) {
+    if plan.changes.is_empty() {
+        println!("No changes. {} is up-to-date.", &plan.sandbox);
+        return;
+    }
+    let (mut created, mut updated, mut replaced, mut deleted) = (0, 0, 0, 0);
+    for change in &plan.changes {
+        let symbol = match change.action {
+            Action::Create => {
+                created += 1;
+                format!("  {}", "+".green())
+            }
+            Action::Update => {
+                updated += 1;
+                format!("  {}", "~".yellow())
+            }
+            Action::Replace => {
+                replaced += 1;
+                "-/+".red().to_string()
+            }
+            Action::Delete => {
+                deleted += 1;
+                format!("  {}", "-".red())
+            }
+        };
+        println!("{} {} {}", symbol, change.kind.to_str().blue(), &change.name);
+        for f in &change.fields {
+            println!("      {}", render_field(f));
+        }
+    }
+    println!(
+        "\nPlan: {} to create, {} to update, {} to replace, {} to delete.",
+        created, updated, replaced, deleted
+    );
+}
+
+// the plan holds resolved secrets, so only its owner can read it
+pub fn save(plan: &Plan, path: &str) {
+    let data = serde_json::to_string_pretty(plan).unwrap();
+    u::write_str(path, &data);
+    let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+}
+
+pub fn load(path: &str) -> Option<Plan> {
+    if !u::file_exists(path) {
+        return None;
+    }
+    serde_json::from_str(&u::slurp(path)).ok()
+}
+
+/// Whether the deployed sandbox still matches the state the plan was
+/// computed against
+pub async fn is_stale(auth: &Auth, plan: &Plan) -> Result<bool, String> {
+    let modified: HashSet<String> = plan.modified.iter().cloned().collect();
+    let changes = changes_of(auth, &plan.topology, &modified).await?;
+    Ok(changes != plan.changes)
+}
+
+fn changed(plan: &Plan) -> HashSet<(Kind, String)> {
+    plan.changes
+        .iter()
+        .filter(|c| c.action != Action::Delete)
+        .map(|c| (c.kind.clone(), c.name.clone()))
+        .collect()
+}
+
+fn narrow_node(topology: &Topology, names: &HashSet<(Kind, String)>) -> Topology {
+    let has = |kind: Kind, name: &str| names.contains(&(kind, name.to_string()));
+    let mut t = topology.clone();
+    t.functions.retain(|_, f| has(Kind::Function, &f.fqn));
+    t.roles.retain(|_, r| has(Kind::Role, &r.name));
+    t.routes.retain(|name, r| name != "default" && has(Kind::Route, &route_key(r)));
+    if !t.routes.is_empty() {
+        if let Some(d) = topology.routes.get("default") {
+            t.routes.insert(s!("default"), d.clone());
+        }
+    }
+    t.events.retain(|_, e| has(Kind::Event, &e.rule_name));
+    t.queues.retain(|_, q| has(Kind::Queue, &q.name));
+    t.mutations.retain(|_, m| has(Kind::Mutation, &m.api_name));
+    t.flow = match &t.flow {
+        Some(f) if has(Kind::State, &f.name) => Some(f.clone()),
+        _ => None,
+    };
+    t.channels.retain(|_, c| has(Kind::Channel, &c.name));
+    t.schedules.retain(|_, s| has(Kind::Schedule, &s.name));
+    t.pools.retain(|_, p| has(Kind::Pool, &p.name));
+    t.pages.retain(|_, p| has(Kind::Page, &p.fqn));
+    t.stores.retain(|_, s| has(Kind::Store, &s.name));
+    t.all_functions = HashMap::new();
+    t.transducer = match &t.transducer {
+        Some(tx) if has(Kind::Function, &tx.function.fqn) => Some(tx.clone()),
+        _ => None,
+    };
+    t.nodes = HashMap::new();
+    t
+}
+
+/// The planned topology reduced to the resources to create, update or
+/// replace
+pub fn narrow(plan: &Plan) -> Topology {
+    let names = changed(plan);
+    let mut root = narrow_node(&plan.topology, &names);
+    for (name, node) in &plan.topology.nodes {
+        root.nodes.insert(name.clone(), narrow_node(node, &names));
+    }
+    root
+}
+
+/// Function and state machine arns to delete before applying, including
+/// the ones being replaced
+pub fn arns_to_delete(auth: &Auth, plan: &Plan) -> Vec<String> {
+    let mut xs: Vec<String> = vec![];
+    for c in &plan.changes {
+        if c.action == Action::Delete || c.action == Action::Replace {
+            match c.kind {
+                Kind::Function => xs.push(auth.lambda_arn(&c.name)),
+                Kind::State => xs.push(auth.sfn_arn(&c.name)),
+                _ => (),
+            }
+        }
+    }
+    xs
+}
+
+/// Gateway and route key of routes no longer in the topology
+pub fn routes_to_delete(plan: &Plan) -> Vec<(String, String)> {
+    let mut xs: Vec<(String, String)> = vec![];
+    for c in &plan.changes {
+        if c.action == Action::Delete && c.kind == Kind::Route {
+            let gateway = c.fields.iter().find(|f| f.path == "gateway");
+            if let Some(FieldDiff {
+                before: Some(gw), ..
+            }) = gateway
+            {
+                xs.push((gw.clone(), c.name.clone()));
+            }
+        }
+    }
+    xs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn resource(kind: Kind, fields: Vec<(&str, &str)>) -> Resource {
+        Resource {
+            kind: kind,
+            namespace: s!("etl"),
+            name: s!("etl_loader_dev"),
+            fields: fields
+                .into_iter()
+                .map(|(k, v)| (s!(k), s!(v)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let desired = resource(
+            Kind::Function,
+            vec![("memory", "256"), ("environment.FOO", "bar")],
+        );
+        assert_eq!(diff(&desired, None).unwrap().action, Action::Create);
+
+        let live: Fields = desired.fields.clone();
+        assert!(diff(&desired, Some(&live)).is_none());
+
+        let live: BTreeMap<String, String> = BTreeMap::from([
+            (s!("memory"), s!("128")),
+            (s!("environment.FOO"), s!("bar")),
+            (s!("environment.OLD"), s!("x")),
+        ]);
+        let change = diff(&desired, Some(&live)).unwrap();
+        assert_eq!(change.action, Action::Update);
+        assert_eq!(change.fields.len(), 2);
+        assert_eq!(change.fields[0].path, "environment.OLD");
+        assert_eq!(change.fields[0].after, None);
+        assert_eq!(change.fields[1].before, Some(s!("128")));
+    }
+
+    #[test]
+    fn test_diff_replace() {
+        let desired = resource(Kind::State, vec![("type", "EXPRESS")]);
+        let live: Fields = BTreeMap::from([(s!("type"), s!("STANDARD"))]);
+        let change = diff(&desired, Some(&live)).unwrap();
+        assert_eq!(change.action, Action::Replace);
+        assert!(change.fields[0].forces_replacement);
+
+        let desired = resource(Kind::Store, vec![("kind", "dynamodb"), ("hash_key", "id")]);
+        let live: Fields = BTreeMap::from([
+            (s!("kind"), s!("dynamodb")),
+            (s!("hash_key"), s!("pk")),
+        ]);
+        let change = diff(&desired, Some(&live)).unwrap();
+        assert_eq!(change.action, Action::Replace);
+    }
+}
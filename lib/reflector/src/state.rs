@@ -0,0 +1,428 @@
+use compiler::spec::{
+    StoreKind,
+    function::Provider,
+};
+use composer::{
+    Function,
+    Mutation,
+    Queue,
+    Route,
+    Store,
+    Topology,
+};
+use kit::*;
+use provider::aws::lambda;
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use std::collections::{
+    BTreeMap,
+    HashSet,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Function,
+    Role,
+    Route,
+    Event,
+    Queue,
+    Mutation,
+    State,
+    Channel,
+    Schedule,
+    Pool,
+    Page,
+    Store,
+}
+
+impl Kind {
+    pub fn to_str(&self) -> String {
+        match self {
+            Kind::Function => s!("function"),
+            Kind::Role => s!("role"),
+            Kind::Route => s!("route"),
+            Kind::Event => s!("event"),
+            Kind::Queue => s!("queue"),
+            Kind::Mutation => s!("mutation"),
+            Kind::State => s!("state"),
+            Kind::Channel => s!("channel"),
+            Kind::Schedule => s!("schedule"),
+            Kind::Pool => s!("pool"),
+            Kind::Page => s!("page"),
+            Kind::Store => s!("store"),
+        }
+    }
+
+    /// Fields AWS cannot change in place. The deployer recreates a
+    /// function whose package type changed, a state machine cannot
+    /// switch between Standard and Express and a table keeps its keys
+    pub fn forces_replacement(&self, field: &str) -> bool {
+        match self {
+            Kind::Function => field == "package_type",
+            Kind::State => field == "type",
+            Kind::Store => field == "hash_key" || field == "range_key",
+            _ => false,
+        }
+    }
+}
+
+/// Flattened attributes of a resource. Nested maps and JSON documents
+/// are keyed by path, e.g. `environment.FOO` or `policy.Statement[0].Effect`
+pub type Fields = BTreeMap<String, String>;
+
+/// A deployable resource identified by its AWS name: the function fqn,
+/// role name, route key, rule name, queue name, api name, state machine
+/// name, channel namespace, schedule, pool, distribution or store name
+#[derive(Clone, Debug)]
+pub struct Resource {
+    pub kind: Kind,
+    pub namespace: String,
+    pub name: String,
+    pub fields: Fields,
+}
+
+pub fn flatten(prefix: &str, v: &Value, fields: &mut Fields) {
+    match v {
+        Value::Object(m) => {
+            for (k, x) in m {
+                flatten(&format!("{}.{}", prefix, k), x, fields);
+            }
+        }
+        Value::Array(xs) => {
+            for (i, x) in xs.iter().enumerate() {
+                flatten(&format!("{}[{}]", prefix, i), x, fields);
+            }
+        }
+        Value::String(s) => {
+            fields.insert(prefix.to_string(), s.clone());
+        }
+        Value::Null => (),
+        _ => {
+            fields.insert(prefix.to_string(), v.to_string());
+        }
+    }
+}
+
+pub fn flatten_str(prefix: &str, s: &str, fields: &mut Fields) {
+    match serde_json::from_str::<Value>(s) {
+        Ok(v) => flatten(prefix, &v, fields),
+        Err(_) => {
+            fields.insert(prefix.to_string(), s.to_string());
+        }
+    }
+}
+
+/// Lists are compared as a whole, independent of their order
+pub fn join_sorted(xs: &[String]) -> String {
+    let mut xs = xs.to_vec();
+    xs.sort();
+    xs.join(", ")
+}
+
+/// The route key as created by the gateway: trailing slashes are
+/// dropped except for the root path
+pub fn route_key(route: &Route) -> String {
+    if route.path == "/" {
+        format!("{} {}", &route.method, &route.path)
+    } else {
+        strip(&format!("{} {}", &route.method, &route.path), "/")
+    }
+}
+
+/// Gateways mounted under a domain path drop that prefix from the
+/// deployed route, so a live key matches when it is a suffix of the
+/// desired one
+pub fn route_matches(desired: &str, live: &str) -> bool {
+    let (dm, dp) = desired.split_once(' ').unwrap_or((desired, ""));
+    let (lm, lp) = live.split_once(' ').unwrap_or((live, ""));
+    dm == lm && !lp.is_empty() && dp.ends_with(lp)
+}
+
+pub fn authorization_of(route: &Route) -> String {
+    match &route.authorizer {
+        Some(a) if a.create => match a.kind.as_ref() {
+            "lambda" => s!("CUSTOM"),
+            "cognito" => s!("JWT"),
+            _ => s!("NONE"),
+        },
+        _ => s!("NONE"),
+    }
+}
+
+fn function_fields(f: &Function, version: Option<&String>) -> Fields {
+    let rt = &f.runtime;
+    let mut h: Fields = BTreeMap::new();
+    let is_zip = rt.package_type == "zip";
+    if is_zip {
+        h.insert(
+            s!("runtime"),
+            lambda::make_runtime(&rt.lang.to_str()).as_str().to_string(),
+        );
+        h.insert(s!("handler"), rt.handler.clone());
+        if !rt.layers.is_empty() {
+            h.insert(s!("layers"), join_sorted(&rt.layers));
+        }
+    }
+    if let Some(m) = rt.memory_size {
+        h.insert(s!("memory"), m.to_string());
+    }
+    if let Some(t) = rt.timeout {
+        h.insert(s!("timeout"), t.to_string());
+    }
+    h.insert(s!("role"), rt.role.arn.clone());
+    h.insert(
+        s!("package_type"),
+        lambda::make_package_type(&rt.package_type).as_str().to_string(),
+    );
+    h.insert(
+        s!("architecture"),
+        lambda::make_arch(&rt.arch.to_str()).as_str().to_string(),
+    );
+    for (k, v) in &rt.environment {
+        h.insert(format!("environment.{}", k), v.clone());
+    }
+    if let Some(v) = version {
+        h.insert(s!("version"), v.clone());
+    }
+    h
+}
+
+/// Names of the types and inputs declared in a GraphQL schema
+pub fn type_names(sdl: &str) -> HashSet<String> {
+    let mut h: HashSet<String> = HashSet::new();
+    for line in sdl.lines() {
+        let mut words = line.split_whitespace();
+        if let (Some("type" | "input"), Some(name)) = (words.next(), words.next()) {
+            h.insert(name.trim_end_matches('{').to_string());
+        }
+    }
+    h
+}
+
+/// Function name of a lambda arn, without its alias or version
+pub fn function_of_arn(arn: &str) -> String {
+    match arn.split_once(":function:") {
+        Some((_, rest)) => rest.split(':').next().unwrap_or_default().to_string(),
+        None => arn.to_string(),
+    }
+}
+
+fn queue_fields(queue: &Queue) -> Fields {
+    let mut h: Fields = BTreeMap::new();
+    h.insert(s!("fifo"), queue.fifo.to_string());
+    h.insert(
+        s!("visibility_timeout"),
+        queue.visibility_timeout.unwrap_or(900).to_string(),
+    );
+    if let Some(r) = queue.retention {
+        h.insert(s!("retention"), r.to_string());
+    }
+    if let Some(d) = &queue.dlq {
+        h.insert(s!("redrive.deadLetterTargetArn"), d.arn.clone());
+        h.insert(s!("redrive.maxReceiveCount"), d.max_receive_count.to_string());
+    }
+    h
+}
+
+fn mutation_fields(mutation: &Mutation) -> Fields {
+    let mut h: Fields = BTreeMap::new();
+    h.insert(s!("authorizer"), function_of_arn(&mutation.authorizer));
+    let mut names: Vec<String> = vec![];
+    for sdl in mutation.types.values() {
+        names.extend(type_names(sdl));
+    }
+    names.sort();
+    names.dedup();
+    h.insert(s!("types"), join_sorted(&names));
+    h
+}
+
+fn store_fields(store: &Store) -> Fields {
+    let mut h: Fields = BTreeMap::new();
+    h.insert(s!("kind"), store.kind.to_str());
+    if store.kind == StoreKind::Dynamodb {
+        if let Some(k) = &store.hash_key {
+            h.insert(s!("hash_key"), k.clone());
+        }
+        if let Some(k) = &store.range_key {
+            h.insert(s!("range_key"), k.clone());
+        }
+    }
+    h
+}
+
+fn resources_of(topology: &Topology, modified: &HashSet<String>) -> Vec<Resource> {
+    let ns = &topology.namespace;
+    let make = |kind: Kind, name: &str, fields: Fields| Resource {
+        kind: kind,
+        namespace: ns.clone(),
+        name: name.to_string(),
+        fields: fields,
+    };
+    let mut xs: Vec<Resource> = vec![];
+
+    for role in topology.roles.values() {
+        if role.kind.to_str() == "override" {
+            let mut h: Fields = BTreeMap::new();
+            h.insert(s!("policy_arn"), role.policy_arn.clone());
+            flatten_str("policy", &role.policy.to_string(), &mut h);
+            flatten_str("trust", &role.trust.to_string(), &mut h);
+            xs.push(make(Kind::Role, &role.name, h));
+        }
+    }
+
+    for f in topology.functions.values() {
+        if let Provider::Lambda = f.runtime.provider {
+            // the code version only takes part when the code changed
+            let version = match modified.contains(&f.fqn) {
+                true => topology.tags.get("version"),
+                false => None,
+            };
+            xs.push(make(Kind::Function, &f.fqn, function_fields(f, version)));
+        }
+    }
+
+    // the transducer is tagged with the topology, so it is desired too
+    if let Some(tx) = &topology.transducer {
+        let f = &tx.function;
+        xs.push(make(Kind::Function, &f.fqn, function_fields(f, None)));
+    }
+
+    for (name, route) in &topology.routes {
+        if !route.skip && name != "default" {
+            let mut h: Fields = BTreeMap::new();
+            h.insert(s!("gateway"), route.gateway.clone());
+            h.insert(s!("authorization"), authorization_of(route));
+            xs.push(make(Kind::Route, &route_key(route), h));
+        }
+    }
+
+    for event in topology.events.values() {
+        if !event.skip {
+            let mut h: Fields = BTreeMap::new();
+            h.insert(s!("bus"), event.bus.clone());
+            h.insert(s!("state"), s!("ENABLED"));
+            let pattern = serde_json::to_value(&event.pattern).unwrap();
+            flatten("pattern", &pattern, &mut h);
+            let arns: Vec<String> = event.targets.iter().map(|t| t.arn.clone()).collect();
+            h.insert(s!("targets"), join_sorted(&arns));
+            xs.push(make(Kind::Event, &event.rule_name, h));
+        }
+    }
+
+    for queue in topology.queues.values() {
+        if queue.should_create {
+            xs.push(make(Kind::Queue, &queue.name, queue_fields(queue)));
+        }
+    }
+
+    for mutation in topology.mutations.values() {
+        xs.push(make(Kind::Mutation, &mutation.api_name, mutation_fields(mutation)));
+    }
+
+    if let Some(flow) = &topology.flow {
+        let mut h: Fields = BTreeMap::new();
+        let kind = match flow.mode.as_ref() {
+            "Express" => "EXPRESS",
+            _ => "STANDARD",
+        };
+        h.insert(s!("type"), s!(kind));
+        h.insert(s!("role"), flow.role.arn.clone());
+        flatten("definition", &flow.definition, &mut h);
+        xs.push(make(Kind::State, &flow.name, h));
+    }
+
+    for channel in topology.channels.values() {
+        let mut h: Fields = BTreeMap::new();
+        h.insert(s!("api"), channel.api_name.clone());
+        h.insert(s!("handler"), channel.handler.clone());
+        xs.push(make(Kind::Channel, &channel.name, h));
+    }
+
+    // schedules without a target are not deployed
+    for schedule in topology.schedules.values() {
+        if !schedule.target_arn.is_empty() {
+            let mut h: Fields = BTreeMap::new();
+            h.insert(s!("group"), schedule.group.clone());
+            h.insert(s!("expression"), schedule.expression.clone());
+            h.insert(s!("target"), schedule.target_arn.clone());
+            xs.push(make(Kind::Schedule, &schedule.name, h));
+        }
+    }
+
+    for pool in topology.pools.values() {
+        let mut h: Fields = BTreeMap::new();
+        for (k, arn) in &pool.triggers {
+            h.insert(format!("triggers.{}", k), arn.clone());
+        }
+        xs.push(make(Kind::Pool, &pool.name, h));
+    }
+
+    // page content is not reflected, only its bucket and distribution
+    for page in topology.pages.values() {
+        if !page.skip_deploy {
+            let mut h: Fields = BTreeMap::new();
+            h.insert(s!("bucket"), page.bucket.clone());
+            h.insert(s!("distribution"), page.fqn.clone());
+            xs.push(make(Kind::Page, &page.fqn, h));
+        }
+    }
+
+    for store in topology.stores.values() {
+        if store.should_create {
+            xs.push(make(Kind::Store, &store.name, store_fields(store)));
+        }
+    }
+    xs
+}
+
+/// Desired state of every resource in the topology and its nodes.
+/// `modified` holds the fqns of functions whose code changed since the
+/// deployed version
+pub fn desired(topology: &Topology, modified: &HashSet<String>) -> Vec<Resource> {
+    let mut xs = resources_of(topology, modified);
+    for node in topology.nodes.values() {
+        xs.extend(resources_of(node, modified));
+    }
+    xs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_flatten() {
+        let mut h: Fields = BTreeMap::new();
+        let v = json!({"Statement": [{"Effect": "Allow", "Action": ["sqs:*"]}], "n": 1});
+        flatten("policy", &v, &mut h);
+        assert_eq!(h.get("policy.Statement[0].Effect").unwrap(), "Allow");
+        assert_eq!(h.get("policy.Statement[0].Action[0]").unwrap(), "sqs:*");
+        assert_eq!(h.get("policy.n").unwrap(), "1");
+        assert_eq!(h.len(), 3);
+    }
+
+    #[test]
+    fn test_type_names() {
+        let sdl = "type Query {\n  a: String\n}\ninput Etl{\n  b: Int\n}\nscalar X";
+        let names = type_names(sdl);
+        assert!(names.contains("Query"));
+        assert!(names.contains("Etl"));
+        assert_eq!(names.len(), 2);
+        let arn = "arn:aws:lambda:us-east-1:123:function:etl_auth_dev:live";
+        assert_eq!(function_of_arn(arn), "etl_auth_dev");
+        assert_eq!(function_of_arn("etl_auth_dev"), "etl_auth_dev");
+    }
+
+    #[test]
+    fn test_route_matches() {
+        assert!(route_matches("GET /api/users", "GET /api/users"));
+        assert!(route_matches("GET /v1/api/users", "GET /api/users"));
+        assert!(!route_matches("POST /api/users", "GET /api/users"));
+        assert!(!route_matches("GET /api/users", "GET /api/orders"));
+    }
+}
@@ -0,0 +1,421 @@
+use super::logs;
+use compiler::Entity;
+use composer::Topology;
+use kit as u;
+use kit::*;
+use provider::{
+    Auth,
+    aws::{
+        cloudwatch,
+        sfn,
+    },
+};
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::HashSet;
+
+/// One component a request went through, with the hops it caused
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Hop {
+    pub entity: String,
+    pub name: String,
+    pub via: Option<String>,
+    pub start: i64,
+    pub end: i64,
+    pub duration: i64,
+    pub error: Option<String>,
+    pub source: String,
+    pub children: Vec<Hop>,
+    // executions this hop mentions in its logs
+    #[serde(skip)]
+    pub links: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Trace {
+    pub id: String,
+    pub namespace: String,
+    pub sandbox: String,
+    pub hops: Vec<Hop>,
+}
+
+pub fn is_execution_arn(id: &str) -> bool {
+    id.starts_with("arn:aws:states:") && id.contains(":execution:")
+}
+
+fn execution_arns_in(message: &str) -> Vec<String> {
+    let mut xs: Vec<String> = vec![];
+    for (i, _) in message.match_indices("arn:aws:states:") {
+        let arn: String = message[i..]
+            .chars()
+            .take_while(|c| !c.is_whitespace() && *c != '"' && *c != '\'' && *c != ',')
+            .collect();
+        if is_execution_arn(&arn) && !xs.contains(&arn) {
+            xs.push(arn);
+        }
+    }
+    xs
+}
+
+// the billed duration Lambda reports at the end of each invocation
+fn report_duration(message: &str) -> Option<i64> {
+    if !message.starts_with("REPORT") {
+        return None;
+    }
+    let (_, rest) = message.split_once("\tDuration: ")?;
+    let (ms, _) = rest.split_once(" ms")?;
+    ms.parse::<f64>().ok().map(|d| d.round() as i64)
+}
+
+fn error_in(message: &str) -> Option<String> {
+    let is_error = message.contains("ERROR")
+        || message.contains("Task timed out")
+        || message.contains("\"errorMessage\"");
+    match is_error {
+        true => Some(message.trim().chars().take(200).collect()),
+        false => None,
+    }
+}
+
+fn log_hop(source: &logs::Source, events: &[cloudwatch::LogEvent]) -> Hop {
+    let start = events.iter().map(|e| e.timestamp).min().unwrap_or_default();
+    let end = events.iter().map(|e| e.timestamp).max().unwrap_or_default();
+    let duration = match events.iter().find_map(|e| report_duration(&e.message)) {
+        Some(d) => d,
+        None => end - start,
+    };
+    let mut links: Vec<String> = vec![];
+    for e in events {
+        for arn in execution_arns_in(&e.message) {
+            if !links.contains(&arn) {
+                links.push(arn);
+            }
+        }
+    }
+    Hop {
+        entity: source.entity.to_str(),
+        name: source.component.clone(),
+        via: None,
+        start: start,
+        end: end,
+        duration: duration,
+        error: events.iter().find_map(|e| error_in(&e.message)),
+        source: source.group.clone(),
+        children: vec![],
+        links: links,
+    }
+}
+
+fn step(name: &str, start: i64, end: i64, error: Option<String>) -> Hop {
+    Hop {
+        entity: s!("step"),
+        name: s!(name),
+        via: None,
+        start: start,
+        end: end,
+        duration: end - start,
+        error: error,
+        source: s!(""),
+        children: vec![],
+        links: vec![],
+    }
+}
+
+/// The execution and its steps from the execution history. Steps that
+/// never exited end with the execution
+pub fn execution_hop(arn: &str, events: &[sfn::ExecutionEvent]) -> Option<Hop> {
+    let first = events.first()?;
+    let last = events.last()?;
+    let mut steps: Vec<Hop> = vec![];
+    let mut open: Vec<(String, i64, Option<String>)> = vec![];
+    let mut error: Option<String> = None;
+    for e in events {
+        if let Some(err) = &e.error {
+            if let Some((_, _, step_error)) = open.last_mut() {
+                *step_error = Some(err.clone());
+            }
+            if error.is_none() {
+                error = Some(err.clone());
+            }
+        }
+        let name = u::safe_unwrap(e.state.as_ref());
+        if e.kind.ends_with("StateEntered") {
+            open.push((name, e.timestamp, None));
+        } else if e.kind.ends_with("StateExited") {
+            if let Some(i) = open.iter().rposition(|(n, _, _)| *n == name) {
+                let (n, start, err) = open.remove(i);
+                steps.push(step(&n, start, e.timestamp, err));
+            }
+        }
+    }
+    for (n, start, err) in open {
+        steps.push(step(&n, start, last.timestamp, err));
+    }
+    steps.sort_by_key(|s| s.start);
+    Some(Hop {
+        entity: Entity::State.to_str(),
+        name: arn.split(':').nth(6).unwrap_or(arn).to_string(),
+        via: None,
+        start: first.timestamp,
+        end: last.timestamp,
+        duration: last.timestamp - first.timestamp,
+        error: error,
+        source: s!(arn),
+        children: steps,
+        links: vec![],
+    })
+}
+
+/// The edges of the topology graph a request can travel along
+struct Graph {
+    // gateway, target entity, target arn
+    routes: Vec<(String, Entity, String)>,
+    // function name, arn and fqn
+    functions: Vec<(String, String, String)>,
+    // kind and name of the event or queue, target arn or fqn
+    consumers: Vec<(String, String, String)>,
+    // functions the state machine invokes
+    steps: HashSet<String>,
+}
+
+impl Graph {
+    fn new(topology: &Topology) -> Graph {
+        let mut g = Graph {
+            routes: vec![],
+            functions: vec![],
+            consumers: vec![],
+            steps: HashSet::new(),
+        };
+        g.add(topology);
+        for node in topology.nodes.values() {
+            g.add(node);
+        }
+        g
+    }
+
+    fn add(&mut self, t: &Topology) {
+        for r in t.routes.values() {
+            self.routes
+                .push((r.gateway.clone(), r.target.entity.clone(), r.target.arn.clone()));
+        }
+        for f in t.functions.values() {
+            self.functions.push((f.name.clone(), f.arn.clone(), f.fqn.clone()));
+        }
+        for e in t.events.values() {
+            for target in &e.targets {
+                self.consumers
+                    .push((s!("event"), e.name.clone(), target.arn.clone()));
+            }
+        }
+        for q in t.queues.values() {
+            for target in &q.targets {
+                self.consumers
+                    .push((s!("queue"), q.name.clone(), target.name.clone()));
+            }
+        }
+        if let Some(flow) = &t.flow {
+            let definition = flow.definition.to_string();
+            for f in t.functions.values() {
+                if definition.contains(&f.arn) || definition.contains(&f.fqn) {
+                    self.steps.insert(f.name.clone());
+                }
+            }
+        }
+    }
+
+    fn arn_of(&self, function: &str) -> Option<&String> {
+        self.functions
+            .iter()
+            .find(|(name, _, _)| name == function)
+            .map(|(_, arn, _)| arn)
+    }
+
+    fn consumer_of(&self, function: &str) -> Option<&(String, String, String)> {
+        let (_, arn, fqn) = self.functions.iter().find(|(name, _, _)| name == function)?;
+        self.consumers.iter().find(|(_, _, t)| t == arn || t == fqn)
+    }
+
+    /// Whether `parent` can cause `child`, and through what
+    fn triggers(&self, parent: &Hop, child: &Hop) -> Option<Option<String>> {
+        let function = Entity::Function.to_str();
+        let state = Entity::State.to_str();
+        let route = Entity::Route.to_str();
+
+        if child.entity == state && parent.links.contains(&child.source) {
+            return Some(None);
+        }
+        if parent.entity == route {
+            let wanted = |entity: Entity, arn: Option<&String>| {
+                self.routes.iter().any(|(gw, e, a)| {
+                    *gw == parent.name
+                        && *e == entity
+                        && match arn {
+                            Some(arn) => a == arn,
+                            None => true,
+                        }
+                })
+            };
+            if child.entity == function && wanted(Entity::Function, self.arn_of(&child.name)) {
+                return Some(None);
+            }
+            if child.entity == state && wanted(Entity::State, None) {
+                return Some(None);
+            }
+        }
+        if parent.entity == function && child.entity == function {
+            if let Some((kind, name, _)) = self.consumer_of(&child.name) {
+                return Some(Some(format!("{} {}", kind, name)));
+            }
+        }
+        if parent.entity == state && child.entity == function && self.steps.contains(&child.name)
+        {
+            return Some(None);
+        }
+        None
+    }
+}
+
+fn build(i: usize, hops: &[Hop], parents: &[Option<usize>]) -> Hop {
+    let mut hop = hops[i].clone();
+    for (j, p) in parents.iter().enumerate() {
+        if *p == Some(i) {
+            hop.children.push(build(j, hops, parents));
+        }
+    }
+    hop.children.sort_by_key(|h| h.start);
+    hop
+}
+
+// each hop hangs off the latest earlier hop that can cause it
+fn nest(graph: &Graph, mut hops: Vec<Hop>) -> Vec<Hop> {
+    hops.sort_by_key(|h| h.start);
+    let mut parents: Vec<Option<usize>> = vec![None; hops.len()];
+    for i in 0..hops.len() {
+        for j in (0..i).rev() {
+            if let Some(via) = graph.triggers(&hops[j], &hops[i]) {
+                parents[i] = Some(j);
+                hops[i].via = via;
+                break;
+            }
+        }
+    }
+    (0..hops.len())
+        .filter(|i| parents[*i].is_none())
+        .map(|i| build(i, &hops, &parents))
+        .collect()
+}
+
+async fn find_execution(auth: &Auth, arn: &str) -> Option<Hop> {
+    let client = sfn::make_client(auth).await;
+    let events = sfn::get_execution_history(&client, arn).await;
+    execution_hop(arn, &events)
+}
+
+/// Reconstructs the path of a request id or execution arn through the
+/// topology from the logs of the last `since` (e.g. `1h`)
+pub async fn trace(auth: &Auth, topology: &Topology, id: &str, since: &str) -> Trace {
+    let since = match u::duration_millis(since) {
+        Some(ms) => ms,
+        None => panic!("Invalid duration {}", since),
+    };
+    let start_time = u::current_millis() - since;
+    let sources = logs::sources(auth, topology, None).await;
+    let client = cloudwatch::make_client(auth).await;
+    let pattern = Some(format!("\"{}\"", id));
+
+    let mut hops: Vec<Hop> = vec![];
+    let mut executions: Vec<String> = vec![];
+    if is_execution_arn(id) {
+        executions.push(s!(id));
+    }
+    for source in &sources {
+        let res =
+            cloudwatch::filter_log_events(&client, &source.group, start_time, pattern.clone())
+                .await;
+        let events = match res {
+            Ok(xs) => xs,
+            Err(e) => {
+                println!("Failed to read logs of {}: {}", &source.group, e);
+                continue;
+            }
+        };
+        if events.is_empty() {
+            continue;
+        }
+        let hop = log_hop(source, &events);
+        for arn in &hop.links {
+            if !executions.contains(arn) {
+                executions.push(arn.clone());
+            }
+        }
+        // the execution history tells more than the state machine's logs
+        if source.entity != Entity::State {
+            hops.push(hop);
+        }
+    }
+    for arn in executions {
+        if let Some(hop) = find_execution(auth, &arn).await {
+            hops.push(hop);
+        }
+    }
+    Trace {
+        id: s!(id),
+        namespace: topology.namespace.clone(),
+        sandbox: topology.sandbox.clone(),
+        hops: nest(&Graph::new(topology), hops),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(
+        timestamp: i64,
+        kind: &str,
+        state: Option<&str>,
+        error: Option<&str>,
+    ) -> sfn::ExecutionEvent {
+        sfn::ExecutionEvent {
+            timestamp: timestamp,
+            kind: s!(kind),
+            state: state.map(|s| s.to_string()),
+            error: error.map(|s| s.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_execution_hop() {
+        let arn = "arn:aws:states:us-west-2:123:execution:etl_dev:run-1";
+        let events = vec![
+            event(0, "ExecutionStarted", None, None),
+            event(10, "TaskStateEntered", Some("Load"), None),
+            event(250, "TaskStateExited", Some("Load"), None),
+            event(260, "TaskStateEntered", Some("Enhance"), None),
+            event(900, "TaskFailed", None, Some("States.Timeout")),
+            event(905, "ExecutionFailed", None, None),
+        ];
+        let hop = execution_hop(arn, &events).unwrap();
+        assert_eq!(hop.name, "etl_dev");
+        assert_eq!(hop.duration, 905);
+        assert_eq!(hop.error, Some(s!("States.Timeout")));
+        assert_eq!(hop.children.len(), 2);
+        assert_eq!(hop.children[0].name, "Load");
+        assert_eq!(hop.children[0].duration, 240);
+        assert_eq!(hop.children[1].error, Some(s!("States.Timeout")));
+    }
+
+    #[test]
+    fn test_log_parsing() {
+        let report = "REPORT RequestId: abc\tDuration: 12.62 ms\tBilled Duration: 13 ms";
+        assert_eq!(report_duration(report), Some(13));
+        assert_eq!(report_duration("START RequestId: abc"), None);
+        let arn = "arn:aws:states:us-west-2:1:execution:sm:x";
+        let msg = format!("started {{\"executionArn\": \"{}\"}}", arn);
+        assert_eq!(execution_arns_in(&msg), vec![s!(arn)]);
+        assert!(error_in("[ERROR] KeyError: 'id'").is_some());
+        assert!(error_in("INFO done").is_none());
+    }
+}
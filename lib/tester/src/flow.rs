@@ -0,0 +1,437 @@
+use super::{
+    as_json,
+    compare,
+};
+use compiler::{
+    Entity,
+    spec::{
+        EffectSpec,
+        TestSpec,
+    },
+};
+use composer::{
+    Event,
+    Topology,
+};
+use kit as u;
+use kit::*;
+use provider::{
+    Auth,
+    aws::{
+        eventbridge,
+        metrics,
+        sfn,
+        sqs,
+    },
+};
+use serde_json::{
+    Value,
+    json,
+};
+use std::{
+    collections::HashMap,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+const DEFAULT_TIMEOUT: u64 = 30;
+
+// SQS metrics are published once a minute and land a few minutes late
+const QUEUE_TIMEOUT: u64 = 300;
+
+// Where an effect is observed. Events (and channels, which are fed by
+// events) are observed through a temporary rule that copies matching
+// events into a probe queue, so a channel effect matches the events
+// sent to the channel rather than what the channel publishes. Queues are never received from, since that
+// competes with their consumer; the messages sent to them since the
+// probe was armed are counted instead
+enum Probe {
+    State {
+        arn: String,
+        since: i64,
+        execution: Option<String>,
+    },
+    Queue {
+        name: String,
+        start: i64,
+        baseline: f64,
+    },
+    Event {
+        bus: String,
+        rule: String,
+        url: String,
+    },
+}
+
+fn node_of<'a>(topology: &'a Topology, namespace: &str) -> Option<&'a Topology> {
+    if topology.namespace == namespace {
+        Some(topology)
+    } else {
+        topology.nodes.get(namespace)
+    }
+}
+
+fn channel_events(topology: &Topology, channel: &str) -> Vec<Event> {
+    let prefix = format!("{}_channel_", channel);
+    topology
+        .events
+        .values()
+        .filter(|e| {
+            e.targets
+                .iter()
+                .any(|t| t.entity == Entity::Channel && t.id.starts_with(&prefix))
+        })
+        .cloned()
+        .collect()
+}
+
+fn probe_policy(queue_arn: &str, rule_arn: &str) -> String {
+    json!({
+        "Version": "2012-10-17",
+        "Statement": [{
+            "Effect": "Allow",
+            "Principal": {"Service": "events.amazonaws.com"},
+            "Action": "sqs:SendMessage",
+            "Resource": queue_arn,
+            "Condition": {"ArnEquals": {"aws:SourceArn": rule_arn}}
+        }]
+    })
+    .to_string()
+}
+
+async fn arm_event(auth: &Auth, event: &Event) -> Result<Probe, String> {
+    let id = u::uuid_str().chars().take(8).collect::<String>();
+    let rule = format!("tc-probe-{}-{}", &event.name, id);
+    let pattern = match serde_json::to_string(&event.pattern) {
+        Ok(p) => p,
+        Err(e) => return Err(format!("Invalid pattern for {}: {}", &event.name, e)),
+    };
+    let eclient = eventbridge::make_client(auth).await;
+    let qclient = sqs::make_client(auth).await;
+
+    let tags = HashMap::from([(s!("tc:probe"), s!("true"))]);
+    let rule_arn =
+        eventbridge::try_create_rule(&eclient, &event.bus, &rule, &pattern, &tags).await?;
+    let queue_arn = format!("arn:aws:sqs:{}:{}:{}", &auth.region, &auth.account, &rule);
+    let policy = probe_policy(&queue_arn, &rule_arn);
+    let url = match sqs::create_probe_queue(&qclient, &rule, &policy).await {
+        Some(url) => url,
+        None => {
+            let _ = eventbridge::try_delete_rule(&eclient, &event.bus, &rule, &[]).await;
+            return Err(format!("Failed to create probe queue {}", &rule));
+        }
+    };
+    let probe = Probe::Event {
+        bus: event.bus.clone(),
+        rule: rule.clone(),
+        url: url,
+    };
+    let target =
+        eventbridge::make_target(&rule, &queue_arn, "", "sqs", None, None, None, None, None);
+    match eventbridge::try_put_targets(&eclient, &event.bus, &rule, vec![target]).await {
+        Ok(()) => Ok(probe),
+        Err(e) => {
+            disarm(auth, &probe).await;
+            Err(e)
+        }
+    }
+}
+
+async fn sent_to_queue(auth: &Auth, name: &str, start: i64) -> Result<f64, String> {
+    let client = metrics::make_client(auth).await;
+    let end = u::current_millis() + 60_000;
+    metrics::sum(
+        &client,
+        "AWS/SQS",
+        "NumberOfMessagesSent",
+        &[("QueueName", name)],
+        start,
+        end,
+    )
+    .await
+}
+
+// only counts are observed for queues, an expectation on the content
+// of messages could never match
+fn check_queue_expect(effect: &EffectSpec) -> Result<(), String> {
+    let expected = match &effect.expect {
+        Some(e) => as_json(e),
+        None => return Ok(()),
+    };
+    match expected.as_object() {
+        Some(m) if m.len() == 1 && m.get("sent").is_some_and(|n| n.is_u64()) => Ok(()),
+        _ => Err(format!(
+            "Queue effects only match the count of sent messages, {{\"sent\": <n>}}, not {}",
+            &expected
+        )),
+    }
+}
+
+async fn arm(
+    auth: &Auth,
+    topology: &Topology,
+    namespace: &str,
+    effect: &EffectSpec,
+) -> Result<Probe, String> {
+    let (entity, component) = Entity::as_entity_component(&effect.entity);
+    let node = match node_of(topology, namespace) {
+        Some(n) => n,
+        None => return Err(format!("No topology {}", namespace)),
+    };
+    match entity {
+        Entity::State => {
+            let node = match &component {
+                Some(ns) => node_of(topology, ns).unwrap_or(node),
+                None => node,
+            };
+            match &node.flow {
+                Some(f) if f.mode == "Express" => {
+                    Err(s!("State effects need a Standard state machine"))
+                }
+                // any execution may match an empty expectation, and
+                // executions started by other units are only told apart
+                // by their output
+                Some(_) if effect.expect.is_none() => {
+                    Err(s!("State effects need an explicit expect"))
+                }
+                Some(_) => Ok(Probe::State {
+                    arn: auth.sfn_arn(&node.fqn),
+                    since: u::current_millis() / 1000 - 1,
+                    execution: None,
+                }),
+                None => Err(format!("{} has no state machine", &node.namespace)),
+            }
+        }
+        Entity::Queue => {
+            check_queue_expect(effect)?;
+            let name = u::maybe_string(component, "");
+            let queue = match node.queues.get(&name) {
+                Some(q) => q,
+                None => return Err(format!("Queue {} not found", &name)),
+            };
+            let client = sqs::make_client(auth).await;
+            if sqs::find_queue_url(&client, &queue.name).await.is_none() {
+                return Err(format!("Queue {} is not deployed", &queue.name));
+            }
+            // the current minute is counted from the start, so messages
+            // sent in it before the probe was armed are the baseline
+            let start = u::current_millis() / 60_000 * 60_000;
+            let baseline = sent_to_queue(auth, &queue.name, start).await?;
+            Ok(Probe::Queue {
+                name: queue.name.clone(),
+                start: start,
+                baseline: baseline,
+            })
+        }
+        Entity::Event => {
+            let name = u::maybe_string(component, "");
+            match node.events.get(&name) {
+                Some(e) => arm_event(auth, e).await,
+                None => Err(format!("Event {} not found", &name)),
+            }
+        }
+        // what the channel publishes to its subscribers is not seen,
+        // only the events its rule delivers to it
+        Entity::Channel => {
+            let name = u::maybe_string(component, "");
+            match channel_events(node, &name).first() {
+                Some(e) => arm_event(auth, e).await,
+                None => Err(format!("No event publishes to channel {}", &name)),
+            }
+        }
+        _ => Err(format!("Unsupported effect {}", &effect.entity)),
+    }
+}
+
+async fn disarm(auth: &Auth, probe: &Probe) {
+    if let Probe::Event { bus, rule, url } = probe {
+        let eclient = eventbridge::make_client(auth).await;
+        let res = eventbridge::try_delete_rule(&eclient, bus, rule, &[rule.as_str()]).await;
+        if let Err(e) = res {
+            tracing::warn!("{}", e);
+        }
+        let qclient = sqs::make_client(auth).await;
+        sqs::delete_queue(&qclient, url).await;
+    }
+}
+
+fn timeout_of(probe: &Probe, effect: &EffectSpec) -> Duration {
+    let default = match probe {
+        Probe::Queue { .. } => QUEUE_TIMEOUT,
+        _ => DEFAULT_TIMEOUT,
+    };
+    Duration::from_secs(effect.timeout.unwrap_or(default))
+}
+
+// values observed so far; a state machine yields the output of the
+// execution the test started, or of those completed since the probe was
+// armed. A queue yields the count of messages sent to it as
+// `{"sent": n}` and event probes yield the events as they arrive
+async fn poll(auth: &Auth, probe: &Probe, wait: i32) -> Result<Vec<Value>, String> {
+    match probe {
+        Probe::State {
+            arn,
+            since,
+            execution,
+        } => {
+            let client = sfn::make_client(auth).await;
+            let execs = match execution {
+                Some(e) => vec![e.clone()],
+                None => sfn::list_executions_since(&client, arn, *since).await,
+            };
+            let mut outputs: Vec<Value> = vec![];
+            for exec in execs {
+                let (status, output) = sfn::describe_execution(&client, &exec).await;
+                match status.as_ref() {
+                    "RUNNING" | "PENDING_REDRIVE" => (),
+                    "SUCCEEDED" => outputs.push(as_json(&u::maybe_string(output, "{}"))),
+                    _ => return Err(format!("Execution {} {}", &exec, &status)),
+                }
+            }
+            Ok(outputs)
+        }
+        Probe::Queue {
+            name,
+            start,
+            baseline,
+        } => {
+            tokio::time::sleep(Duration::from_secs(wait as u64)).await;
+            let sent = sent_to_queue(auth, name, *start).await? - baseline;
+            if sent > 0.0 {
+                Ok(vec![json!({ "sent": sent as i64 })])
+            } else {
+                Ok(vec![])
+            }
+        }
+        Probe::Event { url, .. } => {
+            let client = sqs::make_client(auth).await;
+            let msgs = sqs::receive_messages(&client, url, wait, 30).await;
+            Ok(msgs.iter().map(|m| as_json(m)).collect())
+        }
+    }
+}
+
+async fn observe(auth: &Auth, probe: &Probe, effect: &EffectSpec) -> Result<(), String> {
+    let expected = as_json(&u::maybe_string(effect.expect.clone(), "{}"));
+    let cond = u::maybe_string(effect.condition.clone(), "includes");
+    let timeout = timeout_of(probe, effect);
+    let start = Instant::now();
+    let mut seen: Vec<Value> = vec![];
+    while start.elapsed() < timeout {
+        let left = (timeout - start.elapsed()).as_secs().clamp(1, 5) as i32;
+        let mut fresh = false;
+        for v in poll(auth, probe, left).await? {
+            if compare(&expected, &v, &cond).is_ok() {
+                return Ok(());
+            }
+            if !seen.contains(&v) {
+                seen.push(v);
+                fresh = true;
+            }
+        }
+        if !fresh {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+    Err(format!(
+        "{}: no match for {} within {}s, observed: {}",
+        &effect.entity,
+        &expected,
+        timeout.as_secs(),
+        Value::Array(seen)
+    ))
+}
+
+/// Probes that must be in place before the test entity is invoked, so
+/// no effect is missed
+pub(crate) struct Probes(Vec<(EffectSpec, Probe)>);
+
+impl Probes {
+    /// Narrows state probes to the execution the test started, when the
+    /// test entity is the state machine being observed
+    pub(crate) fn correlate(&mut self, response: &str) {
+        for (_, probe) in self.0.iter_mut() {
+            if let Probe::State { arn, execution, .. } = probe {
+                let prefix = format!("{}:", arn.replacen(":stateMachine:", ":execution:", 1));
+                if response.starts_with(&prefix) {
+                    *execution = Some(s!(response));
+                }
+            }
+        }
+    }
+}
+
+/// Arms a probe for every effect. When one cannot be armed, the probes
+/// armed so far are removed and the error is returned
+pub(crate) async fn arm_all(
+    auth: &Auth,
+    topology: &Topology,
+    namespace: &str,
+    effects: &[EffectSpec],
+) -> Result<Probes, String> {
+    let mut probes = Probes(vec![]);
+    for effect in effects {
+        match arm(auth, topology, namespace, effect).await {
+            Ok(p) => probes.0.push((effect.clone(), p)),
+            Err(e) => {
+                disarm_all(auth, probes).await;
+                return Err(format!("{}: {}", &effect.entity, e));
+            }
+        }
+    }
+    Ok(probes)
+}
+
+/// Removes the temporary rules and queues without waiting for effects
+pub(crate) async fn disarm_all(auth: &Auth, probes: Probes) {
+    for (_, probe) in probes.0 {
+        disarm(auth, &probe).await;
+    }
+}
+
+/// Waits for every effect and returns the ones that were not observed.
+/// Temporary rules and queues are removed afterwards
+pub(crate) async fn observe_all(auth: &Auth, probes: Probes) -> Vec<String> {
+    let mut errors: Vec<String> = vec![];
+    for (effect, probe) in probes.0 {
+        if let Err(e) = observe(auth, &probe, &effect).await {
+            errors.push(e);
+        }
+        disarm(auth, &probe).await;
+    }
+    errors
+}
+
+/// Whether a test observes state machine executions. Such tests are run
+/// one at a time, so executions of other units are not mistaken for
+/// theirs
+pub(crate) fn observes_state(spec: &TestSpec) -> bool {
+    spec.effects
+        .iter()
+        .any(|e| Entity::as_entity_component(&e.entity).0 == Entity::State)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(expect: Option<&str>) -> EffectSpec {
+        EffectSpec {
+            entity: s!("queue/orders"),
+            expect: expect.map(|e| s!(e)),
+            condition: None,
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_check_queue_expect() {
+        assert!(check_queue_expect(&effect(None)).is_ok());
+        assert!(check_queue_expect(&effect(Some(r#"{"sent": 2}"#))).is_ok());
+        assert!(check_queue_expect(&effect(Some(r#"{"id": 1}"#))).is_err());
+        assert!(check_queue_expect(&effect(Some(r#"{"sent": 2, "id": 1}"#))).is_err());
+        assert!(check_queue_expect(&effect(Some("ok"))).is_err());
+    }
+}
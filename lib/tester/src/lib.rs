@@ -1,8 +1,14 @@
+mod flow;
+pub mod local;
+pub mod report;
+mod schema;
+mod snapshot;
+
 use assert_json_diff::{
-    assert_json_eq,
-    assert_json_include,
+    CompareMode,
+    Config,
+    assert_json_matches_no_panic,
 };
-use colored::Colorize;
 use compiler::{
     Entity,
     spec::TestSpec,
@@ -13,6 +19,7 @@ use composer::{
 };
 use jsonpath_rust::JsonPath;
 use kit as u;
+use kit::*;
 use provider::{
     Auth,
     aws::{
@@ -21,10 +28,17 @@ use provider::{
         sfn,
     },
 };
+use report::{
+    Status,
+    TestResult,
+};
 use serde_json::Value;
 use std::{
     collections::HashMap,
-    time::Instant,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 fn render(s: &str, sandbox: &str) -> String {
@@ -33,35 +47,130 @@ fn render(s: &str, sandbox: &str) -> String {
     u::stencil(s, table)
 }
 
-fn assert_json_path(data: Value, expected: Value, path: &str) {
-    let vec: Vec<&Value> = data.query(path).unwrap();
-    assert_json_eq!(vec.iter().collect::<Vec<_>>(), vec![&expected]);
+fn compare_json_path(data: &Value, expected: &Value, path: &str) -> Result<(), String> {
+    let vec: Vec<&Value> = match data.query(path) {
+        Ok(v) => v,
+        Err(e) => return Err(format!("Invalid condition {}: {}", path, e)),
+    };
+    assert_json_matches_no_panic(&vec, &vec![expected], Config::new(CompareMode::Strict))
 }
 
-fn assert_case(expected: Option<String>, response: &str, cond: Option<String>) {
-    let expected = u::maybe_string(expected, "{}");
-    let expected = u::json_value(&expected);
-    let response = u::json_value(&response);
-    let cond = u::maybe_string(cond, "matches");
-    println!("expected: {}", &expected);
-    println!("response: {}", &response);
-    match cond.as_ref() {
+fn compare(expected: &Value, response: &Value, cond: &str) -> Result<(), String> {
+    match cond {
         "matches" | "=" => {
-            assert_json_eq!(response, expected)
+            assert_json_matches_no_panic(response, expected, Config::new(CompareMode::Strict))
         }
         "includes" | "contains" => {
-            assert_json_include!(actual: response, expected: expected)
+            assert_json_matches_no_panic(response, expected, Config::new(CompareMode::Inclusive))
         }
-        _ => assert_json_path(response, expected, &cond),
+        _ => compare_json_path(response, expected, cond),
+    }
+}
+
+// responses that are not json, like execution arns, are compared as
+// strings
+fn as_json(s: &str) -> Value {
+    match serde_json::from_str(s) {
+        Ok(v) => v,
+        Err(_) => Value::String(s!(s)),
+    }
+}
+
+// an empty object is expected when a test has no other assertion
+fn expectation(spec: &TestSpec) -> Option<Value> {
+    match (&spec.expect, u::opt_as_bool(spec.snapshot), &spec.schema) {
+        (Some(e), _, _) => Some(as_json(e)),
+        (None, false, None) => Some(as_json("{}")),
+        _ => None,
     }
 }
 
-async fn invoke_function(auth: &Auth, fqn: &str, payload: &str) -> String {
+// where snapshots are kept and whether to record them again
+#[derive(Clone)]
+struct Snapshots {
+    dir: String,
+    update: bool,
+}
+
+fn check_case(
+    namespace: &str,
+    entity: &str,
+    unit: &str,
+    spec: &TestSpec,
+    response: &str,
+    duration: Duration,
+    snapshots: &Snapshots,
+) -> TestResult {
+    let response = as_json(response);
+    let actual = snapshot::ignore(&response, &spec.ignore);
+    let expected = expectation(spec).map(|e| snapshot::ignore(&e, &spec.ignore));
+    let cond = u::maybe_string(spec.condition.clone(), "matches");
+
+    let mut errors: Vec<String> = vec![];
+    if let Some(e) = &expected {
+        if let Err(diff) = compare(e, &actual, &cond) {
+            errors.push(diff);
+        }
+    }
+    if let Some(s) = &spec.schema {
+        match schema::read(&snapshots.dir, s) {
+            Ok(schema) => errors.extend(schema::validate(&schema, &response)),
+            Err(e) => return TestResult::errored(namespace, entity, unit, &e),
+        }
+    }
+    if u::opt_as_bool(spec.snapshot) {
+        let path = snapshot::path_of(&snapshots.dir, namespace, unit);
+        if let Err(diff) = snapshot::check(&path, &actual, snapshots.update) {
+            errors.push(diff);
+        }
+    }
+
+    let status = if errors.len() > 0 {
+        Status::Fail
+    } else {
+        Status::Pass
+    };
+    let mut r = TestResult::new(namespace, entity, unit, status, duration);
+    if errors.len() > 0 {
+        r.message = Some(format!(
+            "expected: {}\nresponse: {}\n{}",
+            u::maybe_string(expected.as_ref().map(|e| e.to_string()), "-"),
+            &response,
+            errors.join("\n")
+        ));
+    }
+    r.expected = expected;
+    r.actual = Some(response);
+    r
+}
+
+// tests never read stdin, a missing payload is an empty object
+async fn read_payload(auth: &Auth, dir: &str, payload: &Option<String>) -> Result<String, String> {
+    match payload {
+        Some(p) => invoker::try_read_payload(auth, p).await,
+        None => {
+            let f = format!("{}/payload.json", dir);
+            if u::file_exists(&f) {
+                Ok(u::slurp(&f))
+            } else {
+                Ok(s!("{}"))
+            }
+        }
+    }
+}
+
+fn first_str(xs: &[Value], what: &str) -> Result<String, String> {
+    match xs.first().and_then(|v| v.as_str()) {
+        Some(x) => Ok(x.to_string()),
+        None => Err(format!("Event pattern has no {}", what)),
+    }
+}
+
+async fn invoke_function(auth: &Auth, fqn: &str, payload: &str) -> Result<String, String> {
     let client = lambda::make_client(&auth).await;
-    let maybe_response = lambda::invoke_sync(&client, fqn, &payload).await;
-    match maybe_response {
-        Ok(r) => r,
-        Err(_) => panic!("Failed to execute test"),
+    match lambda::invoke_sync(&client, fqn, &payload).await {
+        Ok(r) => Ok(r),
+        Err(e) => Err(format!("Failed to invoke {}: {}", fqn, e)),
     }
 }
 
@@ -91,18 +200,17 @@ async fn invoke(
     topology: &Topology,
     entity: &str,
     payload: &str,
-) -> String {
+) -> Result<String, String> {
     let (entity, component) = Entity::as_entity_component(entity);
     match entity {
         Entity::Function => {
-            if let Some(c) = component {
-                let maybe_fqn = get_fqn(namespace, topology, &c);
-                match maybe_fqn {
-                    Some(fqn) => invoke_function(auth, &fqn, payload).await,
-                    None => panic!("No function defined"),
-                }
-            } else {
-                panic!("Component not specified")
+            let c = match component {
+                Some(c) => c,
+                None => return Err(s!("Component not specified")),
+            };
+            match get_fqn(namespace, topology, &c) {
+                Some(fqn) => invoke_function(auth, &fqn, payload).await,
+                None => Err(format!("No function {} defined", &c)),
             }
         }
         Entity::State => {
@@ -114,96 +222,138 @@ async fn invoke(
                 None => "Standard",
             };
 
-            if mode == "Express" {
-                let (exec_arn, _maybe_response) =
-                    match sfn::start_sync_execution(client, &arn, &payload, None).await {
-                        Ok((exec_arn, maybe_response)) => (exec_arn, maybe_response),
-                        Err(error) => panic!("Failed to invoke. Error: {}", error),
-                    };
-                return exec_arn;
+            let res = if mode == "Express" {
+                sfn::start_sync_execution(client, &arn, &payload, None).await
             } else {
-                let (exec_arn, _maybe_response) =
-                    match sfn::start_execution(client, &arn, &payload).await {
-                        Ok((exec_arn, maybe_response)) => (exec_arn, maybe_response),
-                        Err(error) => panic!("Failed to invoke. Error: {}", error),
-                    };
-                return exec_arn;
+                sfn::start_execution(client, &arn, &payload).await
+            };
+            match res {
+                Ok((exec_arn, _)) => Ok(exec_arn),
+                Err(e) => Err(format!("Failed to invoke. Error: {}", e)),
             }
         }
         Entity::Event => {
-            if let Some(c) = component {
-                if let Some(e) = &topology.events.get(&c) {
+            let c = match component {
+                Some(c) => c,
+                None => return Err(s!("No component defined")),
+            };
+            match &topology.events.get(&c) {
+                Some(e) => {
                     let client = eventbridge::make_client(auth).await;
-                    let detail_type = e.pattern.detail_type.first().unwrap().as_str().unwrap();
-                    let source = e.pattern.source.first().unwrap().as_str().unwrap();
-                    eventbridge::put_event(client, &e.bus, detail_type, source, payload).await
-                } else {
-                    panic!("Event not found")
+                    let detail_type = first_str(&e.pattern.detail_type, "detail-type")?;
+                    let source = first_str(&e.pattern.source, "source")?;
+                    eventbridge::try_put_event(client, &e.bus, &detail_type, &source, payload).await
                 }
-            } else {
-                panic!("No component defined")
+                None => Err(format!("Event {} not found", &c)),
             }
         }
         Entity::Route => {
-            if let Some(c) = component {
-                if let Some(r) = &topology.routes.get(&c) {
+            let c = match component {
+                Some(c) => c,
+                None => return Err(s!("No component defined")),
+            };
+            match &topology.routes.get(&c) {
+                Some(r) => {
                     let res = invoker::route::request(auth, &r).await;
-                    res.to_string()
-                } else {
-                    panic!("Route not found")
+                    Ok(res.to_string())
                 }
-            } else {
-                panic!("No component defined")
+                None => Err(format!("Route {} not found", &c)),
             }
         }
-
-        _ => todo!(),
+        _ => Err(format!("Cannot invoke {}", entity.to_str())),
     }
 }
 
-async fn test_function_unit(auth: &Auth, fname: &str, fqn: &str, t: &TestSpec) {
-    let TestSpec {
-        name,
-        payload,
-        expect,
-        condition,
-        ..
-    } = t;
+async fn test_function_unit(
+    auth: &Auth,
+    function: &Function,
+    fqn: &str,
+    t: &TestSpec,
+    snapshots: &Snapshots,
+) -> TestResult {
+    let TestSpec { name, payload, .. } = t;
+    let name = u::maybe_string(name.clone(), &function.name);
+    let entity = format!("function/{}", &function.name);
+    println!("Running test: {}", &name);
+
     let start = Instant::now();
     let dir = u::pwd();
-    let payload = invoker::read_payload(auth, &dir, payload.clone()).await;
-    let response = invoke_function(auth, fqn, &payload).await;
-    let name = match name {
-        Some(n) => n,
-        None => fname,
+    let payload = match read_payload(auth, &dir, payload).await {
+        Ok(p) => p,
+        Err(e) => return TestResult::errored(&function.namespace, &entity, &name, &e),
+    };
+    let response = match invoke_function(auth, fqn, &payload).await {
+        Ok(r) => r,
+        Err(e) => return TestResult::errored(&function.namespace, &entity, &name, &e),
     };
+    check_case(
+        &function.namespace,
+        &entity,
+        &name,
+        t,
+        &response,
+        start.elapsed(),
+        snapshots,
+    )
+}
 
-    println!("Running test: {}", &name);
-    assert_case(expect.clone(), &response, condition.clone());
-    let duration = start.elapsed();
-    let _ = println!(
-        "Test unit {} (function/{}) ({}) {:#}",
-        name,
-        fname,
-        "pass".green(),
-        u::time_format(duration)
+// each unit runs in its own task, so a unit that panics is reported as
+// an error instead of aborting the run
+async fn run_function_unit(
+    auth: &Auth,
+    function: &Function,
+    fqn: &str,
+    t: &TestSpec,
+    snapshots: &Snapshots,
+) -> TestResult {
+    let name = u::maybe_string(t.name.clone(), &function.name);
+    let entity = format!("function/{}", &function.name);
+    let (a, f, fqn, t, s) = (
+        auth.clone(),
+        function.clone(),
+        s!(fqn),
+        t.clone(),
+        snapshots.clone(),
     );
+    let h = tokio::spawn(async move { test_function_unit(&a, &f, &fqn, &t, &s).await });
+    let r = match h.await {
+        Ok(r) => r,
+        Err(e) => TestResult::errored(
+            &function.namespace,
+            &entity,
+            &name,
+            &report::panic_message(e),
+        ),
+    };
+    report::print(&r);
+    r
 }
 
-pub async fn test_function(auth: &Auth, sandbox: &str, function: &Function, unit: Option<String>) {
+pub async fn test_function(
+    auth: &Auth,
+    sandbox: &str,
+    function: &Function,
+    unit: Option<String>,
+    update_snapshots: bool,
+) -> Vec<TestResult> {
     let tspecs = &function.test;
+    let fqn = render(&function.fqn, sandbox);
+    let snapshots = Snapshots {
+        dir: function.dir.clone(),
+        update: update_snapshots,
+    };
+    let mut results: Vec<TestResult> = vec![];
     if let Some(u) = unit {
         if let Some(t) = tspecs.get(&u) {
-            let fqn = render(&function.fqn, sandbox);
-            test_function_unit(auth, &function.name, &fqn, &t).await;
+            results.push(run_function_unit(auth, function, &fqn, t, &snapshots).await);
         }
     } else {
         println!("Running all {} test units", &tspecs.len());
         for (_, tspec) in tspecs {
-            let fqn = render(&function.fqn, sandbox);
-            test_function_unit(auth, &function.name, &fqn, &tspec).await;
+            results.push(run_function_unit(auth, function, &fqn, tspec, &snapshots).await);
         }
     }
+    results
 }
 
 pub async fn test_topology_unit(
@@ -212,38 +362,54 @@ pub async fn test_topology_unit(
     name: &str,
     topology: &Topology,
     spec: &TestSpec,
-) {
+    update_snapshots: bool,
+) -> TestResult {
     let dir = u::pwd();
     let TestSpec {
-        payload,
-        expect,
-        condition,
-        entity,
-        ..
+        payload, entity, ..
     } = spec;
 
     tracing::debug!("Testing {}/{}", namespace, name);
 
-    let payload = invoker::read_payload(auth, &dir, payload.clone()).await;
-
-    let start = Instant::now();
     let entity = u::maybe_string(entity.clone(), "state");
+    let payload = match read_payload(auth, &dir, payload).await {
+        Ok(p) => p,
+        Err(e) => return TestResult::errored(namespace, &entity, name, &e),
+    };
 
-    let response = invoke(auth, namespace, topology, &entity, &payload).await;
-    assert_case(expect.clone(), &response, condition.clone());
+    let start = Instant::now();
 
-    let duration = start.elapsed();
-    let _ = println!(
-        "[{}] {}/{}/{} ({:#})",
-        "pass".green(),
-        namespace.cyan(),
+    let mut probes = match flow::arm_all(auth, topology, namespace, &spec.effects).await {
+        Ok(p) => p,
+        Err(e) => return TestResult::errored(namespace, &entity, name, &e),
+    };
+    let response = match invoke(auth, namespace, topology, &entity, &payload).await {
+        Ok(r) => r,
+        Err(e) => {
+            flow::disarm_all(auth, probes).await;
+            return TestResult::errored(namespace, &entity, name, &e);
+        }
+    };
+    probes.correlate(&response);
+    let errors = flow::observe_all(auth, probes).await;
+    let snapshots = Snapshots {
+        dir: topology.dir.clone(),
+        update: update_snapshots,
+    };
+    let mut r = check_case(
+        namespace,
         &entity,
         name,
-        u::time_format(duration)
+        spec,
+        &response,
+        start.elapsed(),
+        &snapshots,
     );
+    r.add_failures(errors);
+    r
 }
 
-fn get_tspecs(topology: &Topology) -> HashMap<String, TestSpec> {
+pub(crate) fn get_tspecs(topology: &Topology) -> HashMap<String, TestSpec> {
     let mut tests: HashMap<String, TestSpec> = HashMap::new();
     for (name, mut spec) in topology.tests.clone() {
         spec.name = Some(name);
@@ -255,37 +421,91 @@ fn get_tspecs(topology: &Topology) -> HashMap<String, TestSpec> {
         for (name, mut spec) in node.tests.clone() {
             spec.name = Some(name);
             spec.namespace = Some(node.namespace.clone());
+            spec.schema = spec.schema.map(|s| schema::rebase(&node.dir, &s));
             tests.insert(u::uuid_str(), spec);
         }
     }
     tests
 }
 
-async fn test_topology_aux(auth: &Auth, name: String, spec: TestSpec, topology: &Topology) {
-    let cname = u::maybe_string(spec.name.clone(), &name);
+fn unit_of(name: &str, spec: &TestSpec, topology: &Topology) -> (String, String, String) {
+    let cname = u::maybe_string(spec.name.clone(), name);
     let namespace = u::maybe_string(spec.namespace.clone(), &topology.namespace);
-    test_topology_unit(auth, &namespace, &cname, topology, &spec).await;
+    let entity = u::maybe_string(spec.entity.clone(), "state");
+    (namespace, entity, cname)
 }
 
-pub async fn test_topology(auth: &Auth, topology: &Topology, unit: Option<String>) {
-    let tspecs = get_tspecs(topology);
+async fn test_topology_aux(
+    auth: &Auth,
+    name: String,
+    spec: TestSpec,
+    topology: &Topology,
+    update_snapshots: bool,
+) -> TestResult {
+    let (namespace, _, cname) = unit_of(&name, &spec, topology);
+    test_topology_unit(auth, &namespace, &cname, topology, &spec, update_snapshots).await
+}
 
-    if let Some(u) = unit {
-        if let Some(spec) = topology.tests.get(&u) {
-            test_topology_unit(auth, &topology.namespace, &u, topology, spec).await;
-        }
-    } else {
-        let mut tasks = vec![];
-        for (name, spec) in tspecs {
-            let a = auth.clone();
-            let t = topology.clone();
-            let h = tokio::spawn(async move {
-                test_topology_aux(&a, name, spec, &t).await;
-            });
-            tasks.push(h);
-        }
-        for task in tasks {
-            let _ = task.await;
-        }
+async fn await_unit(
+    unit: (String, String, String),
+    task: tokio::task::JoinHandle<TestResult>,
+) -> TestResult {
+    let (namespace, entity, name) = unit;
+    let r = match task.await {
+        Ok(r) => r,
+        Err(e) => TestResult::errored(&namespace, &entity, &name, &report::panic_message(e)),
+    };
+    report::print(&r);
+    r
+}
+
+/// Runs all topology tests concurrently and returns their results.
+/// Tests that observe state machine executions run afterwards, one at a
+/// time. A unit that panics is reported as an error
+pub async fn test_topology(
+    auth: &Auth,
+    topology: &Topology,
+    unit: Option<String>,
+    update_snapshots: bool,
+) -> Vec<TestResult> {
+    let tspecs = match unit {
+        Some(u) => match topology.tests.get(&u) {
+            Some(spec) => {
+                let mut spec = spec.clone();
+                spec.name = Some(u);
+                HashMap::from([(u::uuid_str(), spec)])
+            }
+            None => HashMap::new(),
+        },
+        None => get_tspecs(topology),
+    };
+
+    let (serial, concurrent): (Vec<_>, Vec<_>) = tspecs
+        .into_iter()
+        .partition(|(_, spec)| flow::observes_state(spec));
+
+    let spawn = |name: String, spec: TestSpec| {
+        let a = auth.clone();
+        let t = topology.clone();
+        let unit = unit_of(&name, &spec, topology);
+        let h =
+            tokio::spawn(
+                async move { test_topology_aux(&a, name, spec, &t, update_snapshots).await },
+            );
+        (unit, h)
+    };
+
+    let tasks: Vec<_> = concurrent
+        .into_iter()
+        .map(|(name, spec)| spawn(name, spec))
+        .collect();
+    let mut results: Vec<TestResult> = vec![];
+    for (unit, task) in tasks {
+        results.push(await_unit(unit, task).await);
+    }
+    for (name, spec) in serial {
+        let (unit, task) = spawn(name, spec);
+        results.push(await_unit(unit, task).await);
     }
+    results
 }
@@ -0,0 +1,284 @@
+use super::{
+    Snapshots,
+    check_case,
+    get_tspecs,
+    report::{
+        self,
+        TestResult,
+    },
+};
+use compiler::{
+    Entity,
+    spec::TestSpec,
+};
+use composer::{
+    Function,
+    Topology,
+};
+use emulator::{
+    Emulator,
+    Session,
+    Stubs,
+};
+use kit as u;
+use kit::*;
+use provider::Auth;
+use serde_json::{
+    Value,
+    json,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::Instant,
+};
+
+// mocks are keyed by entity/component, stubs by component
+fn make_stubs(mocks: &HashMap<String, Value>) -> Stubs<'static> {
+    let mut responses: HashMap<String, Value> = HashMap::new();
+    for (k, v) in mocks {
+        let key = match k.split_once('/') {
+            Some((_, c)) if !k.starts_with("arn:") => c,
+            _ => k,
+        };
+        responses.insert(key.to_string(), v.clone());
+    }
+    Stubs::new(responses)
+}
+
+// payloads are inline json or local json files; s3 payloads need
+// credentials and are not supported offline
+fn read_payload(payload: Option<String>) -> Result<String, String> {
+    match payload {
+        Some(p) if p.starts_with("s3://") || p.starts_with("//") => {
+            Err(format!("S3 payload {} cannot be read in local mode", p))
+        }
+        Some(p) => Ok(invoker::read_payload_local(Some(p))),
+        None => Ok(s!("{}")),
+    }
+}
+
+// functions mocked by every test need not be started at all
+fn common_stubs(specs: &[&TestSpec]) -> Stubs<'static> {
+    let mut mocks: HashMap<String, Value> = HashMap::new();
+    if let Some((first, rest)) = specs.split_first() {
+        for (k, v) in &first.mocks {
+            if rest.iter().all(|t| t.mocks.contains_key(k)) {
+                mocks.insert(k.clone(), v.clone());
+            }
+        }
+    }
+    make_stubs(&mocks)
+}
+
+fn find_node<'a>(topology: &'a Topology, namespace: &str) -> Result<&'a Topology, String> {
+    if topology.namespace == namespace {
+        Ok(topology)
+    } else {
+        match topology.nodes.get(namespace) {
+            Some(node) => Ok(node),
+            None => Err(format!("Namespace {} not found", namespace)),
+        }
+    }
+}
+
+fn first_str(xs: &[Value], what: &str) -> Result<String, String> {
+    match xs.first().and_then(|v| v.as_str()) {
+        Some(x) => Ok(x.to_string()),
+        None => Err(format!("Event pattern has no {}", what)),
+    }
+}
+
+async fn invoke(
+    emulator: &Arc<Emulator>,
+    node: &Topology,
+    entity: &str,
+    payload: &str,
+) -> Result<String, String> {
+    let (entity, component) = Entity::as_entity_component(entity);
+    match entity {
+        Entity::Function => {
+            let c = component.ok_or("Component not specified")?;
+            match emulator.invoke(&c, u::json_value(payload)).await {
+                Some(output) => Ok(output.to_string()),
+                None => Err(format!("Failed to invoke function {}", c)),
+            }
+        }
+        Entity::State => {
+            let flow = match &node.flow {
+                Some(f) => f,
+                None => return Err(s!("No state machine defined")),
+            };
+            // failed executions are responses too, so tests can assert
+            // on the error and cause
+            match emulator.execute(&flow.name, u::json_value(payload)).await {
+                Ok(output) => Ok(output.to_string()),
+                Err(e) => Ok(json!({"Error": e.error, "Cause": e.cause}).to_string()),
+            }
+        }
+        Entity::Event => {
+            let c = component.ok_or("No component defined")?;
+            match node.events.get(&c) {
+                Some(e) => {
+                    let detail_type = first_str(&e.pattern.detail_type, "detail-type")?;
+                    let source = first_str(&e.pattern.source, "source")?;
+                    let id = emulator.put_event(&source, &detail_type, u::json_value(payload));
+                    Ok(json!({"EventId": id}).to_string())
+                }
+                None => Err(format!("Event {} not found", c)),
+            }
+        }
+        Entity::Route => {
+            let c = component.ok_or("No component defined")?;
+            match node.routes.get(&c) {
+                Some(r) => Ok(emulator.request(r, payload).await.to_string()),
+                None => Err(format!("Route {} not found", c)),
+            }
+        }
+        _ => Err(format!("Testing {} locally is not supported", entity.to_str())),
+    }
+}
+
+// the invocation runs in its own task, so that the session is stopped
+// even when it panics
+async fn spawn_invoke(
+    emulator: &Arc<Emulator>,
+    node: &Topology,
+    entity: &str,
+    payload: &str,
+) -> Result<String, String> {
+    let (e, n, entity, payload) = (emulator.clone(), node.clone(), s!(entity), s!(payload));
+    let h = tokio::spawn(async move { invoke(&e, &n, &entity, &payload).await });
+    match h.await {
+        Ok(res) => res,
+        Err(e) => Err(report::panic_message(e)),
+    }
+}
+
+async fn test_unit(
+    session: &Session,
+    topology: &Topology,
+    namespace: &str,
+    entity: &str,
+    name: &str,
+    spec: &TestSpec,
+    snapshots: &Snapshots,
+) -> TestResult {
+    let payload = match read_payload(spec.payload.clone()) {
+        Ok(p) => p,
+        Err(e) => return TestResult::errored(namespace, entity, name, &e),
+    };
+    let node = match find_node(topology, namespace) {
+        Ok(n) => n,
+        Err(e) => return TestResult::errored(namespace, entity, name, &e),
+    };
+
+    tracing::debug!("Testing {}/{} locally", namespace, name);
+    session.emulator.set_stubs(make_stubs(&spec.mocks));
+    let start = Instant::now();
+    let res = spawn_invoke(&session.emulator, node, entity, &payload).await;
+    let duration = start.elapsed();
+
+    let r = match res {
+        Ok(response) => check_case(
+            namespace, entity, name, spec, &response, duration, snapshots,
+        ),
+        Err(e) => TestResult::errored(namespace, entity, name, &e),
+    };
+    report::print(&r);
+    r
+}
+
+// (namespace, entity, name, spec) of each selected test
+type Unit = (String, String, String, TestSpec);
+
+// one emulator session serves all the tests of a run, each test only
+// swaps in its mocks
+async fn run_units(
+    auth: &Auth,
+    topology: &Topology,
+    units: Vec<Unit>,
+    snapshots: &Snapshots,
+) -> Vec<TestResult> {
+    if units.is_empty() {
+        return vec![];
+    }
+    let specs: Vec<&TestSpec> = units.iter().map(|(_, _, _, t)| t).collect();
+    let session = match Session::start(auth, topology, common_stubs(&specs)).await {
+        Ok(s) => s,
+        Err(e) => {
+            return units
+                .iter()
+                .map(|(namespace, entity, name, _)| {
+                    let r = TestResult::errored(namespace, entity, name, &e);
+                    report::print(&r);
+                    r
+                })
+                .collect();
+        }
+    };
+    let mut results: Vec<TestResult> = vec![];
+    for (namespace, entity, name, spec) in &units {
+        results.push(
+            test_unit(
+                &session, topology, namespace, entity, name, spec, snapshots,
+            )
+            .await,
+        );
+    }
+    session.stop();
+    results
+}
+
+/// Runs topology tests against the emulator. The tests share one
+/// emulator session and run one at a time, each with its own mocks
+pub async fn test_topology(
+    auth: &Auth,
+    topology: &Topology,
+    unit: Option<String>,
+    update_snapshots: bool,
+) -> Vec<TestResult> {
+    let snapshots = Snapshots {
+        dir: topology.dir.clone(),
+        update: update_snapshots,
+    };
+    let mut units: Vec<Unit> = vec![];
+    for (_, spec) in get_tspecs(topology) {
+        let name = u::maybe_string(spec.name.clone(), "");
+        if let Some(u) = &unit {
+            if &name != u || spec.namespace.as_ref() != Some(&topology.namespace) {
+                continue;
+            }
+        }
+        let namespace = u::maybe_string(spec.namespace.clone(), &topology.namespace);
+        let entity = u::maybe_string(spec.entity.clone(), "state");
+        units.push((namespace, entity, name, spec));
+    }
+    run_units(auth, topology, units, &snapshots).await
+}
+
+/// Runs the function's tests against its emulated container
+pub async fn test_function(
+    auth: &Auth,
+    topology: &Topology,
+    function: &Function,
+    unit: Option<String>,
+    update_snapshots: bool,
+) -> Vec<TestResult> {
+    let snapshots = Snapshots {
+        dir: function.dir.clone(),
+        update: update_snapshots,
+    };
+    let entity = format!("function/{}", &function.name);
+    let mut units: Vec<Unit> = vec![];
+    for (key, t) in &function.test {
+        if let Some(u) = &unit {
+            if key != u {
+                continue;
+            }
+        }
+        let name = u::maybe_string(t.name.clone(), key);
+        units.push((topology.namespace.clone(), entity.clone(), name, t.clone()));
+    }
+    run_units(auth, topology, units, &snapshots).await
+}
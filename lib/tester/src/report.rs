@@ -0,0 +1,296 @@
+use colored::Colorize;
+use kit as u;
+use kit::*;
+use serde_derive::Serialize;
+use serde_json::{
+    Value,
+    json,
+};
+use std::{
+    collections::BTreeMap,
+    time::Duration,
+};
+use tokio::task::JoinError;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Fail,
+    Error,
+}
+
+impl Status {
+    pub fn to_str(&self) -> &str {
+        match self {
+            Status::Pass => "pass",
+            Status::Fail => "fail",
+            Status::Error => "error",
+        }
+    }
+}
+
+/// Outcome of one test unit. `Fail` is an assertion mismatch, `Error`
+/// means the entity could not be tested at all
+#[derive(Serialize, Clone, Debug)]
+pub struct TestResult {
+    pub namespace: String,
+    pub entity: String,
+    pub unit: String,
+    pub status: Status,
+    pub duration: u128,
+    pub expected: Option<Value>,
+    pub actual: Option<Value>,
+    pub message: Option<String>,
+}
+
+impl TestResult {
+    pub fn new(
+        namespace: &str,
+        entity: &str,
+        unit: &str,
+        status: Status,
+        duration: Duration,
+    ) -> TestResult {
+        TestResult {
+            namespace: s!(namespace),
+            entity: s!(entity),
+            unit: s!(unit),
+            status: status,
+            duration: duration.as_millis(),
+            expected: None,
+            actual: None,
+            message: None,
+        }
+    }
+
+    pub fn errored(namespace: &str, entity: &str, unit: &str, message: &str) -> TestResult {
+        let mut r = TestResult::new(namespace, entity, unit, Status::Error, Duration::ZERO);
+        r.message = Some(s!(message));
+        r
+    }
+
+    /// Fails a result with assertions made after its response was
+    /// checked
+    pub fn add_failures(&mut self, errors: Vec<String>) {
+        if errors.len() == 0 {
+            return;
+        }
+        if self.status == Status::Pass {
+            self.status = Status::Fail;
+        }
+        let mut lines: Vec<String> = self.message.iter().cloned().collect();
+        lines.extend(errors);
+        self.message = Some(lines.join("\n"));
+    }
+}
+
+/// The panic message of a test unit that ran in its own task
+pub fn panic_message(e: JoinError) -> String {
+    match e.try_into_panic() {
+        Ok(p) => match p.downcast::<String>() {
+            Ok(m) => *m,
+            Err(p) => match p.downcast::<&str>() {
+                Ok(m) => s!(*m),
+                Err(_) => s!("panicked"),
+            },
+        },
+        Err(_) => s!("cancelled"),
+    }
+}
+
+pub fn print(r: &TestResult) {
+    let status = match r.status {
+        Status::Pass => r.status.to_str().green(),
+        _ => r.status.to_str().red(),
+    };
+    println!(
+        "[{}] {}/{}/{} ({:#})",
+        status,
+        r.namespace.cyan(),
+        &r.entity,
+        &r.unit,
+        u::time_format(Duration::from_millis(r.duration as u64))
+    );
+    if r.status != Status::Pass {
+        if let Some(m) = &r.message {
+            println!("{}", m);
+        }
+    }
+}
+
+pub fn has_failures(results: &[TestResult]) -> bool {
+    results.iter().any(|r| r.status != Status::Pass)
+}
+
+fn count(results: &[TestResult], status: Status) -> usize {
+    results.iter().filter(|r| r.status == status).count()
+}
+
+pub fn print_summary(results: &[TestResult]) {
+    let failed = count(results, Status::Fail);
+    let errors = count(results, Status::Error);
+    println!(
+        "{} tests, {} passed, {} failed, {} errors",
+        results.len(),
+        count(results, Status::Pass).to_string().green(),
+        failed.to_string().red(),
+        errors.to_string().red()
+    );
+}
+
+pub fn json(results: &[TestResult]) -> String {
+    let report = json!({
+        "tests": results.len(),
+        "passed": count(results, Status::Pass),
+        "failed": count(results, Status::Fail),
+        "errors": count(results, Status::Error),
+        "results": results
+    });
+    serde_json::to_string_pretty(&report).unwrap()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn seconds(ms: u128) -> String {
+    format!("{:.3}", ms as f64 / 1000.0)
+}
+
+fn junit_case(r: &TestResult) -> String {
+    let open = format!(
+        r#"    <testcase name="{}" classname="{}.{}" time="{}""#,
+        escape(&r.unit),
+        escape(&r.namespace),
+        escape(&r.entity),
+        seconds(r.duration)
+    );
+    let message = u::maybe_string(r.message.clone(), "");
+    let first_line = message.lines().next().unwrap_or_default();
+    match r.status {
+        Status::Pass => format!("{}/>\n", open),
+        Status::Fail => format!(
+            "{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+            open,
+            escape(first_line),
+            escape(&message)
+        ),
+        Status::Error => format!(
+            "{}>\n      <error message=\"{}\">{}</error>\n    </testcase>\n",
+            open,
+            escape(first_line),
+            escape(&message)
+        ),
+    }
+}
+
+/// JUnit XML with one testsuite per namespace
+pub fn junit(results: &[TestResult]) -> String {
+    let mut suites: BTreeMap<&str, Vec<&TestResult>> = BTreeMap::new();
+    for r in results {
+        suites.entry(&r.namespace).or_default().push(r);
+    }
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\">\n",
+        results.len(),
+        count(results, Status::Fail),
+        count(results, Status::Error)
+    );
+    for (namespace, rs) in suites {
+        let duration: u128 = rs.iter().map(|r| r.duration).sum();
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{}\">\n",
+            escape(namespace),
+            rs.len(),
+            rs.iter().filter(|r| r.status == Status::Fail).count(),
+            rs.iter().filter(|r| r.status == Status::Error).count(),
+            seconds(duration)
+        ));
+        for r in rs {
+            xml.push_str(&junit_case(r));
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Writes the report in the given format (`junit` or `json`) and
+/// returns the file it was written to
+pub fn write(format: &str, results: &[TestResult]) -> Result<String, String> {
+    let (path, data) = match format {
+        "junit" => ("tc-test-report.xml", junit(results)),
+        "json" => ("tc-test-report.json", json(results)),
+        _ => {
+            return Err(format!(
+                "Unsupported report format {}, use junit or json",
+                format
+            ));
+        }
+    };
+    u::write_str(path, &data);
+    Ok(s!(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> Vec<TestResult> {
+        let pass = TestResult::new(
+            "etl",
+            "function/enhancer",
+            "basic",
+            Status::Pass,
+            Duration::from_millis(1200),
+        );
+        let mut fail = TestResult::new(
+            "etl",
+            "state",
+            "flow",
+            Status::Fail,
+            Duration::from_millis(300),
+        );
+        fail.message = Some(s!("json atoms at path \".id\" are not equal"));
+        let error = TestResult::errored("api", "route/orders", "get", "Route not found");
+        vec![pass, fail, error]
+    }
+
+    #[test]
+    fn renders_junit_suites_per_namespace() {
+        let xml = junit(&results());
+        assert!(xml.contains(r#"<testsuites tests="3" failures="1" errors="1">"#));
+        assert!(
+            xml.contains(
+                r#"<testsuite name="api" tests="1" failures="0" errors="1" time="0.000">"#
+            )
+        );
+        assert!(xml.contains(
+            r#"<testcase name="basic" classname="etl.function/enhancer" time="1.200"/>"#
+        ));
+        assert!(
+            xml.contains(r#"<failure message="json atoms at path &quot;.id&quot; are not equal">"#)
+        );
+        assert!(xml.contains(r#"<error message="Route not found">Route not found</error>"#));
+    }
+
+    #[test]
+    fn renders_json_summary() {
+        let v: Value = serde_json::from_str(&json(&results())).unwrap();
+        assert_eq!(v["tests"], json!(3));
+        assert_eq!(v["failed"], json!(1));
+        assert_eq!(v["results"][1]["status"], json!("fail"));
+        assert!(has_failures(&results()));
+    }
+
+    #[test]
+    fn rejects_unsupported_report_formats() {
+        let e = write("yaml", &results()).unwrap_err();
+        assert!(e.contains("use junit or json"));
+    }
+}
@@ -0,0 +1,141 @@
+use kit as u;
+use serde_json::Value;
+
+// renders an instance pointer such as `/items/0/qty` as `$.items[0].qty`
+fn as_path(pointer: &str) -> String {
+    let mut path = String::from("$");
+    for segment in pointer.split('/').skip(1) {
+        let segment = segment.replace("~1", "/").replace("~0", "~");
+        match segment.parse::<usize>() {
+            Ok(i) => path.push_str(&format!("[{}]", i)),
+            Err(_) => path.push_str(&format!(".{}", segment)),
+        }
+    }
+    path
+}
+
+/// Validates a value against a schema and returns the violations, one
+/// per offending path
+pub fn validate(schema: &Value, v: &Value) -> Vec<String> {
+    let validator = match jsonschema::validator_for(schema) {
+        Ok(validator) => validator,
+        Err(e) => return vec![format!("Invalid schema: {}", e)],
+    };
+    validator
+        .iter_errors(v)
+        .map(|e| format!("{}: {}", as_path(e.instance_path.as_str()), e))
+        .collect()
+}
+
+fn parse(what: &str, data: &str) -> Result<Value, String> {
+    match serde_json::from_str(data) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(format!("Invalid schema {}: {}", what, e)),
+    }
+}
+
+/// Schemas are given inline or as a path to a json file, relative to
+/// the directory of the spec that declares them
+pub fn read(dir: &str, schema: &Value) -> Result<Value, String> {
+    match schema {
+        Value::String(p) if p.ends_with(".json") => {
+            let path = u::absolutize(dir, p);
+            match std::fs::read_to_string(&path) {
+                Ok(data) => parse(&path, &data),
+                Err(e) => Err(format!("Failed to read schema {}: {}", &path, e)),
+            }
+        }
+        Value::String(s) => parse("(inline)", s),
+        v => Ok(v.clone()),
+    }
+}
+
+/// Anchors a schema file path to the directory of its spec, so specs of
+/// nodes resolve their schemas from their own directory
+pub fn rebase(dir: &str, schema: &Value) -> Value {
+    match schema {
+        Value::String(p) if p.ends_with(".json") => Value::String(u::absolutize(dir, p)),
+        v => v.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "status", "items"],
+            "properties": {
+                "id": {"type": "string", "pattern": "^ord-[0-9]+$"},
+                "status": {"enum": ["pending", "done"]},
+                "total": {"type": "number", "minimum": 0},
+                "items": {"type": "array", "minItems": 1, "items": {"$ref": "#/$defs/item"}}
+            },
+            "$defs": {
+                "item": {
+                    "type": "object",
+                    "required": ["sku"],
+                    "properties": {"sku": {"type": "string"}, "qty": {"type": "integer"}},
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_accepts_conforming_values() {
+        let v = json!({"id": "ord-1", "status": "done", "total": 2.5, "items": [{"sku": "a", "qty": 2}]});
+        assert_eq!(validate(&schema(), &v), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_reports_violations_by_path() {
+        let v = json!({"id": "x", "status": "lost", "total": -1, "items": [{"qty": 1.5, "extra": true}]});
+        let errors = validate(&schema(), &v);
+        let at = |path: &str, word: &str| {
+            errors
+                .iter()
+                .any(|e| e.starts_with(&format!("{}: ", path)) && e.contains(word))
+        };
+        assert!(at("$.id", "^ord-[0-9]+$"), "{:?}", errors);
+        assert!(at("$.status", "lost"), "{:?}", errors);
+        assert!(at("$.total", "minimum"), "{:?}", errors);
+        assert!(at("$.items[0]", "sku"), "{:?}", errors);
+        assert!(at("$.items[0]", "extra"), "{:?}", errors);
+        assert!(at("$.items[0].qty", "integer"), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_reports_invalid_schemas() {
+        let errors = validate(&json!({"type": "objekt"}), &json!({}));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Invalid schema"));
+    }
+
+    #[test]
+    fn test_reads_schema_files_relative_to_the_spec() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        std::fs::create_dir(dir.path().join("schemas")).unwrap();
+        std::fs::write(
+            dir.path().join("schemas/order.json"),
+            r#"{"type": "object"}"#,
+        )
+        .unwrap();
+
+        let path = json!("schemas/order.json");
+        assert_eq!(read(root, &path), Ok(json!({"type": "object"})));
+        assert_eq!(
+            rebase(root, &path),
+            json!(format!("{}/schemas/order.json", root))
+        );
+        assert_eq!(read("/elsewhere", &rebase(root, &path)), read(root, &path));
+
+        let missing = read(root, &json!("schemas/missing.json")).unwrap_err();
+        assert!(missing.starts_with("Failed to read schema"));
+        assert!(read(root, &json!("{not json")).is_err());
+    }
+}
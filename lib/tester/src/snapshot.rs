@@ -0,0 +1,166 @@
+use assert_json_diff::{
+    CompareMode,
+    Config,
+    assert_json_matches_no_panic,
+};
+use kit as u;
+use serde_json::Value;
+
+enum Segment {
+    Key(String),
+    Index(usize),
+    All,
+}
+
+// `$.a.b`, `$.items[*].id`, `$.xs[0]` and `a.b`
+fn parse(path: &str) -> Vec<Segment> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut segments: Vec<Segment> = vec![];
+    for part in path.split('.').filter(|p| !p.is_empty()) {
+        let (key, rest) = match part.find('[') {
+            Some(i) => (&part[..i], &part[i..]),
+            None => (part, ""),
+        };
+        match key {
+            "" => (),
+            "*" => segments.push(Segment::All),
+            k => segments.push(Segment::Key(k.to_string())),
+        }
+        for index in rest.split('[').filter(|x| !x.is_empty()) {
+            let index = index.trim_end_matches(']');
+            match index.parse::<usize>() {
+                Ok(n) => segments.push(Segment::Index(n)),
+                Err(_) if index == "*" => segments.push(Segment::All),
+                Err(_) => segments.push(Segment::Key(index.trim_matches('\'').to_string())),
+            }
+        }
+    }
+    segments
+}
+
+fn remove_at(v: &mut Value, segments: &[Segment]) {
+    let (first, rest) = match segments.split_first() {
+        Some(x) => x,
+        None => return,
+    };
+    let last = rest.len() == 0;
+    match (first, v) {
+        (Segment::Key(k), Value::Object(m)) => {
+            if last {
+                m.remove(k);
+            } else if let Some(x) = m.get_mut(k) {
+                remove_at(x, rest);
+            }
+        }
+        (Segment::Index(n), Value::Array(xs)) => {
+            if last {
+                if *n < xs.len() {
+                    xs.remove(*n);
+                }
+            } else if let Some(x) = xs.get_mut(*n) {
+                remove_at(x, rest);
+            }
+        }
+        (Segment::All, Value::Array(xs)) => {
+            if last {
+                xs.clear();
+            } else {
+                for x in xs.iter_mut() {
+                    remove_at(x, rest);
+                }
+            }
+        }
+        (Segment::All, Value::Object(m)) => {
+            if last {
+                m.clear();
+            } else {
+                for (_, x) in m.iter_mut() {
+                    remove_at(x, rest);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Removes volatile fields, like timestamps and execution arns, so they
+/// do not take part in comparisons
+pub fn ignore(v: &Value, paths: &[String]) -> Value {
+    let mut v = v.clone();
+    for path in paths {
+        remove_at(&mut v, &parse(path));
+    }
+    v
+}
+
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Snapshots are kept under the topology (or function) dir, one file
+/// per test unit
+pub fn path_of(dir: &str, namespace: &str, unit: &str) -> String {
+    format!(
+        "{}/snapshots/{}/{}.json",
+        dir,
+        sanitize(namespace),
+        sanitize(unit)
+    )
+}
+
+/// Compares the response with its snapshot. A missing snapshot is
+/// recorded, as is every snapshot when `update` is set
+pub fn check(path: &str, response: &Value, update: bool) -> Result<(), String> {
+    if update || !u::file_exists(path) {
+        u::mkdir(&u::parent_dir(path));
+        u::write_str(path, &serde_json::to_string_pretty(response).unwrap());
+        println!("Recorded snapshot {}", path);
+        return Ok(());
+    }
+    let recorded: Value = match serde_json::from_str(&u::slurp(path)) {
+        Ok(v) => v,
+        Err(_) => return Err(format!("Invalid snapshot {}", path)),
+    };
+    assert_json_matches_no_panic(response, &recorded, Config::new(CompareMode::Strict))
+        .map_err(|diff| format!("response differs from snapshot {}\n{}", path, diff))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn ignores_volatile_paths() {
+        let v = json!({
+            "executionArn": "arn:aws:states:us-east-1:123:execution:etl:1",
+            "output": {"id": 1, "at": "2024-01-01"},
+            "items": [{"sku": "a", "ts": 1}, {"sku": "b", "ts": 2}]
+        });
+        let paths = vec![
+            String::from("$.executionArn"),
+            String::from("output.at"),
+            String::from("$.items[*].ts"),
+        ];
+        assert_eq!(
+            ignore(&v, &paths),
+            json!({"output": {"id": 1}, "items": [{"sku": "a"}, {"sku": "b"}]})
+        );
+    }
+
+    #[test]
+    fn names_snapshots_by_unit() {
+        assert_eq!(
+            path_of("/tmp/etl", "etl", "basic run/1"),
+            "/tmp/etl/snapshots/etl/basic_run_1.json"
+        );
+    }
+}
@@ -6,7 +6,10 @@ use itertools::Itertools;
 use kit as u;
 use provider::Auth;
 use std::{
-    collections::HashMap,
+    collections::{
+        HashMap,
+        HashSet,
+    },
     panic,
     str::FromStr,
     time::Instant,
@@ -112,26 +115,94 @@ pub async fn test_interactive(auth: Auth, sandbox: Option<String>) {
         let (name, maybe_unit) = interactive::prompt_test_units(units.clone());
         if let Some(spec) = maybe_unit {
             let resolved = resolver::render(&auth, &sandbox, &topology).await;
-            tester::test_topology_unit(&auth, &topology.namespace, &name, &resolved, &spec).await;
+            let r = tester::test_topology_unit(
+                &auth,
+                &topology.namespace,
+                &name,
+                &resolved,
+                &spec,
+                false,
+            )
+            .await;
+            tester::report::print(&r);
         }
     } else {
         println!("Interactive mode supported only in topology directory");
     }
 }
 
-pub async fn test(auth: Auth, sandbox: Option<String>, unit: Option<String>, recursive: bool) {
+// prints the summary, writes the report and exits non-zero if any
+// test did not pass
+fn finish_tests(results: Vec<tester::report::TestResult>, report: Option<String>) {
+    tester::report::print_summary(&results);
+    if let Some(format) = report {
+        match tester::report::write(&format, &results) {
+            Ok(path) => println!("Wrote {}", path),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if tester::report::has_failures(&results) {
+        std::process::exit(1);
+    }
+}
+
+pub async fn test(
+    auth: Auth,
+    sandbox: Option<String>,
+    unit: Option<String>,
+    recursive: bool,
+    report: Option<String>,
+    update_snapshots: bool,
+) {
     let dir = u::pwd();
     let sandbox = resolver::maybe_sandbox(sandbox);
 
-    if composer::is_topology_dir(&dir) {
+    let results = if composer::is_topology_dir(&dir) {
         let topology = composer::compose(&dir, recursive);
         let resolved = resolver::render(&auth, &sandbox, &topology).await;
-        tester::test_topology(&auth, &resolved, unit).await;
+        tester::test_topology(&auth, &resolved, unit, update_snapshots).await
     } else {
-        if let Some(f) = composer::current_function(&dir) {
-            tester::test_function(&auth, &sandbox, &f, unit).await;
+        match composer::current_function(&dir) {
+            Some(f) => tester::test_function(&auth, &sandbox, &f, unit, update_snapshots).await,
+            None => vec![],
         }
-    }
+    };
+    finish_tests(results, report);
+}
+
+/// Runs tests against the emulator instead of deployed entities. No
+/// aws account is needed unless a profile is given
+pub async fn test_local(
+    profile: Option<String>,
+    sandbox: Option<String>,
+    unit: Option<String>,
+    recursive: bool,
+    report: Option<String>,
+    update_snapshots: bool,
+) {
+    let dir = u::pwd();
+    let sandbox = resolver::maybe_sandbox(sandbox);
+    let auth = match profile {
+        Some(_) => init(profile, None).await,
+        None => Auth::local(None),
+    };
+    let topology = composer::compose(&dir, recursive);
+    let resolved = resolver::render(&auth, &sandbox, &topology).await;
+
+    let results = if composer::is_topology_dir(&dir) {
+        tester::local::test_topology(&auth, &resolved, unit, update_snapshots).await
+    } else {
+        match composer::current_function(&dir) {
+            Some(f) => {
+                tester::local::test_function(&auth, &resolved, &f, unit, update_snapshots).await
+            }
+            None => vec![],
+        }
+    };
+    finish_tests(results, report);
 }
 
 pub struct ComposeOpts {
@@ -332,11 +403,18 @@ pub async fn create_topology(
     topology: &Topology,
     concurrency: Option<i32>,
     force: bool,
+    resume: bool,
 ) {
-    deployer::create(auth, topology, concurrency, force).await;
-
-    for (_, node) in &topology.nodes {
-        deployer::create(auth, node, concurrency, force).await;
+    let failed = deployer::deploy(auth, topology, concurrency, force, resume).await;
+    if let Some(e) = failed {
+        println!(
+            "Failed at {}/{}: {}",
+            &e.namespace,
+            &e.step,
+            u::safe_unwrap(e.error.as_ref())
+        );
+        println!("Run tc create --resume to retry from it, or --rollback to undo the deploy");
+        std::process::exit(1);
     }
 }
 
@@ -374,6 +452,8 @@ pub struct CreateOpts {
     pub sync: bool,
     pub force: bool,
     pub concurrency: Option<i32>,
+    pub resume: bool,
+    pub rollback: bool,
 }
 
 pub async fn create(
@@ -393,6 +473,8 @@ pub async fn create(
         cache,
         force,
         concurrency,
+        resume,
+        rollback,
         ..
     } = opts;
 
@@ -430,7 +512,12 @@ pub async fn create(
     };
 
     let auth = init(Some(topology.env.to_string()), None).await;
-    create_topology(&auth, &topology, concurrency, force).await;
+    if rollback {
+        deployer::rollback(&auth, &topology).await;
+        return;
+    }
+    deployer::guardrails::enforce(&auth, &topology).await;
+    create_topology(&auth, &topology, concurrency, force, resume).await;
 
     match std::env::var("TC_INSPECT_BUILD") {
         Ok(_) => (),
@@ -475,6 +562,240 @@ pub async fn dry_run_create(profile: Option<String>, sandbox: Option<String>, re
     create_topology_dry_run(&auth, &rt).await;
 }
 
+// fqns of functions whose code changed since the deployed version
+async fn modified_functions(auth: &Auth, topology: &Topology) -> HashSet<String> {
+    let rt = resolver::function::Root {
+        namespace: topology.namespace.to_string(),
+        fqn: topology.fqn.to_string(),
+        version: topology.version.to_string(),
+        kind: topology.kind.clone(),
+    };
+    let mut h: HashSet<String> = HashSet::new();
+    for f in resolver::function::find_modified(auth, &rt, topology).await.values() {
+        h.insert(f.fqn.clone());
+    }
+    for node in topology.nodes.values() {
+        for f in resolver::function::find_modified(auth, &rt, node).await.values() {
+            h.insert(f.fqn.clone());
+        }
+    }
+    h
+}
+
+pub async fn plan(auth: Auth, sandbox: Option<String>, recursive: bool, out: Option<String>) {
+    let sandbox = resolver::maybe_sandbox(sandbox);
+    let dir = u::pwd();
+    println!("Composing topology {} ...", &composer::topology_name(&dir));
+    let ct = composer::compose(&dir, recursive);
+    println!("Resolving topology {} ...", &ct.namespace);
+    let rt = resolver::resolve(&auth, &sandbox, &ct, false, true).await;
+    let modified = modified_functions(&auth, &rt).await;
+    println!("Reflecting {}@{}.{} ...", &rt.namespace, &sandbox, &auth.name);
+    let plan = match reflector::plan(&auth, &rt, modified).await {
+        Ok(p) => p,
+        Err(e) => {
+            println!("Failed to reflect {}: {}", &rt.namespace, e);
+            std::process::exit(1);
+        }
+    };
+    reflector::render_plan(&plan);
+    if let Some(path) = out {
+        reflector::save_plan(&plan, &path);
+        println!(
+            "Saved plan to {} (it holds resolved secrets, keep it private)",
+            &path
+        );
+    }
+}
+
+pub async fn apply(plan_file: &str) {
+    let plan = match reflector::load_plan(plan_file) {
+        Some(p) => p,
+        None => {
+            println!("Invalid plan file {}", plan_file);
+            std::process::exit(1);
+        }
+    };
+    let start = Instant::now();
+    let auth = init(Some(plan.env.to_string()), None).await;
+    let topology = &plan.topology;
+
+    if deployer::guard::is_frozen(&auth, topology).await {
+        println!("{}::{} is frozen. Aborting", &auth.name, &plan.sandbox);
+        std::process::exit(1);
+    }
+    deployer::guard::prevent_stable_updates(&auth, &plan.sandbox, topology).await;
+    deployer::guardrails::enforce(&auth, topology).await;
+
+    println!("Checking {}@{}.{} ...", &plan.namespace, &plan.sandbox, &auth.name);
+    match reflector::is_stale(&auth, &plan).await {
+        Ok(false) => (),
+        Ok(true) => {
+            println!("Sandbox changed since the plan was made. Run tc plan again");
+            std::process::exit(1);
+        }
+        Err(e) => {
+            println!("Failed to reflect {}: {}", &plan.namespace, e);
+            std::process::exit(1);
+        }
+    }
+    if plan.changes.is_empty() {
+        println!("No changes to apply");
+        return;
+    }
+    reflector::render_plan(&plan);
+
+    deployer::delete_resources(&auth, reflector::arns_to_delete(&auth, &plan)).await;
+    deployer::delete_routes(&auth, reflector::routes_to_delete(&plan)).await;
+    deployer::apply(&auth, &reflector::narrow_plan(&plan)).await;
+    builder::clean(true);
+
+    let duration = start.elapsed();
+    println!("Time elapsed: {:#}", u::time_format(duration));
+}
+
+pub async fn drift(auth: Auth, sandbox: Option<String>, recursive: bool, format: Option<String>) {
+    let sandbox = resolver::maybe_sandbox(sandbox);
+    let format = u::maybe_string(format, "table");
+    let ct = composer::compose(&u::pwd(), recursive);
+    if format != "json" {
+        println!("Resolving topology {} ...", &ct.namespace);
+    }
+    let rt = resolver::resolve(&auth, &sandbox, &ct, false, true).await;
+    let report = match reflector::drift(&auth, &rt).await {
+        Ok(r) => r,
+        Err(e) => {
+            println!("Failed to reflect {}: {}", &rt.namespace, e);
+            std::process::exit(1);
+        }
+    };
+    match format.as_ref() {
+        "json" => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+        _ => reflector::render_drift(&report),
+    }
+    if !report.drifts.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn render_audit(audits: &[composer::audit::Audit]) {
+    for a in audits {
+        let kind = a.kind.to_str();
+        println!("{}/{} {} ({})", &a.namespace, &a.function, &a.role.blue(), kind);
+        for m in &a.missing {
+            println!("  {} {} on {}", "missing".red(), &m.action, &m.resource);
+        }
+        for w in &a.wildcards {
+            println!(
+                "  {} {} {} on {}",
+                "over-broad".yellow(),
+                &w.sid,
+                w.actions.join(","),
+                w.resources.join(",")
+            );
+        }
+    }
+    let missing: usize = audits.iter().map(|a| a.missing.len()).sum();
+    let broad: usize = audits.iter().map(|a| a.wildcards.len()).sum();
+    println!(
+        "\n{} function(s), {} missing permission(s), {} over-broad statement(s)",
+        audits.len(),
+        missing,
+        broad
+    );
+}
+
+/// Compares the policy each function needs, inferred from its edges in
+/// the topology, with the role it is deployed with. With `out`, writes a
+/// tightened role file per function there
+pub async fn audit_iam(recursive: bool, format: Option<String>, out: Option<String>) {
+    let format = u::maybe_string(format, "table");
+    let ct = composer::compose(&u::pwd(), recursive);
+    let audits = composer::audit::audit(&ct);
+    match format.as_ref() {
+        "json" => println!("{}", serde_json::to_string_pretty(&audits).unwrap()),
+        _ => render_audit(&audits),
+    }
+    if let Some(dir) = out {
+        let dir = u::expand_path(&dir);
+        u::mkdir(&dir);
+        for a in &audits {
+            let path = format!("{}/{}.json", &dir, &a.function);
+            let policy = serde_json::to_string_pretty(&a.policy()).unwrap();
+            u::write_str(&path, &policy);
+            if format != "json" {
+                println!("Wrote {}", &path);
+            }
+        }
+    }
+    if audits.iter().any(|a| !a.missing.is_empty()) {
+        std::process::exit(1);
+    }
+}
+
+pub async fn logs(
+    auth: Auth,
+    sandbox: Option<String>,
+    entity: Option<String>,
+    since: Option<String>,
+    follow: bool,
+    filter: Option<String>,
+) {
+    let sandbox = resolver::maybe_sandbox(sandbox);
+    let since = u::maybe_string(since, "10m");
+    let ct = composer::compose(&u::pwd(), true);
+    let rt = resolver::resolve(&auth, &sandbox, &ct, false, true).await;
+    reflector::tail_logs(&auth, &rt, entity, &since, follow, filter).await;
+}
+
+fn as_node(hop: &reflector::Hop) -> inspector::Node {
+    let label = match &hop.via {
+        Some(via) => format!("{} {} (via {})", &hop.entity, &hop.name, via),
+        None => format!("{} {}", &hop.entity, &hop.name),
+    };
+    let mut detail = serde_json::to_value(hop).unwrap();
+    if let Some(m) = detail.as_object_mut() {
+        m.remove("children");
+        m.insert("started_at".to_string(), serde_json::json!(u::ms_to_dt(hop.start).to_rfc3339()));
+    }
+    inspector::Node {
+        label: label,
+        duration: Some(hop.duration),
+        error: hop.error.is_some(),
+        detail: detail,
+        children: hop.children.iter().map(as_node).collect(),
+    }
+}
+
+pub async fn trace(
+    auth: Auth,
+    sandbox: Option<String>,
+    id: &str,
+    since: Option<String>,
+    format: Option<String>,
+) {
+    let sandbox = resolver::maybe_sandbox(sandbox);
+    let since = u::maybe_string(since, "1h");
+    let format = u::maybe_string(format, "tree");
+    let ct = composer::compose(&u::pwd(), true);
+    let rt = resolver::resolve(&auth, &sandbox, &ct, false, true).await;
+    let trace = reflector::trace(&auth, &rt, id, &since).await;
+    if trace.hops.is_empty() {
+        println!("No trace of {} in the last {}", id, &since);
+        return;
+    }
+    match format.as_ref() {
+        "json" => println!("{}", serde_json::to_string_pretty(&trace).unwrap()),
+        _ => {
+            let nodes: Vec<inspector::Node> = trace.hops.iter().map(as_node).collect();
+            if let Err(e) = inspector::run_timeline(id, &nodes) {
+                println!("Failed to show trace {}: {}", id, e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
 pub async fn update_aux(
     auth: &Auth,
     sandbox: &str,
@@ -524,6 +845,7 @@ pub async fn update(
         notifier::notify(&topology.namespace, &msg).await;
     }
     deployer::guard::prevent_stable_updates(&auth, &sandbox, &rt).await;
+    deployer::guardrails::enforce(&auth, &rt).await;
 
     let msg = composer::count_of(&topology);
     println!("{}", msg);
@@ -838,7 +1160,18 @@ pub async fn changelog_with_limit(n: u8) {
     tagger::changelog::limit(&namespace, n)
 }
 
-pub async fn prune(auth: &Auth, sandbox: Option<String>, filter: Option<String>, dry_run: bool) {
+pub async fn prune(
+    auth: &Auth,
+    sandbox: Option<String>,
+    filter: Option<String>,
+    dry_run: bool,
+    expired: bool,
+) {
+    if expired {
+        let config = Config::new();
+        deployer::prune_expired(auth, &config.resolver.stable_sandbox, dry_run).await;
+        return;
+    }
     match sandbox {
         Some(sbox) => {
             let dir = u::pwd();
@@ -929,16 +1262,36 @@ pub async fn scaffold_llm(
 }
 
 pub async fn emulate(
-    auth: &Auth,
+    profile: Option<String>,
     sandbox: Option<String>,
     maybe_entity: Option<String>,
     shell: bool,
 ) {
     let sandbox = u::maybe_string(sandbox, "stable");
-    let topology = composer::compose(&u::pwd(), false);
-    let rt = resolver::try_resolve(&auth, &sandbox, &topology, &maybe_entity, false, true).await;
-    let entity_component = u::maybe_string(maybe_entity, "function");
-    emulator::emulate(auth, &rt, &entity_component, shell).await;
+    let dir = u::pwd();
+
+    let res = if maybe_entity.is_none() && composer::current_function(&dir).is_none() {
+        // the whole topology, nodes included, is only rendered, so it can
+        // be emulated without an aws account unless a profile is given
+        let topology = composer::compose(&dir, true);
+        let auth = match profile {
+            Some(_) => init(profile, None).await,
+            None => Auth::local(None),
+        };
+        let rt = resolver::render(&auth, &sandbox, &topology).await;
+        emulator::emulate_topology(&auth, &rt).await
+    } else {
+        let topology = composer::compose(&dir, false);
+        let auth = init(profile, None).await;
+        let rt =
+            resolver::try_resolve(&auth, &sandbox, &topology, &maybe_entity, false, true).await;
+        let entity_component = u::maybe_string(maybe_entity, "function");
+        emulator::emulate(&auth, &rt, &entity_component, shell).await
+    };
+    if let Err(e) = res {
+        println!("{}", e);
+        std::process::exit(1);
+    }
 }
 
 pub async fn run(dir: Option<String>, task: String, trace: bool) {
@@ -31,6 +31,10 @@ struct Tc {
 
 #[derive(Debug, Subcommand)]
 enum Cmd {
+    /// Apply a saved plan to its sandbox
+    Apply(ApplyArgs),
+    /// Audit function roles against the permissions the topology needs
+    Audit(AuditArgs),
     /// Build layers, extensions and pack function code
     Build(BuildArgs),
     /// Trigger deploy via CI
@@ -60,6 +64,8 @@ enum Cmd {
     Delete(DeleteArgs),
     /// Diff Sandboxe and local state
     Diff(DiffArgs),
+    /// Report out-of-band changes to a sandbox
+    Drift(DriftArgs),
     /// Emulate a topology or entity
     Emulate(EmulateArgs),
     /// Freeze a sandbox and make it immutable
@@ -70,8 +76,12 @@ enum Cmd {
     Invoke(InvokeArgs),
     /// List resources in a topology
     List(ListArgs),
+    /// Tail and search the logs of a topology
+    Logs(LogsArgs),
     /// Run MCP server
     Mcp(DefaultArgs),
+    /// Show resource-level changes to a sandbox
+    Plan(PlanArgs),
     /// Prune all resources in given sandbox
     Prune(PruneArgs),
     /// Reflect target state of entities
@@ -92,6 +102,8 @@ enum Cmd {
     Test(TestArgs),
     /// Create semver tags scoped by a topology
     Tag(TagArgs),
+    /// Trace a request id or execution arn through a topology
+    Trace(TraceArgs),
     /// Unfreeze a sandbox and make it mutable
     Unfreeze(UnFreezeArgs),
     /// Update entity and components
@@ -212,6 +224,102 @@ pub struct DiffArgs {
     trace: bool,
 }
 
+#[derive(Debug, Args)]
+pub struct AuditArgs {
+    /// What to audit. Only `iam` is supported
+    #[arg(value_parser = ["iam"])]
+    kind: String,
+    /// Directory to write tightened role files to
+    #[arg(long, short = 'o')]
+    out: Option<String>,
+    #[arg(long, short = 'f')]
+    format: Option<String>,
+    #[arg(long, action, short = 'r')]
+    recursive: bool,
+    #[arg(long, action, short = 't')]
+    trace: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DriftArgs {
+    #[arg(long, short = 'e')]
+    profile: Option<String>,
+    #[arg(long, short = 'R')]
+    role: Option<String>,
+    #[arg(long, short = 's')]
+    sandbox: Option<String>,
+    #[arg(long, short = 'f')]
+    format: Option<String>,
+    #[arg(long, action, short = 'r')]
+    recursive: bool,
+    #[arg(long, action, short = 't')]
+    trace: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct LogsArgs {
+    /// Entity or entity/component, e.g. function/loader
+    entity: Option<String>,
+    #[arg(long, short = 'e')]
+    profile: Option<String>,
+    #[arg(long, short = 'R')]
+    role: Option<String>,
+    #[arg(long, short = 's')]
+    sandbox: Option<String>,
+    /// How far back to start, e.g. 10m, 2h or 1d
+    #[arg(long)]
+    since: Option<String>,
+    #[arg(long, action, short = 'f')]
+    follow: bool,
+    /// CloudWatch filter pattern, e.g. ERROR
+    #[arg(long)]
+    filter: Option<String>,
+    #[arg(long, action, short = 't')]
+    trace: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct TraceArgs {
+    /// Request id or state machine execution arn
+    id: String,
+    #[arg(long, short = 'e')]
+    profile: Option<String>,
+    #[arg(long, short = 'R')]
+    role: Option<String>,
+    #[arg(long, short = 's')]
+    sandbox: Option<String>,
+    /// How far back to search the logs, e.g. 1h
+    #[arg(long)]
+    since: Option<String>,
+    #[arg(long, short = 'f')]
+    format: Option<String>,
+    #[arg(long, action, short = 't')]
+    trace: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PlanArgs {
+    #[arg(long, short = 'e')]
+    profile: Option<String>,
+    #[arg(long, short = 'R')]
+    role: Option<String>,
+    #[arg(long, short = 's')]
+    sandbox: Option<String>,
+    #[arg(long, short = 'o')]
+    out: Option<String>,
+    #[arg(long, action, short = 'r')]
+    recursive: bool,
+    #[arg(long, action, short = 't')]
+    trace: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ApplyArgs {
+    plan: String,
+    #[arg(long, action, short = 't')]
+    trace: bool,
+}
+
 #[derive(Debug, Args)]
 pub struct ReflectArgs {
     #[arg(long, short = 'e')]
@@ -358,6 +466,12 @@ pub struct TestArgs {
     interactive: bool,
     #[arg(long, action, short = 'r')]
     recursive: bool,
+    #[arg(long, action)]
+    local: bool,
+    #[arg(long, value_parser = ["junit", "json"])]
+    report: Option<String>,
+    #[arg(long, action)]
+    update_snapshots: bool,
 }
 
 #[derive(Debug, Args)]
@@ -403,6 +517,10 @@ pub struct CreateArgs {
     force: bool,
     #[arg(long, short = 'n')]
     concurrency: Option<i32>,
+    #[arg(long, action)]
+    resume: bool,
+    #[arg(long, action, conflicts_with = "resume")]
+    rollback: bool,
 }
 
 #[derive(Debug, Args)]
@@ -541,6 +659,8 @@ pub struct PruneArgs {
     filter: Option<String>,
     #[arg(long, action, alias = "dry-run")]
     dry_run: bool,
+    #[arg(long, action)]
+    expired: bool,
     #[arg(long, action, short = 't')]
     trace: bool,
 }
@@ -707,14 +827,21 @@ async fn test(args: TestArgs) {
         recursive,
         trace,
         role,
+        local,
+        report,
+        update_snapshots,
         ..
     } = args;
     init_tracing(trace);
+    if local {
+        tc::test_local(profile, sandbox, unit, recursive, report, update_snapshots).await;
+        return;
+    }
     let env = tc::init(profile, role).await;
     if interactive {
         tc::test_interactive(env, sandbox).await;
     } else {
-        tc::test(env, sandbox, unit, recursive).await;
+        tc::test(env, sandbox, unit, recursive, report, update_snapshots).await;
     }
 }
 
@@ -732,6 +859,8 @@ async fn create(args: CreateArgs) {
         remote,
         force,
         concurrency,
+        resume,
+        rollback,
         ..
     } = args;
 
@@ -748,6 +877,8 @@ async fn create(args: CreateArgs) {
             sync: sync,
             force: force,
             concurrency: concurrency,
+            resume: resume,
+            rollback: rollback,
         };
         tc::create(profile, sandbox, topology, opts).await;
     }
@@ -885,6 +1016,83 @@ async fn diff(args: DiffArgs) {
     }
 }
 
+async fn audit(args: AuditArgs) {
+    let AuditArgs {
+        out,
+        format,
+        recursive,
+        trace,
+        ..
+    } = args;
+    init_tracing(trace);
+    tc::audit_iam(recursive, format, out).await;
+}
+
+async fn drift(args: DriftArgs) {
+    let DriftArgs {
+        profile,
+        role,
+        sandbox,
+        format,
+        recursive,
+        trace,
+    } = args;
+    init_tracing(trace);
+    let env = tc::init(profile, role).await;
+    tc::drift(env, sandbox, recursive, format).await;
+}
+
+async fn logs(args: LogsArgs) {
+    let LogsArgs {
+        entity,
+        profile,
+        role,
+        sandbox,
+        since,
+        follow,
+        filter,
+        trace,
+    } = args;
+    init_tracing(trace);
+    let env = tc::init(profile, role).await;
+    tc::logs(env, sandbox, entity, since, follow, filter).await;
+}
+
+async fn trace(args: TraceArgs) {
+    let TraceArgs {
+        id,
+        profile,
+        role,
+        sandbox,
+        since,
+        format,
+        trace,
+    } = args;
+    init_tracing(trace);
+    let env = tc::init(profile, role).await;
+    tc::trace(env, sandbox, &id, since, format).await;
+}
+
+async fn plan(args: PlanArgs) {
+    let PlanArgs {
+        profile,
+        role,
+        sandbox,
+        out,
+        recursive,
+        trace,
+    } = args;
+    init_tracing(trace);
+    let env = tc::init(profile, role).await;
+    tc::plan(env, sandbox, recursive, out).await;
+}
+
+async fn apply(args: ApplyArgs) {
+    let ApplyArgs { plan, trace } = args;
+    init_tracing(trace);
+    tc::apply(&plan).await;
+}
+
 async fn invoke(args: InvokeArgs) {
     let InvokeArgs {
         profile,
@@ -1131,11 +1339,12 @@ async fn prune(args: PruneArgs) {
         filter,
         trace,
         dry_run,
+        expired,
         ..
     } = args;
     init_tracing(trace);
     let env = tc::init(profile, None).await;
-    tc::prune(&env, sandbox, filter, dry_run).await;
+    tc::prune(&env, sandbox, filter, dry_run, expired).await;
 }
 
 async fn emulate(args: EmulateArgs) {
@@ -1148,8 +1357,7 @@ async fn emulate(args: EmulateArgs) {
         ..
     } = args;
     init_tracing(trace);
-    let env = tc::init(profile, None).await;
-    tc::emulate(&env, sandbox, entity, shell).await;
+    tc::emulate(profile, sandbox, entity, shell).await;
 }
 
 async fn scaffold(args: ScaffoldArgs) {
@@ -1237,6 +1445,8 @@ async fn run_command() {
     let args = Tc::parse();
 
     match args.cmd {
+        Cmd::Apply(args) => apply(args).await,
+        Cmd::Audit(args) => audit(args).await,
         Cmd::Build(args) => build(args).await,
         Cmd::Cache(args) => cache(args).await,
         Cmd::Config(args) => config(args).await,
@@ -1244,6 +1454,8 @@ async fn run_command() {
         Cmd::Compile(args) => compile(args).await,
         Cmd::Compose(args) => compose(args).await,
         Cmd::Diff(args) => diff(args).await,
+        Cmd::Drift(args) => drift(args).await,
+        Cmd::Logs(args) => logs(args).await,
         Cmd::Resolve(args) => resolve(args).await,
         Cmd::Create(args) => create(args).await,
         Cmd::Delete(args) => delete(args).await,
@@ -1252,6 +1464,7 @@ async fn run_command() {
         Cmd::Invoke(args) => invoke(args).await,
         Cmd::List(args) => list(args).await,
         Cmd::Mcp(args) => mcp(args).await,
+        Cmd::Plan(args) => plan(args).await,
         Cmd::Prune(args) => prune(args).await,
         Cmd::Route(args) => route(args).await,
         Cmd::Run(args) => run(args).await,
@@ -1260,6 +1473,7 @@ async fn run_command() {
         Cmd::Snapshot(args) => snapshot(args).await,
         Cmd::Tag(args) => tag(args).await,
         Cmd::Test(args) => test(args).await,
+        Cmd::Trace(args) => trace(args).await,
         Cmd::Unfreeze(args) => unfreeze(args).await,
         Cmd::Update(args) => update(args).await,
         Cmd::Upgrade(args) => upgrade(args).await,
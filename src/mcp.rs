@@ -170,9 +170,17 @@ impl Tc {
         deployer::guard::prevent_stable_updates(&auth, &sandbox, &rt).await;
         composer::count_of(&ct);
 
-        tc::create_topology(&auth, &rt, Some(1), false).await;
+        let failed = deployer::deploy(&auth, &rt, Some(1), false, false).await;
         let duration = start.elapsed();
-        format!("Time elapsed: {:#}", u::time_format(duration))
+        match failed {
+            Some(e) => format!(
+                "Failed at {}/{}: {}",
+                &e.namespace,
+                &e.step,
+                u::safe_unwrap(e.error.as_ref())
+            ),
+            None => format!("Time elapsed: {:#}", u::time_format(duration)),
+        }
     }
 
     #[tool(description = "Update a topology in a sandbox")]
@@ -252,17 +260,17 @@ impl Tc {
         }): Parameters<TestRequest>,
     ) -> String {
         let auth = tc::init(Some(profile), None).await;
-        if composer::is_topology_dir(&dir) {
+        let results = if composer::is_topology_dir(&dir) {
             let topology = composer::compose(&dir, recursive);
             let resolved = resolver::render(&auth, &sandbox, &topology).await;
-            tester::test_topology(&auth, &resolved, unit).await;
+            tester::test_topology(&auth, &resolved, unit, false).await
         } else {
-            if let Some(f) = composer::current_function(&dir) {
-                tester::test_function(&auth, &sandbox, &f, unit).await;
+            match composer::current_function(&dir) {
+                Some(f) => tester::test_function(&auth, &sandbox, &f, unit, false).await,
+                None => vec![],
             }
-        }
-        // FIXME: capture output
-        String::from("")
+        };
+        tester::report::json(&results)
     }
 
     #[tool(description = "Resovle a topology")]